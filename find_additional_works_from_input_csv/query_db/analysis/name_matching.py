@@ -1,12 +1,84 @@
 import re
+import math
 import unicodedata
+from collections import Counter
 import jellyfish
 from nameparser import HumanName
+from rapidfuzz import fuzz
+from rapidfuzz.distance import Levenshtein
+from unidecode import unidecode
 
+# Scripts whose names are usually written family name first.
+FAMILY_NAME_FIRST_SCRIPTS = ('CJK', 'HANGUL', 'HIRAGANA', 'KATAKANA')
 
-def parse_name_by_style(name, style):
-    name = name.strip()
 
+def ngram_cosine_similarity(str1, str2, n=3):
+    """Cosine similarity of the character n-gram counts, padded so that short strings have n-grams too."""
+    def ngrams(text):
+        padded = f" {text} "
+        return Counter(padded[i:i + n] for i in range(max(len(padded) - n + 1, 1)))
+    grams1, grams2 = ngrams(str1), ngrams(str2)
+    dot = sum(count * grams2[gram] for gram, count in grams1.items())
+    norm = math.sqrt(sum(c * c for c in grams1.values())) * math.sqrt(sum(c * c for c in grams2.values()))
+    return dot / norm if norm else 0.0
+
+
+# String similarity metrics selectable by name in the config, all scaled to 0-1.
+SIMILARITY_METRICS = {
+    'jaro_winkler': jellyfish.jaro_winkler_similarity,
+    'levenshtein': Levenshtein.normalized_similarity,
+    'token_set': lambda str1, str2: fuzz.token_set_ratio(str1, str2) / 100.0,
+    'partial_ratio': lambda str1, str2: fuzz.partial_ratio(str1, str2) / 100.0,
+    'ngram_cosine': ngram_cosine_similarity,
+}
+
+
+def get_similarity_metric(name):
+    try:
+        return SIMILARITY_METRICS[name]
+    except KeyError:
+        raise ValueError(f"Unknown similarity metric '{name}', expected one of: {', '.join(SIMILARITY_METRICS)}")
+
+
+def is_non_latin(name):
+    """True if the name has letters outside the Latin script, e.g. Cyrillic, Greek or hanzi."""
+    return any(char.isalpha() and not unicodedata.name(char, '').startswith('LATIN') for char in name)
+
+
+def is_family_name_first(name):
+    return any(unicodedata.name(char, '').startswith(FAMILY_NAME_FIRST_SCRIPTS) for char in name)
+
+
+def describe_transliteration(name, style='auto'):
+    """How a name is romanized before matching, e.g. "张伟 -> Zhang Wei (family name first)"; '' for Latin names."""
+    original = name.strip()
+    if not is_non_latin(original):
+        return ''
+    romanized = ' '.join(unidecode(original).split())
+    if style in ('auto', 'first_last') and is_family_name_first(original):
+        return f"{original} -> {romanized} (family name first)"
+    return f"{original} -> {romanized}"
+
+
+def parse_name_by_style(name, style, transliterate=True):
+    """Parses a name, romanizing non-Latin names first so that "Иванов" compares equal to "Ivanov".
+    
+    'transliterated' in the result tells whether that happened. East Asian names are taken
+    as family name first, as pinyin in a CRIS is mostly given in the other order.
+    """
+    original = name.strip()
+    transliterated = transliterate and is_non_latin(original)
+    if transliterated:
+        if style in ('auto', 'first_last') and is_family_name_first(original):
+            style = 'last_first'
+        name = ' '.join(unidecode(original).split())
+    parsed = _parse_name(name if transliterated else original, style)
+    parsed['original'] = original
+    parsed['transliterated'] = transliterated
+    return parsed
+
+
+def _parse_name(name, style):
     if style == 'last_initial':
         parts = name.split()
         if len(parts) >= 2:
@@ -61,12 +133,14 @@ def parse_name_by_style(name, style):
     return {'first': first.lower(), 'last': last.lower(), 'middle': middle.lower(), 'normalized': normalized, 'original': name, 'style': 'first_last'}
 
 
-def are_names_similar(name1_str, name2_str, name1_style='auto', name2_style='auto', threshold=0.85):
-    name1 = parse_name_by_style(name1_str, name1_style)
-    name2 = parse_name_by_style(name2_str, name2_style)
+def are_names_similar(name1_str, name2_str, name1_style='auto', name2_style='auto', threshold=0.85,
+                      transliterate=True, metric='jaro_winkler'):
+    similarity = get_similarity_metric(metric)
+    name1 = parse_name_by_style(name1_str, name1_style, transliterate)
+    name2 = parse_name_by_style(name2_str, name2_style, transliterate)
     if not name1['last'] or not name2['last']:
-        return name1['normalized'] == name2['normalized']
-    last_similarity = jellyfish.jaro_winkler_similarity(name1['last'], name2['last'])
+        return bool(name1['normalized']) and name1['normalized'] == name2['normalized']
+    last_similarity = similarity(name1['last'], name2['last'])
     if last_similarity < threshold:
         return False
     if name1['first'] and name2['first']:
@@ -74,9 +148,21 @@ def are_names_similar(name1_str, name2_str, name1_style='auto', name2_style='aut
             if name1['first'][0] == name2['first'][0]:
                 return True
         else:
-            first_similarity = jellyfish.jaro_winkler_similarity(name1['first'], name2['first'])
+            first_similarity = similarity(name1['first'], name2['first'])
             if first_similarity >= threshold:
                 return True
     if last_similarity >= 0.95:
         return True
-    return False
\ No newline at end of file
+    return False
+
+def name_key(name_str, style='auto'):
+    """Last name and first initial, romanized, to look names up by; None if no last name is left.
+    
+    The key is always built from the romanized name, as an empty key would pair every name without one.
+    """
+    name = parse_name_by_style(name_str, style)
+    last = re.sub(r'[-.,\s]+', ' ', unidecode(name['last']).lower()).strip()
+    if not last:
+        return None
+    first = unidecode(name['first']).strip()
+    return f"{last} {first[:1].lower()}".strip()
\ No newline at end of file
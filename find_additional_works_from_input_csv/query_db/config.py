@@ -1,11 +1,23 @@
 import sys
 import yaml
 
+from query_db.analysis.name_matching import get_similarity_metric
+
+METRIC_SETTINGS = ('name_matching_metric', 'entity_matching_metric')
+
 
 def load_config(config_path):
     try:
         with open(config_path, 'r') as f:
-            return yaml.safe_load(f)
+            config = yaml.safe_load(f)
     except Exception as e:
         print(f"Error loading configuration file: {e}")
-        sys.exit(1)
\ No newline at end of file
+        sys.exit(1)
+    for setting in METRIC_SETTINGS:
+        if setting in (config or {}):
+            try:
+                get_similarity_metric(config[setting])
+            except ValueError as e:
+                print(f"Error in configuration file: {setting}: {e}")
+                sys.exit(1)
+    return config
\ No newline at end of file
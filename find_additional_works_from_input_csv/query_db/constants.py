@@ -5,12 +5,18 @@ DISCOVERED_WORKS_SUFFIX = "_discovered_works.csv"
 ENTITY_MAPPINGS_SUFFIX = "_entity_mappings.csv"
 LINKING_AFFILIATIONS_SUFFIX = "_linking_affiliations.csv"
 UNMATCHED_IDS_SUFFIX = "_unmatched_ids.csv"
+DEPARTMENT_WORKLIST_SUFFIX = "_department_worklist.csv"
+DEPARTMENT_SUMMARY_SUFFIX = "_department_summary.csv"
 
 # Linkage status types
 STATUS_ORG_MATCH = "org_match_found"
 STATUS_FIRST_AVAILABLE = "first_available"
 STATUS_NAME_MATCH_NO_ORG = "name_match_no_org_affiliation"
 
+# Finding types routed to departments
+FINDING_DISCOVERED_WORK = "discovered_work"
+FINDING_NAME_CHANGE = "name_change"
+
 # Match types for discovered works
 MATCH_TYPE_AFFILIATION = "affiliation_exact"
 MATCH_TYPE_ENTITY = "entity_extracted"
@@ -20,6 +26,8 @@ DEFAULT_CHUNK_SIZE = 100000
 DEFAULT_MEMORY_LIMIT = "8GB"
 DEFAULT_NAME_THRESHOLD = 0.85
 DEFAULT_ENTITY_THRESHOLD = 85
+DEFAULT_NAME_METRIC = "jaro_winkler"
+DEFAULT_ENTITY_METRIC = "partial_ratio"
 
 # Database table names
 TABLE_AUTHOR_REFERENCES = "author_references"
@@ -31,10 +39,15 @@ TEMP_TABLE_AFFILIATION_DISCOVERED = "affiliation_discovered_works"
 TEMP_TABLE_ALREADY_DISCOVERED = "already_discovered_works"
 TEMP_TABLE_KNOWN_ORGS = "temp_known_orgs"
 TEMP_TABLE_LINKAGE_RESULTS = "temp_linkage_results"
+TEMP_TABLE_PERSON_NAMES = "temp_person_names"
+TEMP_TABLE_DEPARTMENTS = "temp_departments"
+TEMP_TABLE_DEPARTMENT_FINDINGS = "temp_department_findings"
 
 # CSV field names
 LINKAGE_FIELDNAMES = ['input_doi', 'input_work_id', 'input_author_name', 
-                      'ref_author_name', 'ref_affiliation', 'linkage_status']
+                      'ref_author_name', 'ref_affiliation', 'linkage_status',
+                      'person_id', 'person_orcid', 'matched_name_variant',
+                      'name_transliterated', 'name_transliteration']
 
 ENTITY_MAPPING_FIELDNAMES = ['normalized_affiliation', 'original_affiliation', 
                              'extracted_entities', 'all_extracted_entities']
\ No newline at end of file
@@ -3,9 +3,20 @@
 import pandas as pd
 from query_db.db import DatabaseManager
 from query_db.constants import *
+from query_db.analysis.name_matching import get_similarity_metric, name_key
 from query_db.utils import extract_doi, normalize_text, sanitize_file_path_for_sql, validate_column_name
 
 
+def normalize_orcid_column(orcids: pd.Series) -> pd.Series:
+    """Bare, uppercased ORCID iDs, so that 'https://orcid.org/0000-0002-1825-009x' matches '0000-0002-1825-009X'."""
+    return orcids.str.replace(r'^https?://orcid\.org/', '', regex=True).str.upper()
+
+
+def year_column(values: pd.Series) -> pd.Series:
+    """The year of values given as years or ISO dates ('2019', '2019-05-08'); missing when there is none."""
+    return pd.to_numeric(values.str.strip().str[:4], errors='coerce').astype('Int64')
+
+
 class AuthorReferencesRepository:
     def __init__(self, db_manager: DatabaseManager):
         self.db = db_manager
@@ -51,10 +62,198 @@ class AuthorReferencesRepository:
         except Exception as e:
             raise RuntimeError(f"Failed to create input IDs table: {e}")
     
+    def create_person_names_table(self, registry_file: str, person_id_col: str, name_col: str,
+                                  orcid_col: str = None, name_style: str = 'auto', email_col: str = None,
+                                  valid_from_col: str = None, valid_to_col: str = None):
+        """Loads a CRIS person registry, one row per person and name variant, into a temp table.
+        
+        Each variant gets a name key (last name and first initial) that input author names
+        are looked up by, so every other variant of the same person can be tried as well.
+        A variant may have the years it was in use, and a person ORCID iDs and e-mail addresses
+        that tell apart persons with the same name.
+        
+        Returns:
+            tuple: (number of persons, number of name variants)
+        """
+        try:
+            columns = {person_id_col: 'person_id', name_col: 'name_variant'}
+            for col, name in [(orcid_col, 'orcid'), (email_col, 'email'),
+                              (valid_from_col, 'valid_from'), (valid_to_col, 'valid_to')]:
+                if col:
+                    columns[col] = name
+            
+            registry_df = pd.read_csv(registry_file, usecols=list(columns), dtype=str, keep_default_na=False)
+            registry_df = registry_df.rename(columns=columns)
+            for name in ['orcid', 'email', 'valid_from', 'valid_to']:
+                if name not in registry_df.columns:
+                    registry_df[name] = ''
+            
+            for col in ['person_id', 'name_variant', 'orcid', 'email']:
+                registry_df[col] = registry_df[col].str.strip()
+            registry_df['orcid'] = normalize_orcid_column(registry_df['orcid'])
+            registry_df['email'] = registry_df['email'].str.lower()
+            registry_df['valid_from'] = year_column(registry_df['valid_from'])
+            registry_df['valid_to'] = year_column(registry_df['valid_to'])
+            registry_df = registry_df[(registry_df['person_id'] != '') & (registry_df['name_variant'] != '')].copy()
+            
+            # The ORCID and e-mail addresses are often given on one row per person only.
+            registry_df['orcid'] = registry_df.groupby('person_id')['orcid'].transform('max')
+            registry_df['emails'] = registry_df.groupby('person_id')['email'].transform(
+                lambda emails: ' '.join(sorted(set(emails) - {''})))
+            # Variants without a key are kept, they are only reached through their person ID.
+            registry_df['name_key'] = registry_df['name_variant'].apply(lambda name: name_key(name, name_style))
+            registry_df = registry_df[['person_id', 'orcid', 'emails', 'name_variant', 'name_key',
+                                       'valid_from', 'valid_to']].drop_duplicates()
+            
+            self.db.register_df('person_registry_df', registry_df)
+            self.db.execute(f"CREATE OR REPLACE TEMP TABLE {TEMP_TABLE_PERSON_NAMES} AS SELECT * FROM person_registry_df")
+            
+            return registry_df['person_id'].nunique(), len(registry_df)
+            
+        except Exception as e:
+            raise RuntimeError(f"Failed to load person registry: {e}")
+    
+    def create_departments_table(self, departments_file: str, department_col: str, person_id_col: str = None,
+                                 doi_col: str = None, work_id_col: str = None):
+        """Loads a CRIS organizational-unit table assigning persons and/or works to departments.
+        
+        Returns:
+            int: Number of distinct departments
+        """
+        try:
+            columns = {department_col: 'department'}
+            for col, name in [(person_id_col, 'person_id'), (doi_col, 'doi'), (work_id_col, 'work_id')]:
+                if col:
+                    columns[col] = name
+            
+            departments_df = pd.read_csv(departments_file, usecols=list(columns), dtype=str, keep_default_na=False)
+            departments_df = departments_df.rename(columns=columns)
+            for name in ['person_id', 'doi', 'work_id']:
+                if name not in departments_df.columns:
+                    departments_df[name] = ''
+                departments_df[name] = departments_df[name].str.strip()
+            departments_df['department'] = departments_df['department'].str.strip()
+            departments_df['doi'] = departments_df['doi'].apply(lambda x: extract_doi(x) or '')
+            departments_df = departments_df[departments_df['department'] != '']
+            departments_df = departments_df[['department', 'person_id', 'doi', 'work_id']].drop_duplicates()
+            
+            self.db.register_df('departments_df', departments_df)
+            self.db.execute(f"CREATE OR REPLACE TEMP TABLE {TEMP_TABLE_DEPARTMENTS} AS SELECT * FROM departments_df")
+            
+            return departments_df['department'].nunique()
+            
+        except Exception as e:
+            raise RuntimeError(f"Failed to load departments table: {e}")
+    
+    def attribute_findings_to_departments(self):
+        """Routes discovered works and name changes to departments.
+        
+        A finding belongs to the departments of its input work (by DOI or work ID) and of the
+        registry persons its input author was linked to. Findings with neither are kept with an
+        empty department so they can still be assigned by hand.
+        
+        Returns:
+            int: Number of worklist rows
+        """
+        try:
+            finding_keys = ['finding', 'input_doi', 'input_work_id', 'input_author_name', 'work_id', 'doi', 'author', 'detail']
+            same_finding = ' AND '.join(f"a.{key} IS NOT DISTINCT FROM f.{key}" for key in finding_keys)
+            
+            self.db.execute(f"""
+                CREATE OR REPLACE TEMP TABLE {TEMP_TABLE_DEPARTMENT_FINDINGS} AS
+                WITH findings AS (
+                    SELECT DISTINCT
+                        '{FINDING_DISCOVERED_WORK}' AS finding,
+                        input_doi, input_work_id, input_author_name,
+                        discovered_work_id AS work_id, discovered_doi AS doi,
+                        discovered_author AS author, linking_affiliation AS detail
+                    FROM {TEMP_TABLE_AFFILIATION_DISCOVERED}
+                    UNION
+                    SELECT DISTINCT
+                        '{FINDING_NAME_CHANGE}' AS finding,
+                        input_doi, input_work_id, input_author_name,
+                        input_work_id AS work_id, input_doi AS doi,
+                        ref_author_name AS author, matched_name_variant AS detail
+                    FROM {TEMP_TABLE_LINKAGE_RESULTS}
+                    WHERE matched_name_variant IS NOT NULL AND matched_name_variant != ''
+                ),
+                linked_persons AS (
+                    SELECT DISTINCT input_doi, input_work_id, input_author_name, trim(person) AS person_id
+                    FROM (
+                        SELECT input_doi, input_work_id, input_author_name,
+                               UNNEST(string_split(person_id, ';')) AS person
+                        FROM {TEMP_TABLE_LINKAGE_RESULTS}
+                        WHERE person_id IS NOT NULL AND person_id != ''
+                    )
+                ),
+                attributed AS (
+                    SELECT f.*, d.department, 'work' AS attributed_via
+                    FROM findings AS f
+                    JOIN {TEMP_TABLE_DEPARTMENTS} AS d ON
+                        (d.doi != '' AND d.doi = f.input_doi)
+                        OR (d.work_id != '' AND d.work_id = CAST(f.input_work_id AS VARCHAR))
+                    UNION
+                    SELECT f.*, d.department, 'person' AS attributed_via
+                    FROM findings AS f
+                    JOIN linked_persons AS p
+                        ON p.input_doi IS NOT DISTINCT FROM f.input_doi
+                        AND p.input_work_id IS NOT DISTINCT FROM f.input_work_id
+                        AND p.input_author_name = f.input_author_name
+                    JOIN {TEMP_TABLE_DEPARTMENTS} AS d ON d.person_id != '' AND d.person_id = p.person_id
+                )
+                SELECT department, {', '.join(finding_keys)},
+                       string_agg(DISTINCT attributed_via, '; ') AS attributed_via
+                FROM attributed
+                GROUP BY ALL
+                UNION ALL
+                SELECT '' AS department, {', '.join(f'f.{key}' for key in finding_keys)}, '' AS attributed_via
+                FROM findings AS f
+                WHERE NOT EXISTS (SELECT 1 FROM attributed AS a WHERE {same_finding})
+            """)
+            
+            return self.db.query_one(f"SELECT COUNT(*) FROM {TEMP_TABLE_DEPARTMENT_FINDINGS}")[0]
+            
+        except Exception as e:
+            raise RuntimeError(f"Failed to attribute findings to departments: {e}")
+    
+    def export_department_findings(self, worklist_file: str, summary_file: str):
+        """Writes the department worklist and the per-department counts."""
+        try:
+            safe_worklist_file = sanitize_file_path_for_sql(worklist_file, is_output=True)
+            safe_summary_file = sanitize_file_path_for_sql(summary_file, is_output=True)
+            
+            self.db.execute(f"""
+                COPY (
+                    SELECT * FROM {TEMP_TABLE_DEPARTMENT_FINDINGS}
+                    ORDER BY department = '', department, finding, doi, author
+                ) TO '{safe_worklist_file}' (HEADER, DELIMITER ',')
+            """)
+            
+            self.db.execute(f"""
+                COPY (
+                    SELECT
+                        department,
+                        COUNT(DISTINCT CASE WHEN finding = '{FINDING_DISCOVERED_WORK}'
+                                            THEN COALESCE(work_id, doi) END) AS discovered_works,
+                        COUNT(DISTINCT CASE WHEN finding = '{FINDING_NAME_CHANGE}'
+                                            THEN concat_ws('|', input_doi, input_work_id, input_author_name, author) END) AS name_changes
+                    FROM {TEMP_TABLE_DEPARTMENT_FINDINGS}
+                    GROUP BY department
+                    ORDER BY department = '', department
+                ) TO '{safe_summary_file}' (HEADER, DELIMITER ',')
+            """)
+            
+        except Exception as e:
+            raise RuntimeError(f"Failed to export department findings: {e}")
+    
     def query_authors_for_linkage_udf(self, chunk_df: pd.DataFrame, input_doi_col: str = None, 
                                      input_work_id_col: str = None, authors_col: str = None, 
                                      author_sep: str = '', input_name_style: str = 'first_last',
-                                     reference_name_style: str = 'first_last', name_threshold: float = 0.85):
+                                     reference_name_style: str = 'first_last', name_threshold: float = 0.85,
+                                     transliterate_names: bool = True, name_metric: str = 'jaro_winkler',
+                                     use_person_registry: bool = False, registry_name_style: str = 'auto',
+                                     input_email_col: str = None, input_orcid_col: str = None,
+                                     input_year_col: str = None):
         if not authors_col:
             raise ValueError("authors_col must be specified")
         
@@ -68,6 +267,16 @@ class AuthorReferencesRepository:
             else:
                 processed_chunk_df['clean_doi'] = None
             
+            # Tell apart registry persons with the same name, and pick the name variants in use.
+            if use_person_registry:
+                blank = pd.Series('', index=processed_chunk_df.index, dtype=str)
+                email = processed_chunk_df[input_email_col] if input_email_col else blank
+                orcid = processed_chunk_df[input_orcid_col] if input_orcid_col else blank
+                year = processed_chunk_df[input_year_col] if input_year_col else blank
+                processed_chunk_df['clean_email'] = email.str.strip().str.lower()
+                processed_chunk_df['clean_orcid'] = normalize_orcid_column(orcid.str.strip())
+                processed_chunk_df['clean_year'] = year_column(year)
+            
             temp_input_table = "temp_input_chunk_udf"
             self.db.register_df(temp_input_table, processed_chunk_df)
             
@@ -79,6 +288,10 @@ class AuthorReferencesRepository:
             else: 
                 id_selection.append('NULL AS input_work_id')
             
+            if use_person_registry:
+                id_selection.extend(['clean_email AS input_email', 'clean_orcid AS input_orcid',
+                                     'clean_year AS input_year'])
+            
             additional_valid_cols = {authors_col}
             if input_doi_col:
                 additional_valid_cols.add(input_doi_col)
@@ -88,6 +301,8 @@ class AuthorReferencesRepository:
             escaped_authors_col = validated_authors_col.replace('"', '""')
             escaped_input_style = input_name_style.replace("'", "''")
             escaped_ref_style = reference_name_style.replace("'", "''")
+            escaped_registry_style = registry_name_style.replace("'", "''")
+            get_similarity_metric(name_metric)  # only known metric names reach the query text
             
             author_filter = f'WHERE "{escaped_authors_col}" IS NOT NULL AND trim("{escaped_authors_col}") != \'\''
             
@@ -109,14 +324,88 @@ class AuthorReferencesRepository:
                     {author_filter}
                 """
             
-            udf_linkage_query = f"""
+            # Each input author is matched under its own name and, with a person registry,
+            # under every name variant of the persons it is registered as (e.g. a maiden name).
+            candidates_subquery = f"""
+                SELECT
+                    input_doi, input_work_id, input_author,
+                    input_author AS match_name,
+                    '{escaped_input_style}' AS match_style,
+                    TRUE AS is_input_name,
+                    CAST(NULL AS VARCHAR) AS person_id,
+                    CAST(NULL AS VARCHAR) AS person_orcid
+                FROM ({input_authors_subquery})
+            """
+            
+            if use_person_registry:
+                # The registry person an input author is: one with a variant similar to the input
+                # name. Persons sharing the name are told apart by the input's e-mail address or
+                # ORCID iD; if that does not leave exactly one, no other variants are tried, so an
+                # author cannot be matched through another person's former name.
+                registry_persons_subquery = f"""
+                    SELECT input_doi, input_work_id, input_author, input_year, person_id
+                    FROM (
+                        SELECT *,
+                            count(*) OVER same_author AS persons,
+                            sum(CASE WHEN identified THEN 1 ELSE 0 END) OVER same_author AS identified_persons
+                        FROM (
+                            SELECT
+                                inp.input_doi, inp.input_work_id, inp.input_author, inp.input_year,
+                                known.person_id,
+                                bool_or((inp.input_email != ''
+                                         AND list_contains(string_split(known.emails, ' '), inp.input_email))
+                                        OR (inp.input_orcid != '' AND known.orcid = inp.input_orcid)) AS identified
+                            FROM ({input_authors_subquery}) AS inp
+                            JOIN {TEMP_TABLE_PERSON_NAMES} AS known
+                                ON known.name_key IS NOT NULL AND known.name_key != ''
+                                AND known.name_key = name_key_udf(inp.input_author, '{escaped_input_style}')
+                                AND are_names_similar_udf(
+                                    inp.input_author,
+                                    known.name_variant,
+                                    '{escaped_input_style}',
+                                    '{escaped_registry_style}',
+                                    {name_threshold},
+                                    {'TRUE' if transliterate_names else 'FALSE'},
+                                    '{name_metric}'
+                                )
+                            GROUP BY inp.input_doi, inp.input_work_id, inp.input_author, inp.input_year,
+                                     known.person_id
+                        )
+                        WINDOW same_author AS (PARTITION BY input_doi, input_work_id, input_author, input_year)
+                    )
+                    WHERE (identified_persons = 1 AND identified)
+                       OR (identified_persons = 0 AND persons = 1)
+                """
+                candidates_subquery += f"""
+                UNION ALL
                 SELECT DISTINCT
+                    person.input_doi, person.input_work_id, person.input_author,
+                    variant.name_variant AS match_name,
+                    '{escaped_registry_style}' AS match_style,
+                    FALSE AS is_input_name,
+                    variant.person_id,
+                    variant.orcid AS person_orcid
+                FROM ({registry_persons_subquery}) AS person
+                JOIN {TEMP_TABLE_PERSON_NAMES} AS variant ON variant.person_id = person.person_id
+                -- With a year for the work, only the names in use that year are tried.
+                WHERE person.input_year IS NULL
+                   OR ((variant.valid_from IS NULL OR variant.valid_from <= person.input_year)
+                       AND (variant.valid_to IS NULL OR person.input_year <= variant.valid_to))
+                """
+            
+            udf_linkage_query = f"""
+                SELECT
                     inp.input_doi,
                     inp.input_work_id, 
                     inp.input_author,
                     ref.author_name AS ref_author_name,
-                    ref.normalized_affiliation_name AS ref_affiliation
-                FROM ({input_authors_subquery}) AS inp
+                    ref.normalized_affiliation_name AS ref_affiliation,
+                    string_agg(DISTINCT inp.person_id, '; ') AS person_id,
+                    string_agg(DISTINCT nullif(inp.person_orcid, ''), '; ') AS person_orcid,
+                    -- Set only when the input name itself did not match, i.e. a name change
+                    CASE WHEN bool_or(inp.is_input_name) THEN NULL
+                         ELSE min(inp.match_name) END AS matched_name_variant
+                FROM ({candidates_subquery}) AS inp
                 JOIN {TABLE_AUTHOR_REFERENCES} AS ref ON (
                     -- Match by DOI if both are available and not empty
                     (inp.input_doi IS NOT NULL AND inp.input_doi != '' 
@@ -129,16 +418,20 @@ class AuthorReferencesRepository:
                      AND inp.input_work_id = ref.work_id)
                 ) 
                 AND are_names_similar_udf(
-                    inp.input_author, 
+                    inp.match_name, 
                     ref.author_name, 
-                    '{escaped_input_style}', 
+                    inp.match_style, 
                     '{escaped_ref_style}', 
-                    {name_threshold}
+                    {name_threshold},
+                    {'TRUE' if transliterate_names else 'FALSE'},
+                    '{name_metric}'
                 )
                 WHERE inp.input_author IS NOT NULL 
                 AND inp.input_author != ''
                 AND ref.author_name IS NOT NULL 
                 AND ref.author_name != ''
+                GROUP BY inp.input_doi, inp.input_work_id, inp.input_author,
+                         ref.author_name, ref.normalized_affiliation_name
             """
             
             return self.db.query(udf_linkage_query)
@@ -155,7 +448,12 @@ class AuthorReferencesRepository:
                 input_author_name VARCHAR,
                 ref_author_name VARCHAR,
                 ref_affiliation VARCHAR,
-                linkage_status VARCHAR
+                linkage_status VARCHAR,
+                person_id VARCHAR,
+                person_orcid VARCHAR,
+                matched_name_variant VARCHAR,
+                name_transliterated BOOLEAN,
+                name_transliteration VARCHAR
             )
         """)
     
@@ -524,13 +822,15 @@ class AuthorReferencesRepository:
         except Exception as e:
             raise RuntimeError(f"Failed to get match type counts: {e}")
     
-    def validate_entities_in_db(self, extracted_entities: list, organization_names: list, threshold: float = 0.85) -> dict:
+    def validate_entities_in_db(self, extracted_entities: list, organization_names: list, threshold: float = 0.85,
+                                metric: str = 'partial_ratio') -> dict:
         if not extracted_entities:
             raise ValueError("extracted_entities cannot be empty")
         if not organization_names:
             raise ValueError("organization_names cannot be empty")
         if not (0.0 <= threshold <= 1.0):
             raise ValueError("threshold must be between 0.0 and 1.0")
+        get_similarity_metric(metric)  # only known metric names reach the query text
         
         try:
             temp_entities_table = "temp_extracted_entities"
@@ -571,10 +871,10 @@ class AuthorReferencesRepository:
                     ee.entity_text,
                     ee.source_affiliation,
                     o.org_name as matched_org,
-                    partial_ratio_udf(ee.entity_text, o.org_name) as score
+                    string_similarity_udf(ee.entity_text, o.org_name, '{metric}') as score
                 FROM {temp_entities_table} ee
                 CROSS JOIN {temp_orgs_table} o
-                WHERE partial_ratio_udf(ee.entity_text, o.org_name) > ?
+                WHERE string_similarity_udf(ee.entity_text, o.org_name, '{metric}') > ?
                 ORDER BY ee.entity_text, score DESC
             """
             
@@ -4,7 +4,7 @@ import csv
 import logging
 import pandas as pd
 
-from query_db.analysis.name_matching import are_names_similar
+from query_db.analysis.name_matching import are_names_similar, describe_transliteration, is_non_latin
 from query_db.utils import extract_doi, normalize_text, is_likely_acronym
 from query_db.constants import *
 
@@ -24,6 +24,16 @@ class LinkageService:
             'reference_name_style', 'first_last')
         self.matching_threshold = self.config.get(
             'name_matching_threshold', DEFAULT_NAME_THRESHOLD)
+        self.matching_metric = self.config.get(
+            'name_matching_metric', DEFAULT_NAME_METRIC)
+        self.transliterate_names = self.config.get('name_transliteration', True)
+        self.person_registry = self.config.get('person_registry')
+        self.registry_name_style = (self.person_registry or {}).get(
+            'name_style', self.input_name_style)
+        input_columns = self.config.get('input_columns', {})
+        self.input_email_col = input_columns.get('email')
+        self.input_orcid_col = input_columns.get('orcid')
+        self.input_year_col = input_columns.get('year')
 
     def find_linkages_udf(self, chunk_df, input_doi_col=None, input_work_id_col=None,
                           authors_col=None, author_sep=''):
@@ -39,15 +49,35 @@ class LinkageService:
                 author_sep=author_sep,
                 input_name_style=self.input_name_style,
                 reference_name_style=self.reference_name_style,
-                name_threshold=self.matching_threshold
+                name_threshold=self.matching_threshold,
+                transliterate_names=self.transliterate_names,
+                name_metric=self.matching_metric,
+                use_person_registry=bool(self.person_registry),
+                registry_name_style=self.registry_name_style,
+                input_email_col=self.input_email_col,
+                input_orcid_col=self.input_orcid_col,
+                input_year_col=self.input_year_col
             )
 
             matches_in_chunk = []
 
             for row in matched_results:
-                input_doi, input_work_id, input_author, ref_author_name, ref_affiliation = row
+                (input_doi, input_work_id, input_author, ref_author_name, ref_affiliation,
+                 person_id, person_orcid, matched_name_variant) = row
 
                 status = self._determine_linkage_status(ref_affiliation)
+                # Whether either compared name was romanized before matching, and how.
+                compared_name = matched_name_variant or input_author or ''
+                compared_style = self.registry_name_style if matched_name_variant else self.input_name_style
+                name_transliterated = self.transliterate_names and (
+                    is_non_latin(compared_name) or is_non_latin(ref_author_name or ''))
+                name_transliteration = ''
+                if name_transliterated:
+                    name_transliteration = '; '.join(
+                        f"{side}: {description}" for side, description in (
+                            ('input', describe_transliteration(compared_name, compared_style)),
+                            ('reference', describe_transliteration(ref_author_name or '', self.reference_name_style)))
+                        if description)
 
                 matches_in_chunk.append({
                     'input_doi': input_doi or '',
@@ -55,7 +85,12 @@ class LinkageService:
                     'input_author_name': input_author.strip() if input_author else '',
                     'ref_author_name': ref_author_name or '',
                     'ref_affiliation': ref_affiliation or '',
-                    'linkage_status': status
+                    'linkage_status': status,
+                    'person_id': person_id or '',
+                    'person_orcid': person_orcid or '',
+                    'matched_name_variant': matched_name_variant or '',
+                    'name_transliterated': name_transliterated,
+                    'name_transliteration': name_transliteration
                 })
 
             return matches_in_chunk
@@ -86,6 +121,8 @@ class DiscoveryService:
         self.organization_names = self.config.get('organization_names', [])
         self.entity_matching_threshold = self.config.get(
             'entity_matching_threshold', DEFAULT_ENTITY_THRESHOLD)
+        self.entity_matching_metric = self.config.get(
+            'entity_matching_metric', DEFAULT_ENTITY_METRIC)
         self.use_entity_discovery = self.config.get(
             'use_entity_discovery', True)
 
@@ -142,7 +179,7 @@ class DiscoveryService:
             threshold = self.entity_matching_threshold / 100.0
 
             validated_entities = self.repository.validate_entities_in_db(
-                filtered_entities, self.organization_names, threshold
+                filtered_entities, self.organization_names, threshold, self.entity_matching_metric
             )
 
             entity_to_sources = {}
@@ -178,4 +215,4 @@ class DiscoveryService:
             return self.repository.get_match_type_counts(combined_query)
 
         except Exception as e:
-            raise RuntimeError(f"Failed to get discovery counts: {e}")
+            raise RuntimeError(f"Failed to get discovery counts: {e}")
\ No newline at end of file
@@ -3,7 +3,7 @@ import logging
 from typing import Optional
 
 from rapidfuzz import fuzz
-from query_db.analysis.name_matching import are_names_similar
+from query_db.analysis.name_matching import are_names_similar, get_similarity_metric, name_key
 
 logger = logging.getLogger(__name__)
 
@@ -13,7 +13,9 @@ def are_names_similar_udf(
     name2: Optional[str], 
     name1_style: str, 
     name2_style: str, 
-    threshold: float
+    threshold: float,
+    transliterate: bool,
+    metric: str
 ) -> bool:
     try:
         if name1 is None or name2 is None:
@@ -27,7 +29,9 @@ def are_names_similar_udf(
             name2_str=name2,
             name1_style=name1_style,
             name2_style=name2_style,
-            threshold=threshold
+            threshold=threshold,
+            transliterate=transliterate,
+            metric=metric
         )
     
     except Exception as e:
@@ -35,11 +39,26 @@ def are_names_similar_udf(
             f"Error in are_names_similar_udf: {e}. "
             f"Args: name1='{name1}', name2='{name2}', "
             f"name1_style='{name1_style}', name2_style='{name2_style}', "
-            f"threshold={threshold}"
+            f"threshold={threshold}, transliterate={transliterate}, metric='{metric}'"
         )
         return False
 
 
+def name_key_udf(name: Optional[str], style: str) -> Optional[str]:
+    try:
+        if name is None or not name.strip():
+            return None
+        
+        return name_key(name, style)
+    
+    except Exception as e:
+        logger.warning(
+            f"Error in name_key_udf: {e}. "
+            f"Args: name='{name}', style='{style}'"
+        )
+        return None
+
+
 def partial_ratio_udf(str1: Optional[str], str2: Optional[str]) -> float:
     try:
         if str1 is None or str2 is None:
@@ -59,6 +78,24 @@ def partial_ratio_udf(str1: Optional[str], str2: Optional[str]) -> float:
         return 0.0
 
 
+def string_similarity_udf(str1: Optional[str], str2: Optional[str], metric: str) -> float:
+    try:
+        if str1 is None or str2 is None:
+            return 0.0
+        
+        if not str1.strip() or not str2.strip():
+            return 0.0
+        
+        return float(get_similarity_metric(metric)(str1, str2))
+    
+    except Exception as e:
+        logger.warning(
+            f"Error in string_similarity_udf: {e}. "
+            f"Args: str1='{str1}', str2='{str2}', metric='{metric}'"
+        )
+        return 0.0
+
+
 def register_name_matching_udf(db_manager) -> None:
     try:
         logger.info("Registering name matching UDF with DuckDB")
@@ -71,7 +108,7 @@ def register_name_matching_udf(db_manager) -> None:
         db_manager.create_function(
             name='are_names_similar_udf',
             func=are_names_similar_udf,
-            arg_types=['VARCHAR', 'VARCHAR', 'VARCHAR', 'VARCHAR', 'DOUBLE'],
+            arg_types=['VARCHAR', 'VARCHAR', 'VARCHAR', 'VARCHAR', 'DOUBLE', 'BOOLEAN', 'VARCHAR'],
             return_type='BOOLEAN'
         )
         
@@ -83,6 +120,30 @@ def register_name_matching_udf(db_manager) -> None:
         raise RuntimeError(error_msg) from e
 
 
+def register_name_key_udf(db_manager) -> None:
+    try:
+        logger.info("Registering name key UDF with DuckDB")
+        
+        if not hasattr(db_manager, 'create_function'):
+            raise AttributeError(
+                "DatabaseManager instance must have 'create_function' method"
+            )
+        
+        db_manager.create_function(
+            name='name_key_udf',
+            func=name_key_udf,
+            arg_types=['VARCHAR', 'VARCHAR'],
+            return_type='VARCHAR'
+        )
+        
+        logger.info("Successfully registered name_key_udf")
+        
+    except Exception as e:
+        error_msg = f"Failed to register name key UDF: {e}"
+        logger.error(error_msg)
+        raise RuntimeError(error_msg) from e
+
+
 def register_fuzzy_matching_udf(db_manager) -> None:
     try:
         logger.info("Registering fuzzy matching UDF with DuckDB")
@@ -101,6 +162,15 @@ def register_fuzzy_matching_udf(db_manager) -> None:
         
         logger.info("Successfully registered partial_ratio_udf")
         
+        db_manager.create_function(
+            name='string_similarity_udf',
+            func=string_similarity_udf,
+            arg_types=['VARCHAR', 'VARCHAR', 'VARCHAR'],
+            return_type='DOUBLE'
+        )
+        
+        logger.info("Successfully registered string_similarity_udf")
+        
     except Exception as e:
         error_msg = f"Failed to register fuzzy matching UDF: {e}"
         logger.error(error_msg)
@@ -113,6 +183,8 @@ def register_all_udfs(db_manager) -> None:
         
         register_name_matching_udf(db_manager)
         
+        register_name_key_udf(db_manager)
+        
         register_fuzzy_matching_udf(db_manager)
         
         logger.info("Successfully registered all UDFs")
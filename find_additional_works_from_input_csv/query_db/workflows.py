@@ -8,6 +8,7 @@ from query_db.db import DatabaseManager
 from query_db.repository import AuthorReferencesRepository
 from query_db.services import LinkageService, DiscoveryService
 from query_db.analysis.entity_extraction import EntityExtractor
+from query_db.analysis.name_matching import get_similarity_metric
 from query_db.utils import normalize_text, sanitize_file_path_for_sql, validate_column_name
 from query_db.constants import *
 from query_db.udf import register_all_udfs
@@ -45,13 +46,22 @@ class FileProcessor:
             self.input_name_style = self.config.get('input_name_style', 'auto')
             self.reference_name_style = self.config.get('reference_name_style', 'first_last')
             self.matching_threshold = self.config.get('name_matching_threshold', DEFAULT_NAME_THRESHOLD)
+            self.person_registry = self.config.get('person_registry')
+            self.registry_name_style = (self.person_registry or {}).get('name_style', self.input_name_style)
+            self.departments = self.config.get('departments')
             
             self.entity_extraction_enabled = self.config.get('entity_extraction_enabled', True)
             self.entity_matching_threshold = self.config.get('entity_matching_threshold', DEFAULT_ENTITY_THRESHOLD)
             self.use_entity_discovery = self.config.get('use_entity_discovery', True)
+            for key, default in [('name_matching_metric', DEFAULT_NAME_METRIC),
+                                 ('entity_matching_metric', DEFAULT_ENTITY_METRIC)]:
+                get_similarity_metric(self.config.get(key, default))
         except KeyError as e:
             print(f"Error: Config file missing required key: {e}")
             sys.exit(1)
+        except ValueError as e:
+            print(f"Error: Invalid config value: {e}")
+            sys.exit(1)
     
     def run(self, input_file: str, output_file: str):
         print("--- Running in Processing Mode ---")
@@ -68,8 +78,11 @@ class FileProcessor:
         self.full_log_output_file = f"{base_path}{FULL_LOG_SUFFIX}"
         self.discovery_output_file = f"{base_path}{DISCOVERED_WORKS_SUFFIX}"
         self.entity_mappings_file = f"{base_path}{ENTITY_MAPPINGS_SUFFIX}"
+        self.department_worklist_file = f"{base_path}{DEPARTMENT_WORKLIST_SUFFIX}"
+        self.department_summary_file = f"{base_path}{DEPARTMENT_SUMMARY_SUFFIX}"
         
-        for f in [self.linkage_output_file, self.discovery_output_file, self.entity_mappings_file]:
+        for f in [self.linkage_output_file, self.discovery_output_file, self.entity_mappings_file,
+                  self.department_worklist_file, self.department_summary_file]:
             if os.path.exists(f):
                 os.remove(f)
         
@@ -78,6 +91,10 @@ class FileProcessor:
         
         try:
             self._prescan_ids(input_file)
+            if self.person_registry:
+                self._load_person_registry()
+            if self.departments:
+                self._load_departments()
             self._process_linkages(input_file)
             
             entity_mappings = {}
@@ -91,6 +108,9 @@ class FileProcessor:
             
             self._combine_results()
             
+            if self.departments:
+                self._attribute_departments()
+            
             self._print_summary()
             
         except Exception as e:
@@ -107,6 +127,60 @@ class FileProcessor:
         )
         print(f"-> Found {id_count} unique IDs for exclusion.")
     
+    def _load_person_registry(self):
+        try:
+            registry_file = self.person_registry['file']
+            person_id_col = self.person_registry['person_id']
+            name_col = self.person_registry['name']
+        except KeyError as e:
+            print(f"Error: person_registry config missing required key: {e}")
+            sys.exit(1)
+        if not os.path.exists(registry_file):
+            print(f"Error: Person registry file not found at '{registry_file}'")
+            sys.exit(1)
+        
+        print(f"Loading person registry from '{registry_file}'...")
+        person_count, name_count = self.repository.create_person_names_table(
+            registry_file, person_id_col, name_col,
+            self.person_registry.get('orcid'), self.registry_name_style,
+            self.person_registry.get('email'), self.person_registry.get('valid_from'),
+            self.person_registry.get('valid_to')
+        )
+        print(f"-> Loaded {name_count} name variants for {person_count} persons.")
+    
+    def _load_departments(self):
+        try:
+            departments_file = self.departments['file']
+            department_col = self.departments['department']
+        except KeyError as e:
+            print(f"Error: departments config missing required key: {e}")
+            sys.exit(1)
+        person_id_col = self.departments.get('person_id')
+        doi_col = self.departments.get('doi')
+        work_id_col = self.departments.get('work_id')
+        if not person_id_col and not doi_col and not work_id_col:
+            print("Error: departments config must specify 'person_id', 'doi' or 'work_id'")
+            sys.exit(1)
+        if not os.path.exists(departments_file):
+            print(f"Error: Departments file not found at '{departments_file}'")
+            sys.exit(1)
+        if person_id_col and not self.person_registry:
+            print("Warning: departments.person_id is set but no person_registry is configured; "
+                  "findings can only be attributed by work.")
+        
+        print(f"Loading departments from '{departments_file}'...")
+        department_count = self.repository.create_departments_table(
+            departments_file, department_col, person_id_col, doi_col, work_id_col
+        )
+        print(f"-> Loaded {department_count} departments.")
+    
+    def _attribute_departments(self):
+        print("\nAttributing findings to departments...")
+        row_count = self.repository.attribute_findings_to_departments()
+        self.repository.export_department_findings(self.department_worklist_file, self.department_summary_file)
+        print(f"-> {row_count} worklist rows saved to '{self.department_worklist_file}'")
+        print(f"-> Per-department counts saved to '{self.department_summary_file}'")
+    
     def _process_linkages(self, input_file: str):
         print(f"\nProcessing input file in chunks of {self.chunk_size} rows using UDF-based method...")
         
@@ -242,6 +316,8 @@ class FileProcessor:
         if self.entity_extraction_enabled and os.path.exists(self.entity_mappings_file):
             print(f"-> Entity mappings saved to '{self.entity_mappings_file}'")
         print(f"-> Combined discovered works (with match types) saved to '{self.discovery_output_file}'")
+        if self.departments:
+            print(f"-> Department worklist saved to '{self.department_worklist_file}'")
 
 
 class AffiliationSearchProcessor:
@@ -0,0 +1,244 @@
+import os
+import re
+import csv
+import sys
+import argparse
+from collections import Counter, defaultdict
+
+
+MANIFEST_FILE = "manifest.csv"
+TASK_ROW_COL = "task_row"
+STATUS_COL = "status"
+NOTES_COL = "notes"
+
+
+def parse_arguments():
+    parser = argparse.ArgumentParser(
+        description="""
+        Split a review queue into per-curator CSV task files and track their completion.
+
+        1. Split a queue between curators, keeping each department together:
+           python %(prog)s split --input-file results_department_worklist.csv --task-dir tasks/ --curators alice,bob,carol --group-by department
+
+        2. Read the task files back once curators have filled in their status column:
+           python %(prog)s import --input-file results_department_worklist.csv --task-dir tasks/ --output-file reviewed.csv
+        """,
+        formatter_class=argparse.RawTextHelpFormatter
+    )
+    commands = parser.add_subparsers(dest="command", required=True)
+
+    split = commands.add_parser("split", help="Split the queue into task files.")
+    split.add_argument("-i", "--input-file", required=True,
+                       help="Path to the review queue CSV.")
+    split.add_argument("-t", "--task-dir", required=True,
+                       help="Directory to write the task files and their manifest to.")
+    split.add_argument("-c", "--curators", required=True,
+                       help="Comma-separated curator names, or a file with one name per line.")
+    split.add_argument("-g", "--group-by",
+                       help="Column whose rows stay with one curator (e.g. department or member_id). "
+                            "Without it, rows are dealt out round-robin.")
+    split.add_argument("--max-rows", type=int,
+                       help="Maximum rows per task file. A curator with more rows gets numbered files.")
+
+    import_ = commands.add_parser("import", help="Read completed task files back into the queue.")
+    import_.add_argument("-i", "--input-file", required=True,
+                         help="Path to the review queue CSV that was split.")
+    import_.add_argument("-t", "--task-dir", required=True,
+                         help="Directory holding the task files and their manifest.")
+    import_.add_argument("-o", "--output-file", required=True,
+                         help="Path to write the queue with each row's curator, status and notes.")
+
+    args = parser.parse_args()
+
+    if args.command == "split" and args.max_rows is not None and args.max_rows < 1:
+        parser.error("--max-rows must be at least 1.")
+
+    return args
+
+
+def read_queue(input_file):
+    if not os.path.exists(input_file):
+        print(f"Error: Input file not found at '{input_file}'")
+        sys.exit(1)
+    with open(input_file, newline='', encoding='utf-8') as f:
+        reader = csv.DictReader(f)
+        fieldnames = reader.fieldnames or []
+        rows = list(reader)
+    return fieldnames, rows
+
+
+def read_curators(curators):
+    if os.path.isfile(curators):
+        with open(curators, encoding='utf-8') as f:
+            names = [line.strip() for line in f]
+    else:
+        names = [name.strip() for name in curators.split(',')]
+    names = [name for name in names if name]
+    duplicates = [name for name, count in Counter(names).items() if count > 1]
+    if not names:
+        print("Error: No curators given.")
+        sys.exit(1)
+    if duplicates:
+        print(f"Error: Curators listed more than once: {', '.join(duplicates)}")
+        sys.exit(1)
+    return names
+
+
+def assign_rows(rows, curators, group_by=None):
+    """Returns, for each curator, the queue row numbers (1-based) assigned to them.
+
+    Without a group column, rows are dealt out in turn. With one, whole groups are
+    handed out largest first, each to the curator with the fewest rows so far.
+    """
+    assigned = {curator: [] for curator in curators}
+    if not group_by:
+        for i in range(len(rows)):
+            assigned[curators[i % len(curators)]].append(i + 1)
+        return assigned
+
+    groups = defaultdict(list)
+    for i, row in enumerate(rows):
+        groups[row[group_by]].append(i + 1)
+    for _, group_rows in sorted(groups.items(), key=lambda item: (-len(item[1]), item[0])):
+        curator = min(curators, key=lambda name: (len(assigned[name]), curators.index(name)))
+        assigned[curator].extend(group_rows)
+    return assigned
+
+
+def task_file_name(curator, part, parts):
+    safe_name = re.sub(r'[^A-Za-z0-9_-]+', '_', curator).strip('_') or "curator"
+    if parts == 1:
+        return f"{safe_name}.csv"
+    return f"{safe_name}_{part:03d}.csv"
+
+
+def split_queue(args):
+    print("--- Splitting Review Queue ---")
+    fieldnames, rows = read_queue(args.input_file)
+    curators = read_curators(args.curators)
+
+    if args.group_by and args.group_by not in fieldnames:
+        print(f"Error: Column '{args.group_by}' not found in '{args.input_file}'")
+        sys.exit(1)
+    if os.path.exists(os.path.join(args.task_dir, MANIFEST_FILE)):
+        print(f"Error: '{args.task_dir}' already holds a split. Import it or choose another directory.")
+        sys.exit(1)
+    os.makedirs(args.task_dir, exist_ok=True)
+
+    file_names = Counter(task_file_name(curator, 1, 1) for curator in curators + ["manifest"])
+    clashes = [name for name, count in file_names.items() if count > 1]
+    if clashes:
+        print(f"Error: Curator names map to the same task file: {', '.join(clashes)}")
+        sys.exit(1)
+
+    task_fieldnames = [TASK_ROW_COL] + [name for name in fieldnames if name not in (TASK_ROW_COL, STATUS_COL, NOTES_COL)]
+    task_fieldnames += [STATUS_COL, NOTES_COL]
+
+    assigned = assign_rows(rows, curators, args.group_by)
+    manifest = []
+    for curator in curators:
+        row_numbers = assigned[curator]
+        size = args.max_rows or max(len(row_numbers), 1)
+        chunks = [row_numbers[i:i + size] for i in range(0, len(row_numbers), size)]
+        for part, chunk in enumerate(chunks, start=1):
+            task_file = task_file_name(curator, part, len(chunks))
+            with open(os.path.join(args.task_dir, task_file), 'w', newline='', encoding='utf-8') as f:
+                writer = csv.DictWriter(f, fieldnames=task_fieldnames, extrasaction='ignore')
+                writer.writeheader()
+                for row_number in chunk:
+                    writer.writerow({**rows[row_number - 1], TASK_ROW_COL: row_number,
+                                     STATUS_COL: rows[row_number - 1].get(STATUS_COL, ''),
+                                     NOTES_COL: rows[row_number - 1].get(NOTES_COL, '')})
+            manifest.extend((row_number, curator, task_file) for row_number in chunk)
+        print(f"-> {curator}: {len(row_numbers)} rows in {len(chunks)} files")
+
+    with open(os.path.join(args.task_dir, MANIFEST_FILE), 'w', newline='', encoding='utf-8') as f:
+        writer = csv.writer(f)
+        writer.writerow([TASK_ROW_COL, 'curator', 'task_file'])
+        writer.writerows(sorted(manifest))
+
+    print(f"Split {len(rows)} rows between {len(curators)} curators in '{args.task_dir}'.")
+    print(f"Curators mark a row as done by filling in its '{STATUS_COL}' column.")
+
+
+def import_tasks(args):
+    print("--- Importing Task Files ---")
+    fieldnames, rows = read_queue(args.input_file)
+    manifest_file = os.path.join(args.task_dir, MANIFEST_FILE)
+    if not os.path.exists(manifest_file):
+        print(f"Error: No {MANIFEST_FILE} in '{args.task_dir}'. Run the split command first.")
+        sys.exit(1)
+
+    with open(manifest_file, newline='', encoding='utf-8') as f:
+        manifest = {int(entry[TASK_ROW_COL]): entry for entry in csv.DictReader(f)}
+    if len(manifest) != len(rows):
+        print(f"Error: The manifest lists {len(manifest)} rows but '{args.input_file}' has {len(rows)}. "
+              f"Was the queue changed after it was split?")
+        sys.exit(1)
+
+    returned = {}
+    duplicated = set()
+    unknown = []
+    missing_files = []
+    for task_file in sorted({entry['task_file'] for entry in manifest.values()}):
+        path = os.path.join(args.task_dir, task_file)
+        if not os.path.exists(path):
+            missing_files.append(task_file)
+            continue
+        with open(path, newline='', encoding='utf-8') as f:
+            for row in csv.DictReader(f):
+                try:
+                    row_number = int(row.get(TASK_ROW_COL) or '')
+                except ValueError:
+                    unknown.append((task_file, row.get(TASK_ROW_COL)))
+                    continue
+                if row_number not in manifest:
+                    unknown.append((task_file, row_number))
+                    continue
+                if row_number in returned:
+                    duplicated.add(row_number)
+                returned[row_number] = row
+
+    progress = defaultdict(Counter)
+    output_fieldnames = fieldnames + [name for name in ['curator', 'task_file', STATUS_COL, NOTES_COL] if name not in fieldnames]
+    with open(args.output_file, 'w', newline='', encoding='utf-8') as f:
+        writer = csv.DictWriter(f, fieldnames=output_fieldnames)
+        writer.writeheader()
+        for row_number, row in enumerate(rows, start=1):
+            entry = manifest[row_number]
+            task_row = returned.get(row_number)
+            status = (task_row or {}).get(STATUS_COL, '').strip()
+            if task_row is None:
+                progress[entry['curator']]['missing'] += 1
+            elif status:
+                progress[entry['curator']]['done'] += 1
+            else:
+                progress[entry['curator']]['open'] += 1
+            writer.writerow({**row, 'curator': entry['curator'], 'task_file': entry['task_file'],
+                             STATUS_COL: status, NOTES_COL: (task_row or {}).get(NOTES_COL, '')})
+
+    for curator, counts in sorted(progress.items()):
+        total = sum(counts.values())
+        print(f"-> {curator}: {counts['done']}/{total} done, {counts['open']} open"
+              + (f", {counts['missing']} missing" if counts['missing'] else ""))
+
+    if missing_files:
+        print(f"Warning: {len(missing_files)} task files not found: {', '.join(missing_files)}")
+    missing_rows = len(rows) - len(returned)
+    if missing_rows:
+        print(f"Warning: {missing_rows} rows were not returned in any task file and are left open.")
+    if duplicated:
+        print(f"Warning: {len(duplicated)} rows appear more than once in the task files; the last copy was used.")
+    if unknown:
+        print(f"Warning: {len(unknown)} task file rows have no matching {TASK_ROW_COL} and were ignored.")
+
+    done = sum(counts['done'] for counts in progress.values())
+    print(f"{done}/{len(rows)} rows done. Queue with review status saved to '{args.output_file}'.")
+
+
+if __name__ == '__main__':
+    args = parse_arguments()
+    if args.command == "split":
+        split_queue(args)
+    else:
+        import_tasks(args)
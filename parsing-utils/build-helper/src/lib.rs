@@ -0,0 +1,25 @@
+use std::process::Command;
+
+// Records the commit the binary is built from as the GIT_COMMIT environment
+// variable, for --version-info. A build with uncommitted changes to tracked
+// files gets a -dirty suffix, and one outside a git checkout reports "unknown".
+// Called from each tool's build script.
+pub fn record_git_commit() {
+    let commit = match git(&["rev-parse", "--short=12", "HEAD"]) {
+        Some(hash) if git(&["status", "--porcelain", "--untracked-files=no"]).is_some_and(|s| !s.is_empty()) => format!("{}-dirty", hash),
+        Some(hash) => hash,
+        None => "unknown".to_string(),
+    };
+    println!("cargo:rustc-env=GIT_COMMIT={}", commit);
+
+    println!("cargo:rerun-if-changed=src");
+    if let Some(git_dir) = git(&["rev-parse", "--absolute-git-dir"]) {
+        println!("cargo:rerun-if-changed={}/HEAD", git_dir);
+        println!("cargo:rerun-if-changed={}/index", git_dir);
+    }
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
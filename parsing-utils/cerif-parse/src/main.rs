@@ -0,0 +1,587 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser};
+use csv::Writer;
+use flate2::read::GzDecoder;
+use glob::glob;
+use log::{debug, info, warn, LevelFilter};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use simple_logger::SimpleLogger;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use time::macros::format_description;
+
+#[derive(Parser)]
+#[command(name = "CERIF XML Parser")]
+#[command(about = "Flatten CERIF Publication, Person and OrgUnit entities into the pipeline's long field format")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(short, long, help = "CERIF XML file (.xml or .xml.gz) or directory of them", required = true)]
+    input: String,
+
+    #[arg(short, long, default_value = "cerif_field_data.csv", help = "Output CSV file")]
+    output: String,
+
+    #[arg(short, long, help = "Comma-separated list of fields to keep (e.g., 'cfTitle,cfPers_ResPubl.cfPersId'); default all")]
+    fields: Option<String>,
+
+    #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
+    log_level: String,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the output columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+static VERSION_INFO: LazyLock<String> = LazyLock::new(|| format!(
+    "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+    VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+));
+
+// Entity element names, in classic CERIF and in the OpenAIRE CERIF profile, with the
+// entity type written to the output and the classic identifier element.
+const ENTITIES: &[(&str, &str, &str)] = &[
+    ("cfResPubl", "Publication", "cfResPublId"),
+    ("Publication", "Publication", "cfResPublId"),
+    ("cfPers", "Person", "cfPersId"),
+    ("Person", "Person", "cfPersId"),
+    ("cfOrgUnit", "OrgUnit", "cfOrgUnitId"),
+    ("OrgUnit", "OrgUnit", "cfOrgUnitId"),
+];
+
+fn entity_spec(element_name: &str) -> Option<(&'static str, &'static str)> {
+    ENTITIES.iter()
+        .find(|(name, _, _)| *name == element_name)
+        .map(|(_, entity_type, id_element)| (*entity_type, *id_element))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Doi(String);
+
+#[derive(Debug, Clone, Default)]
+struct XmlNode {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    children: Vec<XmlNode>,
+}
+
+impl XmlNode {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    fn child_text(&self, name: &str) -> Option<&str> {
+        self.children.iter()
+            .find(|c| c.name == name)
+            .map(|c| c.text.as_str())
+            .filter(|t| !t.is_empty())
+    }
+}
+
+fn node_from_start(element: &BytesStart) -> XmlNode {
+    let attributes = element.attributes()
+        .filter_map(|a| a.ok())
+        .filter(|a| !a.key.as_ref().starts_with(b"xmlns"))
+        .filter_map(|a| {
+            let key = String::from_utf8_lossy(a.key.local_name().as_ref()).into_owned();
+            a.unescape_value().ok().map(|v| (key, v.trim().to_string()))
+        })
+        .collect();
+    XmlNode {
+        name: String::from_utf8_lossy(element.local_name().as_ref()).into_owned(),
+        attributes,
+        text: String::new(),
+        children: Vec::new(),
+    }
+}
+
+#[derive(Debug)]
+struct Entity {
+    entity_type: &'static str,
+    id_element: &'static str,
+    record_id: String,
+    node: XmlNode,
+}
+
+// Top-level elements of a classic <CERIF> document that are not entities themselves:
+// link entities (cfPers_ResPubl, ...), multilingual entities (cfResPublTitle, ...) and
+// federated identifiers (cfFedId). They are attached to the entities whose ids they carry.
+#[derive(Debug, Default)]
+struct ParsedFile {
+    entities: Vec<Entity>,
+    attachments: Vec<XmlNode>,
+}
+
+fn open_input(path: &Path) -> Result<Box<dyn BufRead>> {
+    let file = File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    if path.extension().and_then(|e| e.to_str()) == Some("gz") {
+        Ok(Box::new(BufReader::new(GzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+fn parse_file(path: &Path) -> Result<ParsedFile> {
+    let mut reader = Reader::from_reader(open_input(path)?);
+    let mut buf = Vec::new();
+    let mut parsed = ParsedFile::default();
+    // Names of the open elements outside any captured subtree.
+    let mut open_elements: Vec<String> = Vec::new();
+    // The subtree being captured, and whether its root is an entity.
+    let mut stack: Vec<XmlNode> = Vec::new();
+    let mut capturing_entity = false;
+
+    loop {
+        let event = reader.read_event_into(&mut buf)
+            .with_context(|| format!("Malformed XML in {} at byte {}", path.display(), reader.buffer_position()))?;
+        match event {
+            Event::Start(e) => {
+                let node = node_from_start(&e);
+                if !stack.is_empty() {
+                    stack.push(node);
+                } else if entity_spec(&node.name).is_some() {
+                    capturing_entity = true;
+                    stack.push(node);
+                } else if open_elements.last().is_some_and(|p| p == "CERIF") {
+                    capturing_entity = false;
+                    stack.push(node);
+                } else {
+                    open_elements.push(node.name);
+                }
+            }
+            Event::Empty(e) => {
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(node_from_start(&e));
+                }
+            }
+            Event::Text(t) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&t.unescape().context("Invalid XML text")?);
+                }
+            }
+            Event::CData(c) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&String::from_utf8_lossy(&c));
+                }
+            }
+            Event::End(_) => {
+                if let Some(mut node) = stack.pop() {
+                    node.text = node.text.trim().to_string();
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(node),
+                        None if capturing_entity => {
+                            let (entity_type, id_element) = entity_spec(&node.name).expect("captured entity has a known name");
+                            let record_id = node.attr("id")
+                                .or_else(|| node.child_text(id_element))
+                                .unwrap_or("")
+                                .to_string();
+                            parsed.entities.push(Entity { entity_type, id_element, record_id, node });
+                        }
+                        None => parsed.attachments.push(node),
+                    }
+                } else {
+                    open_elements.pop();
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(parsed)
+}
+
+fn normalize_doi(raw: &str) -> Option<Doi> {
+    normalization::normalize_doi(raw).map(Doi)
+}
+
+// OpenAIRE profile publications carry <DOI>; classic CERIF uses a cfFedId whose
+// value is the DOI.
+fn find_doi(node: &XmlNode) -> Option<Doi> {
+    for child in &node.children {
+        let candidate = match child.name.as_str() {
+            "DOI" | "cfDOI" => normalize_doi(&child.text),
+            "cfFedId" => child.child_text("cfFedId").and_then(normalize_doi),
+            _ => None,
+        };
+        if candidate.is_some() {
+            return candidate;
+        }
+    }
+    None
+}
+
+// Merges duplicate entities and attaches top-level link, multilingual and federated
+// identifier records to the entities they reference. Returns the number of
+// attachments that referenced no entity in the file.
+fn resolve(parsed: &mut ParsedFile) -> usize {
+    let mut merged: Vec<Entity> = Vec::with_capacity(parsed.entities.len());
+    let mut index: HashMap<(&'static str, String), usize> = HashMap::new();
+    for entity in parsed.entities.drain(..) {
+        if entity.record_id.is_empty() {
+            merged.push(entity);
+            continue;
+        }
+        match index.get(&(entity.entity_type, entity.record_id.clone())) {
+            Some(&i) => merged[i].node.children.extend(entity.node.children),
+            None => {
+                index.insert((entity.entity_type, entity.record_id.clone()), merged.len());
+                merged.push(entity);
+            }
+        }
+    }
+
+    let mut unattached = 0;
+    for attachment in parsed.attachments.drain(..) {
+        let mut targets = Vec::new();
+        for (_, entity_type, id_element) in ENTITIES {
+            if let Some(id) = attachment.child_text(id_element) {
+                if let Some(&i) = index.get(&(*entity_type, id.to_string())) {
+                    if !targets.contains(&i) {
+                        targets.push(i);
+                    }
+                }
+            }
+        }
+        // cfFedId names its owner through cfInstId, whatever the owner's type.
+        if let Some(inst_id) = attachment.child_text("cfInstId") {
+            for entity_type in ["Publication", "Person", "OrgUnit"] {
+                if let Some(&i) = index.get(&(entity_type, inst_id.to_string())) {
+                    if !targets.contains(&i) {
+                        targets.push(i);
+                    }
+                }
+            }
+        }
+        if targets.is_empty() {
+            debug!("Top-level <{}> references no entity in this file", attachment.name);
+            unattached += 1;
+            continue;
+        }
+        for i in targets {
+            let mut attached = attachment.clone();
+            let own_id = merged[i].id_element;
+            attached.children.retain(|c| c.name != own_id && c.name != "cfInstId");
+            merged[i].node.children.push(attached);
+        }
+    }
+
+    parsed.entities = merged;
+    unattached
+}
+
+fn is_link_or_list_item(parent: &XmlNode, child: &XmlNode) -> bool {
+    // Link entities (cfPers_ResPubl) and OpenAIRE list items (Authors/Author) repeat.
+    child.name.contains('_') || parent.name == format!("{}s", child.name)
+}
+
+// Flattens an entity into (subfield_path, value) rows. Link entities, list items
+// and any element repeated among its siblings are indexed; attributes become keys
+// beneath their element.
+fn flatten(node: &XmlNode, path: &str, skip: Option<&str>, rows: &mut Vec<(String, String)>) {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for child in &node.children {
+        *counts.entry(child.name.as_str()).or_insert(0) += 1;
+    }
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for child in &node.children {
+        if skip == Some(child.name.as_str()) {
+            continue;
+        }
+        let position = seen.entry(child.name.as_str()).or_insert(0);
+        let name = if counts[child.name.as_str()] > 1 || is_link_or_list_item(node, child) {
+            format!("{}[{}]", child.name, position)
+        } else {
+            child.name.clone()
+        };
+        *position += 1;
+        let child_path = if path.is_empty() { name } else { format!("{}.{}", path, name) };
+
+        if child.children.is_empty() && !child.text.is_empty() {
+            rows.push((child_path.clone(), child.text.clone()));
+        }
+        for (key, value) in &child.attributes {
+            if !value.is_empty() {
+                rows.push((format!("{}.{}", child_path, key), value.clone()));
+            }
+        }
+        flatten(child, &child_path, None, rows);
+    }
+}
+
+fn field_name_of(path: &str) -> String {
+    let mut name = String::with_capacity(path.len());
+    let mut in_index = false;
+    for c in path.chars() {
+        match c {
+            '[' => in_index = true,
+            ']' => in_index = false,
+            _ if !in_index => name.push(c),
+            _ => {}
+        }
+    }
+    name
+}
+
+fn find_input_files(input: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if !path.is_dir() {
+        return Err(anyhow::anyhow!("Input path does not exist: {}", input));
+    }
+    let mut files = Vec::new();
+    for pattern in ["**/*.xml", "**/*.xml.gz"] {
+        let full = path.join(pattern);
+        files.extend(glob(&full.to_string_lossy())?.filter_map(Result::ok));
+    }
+    files.sort();
+    if files.is_empty() {
+        warn!("No .xml or .xml.gz files found under: {}", input);
+    }
+    Ok(files)
+}
+
+fn parse_field_filter(fields: &Option<String>) -> Option<HashSet<String>> {
+    fields.as_ref().map(|spec| {
+        spec.split(',')
+            .map(|f| f.trim().to_string())
+            .filter(|f| !f.is_empty())
+            .collect()
+    })
+}
+
+#[derive(Debug, Default)]
+struct ExtractionStats {
+    entities: HashMap<&'static str, usize>,
+    entities_without_id: usize,
+    publications_with_doi: usize,
+    unattached: usize,
+    rows: usize,
+}
+
+fn write_entities<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    entities: &[Entity],
+    fields: &Option<HashSet<String>>,
+    source: &str,
+    stats: &mut ExtractionStats,
+) -> Result<()> {
+    for entity in entities {
+        *stats.entities.entry(entity.entity_type).or_insert(0) += 1;
+        if entity.record_id.is_empty() {
+            stats.entities_without_id += 1;
+            warn!("Skipping <{}> without an id in {}", entity.node.name, source);
+            continue;
+        }
+        let doi = if entity.entity_type == "Publication" { find_doi(&entity.node) } else { None };
+        if doi.is_some() {
+            stats.publications_with_doi += 1;
+        }
+        let doi_str = doi.as_ref().map(|d| d.0.as_str()).unwrap_or("");
+        let prefix = doi_str.split_once('/').map(|(p, _)| p).unwrap_or("");
+
+        let mut rows = Vec::new();
+        for (key, value) in &entity.node.attributes {
+            if key != "id" && !value.is_empty() {
+                rows.push((key.clone(), value.clone()));
+            }
+        }
+        flatten(&entity.node, "", Some(entity.id_element), &mut rows);
+
+        for (subfield_path, value) in rows {
+            let field_name = field_name_of(&subfield_path);
+            if fields.as_ref().is_some_and(|f| !f.contains(&field_name)) {
+                continue;
+            }
+            writer.write_record([
+                entity.entity_type,
+                &entity.record_id,
+                doi_str,
+                &field_name,
+                &subfield_path,
+                &value,
+                prefix,
+                source,
+            ])?;
+            stats.rows += 1;
+        }
+    }
+    Ok(())
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let total_secs = elapsed.as_secs();
+    let minutes = total_secs / 60;
+    let seconds = total_secs % 60;
+    let millis = elapsed.subsec_millis();
+
+    if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}.{:03}s", seconds, millis)
+    }
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let start_time = Instant::now();
+    let cli = Cli::parse();
+
+    setup_logging(&cli.log_level)?;
+    info!("Starting CERIF XML parser");
+
+    let files = find_input_files(&cli.input)?;
+    info!("Found {} files to process.", files.len());
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let output_path = PathBuf::from(&cli.output);
+    if let Some(parent) = output_path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory structure for: {}", output_path.display()))?;
+        }
+    }
+    let mut writer = Writer::from_path(&output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path.display()))?;
+    writer.write_record([
+        "entity_type", "record_id", "doi", "field_name", "subfield_path", "value", "doi_prefix", "source_file_path",
+    ])?;
+
+    let fields = parse_field_filter(&cli.fields);
+    let mut stats = ExtractionStats::default();
+    let mut files_with_errors = 0;
+    for file in &files {
+        let mut parsed = match parse_file(file) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                warn!("Error parsing {}: {:#}", file.display(), e);
+                files_with_errors += 1;
+                continue;
+            }
+        };
+        stats.unattached += resolve(&mut parsed);
+        write_entities(&mut writer, &parsed.entities, &fields, &file.to_string_lossy(), &mut stats)?;
+    }
+    writer.flush()?;
+
+    info!("-------------------- FINAL SUMMARY --------------------");
+    info!("Total execution time: {}", format_elapsed(start_time.elapsed()));
+    info!("Files: {} ({} with errors)", files.len(), files_with_errors);
+    for entity_type in ["Publication", "Person", "OrgUnit"] {
+        info!("{} entities: {}", entity_type, stats.entities.get(entity_type).copied().unwrap_or(0));
+    }
+    info!("Publications with DOI: {}", stats.publications_with_doi);
+    if stats.entities_without_id > 0 {
+        info!("Entities skipped without an id: {}", stats.entities_without_id);
+    }
+    if stats.unattached > 0 {
+        info!("Top-level link/multilingual records referencing no entity: {}", stats.unattached);
+    }
+    info!("Rows written: {} -> {}", stats.rows, cli.output);
+    info!("-------------------------------------------------------");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flatten_file(name: &str, xml: &str) -> (String, ExtractionStats) {
+        let path = std::env::temp_dir().join(format!("cerif-parse-{}-{}", std::process::id(), name));
+        fs::write(&path, xml).unwrap();
+        let parsed = parse_file(&path);
+        fs::remove_file(&path).unwrap();
+        let mut parsed = parsed.unwrap();
+
+        let mut stats = ExtractionStats::default();
+        stats.unattached += resolve(&mut parsed);
+        let mut writer = Writer::from_writer(Vec::new());
+        write_entities(&mut writer, &parsed.entities, &None, "export.xml", &mut stats).unwrap();
+        (String::from_utf8(writer.into_inner().unwrap()).unwrap(), stats)
+    }
+
+    #[test]
+    fn classic_cerif_attaches_links_and_multilingual_records() {
+        let (rows, stats) = flatten_file("classic.xml", r#"<?xml version="1.0"?>
+<CERIF xmlns="urn:xmlns:org:eurocris:cerif-1.6-2">
+  <cfResPubl><cfResPublId>pub-1</cfResPublId><cfResPublDate>2020-05-01</cfResPublDate></cfResPubl>
+  <cfPers><cfPersId>pers-1</cfPersId><cfGender>f</cfGender></cfPers>
+  <cfResPublTitle><cfResPublId>pub-1</cfResPublId><cfLangCode>en</cfLangCode><cfTitle cfTrans="o">Graphene sensors</cfTitle></cfResPublTitle>
+  <cfPers_ResPubl><cfPersId>pers-1</cfPersId><cfResPublId>pub-1</cfResPublId><cfClassId>author</cfClassId></cfPers_ResPubl>
+  <cfFedId><cfFedIdId>f1</cfFedIdId><cfInstId>pub-1</cfInstId><cfFedId>https://doi.org/10.1234/ABC</cfFedId></cfFedId>
+  <cfPers_OrgUnit><cfPersId>pers-9</cfPersId><cfOrgUnitId>org-9</cfOrgUnitId></cfPers_OrgUnit>
+</CERIF>
+"#);
+        assert_eq!(rows, "\
+Publication,pub-1,10.1234/abc,cfResPublDate,cfResPublDate,2020-05-01,10.1234,export.xml
+Publication,pub-1,10.1234/abc,cfResPublTitle.cfLangCode,cfResPublTitle.cfLangCode,en,10.1234,export.xml
+Publication,pub-1,10.1234/abc,cfResPublTitle.cfTitle,cfResPublTitle.cfTitle,Graphene sensors,10.1234,export.xml
+Publication,pub-1,10.1234/abc,cfResPublTitle.cfTitle.cfTrans,cfResPublTitle.cfTitle.cfTrans,o,10.1234,export.xml
+Publication,pub-1,10.1234/abc,cfPers_ResPubl.cfPersId,cfPers_ResPubl[0].cfPersId,pers-1,10.1234,export.xml
+Publication,pub-1,10.1234/abc,cfPers_ResPubl.cfClassId,cfPers_ResPubl[0].cfClassId,author,10.1234,export.xml
+Publication,pub-1,10.1234/abc,cfFedId.cfFedIdId,cfFedId.cfFedIdId,f1,10.1234,export.xml
+Publication,pub-1,10.1234/abc,cfFedId.cfFedId,cfFedId.cfFedId,https://doi.org/10.1234/ABC,10.1234,export.xml
+Person,pers-1,,cfGender,cfGender,f,,export.xml
+Person,pers-1,,cfPers_ResPubl.cfResPublId,cfPers_ResPubl[0].cfResPublId,pub-1,,export.xml
+Person,pers-1,,cfPers_ResPubl.cfClassId,cfPers_ResPubl[0].cfClassId,author,,export.xml
+");
+        assert_eq!(stats.unattached, 1);
+        assert_eq!(stats.publications_with_doi, 1);
+    }
+
+    #[test]
+    fn openaire_profile_indexes_list_items_and_keeps_nested_people() {
+        let (rows, stats) = flatten_file("openaire.xml", r#"<Publication xmlns="https://www.openaire.eu/cerif-profile/1.1/" id="p1">
+  <Title xml:lang="en">A title</Title>
+  <DOI>10.1234/XYZ</DOI>
+  <Authors>
+    <Author><DisplayName>Doe, Ann</DisplayName><Person id="a1"/></Author>
+    <Author><DisplayName>Roe, Bo</DisplayName></Author>
+  </Authors>
+</Publication>
+"#);
+        assert_eq!(rows, "\
+Publication,p1,10.1234/xyz,Title,Title,A title,10.1234,export.xml
+Publication,p1,10.1234/xyz,Title.lang,Title.lang,en,10.1234,export.xml
+Publication,p1,10.1234/xyz,DOI,DOI,10.1234/XYZ,10.1234,export.xml
+Publication,p1,10.1234/xyz,Authors.Author.DisplayName,Authors.Author[0].DisplayName,\"Doe, Ann\",10.1234,export.xml
+Publication,p1,10.1234/xyz,Authors.Author.Person.id,Authors.Author[0].Person.id,a1,10.1234,export.xml
+Publication,p1,10.1234/xyz,Authors.Author.DisplayName,Authors.Author[1].DisplayName,\"Roe, Bo\",10.1234,export.xml
+");
+        assert_eq!(stats.entities.get("Person"), None);
+    }
+}
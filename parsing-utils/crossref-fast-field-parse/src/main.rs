@@ -1,39 +1,74 @@
 use anyhow::{Context, Result};
-use clap::Parser;
-use csv::Writer;
+#[cfg(feature = "arrow")]
+use arrow_array::builder::StringBuilder;
+#[cfg(feature = "arrow")]
+use arrow_array::{ArrayRef, RecordBatch};
+#[cfg(feature = "arrow")]
+use arrow_ipc::writer::FileWriter;
+#[cfg(feature = "arrow")]
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use clap::{ArgAction, ArgGroup, Parser, ValueEnum};
 use crossbeam_channel::{bounded, Receiver, Sender};
-use dashmap::{DashMap, DashSet};
-use flate2::read::GzDecoder;
+use fast_field_common::{
+    canonical_json, disk_space, error_sidecar, http_input, input_manifest, json_array, missing_feature, output_compare,
+    parquet_input, provenance, remote_input, remote_output, snapshot_format, snapshot_lock, throttle, validation, zip_input,
+};
+use flate2::bufread::GzDecoder;
+use flate2::write::GzEncoder;
 use glob::glob;
 use indicatif::{ProgressBar, ProgressStyle};
 use lazy_static::lazy_static;
 use log::{debug, error, info, warn, LevelFilter};
+#[cfg(feature = "parquet")]
+use parquet::basic::{Compression, ZstdLevel};
+#[cfg(feature = "parquet")]
+use parquet::data_type::{ByteArray, ByteArrayType};
+#[cfg(feature = "parquet")]
+use parquet::file::properties::WriterProperties;
+#[cfg(feature = "parquet")]
+use parquet::file::writer::SerializedFileWriter;
+#[cfg(feature = "parquet")]
+use parquet::schema::parser::parse_message_type;
+#[cfg(feature = "parquet")]
+use parquet::schema::types::ColumnPath;
 use rayon::prelude::*;
+#[cfg(feature = "sqlite")]
+use rusqlite::Connection;
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use simple_logger::SimpleLogger;
+use std::cell::Cell;
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
 use std::fs::{self, File, OpenOptions};
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 use time::macros::format_description;
-#[cfg(target_os = "linux")]
-use std::fs::read_to_string;
 #[cfg(target_os = "windows")]
 use std::process::Command as WinCommand;
 
 #[derive(Parser)]
 #[command(name = "Crossref Data File Fast Field Parser")]
 #[command(about = "Efficiently extract field data from the Crossref data file in its compressed JSONL.gz format")]
-#[command(version = "1.1.")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+#[command(group(ArgGroup::new("source").required(true).args(["input", "input_manifest"])))]
 struct Cli {
-    #[arg(short, long, help = "Directory containing JSONL.gz files", required = true)]
-    input: String,
+    #[arg(short, long, help = "Directory containing JSONL files (.jsonl.gz, .jsonl.zst, .jsonl or .ndjson) or Parquet files, a tar or zip archive of them, an s3://, az:// or gs://bucket/prefix URI, an http(s):// URL or a file listing URLs, or - for stdin")]
+    input: Option<String>,
 
-    #[arg(short, long, default_value = "field_data.csv", help = "Output CSV file or directory")]
+    #[arg(long, help = "File listing the input files to read, one path per line, each optionally followed by a tab and the number of records it should hold. Used instead of --input")]
+    input_manifest: Option<String>,
+
+    #[arg(long, conflicts_with = "input_manifest", help = "Glob pattern, relative to the --input directory, selecting the files to read instead of every file with an input extension (e.g. 'updated_date=2024-*/**/*.gz')")]
+    glob: Option<String>,
+
+    #[arg(short, long, default_value = "field_data.csv", help = "Output CSV file or directory, or an s3://, az:// or gs:// URI to upload it to")]
     output: String,
 
     #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
@@ -46,20 +81,512 @@ struct Cli {
     batch_size: usize,
 
 
-    #[arg(short = 'g', long, help = "Organize output by member ID")]
+    #[arg(short = 'g', long, help = "Organize output into one file per member ID, or per DOI prefix or publication year with --organize-by")]
     organize: bool,
 
+    #[arg(long, value_enum, default_value_t = OrganizeBy::Member, requires = "organize", help = "With --organize, what the CSV output files are split by: member (one file per member ID), prefix (one file per DOI prefix) or year (one year=YYYY directory per issued year)")]
+    organize_by: OrganizeBy,
+
+    #[arg(long, value_enum, default_value_t = OutputFormat::Csv, help = "Output file format: csv, parquet (a single Parquet file with dictionary-encoded field_name, member_id and doi_prefix columns), arrow (Arrow IPC files, also known as Feather, that can be memory-mapped; one per member with --organize), sqlite (a single SQLite database indexed on doi and field_name), avro (a single Avro object container file with the schema embedded), sled (a key-value database mapping each DOI to its fields, for lookups with the lookup command) or xlsx (a spreadsheet with typed cells and a stats sheet, for small runs; chosen by default when -o ends in .xlsx)")]
+    output_format: OutputFormat,
+
+    #[arg(long, value_enum, help = "Compress CSV output: gz (gzip) or zst (Zstandard); under --organize, .gz or .zst is added to each member file name")]
+    compress_output: Option<OutputCompression>,
+
+    #[arg(long, requires = "compress_output", help = "Compression level for --compress-output: 0-9 for gz (default 6), 1-22 for zst (default 3)")]
+    compress_level: Option<i32>,
+
+    #[arg(long, default_value = ",", value_parser = parse_delimiter, help = "Field delimiter for CSV output: a single ASCII character, or 'tab' (also '\\t') for TSV")]
+    delimiter: u8,
+
+    #[arg(long, value_enum, default_value_t = QuoteStyle::Necessary, help = "When CSV output fields are quoted: necessary (only fields holding the delimiter, a quote or a line break), always, non-numeric or never (fields are written as is, even if that breaks the row)")]
+    quote_style: QuoteStyle,
+
+    #[arg(long, value_enum, default_value_t = Terminator::Lf, help = "Line ending for CSV output: lf or crlf")]
+    terminator: Terminator,
+
+    #[arg(long, help = "Write one CSV row per DOI with one column per requested field, instead of one row per extracted value. Rows are grouped in memory until the run ends")]
+    pivot: bool,
+
+    #[arg(long, help = "Add source_file_path and source_line columns giving the input file (or archive member) and line each row was extracted from")]
+    source_columns: bool,
+
+    #[arg(long, requires = "pivot", default_value = "; ", help = "Separator between the values of a field that has several for one DOI (used with --pivot)")]
+    pivot_delimiter: String,
+
     #[arg(long, help = "Filter by member ID")]
     member: Option<String>,
 
     #[arg(long, help = "Filter by DOI prefix")]
     doi_prefix: Option<String>,
 
+    #[arg(long, help = "Only extract records deposited on or after this ISO 8601 date/time (deposited.date-time)")]
+    deposited_from: Option<String>,
+
+    #[arg(long, help = "Only extract records deposited on or before this ISO 8601 date/time (deposited.date-time)")]
+    deposited_until: Option<String>,
+
     #[arg(long, default_value = "100", help = "Maximum number of open files when using --organize")]
     max_open_files: usize,
 
-    #[arg(short, long, help = "Comma-separated list of fields to extract (e.g., 'author.family,title,ISSN')")]
-    fields: String,
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), help = "Split CSV output into files of at most N rows: part-00001.csv, part-00002.csv, ... in the --output directory, or in a directory per member with --organize")]
+    max_rows_per_file: Option<u64>,
+
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), help = "Write buffered CSV rows out to the output files after every N rows, so that a crash loses at most the rows since the last flush (checked after each batch)")]
+    flush_every_rows: Option<u64>,
+
+    #[arg(long, value_enum, default_value_t = Fsync::Never, help = "When output files are synced to disk: never (left to the operating system), on-close (as each file is closed) or periodic (also at every --flush-every-rows flush)")]
+    fsync: Fsync,
+
+    #[arg(long, requires = "organize", help = "With --organize, also write _index.json with each member's files, row count, DOI count and a Bloom filter of its DOIs")]
+    doi_index: bool,
+
+    #[arg(short, long, help = "Comma-separated list of fields to extract (e.g., 'author.family,title,ISSN'). Required unless --record-kind is grants, peer-reviews or components, which have their own defaults")]
+    fields: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = JsonShape::Datafile, help = "Shape of each input line: datafile (a bare work, as in the public data file) or api (a REST API response wrapping one work or a page of works in 'message')")]
+    json_shape: JsonShape,
+
+    #[arg(long, value_enum, default_value_t = InputFormat::Jsonl, help = "Layout of each input file: jsonl (one JSON document per line), json-array (one JSON array of documents, streamed element by element) or torrent (a chunk of the Crossref public data file torrent: one JSON object whose \"items\" array holds the works)")]
+    format: InputFormat,
+
+    #[arg(long, value_enum, default_value_t = RecordKind::Works, help = "Kind of record to extract: works (all records keyed by DOI), grants (grant records only, with an award column), peer-reviews (peer review records only, with the reviewed DOI) or components (component records only, with the DOI of the work they are part of)")]
+    record_kind: RecordKind,
+
+    #[arg(long, default_value = "parent_links.csv", help = "Output CSV linking each peer review or component DOI to its parent works (used with --record-kind peer-reviews or components)")]
+    links_output: String,
+
+    #[arg(long, help = "JSON file of per-field validation rules (regex, enum, url, date_range)")]
+    validation_rules: Option<String>,
+
+    #[arg(long, default_value = "validation_violations.csv", help = "Output CSV for the validation violations report (used with --validation-rules)")]
+    violations_report: String,
+
+    #[arg(long = "max-read-MBps", help = "Cap combined input read throughput across all threads, in MB/s")]
+    max_read_mbps: Option<f64>,
+
+    #[arg(long, help = "JSONL sidecar recording records that failed to parse or panicked during extraction (default: next to the output)")]
+    error_sidecar: Option<String>,
+
+    #[arg(long, help = "Snapshot lock file: written on the first run, and on later runs the extraction refuses to start if inputs, tool version, schema files or settings differ from it")]
+    pin: Option<String>,
+
+    #[arg(long, requires = "pin", help = "With --pin, record a SHA-256 of every local input file instead of its modification time, so a restored or re-downloaded copy of the same snapshot still matches the lock. Reads every input once more before extraction")]
+    pin_hash: bool,
+
+    #[arg(long, help = "Skip the check that the output filesystem has room for the estimated output")]
+    no_space_check: bool,
+
+    #[arg(long, help = "Skip the check that the input looks like Crossref metadata rather than an OpenAlex snapshot or other JSON")]
+    no_format_check: bool,
+
+    #[arg(long, value_enum, value_name = "GOAL", help = "Instead of extracting, sample the input and report how often the fields relevant to GOAL (authorship, funding or venue) are filled, ending with a --fields list to extract them")]
+    suggest_fields: Option<SuggestGoal>,
+
+    #[arg(long, value_name = "PREVIOUS_OUTPUT", help = "After extracting, compare the CSV output row by row with a previous run's output (a file, or a directory from --organize) and fail if any rows differ")]
+    compare_with: Option<String>,
+
+    #[arg(long, default_value = "comparison_report.csv", help = "Output CSV listing the rows removed or added relative to --compare-with")]
+    compare_report: String,
+
+    #[arg(long, alias = "s3-no-sign-request", help = "Read s3://, az:// or gs:// input without signing requests, as public buckets require")]
+    no_sign_request: bool,
+
+    #[arg(long, help = "Local directory where a remote --output is written before it is uploaded (default: the system temporary directory)")]
+    staging_dir: Option<String>,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.1.";
+
+// Bump when the output columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The build that wrote an output, as recorded in snapshot locks and provenance.
+fn tool() -> Value {
+    serde_json::json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_commit": env!("GIT_COMMIT"),
+        "output_schema": OUTPUT_SCHEMA_VERSION,
+    })
+}
+
+// Source schemas whose structure is built into the binary.
+const BUNDLED_SCHEMAS: &[&str] = &["Crossref REST API v1 works"];
+
+// The --version-info report: enough to tell which build wrote a file.
+lazy_static! {
+    static ref VERSION_INFO: String = format!(
+        "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+        VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), BUNDLED_SCHEMAS.join(", "), OUTPUT_SCHEMA_VERSION
+    );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RecordKind {
+    Works,
+    Grants,
+    PeerReviews,
+    Components,
+}
+
+impl RecordKind {
+    // The record type a mode extracts; other records are skipped.
+    fn required_type(self) -> Option<&'static str> {
+        match self {
+            RecordKind::Works => None,
+            RecordKind::Grants => Some("grant"),
+            RecordKind::PeerReviews => Some("peer-review"),
+            RecordKind::Components => Some("component"),
+        }
+    }
+
+    // The relation under `relation` that points from a record to its parent works.
+    fn parent_relation(self) -> Option<&'static str> {
+        match self {
+            RecordKind::PeerReviews => Some("is-review-of"),
+            RecordKind::Components => Some("is-part-of"),
+            RecordKind::Works | RecordKind::Grants => None,
+        }
+    }
+
+    // The column added after `doi`, holding the award or parent DOI.
+    fn link_column(self) -> Option<&'static str> {
+        match self {
+            RecordKind::Works => None,
+            RecordKind::Grants => Some("award"),
+            RecordKind::PeerReviews | RecordKind::Components => Some("parent_doi"),
+        }
+    }
+
+    fn default_fields(self) -> Option<&'static [&'static str]> {
+        match self {
+            RecordKind::Works => None,
+            RecordKind::Grants => Some(GRANT_DEFAULT_FIELDS),
+            RecordKind::PeerReviews => Some(PEER_REVIEW_DEFAULT_FIELDS),
+            RecordKind::Components => Some(COMPONENT_DEFAULT_FIELDS),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum InputFormat {
+    Jsonl,
+    JsonArray,
+    Torrent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputCompression {
+    Gz,
+    Zst,
+}
+
+impl OutputCompression {
+    fn levels(self) -> std::ops::RangeInclusive<i32> {
+        match self {
+            OutputCompression::Gz => 0..=9,
+            OutputCompression::Zst => 1..=22,
+        }
+    }
+
+    fn default_level(self) -> i32 {
+        match self {
+            OutputCompression::Gz => 6,
+            OutputCompression::Zst => zstd::DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CsvCompression {
+    format: OutputCompression,
+    level: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum QuoteStyle {
+    Necessary,
+    Always,
+    NonNumeric,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Terminator {
+    Lf,
+    Crlf,
+}
+
+// How CSV output rows are delimited, quoted and ended. The default matches the
+// csv crate's, which the link table and comparison report always use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CsvDialect {
+    delimiter: u8,
+    quote_style: QuoteStyle,
+    terminator: Terminator,
+}
+
+impl Default for CsvDialect {
+    fn default() -> Self {
+        Self { delimiter: b',', quote_style: QuoteStyle::Necessary, terminator: Terminator::Lf }
+    }
+}
+
+// When output files are synced to disk. Without syncing, a crash can lose
+// whatever the operating system had not written back yet, even from files the
+// run had already closed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum Fsync {
+    #[default]
+    Never,
+    OnClose,
+    Periodic,
+}
+
+// Syncs an output file that its writer has already closed. The Parquet, SQLite
+// and XLSX writers do not hand the file back, so it is opened again.
+#[cfg(any(feature = "parquet", feature = "sqlite", feature = "xlsx"))]
+fn sync_closed_file(path: &Path, fsync: Fsync) -> Result<()> {
+    if fsync == Fsync::Never {
+        return Ok(());
+    }
+    OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.sync_all())
+        .with_context(|| format!("Failed to sync output file to disk: {}", path.display()))
+}
+
+// Settings shared by the CSV output strategies.
+#[derive(Debug, Clone, Copy, Default)]
+struct CsvSettings {
+    compression: Option<CsvCompression>,
+    dialect: CsvDialect,
+    max_rows_per_file: Option<u64>,
+    flush_every_rows: Option<u64>,
+    fsync: Fsync,
+}
+
+fn parse_delimiter(value: &str) -> Result<u8, String> {
+    let delimiter = match value {
+        "tab" | "\\t" | "\t" => b'\t',
+        _ if value.len() == 1 && value.is_ascii() => value.as_bytes()[0],
+        _ => return Err(format!("expected a single ASCII character or 'tab', got '{}'", value)),
+    };
+    if matches!(delimiter, b'"' | b'\n' | b'\r') {
+        return Err("the delimiter cannot be a quote or a line break".to_string());
+    }
+    Ok(delimiter)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Csv,
+    Parquet,
+    Arrow,
+    Sqlite,
+    Avro,
+    Sled,
+    Xlsx,
+}
+
+impl OutputFormat {
+    // The cargo feature the format's writer is built with, if this binary was
+    // built without it.
+    fn missing_feature(self) -> Option<&'static str> {
+        let (feature, enabled) = match self {
+            OutputFormat::Csv | OutputFormat::Avro => return None,
+            OutputFormat::Parquet => ("parquet", cfg!(feature = "parquet")),
+            OutputFormat::Arrow => ("arrow", cfg!(feature = "arrow")),
+            OutputFormat::Sqlite => ("sqlite", cfg!(feature = "sqlite")),
+            OutputFormat::Sled => ("sled", cfg!(feature = "sled")),
+            OutputFormat::Xlsx => ("xlsx", cfg!(feature = "xlsx")),
+        };
+        (!enabled).then_some(feature)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OrganizeBy {
+    Member,
+    Prefix,
+    Year,
+}
+
+impl OrganizeBy {
+    fn key(self, field_data: &FieldData) -> &str {
+        match self {
+            OrganizeBy::Member => &field_data.member_id.0,
+            OrganizeBy::Prefix => &field_data.doi_prefix.0,
+            OrganizeBy::Year if field_data.year.is_empty() => "unknown",
+            OrganizeBy::Year => &field_data.year,
+        }
+    }
+
+    // Years are written Hive-style, as year=YYYY/part-00001.csv, so that
+    // downstream tools can read the output directory as a partitioned dataset.
+    // Output split by --max-rows-per-file gets a directory per key as well.
+    fn file_name(self, key: &str, part: Option<u32>, compression: Option<CsvCompression>) -> String {
+        match (self, part) {
+            (OrganizeBy::Year, part) => format!("year={}/{}", key, CsvSink::part_file_name(part.unwrap_or(1), compression)),
+            (_, Some(part)) => format!("{}/{}", key, CsvSink::part_file_name(part, compression)),
+            (_, None) => CsvSink::file_name(key, compression),
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            OrganizeBy::Member => "member",
+            OrganizeBy::Prefix => "prefix",
+            OrganizeBy::Year => "year",
+        }
+    }
+
+    fn plural(self) -> &'static str {
+        match self {
+            OrganizeBy::Member => "members",
+            OrganizeBy::Prefix => "prefixes",
+            OrganizeBy::Year => "years",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SuggestGoal {
+    Authorship,
+    Funding,
+    Venue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum JsonShape {
+    Datafile,
+    Api,
+}
+
+impl JsonShape {
+    // REST API responses wrap a single work (`/works/{doi}`) or a page of works
+    // (`message.items`, from `/works` queries) in `message`. Lines that are
+    // already bare works are passed through, so mixed dumps also work.
+    fn records(self, line: Value) -> Vec<Value> {
+        match self {
+            JsonShape::Datafile => vec![line],
+            JsonShape::Api => {
+                let Value::Object(mut response) = line else {
+                    return vec![line];
+                };
+                match response.remove("message") {
+                    Some(Value::Object(mut message)) => match message.remove("items") {
+                        Some(Value::Array(items)) => items,
+                        _ => vec![Value::Object(message)],
+                    },
+                    Some(_) | None => vec![Value::Object(response)],
+                }
+            }
+        }
+    }
+}
+
+// Fields extracted from grant records when --fields is not given.
+const GRANT_DEFAULT_FIELDS: &[&str] = &[
+    "award",
+    "award-start.date-parts",
+    "project.funding.funder.name",
+    "project.funding.funder.id.id",
+    "project.funding.funder.id.id-type",
+    "project.funding.scheme",
+    "project.lead-investigator.ORCID",
+    "project.lead-investigator.family",
+    "project.lead-investigator.given",
+    "project.lead-investigator.affiliation.name",
+    "project.lead-investigator.affiliation.id.id",
+    "project.lead-investigator.affiliation.id.id-type",
+    "project.investigator.ORCID",
+    "project.investigator.family",
+    "project.investigator.given",
+    "project.investigator.affiliation.name",
+    "project.investigator.affiliation.id.id",
+    "project.investigator.affiliation.id.id-type",
+];
+
+// Fields extracted from peer review records when --fields is not given.
+const PEER_REVIEW_DEFAULT_FIELDS: &[&str] = &[
+    "review.type",
+    "review.stage",
+    "review.recommendation",
+    "review.revision-round",
+    "review.running-number",
+    "review.language",
+    "review.competing-interest-statement",
+    "author.given",
+    "author.family",
+    "author.ORCID",
+    "published.date-parts",
+];
+
+// Fields extracted from component records when --fields is not given.
+const COMPONENT_DEFAULT_FIELDS: &[&str] = &[
+    "title",
+    "description",
+    "publisher",
+    "created.date-parts",
+];
+
+impl SuggestGoal {
+    // Work fields that --suggest-fields measures for each goal. Ties in
+    // coverage keep this order.
+    fn candidate_fields(self) -> &'static [&'static str] {
+        match self {
+            SuggestGoal::Authorship => &[
+                "author.given",
+                "author.family",
+                "author.name",
+                "author.sequence",
+                "author.ORCID",
+                "author.authenticated-orcid",
+                "author.affiliation.name",
+                "author.affiliation.id.id",
+                "author.affiliation.id.id-type",
+                "author.affiliation.department",
+                "author.affiliation.place",
+                "editor.given",
+                "editor.family",
+                "editor.ORCID",
+                "editor.affiliation.name",
+            ],
+            SuggestGoal::Funding => &[
+                "funder.name",
+                "funder.DOI",
+                "funder.doi-asserted-by",
+                "funder.award",
+                "funder.id.id",
+                "funder.id.id-type",
+            ],
+            SuggestGoal::Venue => &[
+                "container-title",
+                "short-container-title",
+                "publisher",
+                "publisher-location",
+                "ISSN",
+                "issn-type.value",
+                "issn-type.type",
+                "ISBN",
+                "isbn-type.value",
+                "volume",
+                "issue",
+                "page",
+                "article-number",
+                "event.name",
+                "event.acronym",
+                "institution.name",
+                "type",
+            ],
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -74,22 +601,34 @@ struct DoiPrefix(String);
 #[derive(Debug, Clone)]
 struct FieldData {
     doi: Doi,
+    // The award number of a grant, or the first parent DOI of a peer review or component.
+    link: String,
     field_name: String,
     subfield_path: String,
     value: String,
     member_id: MemberId,
     doi_prefix: DoiPrefix,
+    // Issued year of the record, or empty when it has none.
+    year: String,
+    // The input file (or archive member) and the line of it the record was
+    // read from, numbered from 1 as in error sidecars.
+    source_file_path: Arc<Path>,
+    source_line: usize,
 }
 
 impl Default for FieldData {
     fn default() -> Self {
         Self {
             doi: Doi(String::new()),
+            link: String::new(),
             field_name: String::new(),
             subfield_path: String::new(),
             value: String::new(),
             member_id: MemberId(String::new()),
             doi_prefix: DoiPrefix(String::new()),
+            year: String::new(),
+            source_file_path: Arc::from(Path::new("")),
+            source_line: 0,
         }
     }
 }
@@ -101,6 +640,29 @@ struct FileStats {
     member_counts: HashMap<MemberId, usize>,
     prefix_counts: HashMap<DoiPrefix, usize>,
     total_fields_extracted: usize,
+    records_panicked: usize,
+    gzip_members: usize,
+    records_read: usize,
+    violations: validation::Violations,
+    other_types_skipped: usize,
+    grants: GrantStats,
+    parent_links: ParentLinkStats,
+}
+
+#[derive(Debug, Default)]
+struct GrantStats {
+    grants: usize,
+    missing_award: usize,
+    with_funder_id: usize,
+    with_investigator_ror: usize,
+    investigator_orcids: HashSet<String>,
+}
+
+#[derive(Debug, Default)]
+struct ParentLinkStats {
+    records: usize,
+    without_parent: usize,
+    links: usize,
 }
 
 struct ProcessedFileResult {
@@ -109,87 +671,139 @@ struct ProcessedFileResult {
     filepath: PathBuf,
 }
 
+// Totals across files. Each rayon worker folds the results of the files it
+// processes into its own copy as each file completes, and the per-worker copies
+// are merged once all files are done, so workers never share a map.
+#[derive(Default)]
 struct IncrementalStats {
-    total_field_records: AtomicUsize,
-    processed_files_ok: AtomicUsize,
-    processed_files_error: AtomicUsize,
-
-    unique_records: DashSet<String>,
-    members: DashMap<MemberId, AtomicUsize>,
-    prefixes: DashMap<DoiPrefix, AtomicUsize>,
-    unique_fields: DashMap<String, AtomicUsize>,
+    total_field_records: usize,
+    processed_files_ok: usize,
+    processed_files_error: usize,
+    records_panicked: usize,
+    gzip_members: usize,
+    grants: usize,
+    grants_missing_award: usize,
+    grants_with_funder_id: usize,
+    grants_with_investigator_ror: usize,
+    other_type_records_skipped: usize,
+    parent_link_records: usize,
+    parent_link_records_without_parent: usize,
+    parent_links: usize,
+
+    unique_records: HashSet<Doi>,
+    investigator_orcids: HashSet<String>,
+    members: HashMap<MemberId, usize>,
+    prefixes: HashMap<DoiPrefix, usize>,
+    unique_fields: HashMap<String, usize>,
+    violations: validation::Violations,
+    file_errors: Vec<(PathBuf, anyhow::Error)>,
+    multi_member_files: Vec<(PathBuf, usize)>,
+    records_read: Vec<(PathBuf, usize)>,
 }
 
-impl IncrementalStats {
-    fn new() -> Self {
-        Self {
-            total_field_records: AtomicUsize::new(0),
-            processed_files_ok: AtomicUsize::new(0),
-            processed_files_error: AtomicUsize::new(0),
-            unique_records: DashSet::new(),
-            members: DashMap::new(),
-            prefixes: DashMap::new(),
-            unique_fields: DashMap::new(),
-        }
+fn merge_counts<K: Eq + Hash>(into: &mut HashMap<K, usize>, from: HashMap<K, usize>) {
+    for (key, count) in from {
+        *into.entry(key).or_insert(0) += count;
     }
+}
 
-    fn aggregate_file_stats(&self, file_stats: FileStats) {
-        self.processed_files_ok.fetch_add(1, Ordering::Relaxed);
-        self.total_field_records.fetch_add(file_stats.total_fields_extracted, Ordering::Relaxed);
-
-        for doi in file_stats.unique_dois {
-            self.unique_records.insert(doi.0);
-        }
-
-        for (field_name, count) in file_stats.field_counts {
-             self.unique_fields.entry(field_name)
-                .or_insert_with(|| AtomicUsize::new(0))
-                .fetch_add(count, Ordering::Relaxed);
-        }
-
-        for (member_id, count) in file_stats.member_counts {
-             self.members.entry(member_id)
-                .or_insert_with(|| AtomicUsize::new(0))
-                .fetch_add(count, Ordering::Relaxed);
-        }
+fn merge_sets<T: Eq + Hash>(into: &mut HashSet<T>, mut from: HashSet<T>) {
+    if from.len() > into.len() {
+        std::mem::swap(into, &mut from);
+    }
+    into.extend(from);
+}
 
-         for (prefix, count) in file_stats.prefix_counts {
-             self.prefixes.entry(prefix)
-                .or_insert_with(|| AtomicUsize::new(0))
-                .fetch_add(count, Ordering::Relaxed);
+impl IncrementalStats {
+    fn add_result(mut self, result: ProcessedFileResult) -> Self {
+        match result.error {
+            Some(e) => {
+                self.processed_files_error += 1;
+                self.file_errors.push((result.filepath, e));
+            }
+            None => {
+                self.records_read.push((result.filepath.clone(), result.stats.records_read));
+                if result.stats.gzip_members > 1 {
+                    self.multi_member_files.push((result.filepath, result.stats.gzip_members));
+                }
+                self.aggregate_file_stats(result.stats);
+            }
         }
+        self
     }
 
-    fn increment_error_files(&self) {
-        self.processed_files_error.fetch_add(1, Ordering::Relaxed);
+    fn aggregate_file_stats(&mut self, file_stats: FileStats) {
+        self.processed_files_ok += 1;
+        self.total_field_records += file_stats.total_fields_extracted;
+        self.records_panicked += file_stats.records_panicked;
+        self.gzip_members += file_stats.gzip_members;
+        self.grants += file_stats.grants.grants;
+        self.grants_missing_award += file_stats.grants.missing_award;
+        self.grants_with_funder_id += file_stats.grants.with_funder_id;
+        self.grants_with_investigator_ror += file_stats.grants.with_investigator_ror;
+        self.other_type_records_skipped += file_stats.other_types_skipped;
+        self.parent_link_records += file_stats.parent_links.records;
+        self.parent_link_records_without_parent += file_stats.parent_links.without_parent;
+        self.parent_links += file_stats.parent_links.links;
+
+        merge_sets(&mut self.unique_records, file_stats.unique_dois);
+        merge_sets(&mut self.investigator_orcids, file_stats.grants.investigator_orcids);
+        merge_counts(&mut self.unique_fields, file_stats.field_counts);
+        merge_counts(&mut self.members, file_stats.member_counts);
+        merge_counts(&mut self.prefixes, file_stats.prefix_counts);
+        validation::merge_violations(&mut self.violations, file_stats.violations);
     }
 
+    fn merge(mut self, other: IncrementalStats) -> Self {
+        self.total_field_records += other.total_field_records;
+        self.processed_files_ok += other.processed_files_ok;
+        self.processed_files_error += other.processed_files_error;
+        self.records_panicked += other.records_panicked;
+        self.gzip_members += other.gzip_members;
+        self.grants += other.grants;
+        self.grants_missing_award += other.grants_missing_award;
+        self.grants_with_funder_id += other.grants_with_funder_id;
+        self.grants_with_investigator_ror += other.grants_with_investigator_ror;
+        self.other_type_records_skipped += other.other_type_records_skipped;
+        self.parent_link_records += other.parent_link_records;
+        self.parent_link_records_without_parent += other.parent_link_records_without_parent;
+        self.parent_links += other.parent_links;
+
+        merge_sets(&mut self.unique_records, other.unique_records);
+        merge_sets(&mut self.investigator_orcids, other.investigator_orcids);
+        merge_counts(&mut self.unique_fields, other.unique_fields);
+        merge_counts(&mut self.members, other.members);
+        merge_counts(&mut self.prefixes, other.prefixes);
+        validation::merge_violations(&mut self.violations, other.violations);
+        self.file_errors.extend(other.file_errors);
+        self.multi_member_files.extend(other.multi_member_files);
+        self.records_read.extend(other.records_read);
+        self
+    }
 
-
-    fn get_final_stats(&self) -> FinalStats {
-        let final_fields: HashMap<String, usize> = self.unique_fields
-            .iter()
-            .map(|entry| (entry.key().clone(), entry.value().load(Ordering::Relaxed)))
-            .collect();
-
-        let final_members: HashMap<MemberId, usize> = self.members
-            .iter()
-            .map(|entry| (entry.key().clone(), entry.value().load(Ordering::Relaxed)))
-            .collect();
-
-        let final_prefixes: HashMap<DoiPrefix, usize> = self.prefixes
-            .iter()
-            .map(|entry| (entry.key().clone(), entry.value().load(Ordering::Relaxed)))
-            .collect();
-
+    fn into_final_stats(self) -> FinalStats {
         FinalStats {
-            total_field_records: self.total_field_records.load(Ordering::Relaxed),
-            processed_files_ok: self.processed_files_ok.load(Ordering::Relaxed),
-            processed_files_error: self.processed_files_error.load(Ordering::Relaxed),
+            total_field_records: self.total_field_records,
+            processed_files_ok: self.processed_files_ok,
+            processed_files_error: self.processed_files_error,
+            records_panicked: self.records_panicked,
+            gzip_members: self.gzip_members,
+            multi_member_files: self.multi_member_files,
+            records_read: self.records_read,
+            grants: self.grants,
+            grants_missing_award: self.grants_missing_award,
+            grants_with_funder_id: self.grants_with_funder_id,
+            grants_with_investigator_ror: self.grants_with_investigator_ror,
+            other_type_records_skipped: self.other_type_records_skipped,
+            parent_link_records: self.parent_link_records,
+            parent_link_records_without_parent: self.parent_link_records_without_parent,
+            parent_links: self.parent_links,
+            unique_investigator_orcids: self.investigator_orcids.len(),
             unique_dois: self.unique_records.len(),
-            unique_members: final_members,
-            unique_prefixes: final_prefixes,
-            unique_fields: final_fields,
+            unique_members: self.members,
+            unique_prefixes: self.prefixes,
+            unique_fields: self.unique_fields,
+            violations: self.violations,
         }
     }
 }
@@ -198,10 +812,24 @@ struct FinalStats {
     total_field_records: usize,
     processed_files_ok: usize,
     processed_files_error: usize,
+    records_panicked: usize,
+    gzip_members: usize,
+    multi_member_files: Vec<(PathBuf, usize)>,
+    records_read: Vec<(PathBuf, usize)>,
+    grants: usize,
+    grants_missing_award: usize,
+    grants_with_funder_id: usize,
+    grants_with_investigator_ror: usize,
+    other_type_records_skipped: usize,
+    parent_link_records: usize,
+    parent_link_records_without_parent: usize,
+    parent_links: usize,
+    unique_investigator_orcids: usize,
     unique_dois: usize,
     unique_members: HashMap<MemberId, usize>,
     unique_prefixes: HashMap<DoiPrefix, usize>,
     unique_fields: HashMap<String, usize>,
+    violations: validation::Violations,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -587,11 +1215,14 @@ struct PatternTrieNode {
 #[derive(Debug)]
 struct PatternTrie {
     root: PatternTrieNode,
+    // Pattern names in the order they were requested.
+    patterns: Vec<String>,
 }
 
 impl PatternTrie {
     fn new(field_specs: &[Vec<String>]) -> Self {
         let mut root = PatternTrieNode::default();
+        let mut patterns = Vec::new();
         let mut unique_specs = field_specs.to_vec();
 
         // Handle implicit wildcards for "relation" fields
@@ -634,9 +1265,12 @@ impl PatternTrie {
                 }
             }
             // Mark the final node as a termination point for this pattern.
-            current_node.terminating_patterns.push(full_pattern_name);
+            current_node.terminating_patterns.push(full_pattern_name.clone());
+            if !patterns.contains(&full_pattern_name) {
+                patterns.push(full_pattern_name);
+            }
         }
-        Self { root }
+        Self { root, patterns }
     }
     
     fn extract(&self, record: &Value) -> Vec<(String, String, String)> {
@@ -659,10 +1293,7 @@ impl PatternTrie {
                 Value::Number(n) => n.to_string(),
                 Value::Bool(b) => b.to_string(),
                 Value::Null => "".to_string(),
-                _ => serde_json::to_string(json_node).unwrap_or_else(|e| {
-                    warn!("Failed to serialize complex value at path '{}': {}", current_path, e);
-                    "[serialization error]".to_string()
-                }),
+                _ => canonical_json::to_string(json_node),
             };
 
             for pattern_name in &trie_node.terminating_patterns {
@@ -719,19 +1350,261 @@ fn parse_field_specifications(field_specs: &str) -> Vec<Vec<String>> {
         .collect()
 }
 
-fn find_jsonl_gz_files<P: AsRef<Path>>(directory: P) -> Result<Vec<PathBuf>> {
-    let pattern = directory.as_ref().join("**/*.jsonl.gz");
-    let pattern_str = pattern.to_string_lossy();
-    info!("Searching for files matching pattern: {}", pattern_str);
-    let paths: Vec<PathBuf> = glob(&pattern_str)?
-        .filter_map(Result::ok)
-        .collect();
+// `json.gz` is the Crossref public data file torrent's chunks (`0.json.gz`, ...).
+const INPUT_EXTENSIONS: [&str; 6] = ["jsonl.gz", "jsonl.zst", "jsonl", "ndjson", "json.gz", "parquet"];
+
+// JSON files, as in DataCite and repository exports. Archive members, manifest
+// entries and a file given as --input are read whatever the format, but
+// directories and buckets are only searched for them with --format json-array,
+// so that other JSON lying next to the input (this parser's own provenance
+// files, say) is not read as JSONL.
+const JSON_ARRAY_EXTENSIONS: [&str; 2] = ["json", "json.zst"];
+
+// Every file under the directory with an input extension, or with --glob the
+// files matching that pattern instead.
+fn find_jsonl_files<P: AsRef<Path>>(directory: P, file_glob: Option<&str>, format: InputFormat) -> Result<Vec<PathBuf>> {
+    let extensions = listed_extensions(format);
+    let patterns: Vec<String> = match file_glob {
+        Some(pattern) => vec![pattern.to_string()],
+        None => extensions.iter().map(|ext| format!("**/*.{}", ext)).collect(),
+    };
+    let mut paths = Vec::new();
+    for pattern in &patterns {
+        let pattern = directory.as_ref().join(pattern);
+        let pattern_str = pattern.to_string_lossy();
+        info!("Searching for files matching pattern: {}", pattern_str);
+        let matches = glob(&pattern_str).with_context(|| format!("Invalid glob pattern: {}", pattern_str))?;
+        paths.extend(matches.filter_map(Result::ok).filter(|path| path.is_file() && !is_own_output(path)));
+    }
     if paths.is_empty() {
-        warn!("No files found matching the pattern: {}", pattern_str);
+        match file_glob {
+            Some(pattern) => warn!("No files matching {} found in: {}", pattern, directory.as_ref().display()),
+            None => warn!("No .{} files found in: {}", extensions.join(", ."), directory.as_ref().display()),
+        }
     }
+    paths.sort();
     Ok(paths)
 }
 
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+// Number of gzip members decoded from one input, shared with its decoder.
+type GzipMemberCount = Rc<Cell<usize>>;
+
+// Picks the decompressor from the file's leading bytes, so gzip, Zstandard and
+// plain JSONL are read whatever the file is named.
+fn decompress<'a, R: Read + 'a>(reader: R) -> io::Result<Box<dyn Read + 'a>> {
+    decompress_counting(reader, GzipMemberCount::default())
+}
+
+fn decompress_counting<'a, R: Read + 'a>(reader: R, gzip_members: GzipMemberCount) -> io::Result<Box<dyn Read + 'a>> {
+    let mut reader = BufReader::new(reader);
+    let head = reader.fill_buf()?;
+    if head.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(GzipMembers::new(reader, gzip_members)))
+    } else if head.starts_with(&ZSTD_MAGIC) {
+        Ok(Box::new(zstd::Decoder::with_buffer(reader)?))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+// Files made by concatenating gzip files, as some snapshot files are, hold
+// several gzip members, and a GzDecoder stops at the end of the first one,
+// silently dropping the records after it. Like MultiGzDecoder, this decodes the
+// members one after another, and it also counts them.
+struct GzipMembers<R: BufRead> {
+    decoder: Option<GzDecoder<R>>,
+    members: GzipMemberCount,
+}
+
+impl<R: BufRead> GzipMembers<R> {
+    fn new(reader: R, members: GzipMemberCount) -> Self {
+        members.set(members.get() + 1);
+        Self { decoder: Some(GzDecoder::new(reader)), members }
+    }
+}
+
+impl<R: BufRead> Read for GzipMembers<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(decoder) = self.decoder.as_mut() {
+            let n = decoder.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            let Some(mut reader) = self.decoder.take().map(GzDecoder::into_inner) else { break };
+            if reader.fill_buf()?.is_empty() {
+                break;
+            }
+            self.members.set(self.members.get() + 1);
+            self.decoder = Some(GzDecoder::new(reader));
+        }
+        Ok(0)
+    }
+}
+
+// The JSON documents of an input: its lines, or with --format json-array the
+// elements of the array it holds (with --format torrent, its "items" array). Gzip members are counted into `gzip_members`.
+fn input_documents<'a, R: Read + 'a>(input: R, format: InputFormat, gzip_members: GzipMemberCount) -> io::Result<Box<dyn Iterator<Item = io::Result<String>> + 'a>> {
+    let reader = BufReader::new(decompress_counting(input, gzip_members)?);
+    Ok(match format {
+        InputFormat::Jsonl => Box::new(reader.lines()),
+        InputFormat::JsonArray => Box::new(json_array::Elements::new(reader)),
+        InputFormat::Torrent => Box::new(json_array::Elements::items(reader)),
+    })
+}
+
+// Opens a local file, or a remote object or URL when the input was remote.
+fn open_input(filepath: &Path, remote: Option<&dyn remote_input::RemoteSource>) -> Result<Box<dyn Read>> {
+    match remote {
+        Some(remote) => remote.open(filepath),
+        None => Ok(Box::new(File::open(filepath).with_context(|| format!("Failed to open file: {}", filepath.display()))?)),
+    }
+}
+
+fn is_parquet(path: &Path) -> bool {
+    path.to_string_lossy().to_lowercase().ends_with(".parquet")
+}
+
+// Parquet needs random access to its footer, so a Parquet input (local,
+// remote or archive member) is read into memory whole before its rows are read.
+fn read_parquet(filepath: &Path, mut input: impl Read) -> Result<parquet_input::JsonLines> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)
+        .with_context(|| format!("Failed to read Parquet file: {}", filepath.display()))?;
+    parquet_input::JsonLines::new(filepath, data)
+}
+
+const ARCHIVE_EXTENSIONS: [&str; 4] = [".tar", ".tar.gz", ".tgz", ".tar.zst"];
+
+fn is_tar_archive(path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    ARCHIVE_EXTENSIONS.iter().any(|ext| name.ends_with(ext))
+}
+
+fn is_zip_archive(path: &Path) -> bool {
+    path.to_string_lossy().to_lowercase().ends_with(".zip")
+}
+
+fn is_archive(path: &Path) -> bool {
+    is_tar_archive(path) || is_zip_archive(path)
+}
+
+fn has_extension(name: &str, extensions: &[&str]) -> bool {
+    extensions.iter().any(|ext| name.ends_with(&format!(".{}", ext)))
+}
+
+fn is_input_member(name: &str) -> bool {
+    !is_archive(Path::new(name)) && (has_extension(name, &INPUT_EXTENSIONS) || has_extension(name, &JSON_ARRAY_EXTENSIONS))
+}
+
+// Whether a path listed in an --input-manifest is one this tool reads.
+fn is_input_file(path: &Path) -> bool {
+    is_archive(path) || is_input_member(&path.to_string_lossy())
+}
+
+// The extensions that directories and buckets are searched for.
+fn listed_extensions(format: InputFormat) -> Vec<&'static str> {
+    let mut extensions = INPUT_EXTENSIONS.to_vec();
+    if format == InputFormat::JsonArray {
+        extensions.extend(JSON_ARRAY_EXTENSIONS);
+    }
+    extensions
+}
+
+fn is_listed_input(name: &str, format: InputFormat) -> bool {
+    !is_archive(Path::new(name)) && !is_own_output(Path::new(name)) && has_extension(name, &listed_extensions(format))
+}
+
+// What this tool writes, which would otherwise be found as input when an
+// earlier run's output sits in the input directory or bucket: compressed CSV
+// output (a `gz` or `zst` file), error sidecars (`jsonl`) and provenance
+// (`json`).
+fn is_own_output(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    name.ends_with(".csv.gz") || name.ends_with(".csv.zst") || error_sidecar::is_sidecar(path) || provenance::is_provenance(path)
+}
+
+// Leaves out the output and error sidecar of this run, when they are written
+// into the input directory, whatever they are named.
+fn without_run_outputs(files: Vec<PathBuf>, cli: &Cli) -> Vec<PathBuf> {
+    let outputs: Vec<PathBuf> = [PathBuf::from(&cli.output), error_sidecar_path(cli)].iter()
+        .filter_map(|path| fs::canonicalize(path).ok())
+        .collect();
+    files.into_iter()
+        .filter(|file| {
+            let is_output = fs::canonicalize(file).is_ok_and(|file| outputs.iter().any(|output| file.starts_with(output)));
+            if is_output {
+                info!("Not reading {}: it is this run's output", file.display());
+            }
+            !is_output
+        })
+        .collect()
+}
+
+// Streams the JSONL members of a tar archive (itself optionally gzip or
+// Zstandard compressed) to the workers without extracting it to disk. Each
+// member is read into memory whole, so at most the channel's capacity of
+// members is held at once.
+fn read_archive_members(
+    archive: &Path,
+    remote: Option<&dyn remote_input::RemoteSource>,
+    read_limiter: Option<Arc<throttle::RateLimiter>>,
+    sender: Sender<(PathBuf, Vec<u8>)>,
+    progress_bar: &ProgressBar,
+) -> Result<usize> {
+    if is_zip_archive(archive) {
+        return read_zip_members(archive, remote, read_limiter, sender, progress_bar);
+    }
+    let file = open_input(archive, remote)?;
+    let mut tar = tar::Archive::new(decompress(throttle::ThrottledReader::new(file, read_limiter))?);
+    let mut members = 0;
+    for entry in tar.entries().with_context(|| format!("Failed to read archive: {}", archive.display()))? {
+        let mut entry = entry.with_context(|| format!("Failed to read entry in archive: {}", archive.display()))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().into_owned();
+        if !is_input_member(&name) {
+            continue;
+        }
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut data)
+            .with_context(|| format!("Failed to read {} from archive: {}", name, archive.display()))?;
+        progress_bar.inc_length(1);
+        members += 1;
+        if sender.send((PathBuf::from(format!("{}!{}", archive.display(), name)), data)).is_err() {
+            break;
+        }
+    }
+    Ok(members)
+}
+
+fn read_zip_members(
+    archive: &Path,
+    remote: Option<&dyn remote_input::RemoteSource>,
+    read_limiter: Option<Arc<throttle::RateLimiter>>,
+    sender: Sender<(PathBuf, Vec<u8>)>,
+    progress_bar: &ProgressBar,
+) -> Result<usize> {
+    let mut zip = zip_input::open(archive, remote, read_limiter)?;
+    let mut members = 0;
+    for (index, name) in zip_input::input_members(&zip, is_input_member) {
+        let mut entry = zip.by_index(index)
+            .with_context(|| format!("Failed to read {} from archive: {}", name, archive.display()))?;
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut data)
+            .with_context(|| format!("Failed to read {} from archive: {}", name, archive.display()))?;
+        progress_bar.inc_length(1);
+        members += 1;
+        if sender.send((PathBuf::from(format!("{}!{}", archive.display(), name)), data)).is_err() {
+            break;
+        }
+    }
+    Ok(members)
+}
+
 trait FileProcessor {
     fn process(
         &self, 
@@ -739,12 +1612,29 @@ trait FileProcessor {
         sender: &Sender<Vec<FieldData>>, 
         batch_size: usize
     ) -> ProcessedFileResult;
+
+    fn process_archive_member(
+        &self,
+        label: &Path,
+        data: Vec<u8>,
+        sender: &Sender<Vec<FieldData>>,
+        batch_size: usize
+    ) -> ProcessedFileResult;
 }
 
 struct JsonlProcessor {
     extractor: Arc<PatternTrie>,
+    json_shape: JsonShape,
+    record_kind: RecordKind,
     filter_member: Option<String>,
     filter_doi_prefix: Option<String>,
+    deposited_window: Option<DepositedWindow>,
+    validator: Option<Arc<validation::Validator>>,
+    read_limiter: Option<Arc<throttle::RateLimiter>>,
+    input_format: InputFormat,
+    remote: Option<Box<dyn remote_input::RemoteSource>>,
+    error_sidecar: Arc<error_sidecar::ErrorSidecar>,
+    link_table: Option<Arc<link_table::LinkTable>>,
 }
 
 impl FileProcessor for JsonlProcessor {
@@ -754,104 +1644,224 @@ impl FileProcessor for JsonlProcessor {
         sender: &Sender<Vec<FieldData>>, 
         batch_size: usize
     ) -> ProcessedFileResult {
-        let mut batch_buffer = Vec::with_capacity(batch_size); 
-        let mut file_stats = FileStats::default();
-
-        let file = match File::open(filepath) {
+        if filepath == Path::new(STDIN_INPUT) {
+            return self.process_stream(filepath, throttle::ThrottledReader::new(io::stdin().lock(), self.read_limiter.clone()), sender, batch_size);
+        }
+        let file = match open_input(filepath, self.remote.as_deref()) {
             Ok(f) => f,
-            Err(e) => {
-                let err = anyhow::Error::new(e).context(format!("Failed to open file: {}", filepath.display()));
-                return ProcessedFileResult { stats: file_stats, error: Some(err), filepath: filepath.to_path_buf() };
+            Err(err) => {
+                return ProcessedFileResult { stats: FileStats::default(), error: Some(err), filepath: filepath.to_path_buf() };
             }
         };
+        let input = throttle::ThrottledReader::new(file, self.read_limiter.clone());
+        if is_parquet(filepath) {
+            return self.process_parquet(filepath, input, sender, batch_size);
+        }
+        self.process_stream(filepath, input, sender, batch_size)
+    }
 
-        let decoder = GzDecoder::new(file);
-        let reader = BufReader::new(decoder);
+    // Archive reads are already throttled as a whole, so members are not.
+    fn process_archive_member(
+        &self,
+        label: &Path,
+        data: Vec<u8>,
+        sender: &Sender<Vec<FieldData>>,
+        batch_size: usize
+    ) -> ProcessedFileResult {
+        if is_parquet(label) {
+            return self.process_parquet(label, data.as_slice(), sender, batch_size);
+        }
+        self.process_stream(label, io::Cursor::new(data), sender, batch_size)
+    }
+}
 
-        let mut lines_processed = 0;
-        let mut records_processed = 0;
-        let mut records_missing_doi = 0;
+impl JsonlProcessor {
+    fn process_stream<R: Read>(
+        &self,
+        filepath: &Path,
+        input: R,
+        sender: &Sender<Vec<FieldData>>,
+        batch_size: usize
+    ) -> ProcessedFileResult {
+        let gzip_members = GzipMemberCount::default();
+        let documents = match input_documents(input, self.input_format, Rc::clone(&gzip_members)) {
+            Ok(d) => d,
+            Err(e) => {
+                let err = anyhow::Error::new(e).context(format!("Failed to open decompressor for: {}", filepath.display()));
+                return ProcessedFileResult { stats: FileStats::default(), error: Some(err), filepath: filepath.to_path_buf() };
+            }
+        };
+        let mut result = self.process_lines(filepath, documents, sender, batch_size);
+        result.stats.gzip_members = gzip_members.get();
+        if result.stats.gzip_members > 1 {
+            debug!("{} holds {} concatenated gzip members", filepath.display(), result.stats.gzip_members);
+        }
+        result
+    }
+
+    fn process_parquet<R: Read>(
+        &self,
+        filepath: &Path,
+        input: R,
+        sender: &Sender<Vec<FieldData>>,
+        batch_size: usize
+    ) -> ProcessedFileResult {
+        match read_parquet(filepath, input) {
+            Ok(rows) => self.process_lines(filepath, rows, sender, batch_size),
+            Err(err) => ProcessedFileResult { stats: FileStats::default(), error: Some(err), filepath: filepath.to_path_buf() },
+        }
+    }
+
+    // Each line is one JSON document: a line of a JSONL file, an element of a
+    // JSON array, or a Parquet row converted to JSON.
+    fn process_lines<I: Iterator<Item = io::Result<String>>>(
+        &self,
+        filepath: &Path,
+        lines: I,
+        sender: &Sender<Vec<FieldData>>,
+        batch_size: usize
+    ) -> ProcessedFileResult {
+        let mut batch_buffer = Vec::with_capacity(batch_size); 
+        let mut file_stats = FileStats::default();
+        let source_file_path: Arc<Path> = Arc::from(filepath);
+
+        let mut lines_processed = 0;
+        let mut records_processed = 0;
+        let mut records_missing_doi = 0;
         let mut records_missing_member = 0;
         let mut records_filtered_out = 0;
         let mut json_parsing_errors = 0;
 
-        for (line_num, line_result) in reader.lines().enumerate() {
+        for (line_num, line_result) in lines.enumerate() {
             lines_processed += 1;
             let line_str = match line_result {
                 Ok(s) => s,
-                Err(e) => {
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
                     warn!("Error reading line {} from {}: {}", line_num + 1, filepath.display(), e);
                     continue;
                 }
+                // Anything but a bad line means the input itself failed, and
+                // reading on would only fail again.
+                Err(e) => {
+                    let err = anyhow::anyhow!("Failed to read line {} from {}: {}", line_num + 1, filepath.display(), e);
+                    return ProcessedFileResult { stats: file_stats, error: Some(err), filepath: filepath.to_path_buf() };
+                }
             };
 
             if line_str.trim().is_empty() {
                 continue;
             }
+            file_stats.records_read += 1;
 
             match serde_json::from_str::<Value>(&line_str) {
-                Ok(record) => {
-                    records_processed += 1;
+                Ok(parsed) => {
+                    for record in self.json_shape.records(parsed) {
+                        records_processed += 1;
+
+                        if let Some(required_type) = self.record_kind.required_type() {
+                            if record.get("type").and_then(Value::as_str) != Some(required_type) {
+                                file_stats.other_types_skipped += 1;
+                                continue;
+                            }
+                        }
 
-                    let member_id_opt = extract_member_id(&record);
-                    let doi_opt = extract_doi(&record);
-                    let doi_prefix_opt = extract_doi_prefix(&record, doi_opt.as_ref());
+                        let member_id_opt = extract_member_id(&record);
+                        let doi_opt = extract_doi(&record);
+                        let doi_prefix_opt = extract_doi_prefix(&record, doi_opt.as_ref());
 
-                    if let Some(filter_m) = &self.filter_member {
-                        if member_id_opt.as_ref().is_none_or(|m| &m.0 != filter_m) {
-                            records_filtered_out += 1;
-                            continue;
+                        if let Some(filter_m) = &self.filter_member {
+                            if member_id_opt.as_ref().is_none_or(|m| &m.0 != filter_m) {
+                                records_filtered_out += 1;
+                                continue;
+                            }
                         }
-                    }
-                     if let Some(filter_p) = &self.filter_doi_prefix {
-                         if doi_prefix_opt.as_ref().is_none_or(|p| &p.0 != filter_p) {
-                             records_filtered_out += 1;
-                              continue;
+                        if let Some(window) = &self.deposited_window {
+                            if extract_deposited(&record).is_none_or(|d| !window.contains(d)) {
+                                records_filtered_out += 1;
+                                continue;
+                            }
+                        }
+                         if let Some(filter_p) = &self.filter_doi_prefix {
+                             if doi_prefix_opt.as_ref().is_none_or(|p| &p.0 != filter_p) {
+                                 records_filtered_out += 1;
+                                  continue;
+                             }
                          }
-                     }
 
-                     let member_id = match member_id_opt {
-                         Some(id) => id,
-                         None => {
-                             records_missing_member += 1;
-                             continue;
-                         }
-                     };
-                     let doi = match doi_opt {
-                          Some(id) => id,
-                          None => {
-                              records_missing_doi += 1;
-                              continue;
-                          }
-                     };
-                     let doi_prefix = doi_prefix_opt.unwrap_or_else(|| DoiPrefix("".to_string()));
-
-                    let extracted_fields = self.extractor.extract(&record);
-
-                    if !extracted_fields.is_empty() {
-                        file_stats.unique_dois.insert(doi.clone());
-                        *file_stats.member_counts.entry(member_id.clone()).or_insert(0) += extracted_fields.len();
-                        *file_stats.prefix_counts.entry(doi_prefix.clone()).or_insert(0) += extracted_fields.len();
-
-                        for (field_name, subfield_path, value) in extracted_fields {
-                            *file_stats.field_counts.entry(field_name.clone()).or_insert(0) += 1;
-                            file_stats.total_fields_extracted += 1;
-
-                            batch_buffer.push(FieldData {
-                                doi: doi.clone(),
-                                field_name,
-                                subfield_path,
-                                value,
-                                member_id: member_id.clone(),
-                                doi_prefix: doi_prefix.clone(),
-                            });
-
-                            if batch_buffer.len() >= batch_size {
-                                if sender.send(std::mem::take(&mut batch_buffer)).is_err() {
-                                    let err = anyhow::anyhow!("Writer thread channel closed unexpectedly on file {}", filepath.display());
-                                    return ProcessedFileResult { stats: file_stats, error: Some(err), filepath: filepath.to_path_buf() };
+                         let member_id = match member_id_opt {
+                             Some(id) => id,
+                             None => {
+                                 records_missing_member += 1;
+                                 continue;
+                             }
+                         };
+                         let doi = match doi_opt {
+                              Some(id) => id,
+                              None => {
+                                  records_missing_doi += 1;
+                                  continue;
+                              }
+                         };
+                         let doi_prefix = doi_prefix_opt.unwrap_or_else(|| DoiPrefix("".to_string()));
+                         let year = extract_issued_year(&record).map(|y| y.to_string()).unwrap_or_default();
+
+                        // A panic while extracting one record skips that record instead of
+                        // unwinding through the worker thread and aborting the run.
+                        let extracted_fields = match panic::catch_unwind(AssertUnwindSafe(|| self.extractor.extract(&record))) {
+                            Ok(fields) => fields,
+                            Err(payload) => {
+                                file_stats.records_panicked += 1;
+                                let message = error_sidecar::panic_message(payload.as_ref());
+                                error!("Extraction panicked on {}:{}: {}. Record skipped.", filepath.display(), line_num + 1, message);
+                                self.error_sidecar.record(filepath, line_num + 1, "extraction_panic", &message, &line_str);
+                                continue;
+                            }
+                        };
+
+                        let link = match self.record_kind {
+                            RecordKind::Grants => record_grant_stats(&record, &mut file_stats.grants),
+                            RecordKind::PeerReviews | RecordKind::Components => {
+                                match self.write_parent_links(&record, &doi, &member_id, &mut file_stats.parent_links) {
+                                    Ok(parent_doi) => parent_doi,
+                                    Err(err) => return ProcessedFileResult { stats: file_stats, error: Some(err), filepath: filepath.to_path_buf() },
+                                }
+                            }
+                            RecordKind::Works => String::new(),
+                        };
+
+                        if !extracted_fields.is_empty() {
+                            file_stats.unique_dois.insert(doi.clone());
+                            *file_stats.member_counts.entry(member_id.clone()).or_insert(0) += extracted_fields.len();
+                            *file_stats.prefix_counts.entry(doi_prefix.clone()).or_insert(0) += extracted_fields.len();
+
+                            for (field_name, subfield_path, value) in extracted_fields {
+                                *file_stats.field_counts.entry(field_name.clone()).or_insert(0) += 1;
+                                file_stats.total_fields_extracted += 1;
+
+                                if let Some(validator) = &self.validator {
+                                    validator.check(&doi.0, &field_name, &subfield_path, &value, &mut file_stats.violations);
+                                }
+
+                                batch_buffer.push(FieldData {
+                                    doi: doi.clone(),
+                                    link: link.clone(),
+                                    field_name,
+                                    subfield_path,
+                                    value,
+                                    member_id: member_id.clone(),
+                                    doi_prefix: doi_prefix.clone(),
+                                    year: year.clone(),
+                                    source_file_path: Arc::clone(&source_file_path),
+                                    source_line: line_num + 1,
+                                });
+
+                                if batch_buffer.len() >= batch_size {
+                                    if sender.send(std::mem::take(&mut batch_buffer)).is_err() {
+                                        let err = anyhow::anyhow!("Writer thread channel closed unexpectedly on file {}", filepath.display());
+                                        return ProcessedFileResult { stats: file_stats, error: Some(err), filepath: filepath.to_path_buf() };
+                                    }
+                                    batch_buffer = Vec::with_capacity(batch_size);
                                 }
-                                batch_buffer = Vec::with_capacity(batch_size);
                             }
                         }
                     }
@@ -859,6 +1869,7 @@ impl FileProcessor for JsonlProcessor {
                 Err(e) => {
                     json_parsing_errors += 1;
                     warn!("Error parsing JSON from {}:{}: {}", filepath.display(), line_num + 1, e);
+                    self.error_sidecar.record(filepath, line_num + 1, "json_parse", &e.to_string(), &line_str);
                 }
             }
         }
@@ -891,6 +1902,154 @@ fn extract_doi(record: &Value) -> Option<Doi> {
         .map(|s| Doi(s.to_string()))
 }
 
+// The DOIs a record links to under `relation`, in order and without repeats.
+// Relations to other identifier types (URIs, ISBNs and so on) are left out.
+fn related_dois<'a>(record: &'a Value, relation: &str) -> Vec<&'a str> {
+    let mut dois: Vec<&str> = Vec::new();
+    let related = record.get("relation").and_then(|r| r.get(relation)).and_then(Value::as_array);
+    for entry in related.map(Vec::as_slice).unwrap_or(&[]) {
+        if !entry.get("id-type").and_then(Value::as_str).is_some_and(|t| t.eq_ignore_ascii_case("doi")) {
+            continue;
+        }
+        let Some(id) = entry.get("id").and_then(Value::as_str).map(str::trim) else { continue };
+        if !id.is_empty() && !dois.contains(&id) {
+            dois.push(id);
+        }
+    }
+    dois
+}
+
+impl JsonlProcessor {
+    // Writes one link table row per parent DOI of a peer review or component and
+    // returns the first parent for the output's parent_doi column.
+    fn write_parent_links(&self, record: &Value, doi: &Doi, member_id: &MemberId, stats: &mut ParentLinkStats) -> Result<String> {
+        let relation = self.record_kind.parent_relation().unwrap_or_default();
+        let parents = related_dois(record, relation);
+        stats.records += 1;
+        stats.links += parents.len();
+        if parents.is_empty() {
+            stats.without_parent += 1;
+        }
+        if let Some(link_table) = &self.link_table {
+            link_table.write(&doi.0, relation, &parents, &member_id.0)?;
+        }
+        Ok(parents.first().map(|p| p.to_string()).unwrap_or_default())
+    }
+}
+
+// Grant records are keyed by their grant DOI, but are matched to funding
+// acknowledgements by award number; returns the award and tallies grant stats.
+fn record_grant_stats(record: &Value, stats: &mut GrantStats) -> String {
+    stats.grants += 1;
+    let award = record.get("award").and_then(Value::as_str).map(str::trim).unwrap_or("");
+    if award.is_empty() {
+        stats.missing_award += 1;
+    }
+
+    let projects = record.get("project").and_then(Value::as_array).map(Vec::as_slice).unwrap_or(&[]);
+    let has_funder_id = projects.iter()
+        .filter_map(|p| p.get("funding").and_then(Value::as_array))
+        .flatten()
+        .filter_map(|f| f.pointer("/funder/id").and_then(Value::as_array))
+        .flatten()
+        .any(|id| id.get("id").and_then(Value::as_str).is_some_and(|v| !v.trim().is_empty()));
+    if has_funder_id {
+        stats.with_funder_id += 1;
+    }
+
+    let mut has_investigator_ror = false;
+    for project in projects {
+        for role in ["investigator", "lead-investigator"] {
+            let people = project.get(role).and_then(Value::as_array).map(Vec::as_slice).unwrap_or(&[]);
+            for orcid in people.iter().filter_map(|p| p.get("ORCID").and_then(Value::as_str)) {
+                stats.investigator_orcids.insert(orcid.trim().to_string());
+            }
+            has_investigator_ror |= people.iter()
+                .filter_map(|p| p.get("affiliation").and_then(Value::as_array))
+                .flatten()
+                .filter_map(|a| a.get("id").and_then(Value::as_array))
+                .flatten()
+                .any(|id| id.get("id-type").and_then(Value::as_str).is_some_and(|t| t.eq_ignore_ascii_case("ror")));
+        }
+    }
+    if has_investigator_ror {
+        stats.with_investigator_ror += 1;
+    }
+
+    award.to_string()
+}
+
+fn extract_deposited(record: &Value) -> Option<&str> {
+    record.get("deposited")
+        .and_then(|d| d.get("date-time"))
+        .and_then(Value::as_str)
+}
+
+// Crossref's issued date is the earliest of the print and online publication
+// dates, given as date-parts ([[2019, 5, 8]]), possibly with only a year.
+fn extract_issued_year(record: &Value) -> Option<i64> {
+    record.get("issued")
+        .and_then(|d| d.get("date-parts"))
+        .and_then(|parts| parts.get(0))
+        .and_then(|parts| parts.get(0))
+        .and_then(Value::as_i64)
+}
+
+lazy_static! {
+    static ref DEPOSIT_BOUND_RE: regex::Regex =
+        regex::Regex::new(r"^\d{4}(-\d{2}(-\d{2}(T\d{2}(:\d{2}(:\d{2})?)?)?)?)?$").unwrap();
+}
+
+// Inclusive window over ISO 8601 timestamps. Bounds may be partial ("2024-05",
+// "2024-05-31"), in which case values are compared at the bound's precision, so
+// `--deposited-until 2024-05` keeps everything deposited during May.
+#[derive(Debug, Clone)]
+struct DepositedWindow {
+    from: Option<String>,
+    until: Option<String>,
+}
+
+impl DepositedWindow {
+    fn from_bounds(from: Option<&str>, until: Option<&str>) -> Result<Option<Self>> {
+        for bound in [from, until].into_iter().flatten() {
+            if !DEPOSIT_BOUND_RE.is_match(bound) {
+                return Err(anyhow::anyhow!("Invalid deposit date bound '{}': expected YYYY[-MM[-DD[Thh[:mm[:ss]]]]]", bound));
+            }
+        }
+        if let (Some(f), Some(u)) = (from, until) {
+            let len = f.len().min(u.len());
+            if f[..len] > u[..len] {
+                return Err(anyhow::anyhow!("Deposit window is empty: {} is after {}", f, u));
+            }
+        }
+        if from.is_none() && until.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Self { from: from.map(String::from), until: until.map(String::from) }))
+    }
+
+    fn contains(&self, timestamp: &str) -> bool {
+        let at_precision = |bound: &str| timestamp.get(..bound.len()).unwrap_or(timestamp).to_string();
+        if let Some(from) = &self.from {
+            if at_precision(from).as_str() < from.as_str() {
+                return false;
+            }
+        }
+        if let Some(until) = &self.until {
+            if at_precision(until).as_str() > until.as_str() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl std::fmt::Display for DepositedWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} .. {}", self.from.as_deref().unwrap_or("*"), self.until.as_deref().unwrap_or("*"))
+    }
+}
+
 fn extract_member_id(record: &Value) -> Option<MemberId> {
     record.get("member")
         .and_then(|v| {
@@ -915,6 +2074,57 @@ fn extract_doi_prefix(record: &Value, doi: Option<&Doi>) -> Option<DoiPrefix> {
         })
 }
 
+mod link_table {
+    use anyhow::{Context, Result};
+    use std::fs::File;
+    use std::io::{BufWriter, Write};
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+
+    const HEADERS: [&str; 4] = ["doi", "relation", "parent_doi", "member_id"];
+
+    // CSV of the links from peer reviews and components to their parent works,
+    // one row per parent. Written alongside the main output, which only has
+    // room for the first parent of each record.
+    pub struct LinkTable {
+        path: PathBuf,
+        writer: Mutex<BufWriter<File>>,
+    }
+
+    impl LinkTable {
+        pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+            let path = path.as_ref().to_path_buf();
+            let file = File::create(&path)
+                .with_context(|| format!("Failed to create link table: {}", path.display()))?;
+            let mut buf = Vec::new();
+            super::push_csv_row(&mut buf, &HEADERS);
+            let mut writer = BufWriter::new(file);
+            writer.write_all(&buf)
+                .with_context(|| format!("Failed to write link table header: {}", path.display()))?;
+            Ok(Self { path, writer: Mutex::new(writer) })
+        }
+
+        pub fn write(&self, doi: &str, relation: &str, parents: &[&str], member_id: &str) -> Result<()> {
+            if parents.is_empty() {
+                return Ok(());
+            }
+            let mut buf = Vec::new();
+            for parent in parents {
+                super::push_csv_row(&mut buf, &[doi, relation, parent, member_id]);
+            }
+            let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+            writer.write_all(&buf)
+                .with_context(|| format!("Failed to write to link table: {}", self.path.display()))
+        }
+
+        pub fn flush(&self) -> Result<()> {
+            let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+            writer.flush()
+                .with_context(|| format!("Failed to flush link table: {}", self.path.display()))
+        }
+    }
+}
+
 mod memory_usage {
     use log::info;
 
@@ -1088,52 +2298,422 @@ fn format_elapsed(elapsed: Duration) -> String {
     }
 }
 
+// The columns of the long-format output: those of the record kind, and with
+// --source-columns the input file and line each row was extracted from.
+#[derive(Debug, Clone, Copy)]
+struct RowLayout {
+    record_kind: RecordKind,
+    source_columns: bool,
+}
+
+fn output_headers(layout: RowLayout) -> Vec<String> {
+    let mut headers = vec!["doi".to_string()];
+    if let Some(link_column) = layout.record_kind.link_column() {
+        headers.push(link_column.to_string());
+    }
+    headers.extend(["field_name", "subfield_path", "value", "member_id", "doi_prefix"].map(String::from));
+    if layout.source_columns {
+        headers.extend(["source_file_path", "source_line"].map(String::from));
+    }
+    headers
+}
+
+// Output rows are encoded by hand into a buffer that is reused from batch to
+// batch and written with one call, instead of going through a csv::Writer
+// record by record. Quoting matches the csv crate's QuoteStyle options: by
+// default a field holding the delimiter, a quote or a line break is quoted,
+// with its quotes doubled.
+const OUTPUT_BUFFER_CAPACITY: usize = 4 * 1024 * 1024;
+
+fn push_csv_field(buf: &mut Vec<u8>, field: &str, dialect: &CsvDialect) {
+    let quote = match dialect.quote_style {
+        QuoteStyle::Always => true,
+        QuoteStyle::Never => false,
+        QuoteStyle::Necessary | QuoteStyle::NonNumeric => {
+            field.bytes().any(|b| b == dialect.delimiter || matches!(b, b'"' | b'\n' | b'\r'))
+                || (dialect.quote_style == QuoteStyle::NonNumeric && field.parse::<f64>().is_err())
+        }
+    };
+    if !quote {
+        buf.extend_from_slice(field.as_bytes());
+        return;
+    }
+    buf.push(b'"');
+    for (i, part) in field.split('"').enumerate() {
+        if i > 0 {
+            buf.extend_from_slice(b"\"\"");
+        }
+        buf.extend_from_slice(part.as_bytes());
+    }
+    buf.push(b'"');
+}
+
+fn push_csv_row<S: AsRef<str>>(buf: &mut Vec<u8>, fields: &[S]) {
+    push_csv_record(buf, fields, &CsvDialect::default());
+}
+
+fn push_csv_record<S: AsRef<str>>(buf: &mut Vec<u8>, fields: &[S], dialect: &CsvDialect) {
+    // A record of one empty field is written as "" rather than as a blank
+    // line, which readers skip, as the csv crate does.
+    if dialect.quote_style != QuoteStyle::Never && fields.len() <= 1 && fields.iter().all(|field| field.as_ref().is_empty()) {
+        buf.extend_from_slice(b"\"\"");
+        push_csv_terminator(buf, dialect);
+        return;
+    }
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            buf.push(dialect.delimiter);
+        }
+        push_csv_field(buf, field.as_ref(), dialect);
+    }
+    push_csv_terminator(buf, dialect);
+}
+
+fn push_csv_terminator(buf: &mut Vec<u8>, dialect: &CsvDialect) {
+    match dialect.terminator {
+        Terminator::Lf => buf.push(b'\n'),
+        Terminator::Crlf => buf.extend_from_slice(b"\r\n"),
+    }
+}
+
+fn push_field_row(buf: &mut Vec<u8>, field_data: &FieldData, layout: RowLayout, dialect: &CsvDialect) {
+    if layout.source_columns {
+        let mut first = true;
+        for_each_output_value(field_data, layout, |value| {
+            if !first {
+                buf.push(dialect.delimiter);
+            }
+            first = false;
+            push_csv_field(buf, value, dialect);
+        });
+        push_csv_terminator(buf, dialect);
+        return;
+    }
+    match layout.record_kind {
+        RecordKind::Works => push_csv_record(buf, &[
+            &field_data.doi.0,
+            &field_data.field_name,
+            &field_data.subfield_path,
+            &field_data.value,
+            &field_data.member_id.0,
+            &field_data.doi_prefix.0,
+        ], dialect),
+        RecordKind::Grants | RecordKind::PeerReviews | RecordKind::Components => push_csv_record(buf, &[
+            &field_data.doi.0,
+            &field_data.link,
+            &field_data.field_name,
+            &field_data.subfield_path,
+            &field_data.value,
+            &field_data.member_id.0,
+            &field_data.doi_prefix.0,
+        ], dialect),
+    }
+}
+
+// The values of a row in column order, for the columnar outputs that fill
+// one buffer per column.
+fn for_each_output_value(field_data: &FieldData, layout: RowLayout, mut push: impl FnMut(&str)) {
+    push(&field_data.doi.0);
+    if layout.record_kind.link_column().is_some() {
+        push(&field_data.link);
+    }
+    push(&field_data.field_name);
+    push(&field_data.subfield_path);
+    push(&field_data.value);
+    push(&field_data.member_id.0);
+    push(&field_data.doi_prefix.0);
+    if layout.source_columns {
+        push(&field_data.source_file_path.to_string_lossy());
+        push(&field_data.source_line.to_string());
+    }
+}
+
+// A CSV output file, written as is or through a compressor.
+enum CsvSink {
+    Plain(File),
+    Gz(GzEncoder<File>),
+    Zst(zstd::Encoder<'static, File>),
+}
+
+impl CsvSink {
+    fn new(file: File, compression: Option<CsvCompression>) -> io::Result<Self> {
+        Ok(match compression {
+            None => CsvSink::Plain(file),
+            Some(CsvCompression { format: OutputCompression::Gz, level }) => {
+                CsvSink::Gz(GzEncoder::new(file, flate2::Compression::new(level as u32)))
+            }
+            Some(CsvCompression { format: OutputCompression::Zst, level }) => CsvSink::Zst(zstd::Encoder::new(file, level)?),
+        })
+    }
+
+    fn file_name(stem: &str, compression: Option<CsvCompression>) -> String {
+        match compression.map(|c| c.format) {
+            None => format!("{}.csv", stem),
+            Some(OutputCompression::Gz) => format!("{}.csv.gz", stem),
+            Some(OutputCompression::Zst) => format!("{}.csv.zst", stem),
+        }
+    }
+
+    // Name of the nth file of output split by --max-rows-per-file, counting from 1.
+    fn part_file_name(part: u32, compression: Option<CsvCompression>) -> String {
+        Self::file_name(&format!("part-{:05}", part), compression)
+    }
+
+    // Writes out the compressor's trailer. A compressed file that is reopened
+    // for appending gets a new gzip member or zstd frame, which readers treat
+    // as one stream.
+    fn finish(&mut self) -> io::Result<()> {
+        match self {
+            CsvSink::Plain(file) => file.flush(),
+            CsvSink::Gz(encoder) => encoder.try_finish(),
+            CsvSink::Zst(encoder) => encoder.do_finish(),
+        }
+    }
+
+    fn file(&self) -> &File {
+        match self {
+            CsvSink::Plain(file) => file,
+            CsvSink::Gz(encoder) => encoder.get_ref(),
+            CsvSink::Zst(encoder) => encoder.get_ref(),
+        }
+    }
+
+    // Finishes the file before it is dropped, syncing it unless --fsync never.
+    fn close(&mut self, fsync: Fsync) -> io::Result<()> {
+        self.finish()?;
+        if fsync != Fsync::Never {
+            self.file().sync_data()?;
+        }
+        Ok(())
+    }
+
+    // A --flush-every-rows checkpoint: the compressor's pending output is
+    // written out, and with --fsync periodic the file is synced.
+    fn checkpoint(&mut self, fsync: Fsync) -> io::Result<()> {
+        self.flush()?;
+        if fsync == Fsync::Periodic {
+            self.file().sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for CsvSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CsvSink::Plain(file) => file.write(buf),
+            CsvSink::Gz(encoder) => encoder.write(buf),
+            CsvSink::Zst(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CsvSink::Plain(file) => file.flush(),
+            CsvSink::Gz(encoder) => encoder.flush(),
+            CsvSink::Zst(encoder) => encoder.flush(),
+        }
+    }
+}
+
+// Written next to organized output so that a DOI lookup can skip the files
+// whose Bloom filter rules it out, instead of scanning every member file.
+const DOI_INDEX_FILE: &str = "_index.json";
+const DOI_INDEX_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+// Bloom filter positions come from the first 8 bytes of the SHA-256 of the
+// lowercased DOI, read little-endian and split into two 32-bit halves h1 and
+// h2 | 1; probe i sets bit (h1 + i * h2) mod bits, least significant bit of
+// each byte first. The README shows the same computation in Python.
+fn doi_index_hash(doi: &str) -> u64 {
+    let digest = Sha256::digest(doi.to_lowercase().as_bytes());
+    let mut first = [0u8; 8];
+    first.copy_from_slice(&digest[..8]);
+    u64::from_le_bytes(first)
+}
+
+fn doi_bloom_filter(hashes: &[u64]) -> (u64, u64, Vec<u8>) {
+    let n = hashes.len().max(1) as f64;
+    let ln2 = std::f64::consts::LN_2;
+    let bytes = ((-n * DOI_INDEX_FALSE_POSITIVE_RATE.ln() / (ln2 * ln2)).ceil() as u64).div_ceil(8).max(8);
+    let bits = bytes * 8;
+    let probes = (bits as f64 / n * ln2).round().clamp(1.0, 16.0) as u64;
+    let mut data = vec![0u8; bytes as usize];
+    for &hash in hashes {
+        let h1 = hash & 0xffff_ffff;
+        let h2 = (hash >> 32) | 1;
+        for i in 0..probes {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % bits;
+            data[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+    }
+    (bits, probes, data)
+}
+
+#[derive(Default)]
+struct DoiIndexEntry {
+    files: Vec<String>,
+    rows: u64,
+    last_doi: String,
+    doi_hashes: Vec<u64>,
+}
+
+// Rows of one record arrive together, so a DOI is hashed once per record
+// rather than once per row. Hashes are kept until the end of the run, 8 bytes
+// per record, since the filter can only be sized once the DOI count is known.
+#[derive(Default)]
+struct DoiIndex {
+    entries: HashMap<String, DoiIndexEntry>,
+}
+
+impl DoiIndex {
+    fn entry(&mut self, key: &str) -> &mut DoiIndexEntry {
+        if !self.entries.contains_key(key) {
+            self.entries.insert(key.to_string(), DoiIndexEntry::default());
+        }
+        self.entries.get_mut(key).expect("entry inserted above")
+    }
+
+    fn add_row(&mut self, key: &str, doi: &str) {
+        let entry = self.entry(key);
+        entry.rows += 1;
+        if !doi.is_empty() && doi != entry.last_doi {
+            entry.last_doi.clear();
+            entry.last_doi.push_str(doi);
+            entry.doi_hashes.push(doi_index_hash(doi));
+        }
+    }
+
+    fn add_file(&mut self, key: &str, file_name: String) {
+        self.entry(key).files.push(file_name);
+    }
+
+    fn write(self, output_dir: &Path, organize_by: OrganizeBy) -> Result<()> {
+        let index_path = output_dir.join(DOI_INDEX_FILE);
+        info!("Writing DOI index for {} {} to: {}", self.entries.len(), organize_by.plural(), index_path.display());
+        let mut groups = serde_json::Map::new();
+        for (key, mut entry) in self.entries {
+            entry.doi_hashes.sort_unstable();
+            entry.doi_hashes.dedup();
+            let (bits, probes, data) = doi_bloom_filter(&entry.doi_hashes);
+            groups.insert(key, serde_json::json!({
+                "files": entry.files,
+                "rows": entry.rows,
+                "dois": entry.doi_hashes.len(),
+                "bloom": { "bits": bits, "hashes": probes, "base64": BASE64_STANDARD.encode(data) },
+            }));
+        }
+        let mut index = serde_json::json!({
+            "hash": "sha256-lowercase-doi",
+            "false_positive_rate": DOI_INDEX_FALSE_POSITIVE_RATE,
+        });
+        index[organize_by.plural()] = Value::Object(groups);
+        let file = File::create(&index_path)
+            .with_context(|| format!("Failed to create DOI index: {}", index_path.display()))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, &index)
+            .and_then(|_| writer.flush().map_err(serde_json::Error::io))
+            .with_context(|| format!("Failed to write DOI index: {}", index_path.display()))
+    }
+}
+
 trait OutputStrategy: Send {
     fn write_batch(&mut self, batch: &[FieldData]) -> Result<()>;
     fn flush(&mut self) -> Result<()>;
     fn report_files_created(&self) -> usize;
+
+    // Files closed before the end of the run, for a remote output to upload
+    // while the rest is still being written.
+    fn take_finished_files(&mut self) -> Vec<PathBuf> {
+        Vec::new()
+    }
 }
 
+// With --max-rows-per-file, the output path is a directory and rows go to
+// part-00001.csv until it is full, then part-00002.csv, and so on. A part is
+// only started once there is a row for it, so no empty file is left at the end.
 struct SingleFileOutput {
-    writer: Writer<File>,
-    #[allow(dead_code)]
+    file: CsvSink,
     headers: Vec<String>,
     file_path: PathBuf,
+    layout: RowLayout,
+    row_buffer: Vec<u8>,
+    csv: CsvSettings,
+    part: u32,
+    rows_in_part: u64,
+    rows_since_flush: u64,
+    finished_parts: Vec<PathBuf>,
 }
 
 impl SingleFileOutput {
-    fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let file_path = path.as_ref().to_path_buf();
-        info!("Initializing single output file: {}", file_path.display());
-        if let Some(parent) = file_path.parent() {
-            fs::create_dir_all(parent)
-                .with_context(|| format!("Failed to create directory structure for: {}", file_path.display()))?;
-        }
-
-        let headers = vec![
-            "doi".to_string(),
-            "field_name".to_string(),
-            "subfield_path".to_string(),
-            "value".to_string(),
-            "member_id".to_string(),
-            "doi_prefix".to_string(),
-        ];
-
-        let file = File::create(&file_path)
-            .with_context(|| format!("Failed to create output file: {}", file_path.display()))?;
+    fn new<P: AsRef<Path>>(path: P, layout: RowLayout, csv: CsvSettings) -> Result<Self> {
+        let file_path = match csv.max_rows_per_file {
+            Some(max_rows) => {
+                let dir = path.as_ref();
+                if dir.exists() && !dir.is_dir() {
+                    return Err(anyhow::anyhow!("Output path for --max-rows-per-file must be a directory: {}", dir.display()));
+                }
+                info!("Initializing output split into files of at most {} rows in directory: {}", max_rows, dir.display());
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create output directory: {}", dir.display()))?;
+                dir.join(CsvSink::part_file_name(1, csv.compression))
+            }
+            None => {
+                let file_path = path.as_ref().to_path_buf();
+                info!("Initializing single output file: {}", file_path.display());
+                if let Some(parent) = file_path.parent() {
+                    fs::create_dir_all(parent)
+                        .with_context(|| format!("Failed to create directory structure for: {}", file_path.display()))?;
+                }
+                file_path
+            }
+        };
 
-        let mut writer = Writer::from_writer(file);
-        writer.write_record(&headers)
-            .context("Failed to write header to single output file")?;
-        writer.flush()
-            .context("Failed to flush header to single output file")?;
+        let headers = output_headers(layout);
+        let file = Self::create_file(&file_path, &headers, &csv)?;
 
         Ok(Self {
-            writer,
+            file,
             headers,
             file_path,
+            layout,
+            row_buffer: Vec::with_capacity(OUTPUT_BUFFER_CAPACITY),
+            csv,
+            part: 1,
+            rows_in_part: 0,
+            rows_since_flush: 0,
+            finished_parts: Vec::new(),
         })
     }
+
+    fn create_file(file_path: &Path, headers: &[String], csv: &CsvSettings) -> Result<CsvSink> {
+        let file = File::create(file_path)
+            .with_context(|| format!("Failed to create output file: {}", file_path.display()))?;
+        let mut file = CsvSink::new(file, csv.compression)
+            .with_context(|| format!("Failed to start compressed output file: {}", file_path.display()))?;
+        let mut header = Vec::new();
+        push_csv_record(&mut header, headers, &csv.dialect);
+        file.write_all(&header)
+            .with_context(|| format!("Failed to write header to: {}", file_path.display()))?;
+        Ok(file)
+    }
+
+    fn start_next_part(&mut self) -> Result<()> {
+        self.file.write_all(&self.row_buffer)
+            .with_context(|| format!("Failed to write to single output file: {}", self.file_path.display()))?;
+        self.row_buffer.clear();
+        self.file.close(self.csv.fsync)
+            .with_context(|| format!("Failed to flush output file: {}", self.file_path.display()))?;
+        self.finished_parts.push(self.file_path.clone());
+
+        self.part += 1;
+        self.rows_in_part = 0;
+        let file_name = CsvSink::part_file_name(self.part, self.csv.compression);
+        self.file_path = self.file_path.with_file_name(file_name);
+        debug!("Starting output file: {}", self.file_path.display());
+        self.file = Self::create_file(&self.file_path, &self.headers, &self.csv)?;
+        Ok(())
+    }
 }
 
 impl OutputStrategy for SingleFileOutput {
@@ -1142,72 +2722,326 @@ impl OutputStrategy for SingleFileOutput {
             return Ok(());
         }
 
+        self.row_buffer.clear();
         for field_data in batch {
-            self.writer.write_record(&[
-                &field_data.doi.0,
-                &field_data.field_name,
-                &field_data.subfield_path,
-                &field_data.value,
-                &field_data.member_id.0,
-                &field_data.doi_prefix.0,
-            ])?;
+            if self.csv.max_rows_per_file.is_some_and(|max_rows| self.rows_in_part >= max_rows) {
+                self.start_next_part()?;
+            }
+            push_field_row(&mut self.row_buffer, field_data, self.layout, &self.csv.dialect);
+            self.rows_in_part += 1;
+        }
+        self.file.write_all(&self.row_buffer)
+            .with_context(|| format!("Failed to write to single output file: {}", self.file_path.display()))?;
+
+        self.rows_since_flush += batch.len() as u64;
+        if self.csv.flush_every_rows.is_some_and(|every| self.rows_since_flush >= every) {
+            self.file.checkpoint(self.csv.fsync)
+                .with_context(|| format!("Failed to flush single output file: {}", self.file_path.display()))?;
+            self.rows_since_flush = 0;
         }
         Ok(())
     }
 
      fn flush(&mut self) -> Result<()> {
         info!("Flushing final data to: {}", self.file_path.display());
-        self.writer.flush()
+        self.file.close(self.csv.fsync)
             .context(format!("Failed to flush single output file: {}", self.file_path.display()))?;
         Ok(())
     }
 
     fn report_files_created(&self) -> usize {
-        1
+        self.part as usize
+    }
+
+    fn take_finished_files(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.finished_parts)
     }
 }
 
-struct OrganizedOutput {
-    base_output_dir: PathBuf,
-    current_writers: HashMap<MemberId, Writer<File>>,
-    created_files: HashSet<PathBuf>,
-    max_open_files: usize,
-    headers: Vec<String>,
-    open_file_lru: VecDeque<MemberId>,
+// With --pivot, the values of each DOI are gathered into one row: one column
+// per requested field, with several values joined by the delimiter. A record's
+// rows can be split between batches and mixed with other files' rows, so rows
+// are held in memory for the whole run and written by flush, in the order
+// their DOIs were first seen. Memory grows with the output, so pivoting suits
+// filtered runs rather than whole snapshots.
+struct PivotOptions {
+    fields: Vec<String>,
+    delimiter: String,
 }
 
-impl OrganizedOutput {
-    fn new<P: AsRef<Path>>(output_path: P, max_open_files: usize) -> Result<Self> {
-        let path = output_path.as_ref();
-        if path.exists() && !path.is_dir() {
-            return Err(anyhow::anyhow!("Output path for organized output must be a directory: {}", path.display()));
-        }
-        fs::create_dir_all(path)
-            .with_context(|| format!("Failed to create base output directory: {}", path.display()))?;
-        info!("Initializing organized output in directory: {}", path.display());
-        info!("Using a maximum of {} open files at once", max_open_files);
+struct PivotRow {
+    link: String,
+    member_id: String,
+    doi_prefix: String,
+    values: Vec<Option<String>>,
+}
 
-        let headers = vec![
-            "doi".to_string(),
-            "field_name".to_string(),
-            "subfield_path".to_string(),
-            "value".to_string(),
-            "member_id".to_string(),
-            "doi_prefix".to_string(),
-        ];
+struct PivotOutput {
+    file: Option<CsvSink>,
+    file_path: PathBuf,
+    dialect: CsvDialect,
+    fsync: Fsync,
+    record_kind: RecordKind,
+    delimiter: String,
+    columns: Vec<String>,
+    column_index: HashMap<String, usize>,
+    rows: Vec<PivotRow>,
+    row_index: HashMap<String, usize>,
+}
 
-        Ok(Self {
-            base_output_dir: path.to_path_buf(),
-            current_writers: HashMap::with_capacity(max_open_files.min(1024)),
-            created_files: HashSet::new(),
-            max_open_files: max_open_files.max(1),
-            headers,
-            open_file_lru: VecDeque::with_capacity(max_open_files),
-        })
-    }
+impl PivotOutput {
+    fn new<P: AsRef<Path>>(path: P, record_kind: RecordKind, csv: CsvSettings, options: PivotOptions) -> Result<Self> {
+        let file_path = path.as_ref().to_path_buf();
+        info!("Initializing pivoted output file: {}", file_path.display());
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory structure for: {}", file_path.display()))?;
+        }
+        let file = File::create(&file_path)
+            .with_context(|| format!("Failed to create output file: {}", file_path.display()))?;
+        let file = CsvSink::new(file, csv.compression)
+            .with_context(|| format!("Failed to start compressed output file: {}", file_path.display()))?;
 
-    fn get_writer(&mut self, member_id: &MemberId) -> Result<&mut Writer<File>> {
-        let key = member_id.clone();
+        let mut output = Self {
+            file: Some(file),
+            file_path,
+            dialect: csv.dialect,
+            fsync: csv.fsync,
+            record_kind,
+            delimiter: options.delimiter,
+            columns: Vec::new(),
+            column_index: HashMap::new(),
+            rows: Vec::new(),
+            row_index: HashMap::new(),
+        };
+        for field in &options.fields {
+            output.column(field);
+        }
+        Ok(output)
+    }
+
+    // Fields the extractor adds itself, such as `relation.*`, get a column
+    // after the requested ones when they first produce a value.
+    fn column(&mut self, field_name: &str) -> usize {
+        if let Some(&index) = self.column_index.get(field_name) {
+            return index;
+        }
+        self.columns.push(field_name.to_string());
+        self.column_index.insert(field_name.to_string(), self.columns.len() - 1);
+        self.columns.len() - 1
+    }
+}
+
+impl OutputStrategy for PivotOutput {
+    fn write_batch(&mut self, batch: &[FieldData]) -> Result<()> {
+        for field_data in batch {
+            let column = self.column(&field_data.field_name);
+            let row_number = match self.row_index.get(&field_data.doi.0) {
+                Some(&row_number) => row_number,
+                None => {
+                    self.rows.push(PivotRow {
+                        link: field_data.link.clone(),
+                        member_id: field_data.member_id.0.clone(),
+                        doi_prefix: field_data.doi_prefix.0.clone(),
+                        values: Vec::new(),
+                    });
+                    self.row_index.insert(field_data.doi.0.clone(), self.rows.len() - 1);
+                    self.rows.len() - 1
+                }
+            };
+            let row = &mut self.rows[row_number];
+            if row.values.len() <= column {
+                row.values.resize(column + 1, None);
+            }
+            match &mut row.values[column] {
+                Some(joined) => {
+                    joined.push_str(&self.delimiter);
+                    joined.push_str(&field_data.value);
+                }
+                slot => *slot = Some(field_data.value.clone()),
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let Some(mut file) = self.file.take() else {
+            return Ok(());
+        };
+        info!("Writing {} pivoted rows to: {}", self.rows.len(), self.file_path.display());
+        let link_column = self.record_kind.link_column();
+        let mut headers: Vec<&str> = vec!["doi"];
+        headers.extend(link_column);
+        headers.extend(self.columns.iter().map(String::as_str));
+        headers.extend(["member_id", "doi_prefix"]);
+
+        let mut dois = vec![String::new(); self.rows.len()];
+        for (doi, &row_number) in &self.row_index {
+            dois[row_number].clone_from(doi);
+        }
+
+        let mut buf = Vec::with_capacity(OUTPUT_BUFFER_CAPACITY);
+        push_csv_record(&mut buf, &headers, &self.dialect);
+        let mut fields: Vec<&str> = Vec::with_capacity(headers.len());
+        for (doi, row) in dois.iter().zip(&self.rows) {
+            fields.clear();
+            fields.push(doi);
+            if link_column.is_some() {
+                fields.push(&row.link);
+            }
+            for column in 0..self.columns.len() {
+                fields.push(row.values.get(column).and_then(Option::as_deref).unwrap_or(""));
+            }
+            fields.push(&row.member_id);
+            fields.push(&row.doi_prefix);
+            push_csv_record(&mut buf, &fields, &self.dialect);
+            if buf.len() >= OUTPUT_BUFFER_CAPACITY {
+                file.write_all(&buf)
+                    .with_context(|| format!("Failed to write to pivoted output file: {}", self.file_path.display()))?;
+                buf.clear();
+            }
+        }
+        file.write_all(&buf)
+            .and_then(|_| file.close(self.fsync))
+            .with_context(|| format!("Failed to flush pivoted output file: {}", self.file_path.display()))?;
+        self.rows.clear();
+        self.row_index.clear();
+        Ok(())
+    }
+
+    fn report_files_created(&self) -> usize {
+        1
+    }
+}
+
+// Rows for each member are held in memory and written once the member has this
+// many bytes waiting, so each file gets a few large sequential appends rather
+// than one small one per batch.
+const MEMBER_BUFFER_BYTES: usize = 1024 * 1024;
+
+// Cap on the rows held across all members. Past it, the largest buffers are
+// written until the total is back under half the cap.
+const ORGANIZED_BUFFER_TOTAL_BYTES: usize = 256 * 1024 * 1024;
+
+struct OrganizedOutput {
+    base_output_dir: PathBuf,
+    current_writers: HashMap<String, CsvSink>,
+    csv: CsvSettings,
+    created_files: HashSet<PathBuf>,
+    max_open_files: usize,
+    headers: Vec<String>,
+    open_file_lru: VecDeque<String>,
+    layout: RowLayout,
+    pending_rows: HashMap<String, Vec<u8>>,
+    organize_by: OrganizeBy,
+    pending_bytes: usize,
+    doi_index: Option<DoiIndex>,
+    // With --max-rows-per-file, the current part of each key and the rows
+    // given to it so far, buffered or written.
+    parts: HashMap<String, (u32, u64)>,
+    rows_since_flush: u64,
+}
+
+impl OrganizedOutput {
+    fn new<P: AsRef<Path>>(output_path: P, max_open_files: usize, layout: RowLayout, csv: CsvSettings, organize_by: OrganizeBy, doi_index: bool) -> Result<Self> {
+        let path = output_path.as_ref();
+        if path.exists() && !path.is_dir() {
+            return Err(anyhow::anyhow!("Output path for organized output must be a directory: {}", path.display()));
+        }
+        fs::create_dir_all(path)
+            .with_context(|| format!("Failed to create base output directory: {}", path.display()))?;
+        info!("Initializing organized output in directory: {}", path.display());
+        info!("Using a maximum of {} open files at once", max_open_files);
+
+        let headers = output_headers(layout);
+
+        Ok(Self {
+            base_output_dir: path.to_path_buf(),
+            current_writers: HashMap::with_capacity(max_open_files.min(1024)),
+            csv,
+            created_files: HashSet::new(),
+            max_open_files: max_open_files.max(1),
+            headers,
+            open_file_lru: VecDeque::with_capacity(max_open_files),
+            layout,
+            pending_rows: HashMap::new(),
+            pending_bytes: 0,
+            organize_by,
+            doi_index: doi_index.then(DoiIndex::default),
+            parts: HashMap::new(),
+            rows_since_flush: 0,
+        })
+    }
+
+    fn file_name(&self, key: &str) -> String {
+        let part = self.csv.max_rows_per_file
+            .map(|_| self.parts.get(key).map_or(1, |&(part, _)| part));
+        self.organize_by.file_name(key, part, self.csv.compression)
+    }
+
+    // Writes out the rows buffered for the key's full part and closes its
+    // file, so that the key's next rows go to a new part.
+    fn start_next_part(&mut self, key: &str) -> Result<()> {
+        self.write_pending(key)?;
+        if let Some(mut writer) = self.current_writers.remove(key) {
+            writer.close(self.csv.fsync)
+                .with_context(|| format!("Failed to flush file for {} {}", self.organize_by.label(), key))?;
+            self.open_file_lru.retain(|open| open != key);
+        }
+        if let Some((part, rows)) = self.parts.get_mut(key) {
+            *part += 1;
+            *rows = 0;
+        }
+        Ok(())
+    }
+
+    fn write_pending(&mut self, key: &str) -> Result<()> {
+        let Some(rows) = self.pending_rows.remove(key) else {
+            return Ok(());
+        };
+        self.pending_bytes -= rows.len();
+        let label = self.organize_by.label();
+        let writer = self.get_writer(key)
+            .with_context(|| format!("Failed to get writer for {} {}", label, key))?;
+        writer.write_all(&rows)
+            .with_context(|| format!("Failed to write to file for {} {}", label, key))?;
+        Ok(())
+    }
+
+    // A --flush-every-rows checkpoint: every buffered row is written out,
+    // giving up the large appends for the rows' safety.
+    fn checkpoint(&mut self) -> Result<()> {
+        let mut pending: Vec<String> = self.pending_rows.keys().cloned().collect();
+        pending.sort_unstable();
+        debug!("Flushing buffered rows for {} {}", pending.len(), self.organize_by.plural());
+        for key in pending {
+            self.write_pending(&key)?;
+        }
+        for (key, writer) in self.current_writers.iter_mut() {
+            writer.checkpoint(self.csv.fsync)
+                .with_context(|| format!("Failed to flush file for {} {}", self.organize_by.label(), key))?;
+        }
+        self.rows_since_flush = 0;
+        Ok(())
+    }
+
+    fn write_largest_pending(&mut self) -> Result<()> {
+        let mut keys: Vec<(String, usize)> = self.pending_rows.iter()
+            .map(|(key, rows)| (key.clone(), rows.len()))
+            .collect();
+        keys.sort_unstable_by_key(|&(_, len)| std::cmp::Reverse(len));
+        for (key, _) in keys {
+            if self.pending_bytes <= ORGANIZED_BUFFER_TOTAL_BYTES / 2 {
+                break;
+            }
+            self.write_pending(&key)?;
+        }
+        Ok(())
+    }
+
+    fn get_writer(&mut self, key: &str) -> Result<&mut CsvSink> {
+        let label = self.organize_by.label();
+        let key = key.to_string();
 
         if self.current_writers.contains_key(&key) {
             if let Some(pos) = self.open_file_lru.iter().position(|x| x == &key) {
@@ -1216,15 +3050,15 @@ impl OrganizedOutput {
             self.open_file_lru.push_front(key.clone());
             
             return self.current_writers.get_mut(&key)
-                .ok_or_else(|| anyhow::anyhow!("Writer unexpectedly missing for member {}", key.0));
+                .ok_or_else(|| anyhow::anyhow!("Writer unexpectedly missing for {} {}", label, key));
         }
 
         while self.current_writers.len() >= self.max_open_files {
             if let Some(lru_key) = self.open_file_lru.pop_back() {
-                info!("Closing LRU file for member {} to maintain max open files limit.", lru_key.0);
+                info!("Closing LRU file for {} {} to maintain max open files limit.", label, lru_key);
                  if let Some(mut writer_to_close) = self.current_writers.remove(&lru_key) {
-                     if let Err(e) = writer_to_close.flush() {
-                         warn!("Error flushing file for member {} before closing: {}", lru_key.0, e);
+                     if let Err(e) = writer_to_close.close(self.csv.fsync) {
+                         warn!("Error flushing file for {} {} before closing: {}", label, lru_key, e);
                      }
                  }
             } else {
@@ -1233,33 +3067,43 @@ impl OrganizedOutput {
              }
         }
 
-        let member_file_path = self.base_output_dir.join(format!("{}.csv", key.0));
-        let file_needs_header = !self.created_files.contains(&member_file_path);
+        let file_name = self.file_name(&key);
+        let file_path = self.base_output_dir.join(&file_name);
+        let file_needs_header = !self.created_files.contains(&file_path);
+        if file_needs_header {
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create output directory: {}", parent.display()))?;
+            }
+        }
 
         let file = OpenOptions::new()
             .create(true)
             .append(true)
-            .open(&member_file_path)
-            .with_context(|| format!("Failed to open/create output file for member {}: {}", key.0, member_file_path.display()))?;
-
-        let mut csv_writer = Writer::from_writer(file);
+            .open(&file_path)
+            .with_context(|| format!("Failed to open/create output file for {} {}: {}", label, key, file_path.display()))?;
+        let mut file = CsvSink::new(file, self.csv.compression)
+            .with_context(|| format!("Failed to start compressed output file: {}", file_path.display()))?;
 
         if file_needs_header {
-             csv_writer.write_record(&self.headers)
-                .with_context(|| format!("Failed to write header to: {}", member_file_path.display()))?;
-            csv_writer.flush()
-                .with_context(|| format!("Failed to flush header to: {}", member_file_path.display()))?;
-            self.created_files.insert(member_file_path.clone());
-            debug!("Created new file with header: {}", member_file_path.display());
+            let mut header = Vec::new();
+            push_csv_record(&mut header, &self.headers, &self.csv.dialect);
+            file.write_all(&header)
+                .with_context(|| format!("Failed to write header to: {}", file_path.display()))?;
+            self.created_files.insert(file_path.clone());
+            if let Some(index) = self.doi_index.as_mut() {
+                index.add_file(&key, file_name);
+            }
+            debug!("Created new file with header: {}", file_path.display());
         } else {
-             debug!("Opened existing file in append mode: {}", member_file_path.display());
+             debug!("Opened existing file in append mode: {}", file_path.display());
          }
 
-        self.current_writers.insert(key.clone(), csv_writer);
+        self.current_writers.insert(key.clone(), file);
         self.open_file_lru.push_front(key.clone());
 
         self.current_writers.get_mut(&key)
-            .ok_or_else(|| anyhow::anyhow!("Writer unexpectedly missing after insert for member {}", key.0))
+            .ok_or_else(|| anyhow::anyhow!("Writer unexpectedly missing after insert for {} {}", label, key))
     }
 }
 
@@ -1270,38 +3114,53 @@ impl OutputStrategy for OrganizedOutput {
             return Ok(());
         }
 
-        let mut grouped_records: HashMap<MemberId, Vec<&FieldData>> = HashMap::new();
+        let mut full_keys = Vec::new();
         for field_data in batch {
-             grouped_records
-                .entry(field_data.member_id.clone())
-                .or_default()
-                .push(field_data);
+            let key = self.organize_by.key(field_data);
+            if let Some(max_rows) = self.csv.max_rows_per_file {
+                if self.parts.get(key).is_some_and(|&(_, rows)| rows >= max_rows) {
+                    self.start_next_part(key)?;
+                }
+                self.parts.entry(key.to_string()).or_insert((1, 0)).1 += 1;
+            }
+            if let Some(index) = self.doi_index.as_mut() {
+                index.add_row(key, &field_data.doi.0);
+            }
+            let rows = self.pending_rows.entry(key.to_string()).or_default();
+            let before = rows.len();
+            push_field_row(rows, field_data, self.layout, &self.csv.dialect);
+            self.pending_bytes += rows.len() - before;
+            if before < MEMBER_BUFFER_BYTES && rows.len() >= MEMBER_BUFFER_BYTES {
+                full_keys.push(key.to_string());
+            }
         }
 
-        for (member_id, records) in grouped_records {
-            let writer = self.get_writer(&member_id)
-                .with_context(|| format!("Failed to get writer for member {}", member_id.0))?;
-
-            for field_data in records {
-                 writer.write_record(&[
-                     &field_data.doi.0,
-                     &field_data.field_name,
-                     &field_data.subfield_path,
-                     &field_data.value,
-                     &field_data.member_id.0,
-                     &field_data.doi_prefix.0,
-                 ])?;
-            }
+        for key in full_keys {
+            self.write_pending(&key)?;
+        }
+        if self.pending_bytes > ORGANIZED_BUFFER_TOTAL_BYTES {
+            self.write_largest_pending()?;
+        }
+        self.rows_since_flush += batch.len() as u64;
+        if self.csv.flush_every_rows.is_some_and(|every| self.rows_since_flush >= every) {
+            self.checkpoint()?;
         }
         Ok(())
     }
 
     fn flush(&mut self) -> Result<()> {
+        let mut pending: Vec<String> = self.pending_rows.keys().cloned().collect();
+        pending.sort_unstable();
+        info!("Writing buffered rows for {} {}...", pending.len(), self.organize_by.plural());
+        for key in pending {
+            self.write_pending(&key)?;
+        }
+
         info!("Flushing {} open CSV files...", self.current_writers.len());
         let mut flush_errors = Vec::new();
-        for (member_id, writer) in self.current_writers.iter_mut() {
-            if let Err(e) = writer.flush() {
-                flush_errors.push(format!("Failed to flush file for member {}: {}", member_id.0, e));
+        for (key, writer) in self.current_writers.iter_mut() {
+            if let Err(e) = writer.close(self.csv.fsync) {
+                flush_errors.push(format!("Failed to flush file for {} {}: {}", self.organize_by.label(), key, e));
             }
         }
         self.current_writers.clear();
@@ -1313,6 +3172,12 @@ impl OutputStrategy for OrganizedOutput {
             self.created_files.len()
         );
 
+        if flush_errors.is_empty() {
+            if let Some(index) = self.doi_index.take() {
+                index.write(&self.base_output_dir, self.organize_by)?;
+            }
+        }
+
         if !flush_errors.is_empty() {
             Err(anyhow::anyhow!("Errors occurred during final flush:\n - {}", flush_errors.join("\n - ")))
         } else {
@@ -1325,119 +3190,1678 @@ impl OutputStrategy for OrganizedOutput {
     }
 }
 
-struct CsvWriterManager {
-    output_strategy: Box<dyn OutputStrategy>,
+// Parquet rows are buffered column by column and written out as a row group
+// once this many have built up, which keeps row groups large enough to scan
+// efficiently while bounding the buffer.
+#[cfg(feature = "parquet")]
+const PARQUET_ROW_GROUP_ROWS: usize = 250_000;
+
+// Low-cardinality columns; the rest are stored plain, since doi and value are
+// nearly unique and would only fill a dictionary page before falling back.
+#[cfg(feature = "parquet")]
+const PARQUET_DICTIONARY_COLUMNS: [&str; 4] = ["field_name", "member_id", "doi_prefix", "source_file_path"];
+
+#[cfg(feature = "parquet")]
+struct ParquetOutput {
+    writer: Option<SerializedFileWriter<File>>,
+    file_path: PathBuf,
+    layout: RowLayout,
+    fsync: Fsync,
+    columns: Vec<Vec<ByteArray>>,
 }
 
-impl CsvWriterManager {
-    fn new<P: AsRef<Path>>(output_path: P, organize: bool, max_open_files: usize) -> Result<Self> {
-        let strategy: Box<dyn OutputStrategy> = if organize {
-            Box::new(OrganizedOutput::new(output_path, max_open_files)?)
-        } else {
-            Box::new(SingleFileOutput::new(output_path)?)
-        };
+#[cfg(feature = "parquet")]
+impl ParquetOutput {
+    fn new<P: AsRef<Path>>(path: P, layout: RowLayout, fsync: Fsync) -> Result<Self> {
+        let file_path = path.as_ref().to_path_buf();
+        info!("Initializing Parquet output file: {}", file_path.display());
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory structure for: {}", file_path.display()))?;
+        }
+
+        let headers = output_headers(layout);
+        let schema = format!(
+            "message field_data {{ {} }}",
+            headers.iter().map(|h| format!("REQUIRED BYTE_ARRAY {} (UTF8);", h)).collect::<Vec<_>>().join(" ")
+        );
+        let schema = Arc::new(parse_message_type(&schema).context("Failed to build Parquet output schema")?);
+        let mut properties = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .set_dictionary_enabled(false);
+        for column in PARQUET_DICTIONARY_COLUMNS {
+            properties = properties.set_column_dictionary_enabled(ColumnPath::from(column), true);
+        }
+
+        let file = File::create(&file_path)
+            .with_context(|| format!("Failed to create output file: {}", file_path.display()))?;
+        let writer = SerializedFileWriter::new(file, schema, Arc::new(properties.build()))
+            .with_context(|| format!("Failed to start Parquet output file: {}", file_path.display()))?;
 
         Ok(Self {
-            output_strategy: strategy,
+            writer: Some(writer),
+            file_path,
+            layout,
+            fsync,
+            columns: vec![Vec::new(); headers.len()],
+        })
+    }
+
+    fn write_row_group(&mut self) -> Result<()> {
+        if self.columns.first().is_none_or(Vec::is_empty) {
+            return Ok(());
+        }
+        let writer = self.writer.as_mut()
+            .ok_or_else(|| anyhow::anyhow!("Parquet output file already closed: {}", self.file_path.display()))?;
+        let mut row_group = writer.next_row_group()?;
+        for values in &mut self.columns {
+            let mut column = row_group.next_column()?
+                .ok_or_else(|| anyhow::anyhow!("Parquet output schema has fewer columns than the rows"))?;
+            column.typed::<ByteArrayType>().write_batch(values, None, None)?;
+            column.close()?;
+            values.clear();
+        }
+        row_group.close()?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl OutputStrategy for ParquetOutput {
+    fn write_batch(&mut self, batch: &[FieldData]) -> Result<()> {
+        for field_data in batch {
+            let mut columns = self.columns.iter_mut();
+            for_each_output_value(field_data, self.layout, |value| {
+                if let Some(column) = columns.next() {
+                    column.push(ByteArray::from(value));
+                }
+            });
+        }
+        if self.columns.first().is_some_and(|rows| rows.len() >= PARQUET_ROW_GROUP_ROWS) {
+            self.write_row_group()
+                .with_context(|| format!("Failed to write to Parquet output file: {}", self.file_path.display()))?;
+        }
+        Ok(())
+    }
+
+    // The footer is written here, so the file is only readable once the run
+    // has finished. Later flushes do nothing.
+    fn flush(&mut self) -> Result<()> {
+        if self.writer.is_none() {
+            return Ok(());
+        }
+        info!("Flushing final data to: {}", self.file_path.display());
+        self.write_row_group()
+            .with_context(|| format!("Failed to write to Parquet output file: {}", self.file_path.display()))?;
+        if let Some(writer) = self.writer.take() {
+            writer.close()
+                .with_context(|| format!("Failed to close Parquet output file: {}", self.file_path.display()))?;
+        }
+        sync_closed_file(&self.file_path, self.fsync)
+    }
+
+    fn report_files_created(&self) -> usize {
+        1
+    }
+}
+
+// Arrow rows are built up column by column and written as a record batch
+// once this many have built up. The files are left uncompressed, so readers
+// can memory-map them instead of decoding.
+#[cfg(feature = "arrow")]
+const ARROW_BATCH_ROWS: usize = 65_536;
+
+#[cfg(feature = "arrow")]
+fn arrow_schema(layout: RowLayout) -> SchemaRef {
+    let fields: Vec<Field> = output_headers(layout).iter()
+        .map(|header| Field::new(header, DataType::Utf8, false))
+        .collect();
+    Arc::new(Schema::new(fields))
+}
+
+#[cfg(feature = "arrow")]
+struct ArrowBatch {
+    schema: SchemaRef,
+    columns: Vec<StringBuilder>,
+    rows: usize,
+}
+
+#[cfg(feature = "arrow")]
+impl ArrowBatch {
+    fn new(schema: SchemaRef) -> Self {
+        let columns = schema.fields().iter().map(|_| StringBuilder::new()).collect();
+        Self { schema, columns, rows: 0 }
+    }
+
+    fn push(&mut self, field_data: &FieldData, layout: RowLayout) {
+        let mut columns = self.columns.iter_mut();
+        for_each_output_value(field_data, layout, |value| {
+            if let Some(column) = columns.next() {
+                column.append_value(value);
+            }
+        });
+        self.rows += 1;
+    }
+
+    fn write_to(&mut self, writer: &mut FileWriter<File>) -> Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let columns: Vec<ArrayRef> = self.columns.iter_mut().map(|column| Arc::new(column.finish()) as ArrayRef).collect();
+        self.rows = 0;
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+        writer.write(&batch)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "arrow")]
+fn create_arrow_file(file_path: &Path, schema: &SchemaRef) -> Result<FileWriter<File>> {
+    let file = File::create(file_path)
+        .with_context(|| format!("Failed to create output file: {}", file_path.display()))?;
+    FileWriter::try_new(file, schema)
+        .with_context(|| format!("Failed to start Arrow output file: {}", file_path.display()))
+}
+
+#[cfg(feature = "arrow")]
+struct ArrowOutput {
+    writer: Option<FileWriter<File>>,
+    batch: ArrowBatch,
+    file_path: PathBuf,
+    layout: RowLayout,
+    fsync: Fsync,
+}
+
+#[cfg(feature = "arrow")]
+impl ArrowOutput {
+    fn new<P: AsRef<Path>>(path: P, layout: RowLayout, fsync: Fsync) -> Result<Self> {
+        let file_path = path.as_ref().to_path_buf();
+        info!("Initializing Arrow output file: {}", file_path.display());
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory structure for: {}", file_path.display()))?;
+        }
+        let schema = arrow_schema(layout);
+        let writer = create_arrow_file(&file_path, &schema)?;
+        Ok(Self {
+            writer: Some(writer),
+            batch: ArrowBatch::new(schema),
+            file_path,
+            layout,
+            fsync,
+        })
+    }
+
+    fn write_pending(&mut self) -> Result<()> {
+        let writer = self.writer.as_mut()
+            .ok_or_else(|| anyhow::anyhow!("Arrow output file already closed: {}", self.file_path.display()))?;
+        self.batch.write_to(writer)
+            .with_context(|| format!("Failed to write to Arrow output file: {}", self.file_path.display()))
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl OutputStrategy for ArrowOutput {
+    fn write_batch(&mut self, batch: &[FieldData]) -> Result<()> {
+        for field_data in batch {
+            self.batch.push(field_data, self.layout);
+            if self.batch.rows >= ARROW_BATCH_ROWS {
+                self.write_pending()?;
+            }
+        }
+        Ok(())
+    }
+
+    // The footer is written here, so the file is only readable once the run
+    // has finished. Later flushes do nothing.
+    fn flush(&mut self) -> Result<()> {
+        if self.writer.is_none() {
+            return Ok(());
+        }
+        info!("Flushing final data to: {}", self.file_path.display());
+        self.write_pending()?;
+        if let Some(mut writer) = self.writer.take() {
+            writer.finish()
+                .with_context(|| format!("Failed to close Arrow output file: {}", self.file_path.display()))?;
+            if self.fsync != Fsync::Never {
+                writer.get_ref().sync_data()
+                    .with_context(|| format!("Failed to sync output file to disk: {}", self.file_path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn report_files_created(&self) -> usize {
+        1
+    }
+}
+
+// An Arrow file cannot be reopened for appending once its footer is written,
+// so a member whose file was closed to stay under --max-open-files continues
+// in a new part: 78.arrow, then 78.1.arrow, and so on.
+#[cfg(feature = "arrow")]
+struct OrganizedArrowOutput {
+    base_output_dir: PathBuf,
+    schema: SchemaRef,
+    layout: RowLayout,
+    fsync: Fsync,
+    max_open_files: usize,
+    open_writers: HashMap<MemberId, (FileWriter<File>, ArrowBatch)>,
+    open_file_lru: VecDeque<MemberId>,
+    parts_started: HashMap<MemberId, usize>,
+    files_created: usize,
+    doi_index: Option<DoiIndex>,
+}
+
+#[cfg(feature = "arrow")]
+impl OrganizedArrowOutput {
+    fn new<P: AsRef<Path>>(output_path: P, max_open_files: usize, layout: RowLayout, fsync: Fsync, doi_index: bool) -> Result<Self> {
+        let path = output_path.as_ref();
+        if path.exists() && !path.is_dir() {
+            return Err(anyhow::anyhow!("Output path for organized output must be a directory: {}", path.display()));
+        }
+        fs::create_dir_all(path)
+            .with_context(|| format!("Failed to create base output directory: {}", path.display()))?;
+        info!("Initializing organized Arrow output in directory: {}", path.display());
+        info!("Using a maximum of {} open files at once", max_open_files);
+
+        Ok(Self {
+            base_output_dir: path.to_path_buf(),
+            schema: arrow_schema(layout),
+            layout,
+            fsync,
+            max_open_files: max_open_files.max(1),
+            open_writers: HashMap::with_capacity(max_open_files.min(1024)),
+            open_file_lru: VecDeque::with_capacity(max_open_files),
+            parts_started: HashMap::new(),
+            files_created: 0,
+            doi_index: doi_index.then(DoiIndex::default),
         })
     }
-
-    fn write_batch(&mut self, batch: &[FieldData]) -> Result<()> {
-        self.output_strategy.write_batch(batch)
-            .context("Error writing batch via CsvWriterManager")
+
+    fn close_writer(member_id: &MemberId, mut writer: FileWriter<File>, mut batch: ArrowBatch, fsync: Fsync) -> Result<()> {
+        batch.write_to(&mut writer)
+            .and_then(|_| writer.finish().map_err(anyhow::Error::from))
+            .and_then(|_| match fsync {
+                Fsync::Never => Ok(()),
+                _ => writer.get_ref().sync_data().map_err(anyhow::Error::from),
+            })
+            .with_context(|| format!("Failed to close Arrow file for member {}", member_id.0))
+    }
+
+    fn get_writer(&mut self, member_id: &MemberId) -> Result<&mut (FileWriter<File>, ArrowBatch)> {
+        let key = member_id.clone();
+
+        if self.open_writers.contains_key(&key) {
+            if let Some(pos) = self.open_file_lru.iter().position(|x| x == &key) {
+                self.open_file_lru.remove(pos);
+            }
+            self.open_file_lru.push_front(key.clone());
+            return self.open_writers.get_mut(&key)
+                .ok_or_else(|| anyhow::anyhow!("Writer unexpectedly missing for member {}", key.0));
+        }
+
+        while self.open_writers.len() >= self.max_open_files {
+            let Some(lru_key) = self.open_file_lru.pop_back() else {
+                error!("LRU queue empty while trying to close files. Limit: {}", self.max_open_files);
+                break;
+            };
+            info!("Closing LRU file for member {} to maintain max open files limit.", lru_key.0);
+            if let Some((writer, batch)) = self.open_writers.remove(&lru_key) {
+                Self::close_writer(&lru_key, writer, batch, self.fsync)?;
+            }
+        }
+
+        let part = self.parts_started.entry(key.clone()).or_insert(0);
+        let file_name = match *part {
+            0 => format!("{}.arrow", key.0),
+            n => format!("{}.{}.arrow", key.0, n),
+        };
+        *part += 1;
+        let member_file_path = self.base_output_dir.join(&file_name);
+        if let Some(index) = self.doi_index.as_mut() {
+            index.add_file(&key.0, file_name);
+        }
+        let writer = create_arrow_file(&member_file_path, &self.schema)?;
+        debug!("Created new Arrow file: {}", member_file_path.display());
+        self.files_created += 1;
+
+        self.open_writers.insert(key.clone(), (writer, ArrowBatch::new(self.schema.clone())));
+        self.open_file_lru.push_front(key.clone());
+        self.open_writers.get_mut(&key)
+            .ok_or_else(|| anyhow::anyhow!("Writer unexpectedly missing after insert for member {}", key.0))
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl OutputStrategy for OrganizedArrowOutput {
+    fn write_batch(&mut self, batch: &[FieldData]) -> Result<()> {
+        let mut grouped_records: HashMap<MemberId, Vec<&FieldData>> = HashMap::new();
+        for field_data in batch {
+            grouped_records
+                .entry(field_data.member_id.clone())
+                .or_default()
+                .push(field_data);
+        }
+
+        let layout = self.layout;
+        for (member_id, records) in grouped_records {
+            if let Some(index) = self.doi_index.as_mut() {
+                for field_data in &records {
+                    index.add_row(&member_id.0, &field_data.doi.0);
+                }
+            }
+            let (writer, pending) = self.get_writer(&member_id)
+                .with_context(|| format!("Failed to get writer for member {}", member_id.0))?;
+            for field_data in records {
+                pending.push(field_data, layout);
+                if pending.rows >= ARROW_BATCH_ROWS {
+                    pending.write_to(writer)
+                        .with_context(|| format!("Failed to write to Arrow file for member {}", member_id.0))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        info!("Flushing {} open Arrow files...", self.open_writers.len());
+        let mut flush_errors = Vec::new();
+        for (member_id, (writer, batch)) in self.open_writers.drain() {
+            if let Err(e) = Self::close_writer(&member_id, writer, batch, self.fsync) {
+                flush_errors.push(format!("{:#}", e));
+            }
+        }
+        self.open_file_lru.clear();
+
+        info!("Total Arrow files created during run: {}", self.files_created);
+
+        if flush_errors.is_empty() {
+            if let Some(index) = self.doi_index.take() {
+                index.write(&self.base_output_dir, OrganizeBy::Member)?;
+            }
+        }
+
+        if !flush_errors.is_empty() {
+            Err(anyhow::anyhow!("Errors occurred during final flush:\n - {}", flush_errors.join("\n - ")))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn report_files_created(&self) -> usize {
+        self.files_created
+    }
+}
+
+// Rows are inserted inside a transaction that is committed every this many
+// rows, instead of one implicit transaction per row.
+#[cfg(feature = "sqlite")]
+const SQLITE_COMMIT_ROWS: usize = 250_000;
+
+#[cfg(feature = "sqlite")]
+const SQLITE_TABLE: &str = "field_data";
+
+#[cfg(feature = "sqlite")]
+struct SqliteOutput {
+    connection: Option<Connection>,
+    file_path: PathBuf,
+    layout: RowLayout,
+    fsync: Fsync,
+    insert_sql: String,
+    uncommitted_rows: usize,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteOutput {
+    fn new<P: AsRef<Path>>(path: P, layout: RowLayout, fsync: Fsync) -> Result<Self> {
+        let file_path = path.as_ref().to_path_buf();
+        info!("Initializing SQLite output database: {}", file_path.display());
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory structure for: {}", file_path.display()))?;
+        }
+        // Replaced like the CSV output file, rather than appended to.
+        if file_path.exists() {
+            fs::remove_file(&file_path)
+                .with_context(|| format!("Failed to remove existing output database: {}", file_path.display()))?;
+        }
+
+        let headers = output_headers(layout);
+        let connection = Connection::open(&file_path)
+            .with_context(|| format!("Failed to create output database: {}", file_path.display()))?;
+        // The database is rebuilt from the input on every run, so a crash
+        // mid-run only needs a rerun; skip the journal and fsyncs. With
+        // --fsync on-close the finished database is synced once, on close.
+        connection.execute_batch(&format!(
+            "PRAGMA journal_mode = OFF; PRAGMA synchronous = OFF; CREATE TABLE {} ({}); BEGIN;",
+            SQLITE_TABLE,
+            headers.iter().map(|h| format!("{} TEXT NOT NULL", h)).collect::<Vec<_>>().join(", ")
+        )).with_context(|| format!("Failed to create table in output database: {}", file_path.display()))?;
+
+        let insert_sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            SQLITE_TABLE,
+            headers.join(", "),
+            vec!["?"; headers.len()].join(", ")
+        );
+
+        Ok(Self {
+            connection: Some(connection),
+            file_path,
+            layout,
+            fsync,
+            insert_sql,
+            uncommitted_rows: 0,
+        })
+    }
+
+    fn insert_rows(&mut self, batch: &[FieldData]) -> rusqlite::Result<()> {
+        let Some(connection) = self.connection.as_ref() else {
+            return Ok(());
+        };
+        let mut statement = connection.prepare_cached(&self.insert_sql)?;
+        let mut values = Vec::new();
+        for field_data in batch {
+            values.clear();
+            for_each_output_value(field_data, self.layout, |value| values.push(value.to_string()));
+            statement.execute(rusqlite::params_from_iter(&values))?;
+        }
+        self.uncommitted_rows += batch.len();
+        if self.uncommitted_rows >= SQLITE_COMMIT_ROWS {
+            connection.execute_batch("COMMIT; BEGIN;")?;
+            self.uncommitted_rows = 0;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl OutputStrategy for SqliteOutput {
+    fn write_batch(&mut self, batch: &[FieldData]) -> Result<()> {
+        self.insert_rows(batch)
+            .with_context(|| format!("Failed to write to output database: {}", self.file_path.display()))
+    }
+
+    // The indexes are built once all rows are in, which is much faster than
+    // keeping them up to date row by row. Later flushes do nothing.
+    fn flush(&mut self) -> Result<()> {
+        let Some(connection) = self.connection.take() else {
+            return Ok(());
+        };
+        info!("Flushing final data to: {}", self.file_path.display());
+        connection.execute_batch("COMMIT;")
+            .with_context(|| format!("Failed to write to output database: {}", self.file_path.display()))?;
+        info!("Indexing doi and field_name in: {}", self.file_path.display());
+        connection.execute_batch(&format!(
+            "CREATE INDEX {table}_doi ON {table} (doi); CREATE INDEX {table}_field_name ON {table} (field_name); ANALYZE;",
+            table = SQLITE_TABLE
+        )).with_context(|| format!("Failed to index output database: {}", self.file_path.display()))?;
+        connection.close()
+            .map_err(|(_, e)| e)
+            .with_context(|| format!("Failed to close output database: {}", self.file_path.display()))?;
+        sync_closed_file(&self.file_path, self.fsync)
+    }
+
+    fn report_files_created(&self) -> usize {
+        1
+    }
+}
+
+// Avro output is an object container file: a header holding the schema and
+// codec, then blocks of rows, each ending with the file's sync marker. Every
+// column is an Avro string (a zigzag varint length and the UTF-8 bytes), and
+// each block is compressed with the deflate codec.
+const AVRO_BLOCK_ROWS: usize = 65_536;
+
+const AVRO_MAGIC: &[u8; 4] = b"Obj\x01";
+
+fn avro_schema(layout: RowLayout) -> String {
+    let fields: Vec<Value> = output_headers(layout).iter()
+        .map(|header| serde_json::json!({ "name": header, "type": "string" }))
+        .collect();
+    serde_json::json!({
+        "type": "record",
+        "name": "FieldRecord",
+        "namespace": "crossref_fast_field_parse",
+        "fields": fields,
+    }).to_string()
+}
+
+fn push_avro_long(buf: &mut Vec<u8>, n: i64) {
+    let mut zigzag = ((n << 1) ^ (n >> 63)) as u64;
+    while zigzag >= 0x80 {
+        buf.push((zigzag as u8 & 0x7f) | 0x80);
+        zigzag >>= 7;
+    }
+    buf.push(zigzag as u8);
+}
+
+fn push_avro_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    push_avro_long(buf, bytes.len() as i64);
+    buf.extend_from_slice(bytes);
+}
+
+struct AvroOutput {
+    writer: Option<BufWriter<File>>,
+    file_path: PathBuf,
+    layout: RowLayout,
+    fsync: Fsync,
+    sync_marker: [u8; 16],
+    block: Vec<u8>,
+    block_rows: usize,
+}
+
+impl AvroOutput {
+    fn new<P: AsRef<Path>>(path: P, layout: RowLayout, fsync: Fsync) -> Result<Self> {
+        let file_path = path.as_ref().to_path_buf();
+        info!("Initializing Avro output file: {}", file_path.display());
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory structure for: {}", file_path.display()))?;
+        }
+        let file = File::create(&file_path)
+            .with_context(|| format!("Failed to create output file: {}", file_path.display()))?;
+
+        // The marker only has to be unlikely to occur in the block data, so it
+        // is taken from a hash of the path and the current time.
+        let started = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+        let digest = Sha256::digest(format!("{}:{}", file_path.display(), started.as_nanos()).as_bytes());
+        let mut sync_marker = [0u8; 16];
+        sync_marker.copy_from_slice(&digest[..16]);
+
+        let mut header = Vec::new();
+        header.extend_from_slice(AVRO_MAGIC);
+        push_avro_long(&mut header, 2);
+        push_avro_bytes(&mut header, b"avro.schema");
+        push_avro_bytes(&mut header, avro_schema(layout).as_bytes());
+        push_avro_bytes(&mut header, b"avro.codec");
+        push_avro_bytes(&mut header, b"deflate");
+        push_avro_long(&mut header, 0);
+        header.extend_from_slice(&sync_marker);
+
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&header)
+            .with_context(|| format!("Failed to write Avro header: {}", file_path.display()))?;
+        Ok(Self {
+            writer: Some(writer),
+            file_path,
+            layout,
+            fsync,
+            sync_marker,
+            block: Vec::with_capacity(OUTPUT_BUFFER_CAPACITY),
+            block_rows: 0,
+        })
+    }
+
+    fn write_block(&mut self) -> Result<()> {
+        if self.block_rows == 0 {
+            return Ok(());
+        }
+        let writer = self.writer.as_mut()
+            .ok_or_else(|| anyhow::anyhow!("Avro output file already closed: {}", self.file_path.display()))?;
+        let mut encoder = flate2::write::DeflateEncoder::new(Vec::with_capacity(self.block.len() / 4), flate2::Compression::default());
+        encoder.write_all(&self.block)?;
+        let compressed = encoder.finish()?;
+
+        let mut block_header = Vec::with_capacity(20);
+        push_avro_long(&mut block_header, self.block_rows as i64);
+        push_avro_long(&mut block_header, compressed.len() as i64);
+        writer.write_all(&block_header)
+            .and_then(|_| writer.write_all(&compressed))
+            .and_then(|_| writer.write_all(&self.sync_marker))
+            .with_context(|| format!("Failed to write to Avro output file: {}", self.file_path.display()))?;
+        self.block.clear();
+        self.block_rows = 0;
+        Ok(())
+    }
+}
+
+impl OutputStrategy for AvroOutput {
+    fn write_batch(&mut self, batch: &[FieldData]) -> Result<()> {
+        for field_data in batch {
+            for_each_output_value(field_data, self.layout, |value| push_avro_bytes(&mut self.block, value.as_bytes()));
+            self.block_rows += 1;
+            if self.block_rows >= AVRO_BLOCK_ROWS {
+                self.write_block()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.writer.is_none() {
+            return Ok(());
+        }
+        info!("Flushing final data to: {}", self.file_path.display());
+        self.write_block()?;
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()
+                .with_context(|| format!("Failed to close Avro output file: {}", self.file_path.display()))?;
+            if self.fsync != Fsync::Never {
+                writer.get_ref().sync_data()
+                    .with_context(|| format!("Failed to sync output file to disk: {}", self.file_path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn report_files_created(&self) -> usize {
+        1
+    }
+}
+
+// Key-value output is a sled database mapping each DOI to its extracted
+// fields, for per-DOI lookups from a full snapshot without a database server.
+// Keys are the lowercased DOI; values are JSON objects holding the record's
+// columns and a "fields" array of its rows. A DOI seen again, as when a
+// snapshot holds a record twice, has its rows appended to the stored value,
+// so the source columns are kept with each row rather than the record.
+#[cfg(feature = "sled")]
+const SLED_FIELD_COLUMNS: [&str; 5] = ["field_name", "subfield_path", "value", "source_file_path", "source_line"];
+
+#[cfg(feature = "sled")]
+fn sled_key(doi: &str) -> String {
+    doi.trim().to_lowercase()
+}
+
+#[cfg(feature = "sled")]
+struct SledOutput {
+    db: Option<sled::Db>,
+    dir_path: PathBuf,
+    headers: Vec<String>,
+    layout: RowLayout,
+}
+
+#[cfg(feature = "sled")]
+impl SledOutput {
+    fn new<P: AsRef<Path>>(path: P, layout: RowLayout) -> Result<Self> {
+        let dir_path = path.as_ref().to_path_buf();
+        info!("Initializing key-value output database: {}", dir_path.display());
+        // A sled database is a directory. Only a previous database is
+        // replaced, never some other directory given by mistake.
+        if dir_path.exists() {
+            if !dir_path.join("conf").is_file() || !dir_path.join("db").is_file() {
+                return Err(anyhow::anyhow!(
+                    "{} exists and is not a key-value output database; choose a new --output",
+                    dir_path.display()
+                ));
+            }
+            fs::remove_dir_all(&dir_path)
+                .with_context(|| format!("Failed to remove existing output database: {}", dir_path.display()))?;
+        }
+        let db = sled::Config::new()
+            .path(&dir_path)
+            .mode(sled::Mode::HighThroughput)
+            .open()
+            .with_context(|| format!("Failed to create output database: {}", dir_path.display()))?;
+
+        Ok(Self {
+            db: Some(db),
+            dir_path,
+            headers: output_headers(layout),
+            layout,
+        })
+    }
+
+    // The row split into the record's columns and the entry for its
+    // "fields" array.
+    fn row_values(&self, field_data: &FieldData) -> (serde_json::Map<String, Value>, Value) {
+        let mut record = serde_json::Map::new();
+        let mut field = serde_json::Map::new();
+        let mut headers = self.headers.iter();
+        for_each_output_value(field_data, self.layout, |value| {
+            if let Some(header) = headers.next() {
+                let target = if SLED_FIELD_COLUMNS.contains(&header.as_str()) { &mut field } else { &mut record };
+                target.insert(header.clone(), Value::String(value.to_string()));
+            }
+        });
+        record.insert("fields".to_string(), Value::Array(Vec::new()));
+        (record, Value::Object(field))
+    }
+
+    fn insert_rows(&mut self, batch: &[FieldData]) -> Result<()> {
+        let Some(db) = self.db.as_ref() else {
+            return Ok(());
+        };
+        // The rows of a record arrive together, so grouping keeps the order
+        // of the fields within each value.
+        let mut records: Vec<(String, serde_json::Map<String, Value>)> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for field_data in batch {
+            let key = sled_key(&field_data.doi.0);
+            let (record, field) = self.row_values(field_data);
+            let position = match positions.get(&key) {
+                Some(&position) => position,
+                None => {
+                    positions.insert(key.clone(), records.len());
+                    records.push((key, record));
+                    records.len() - 1
+                }
+            };
+            if let Some(Value::Array(fields)) = records[position].1.get_mut("fields") {
+                fields.push(field);
+            }
+        }
+
+        let mut sled_batch = sled::Batch::default();
+        for (key, mut record) in records {
+            if let Some(existing) = db.get(key.as_bytes())? {
+                let mut stored: serde_json::Map<String, Value> = serde_json::from_slice(&existing)
+                    .with_context(|| format!("Stored value for {} is not valid JSON", key))?;
+                if let (Some(Value::Array(stored_fields)), Some(Value::Array(fields))) = (stored.get_mut("fields"), record.remove("fields")) {
+                    stored_fields.extend(fields);
+                }
+                record = stored;
+            }
+            sled_batch.insert(key.as_bytes(), serde_json::to_vec(&record)?);
+        }
+        db.apply_batch(sled_batch)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sled")]
+impl OutputStrategy for SledOutput {
+    fn write_batch(&mut self, batch: &[FieldData]) -> Result<()> {
+        self.insert_rows(batch)
+            .with_context(|| format!("Failed to write to output database: {}", self.dir_path.display()))
+    }
+
+    // sled syncs what it flushes, so the database is on disk once this
+    // returns, whatever --fsync says. Later flushes do nothing.
+    fn flush(&mut self) -> Result<()> {
+        let Some(db) = self.db.take() else {
+            return Ok(());
+        };
+        info!("Flushing final data to: {}", self.dir_path.display());
+        db.flush()
+            .with_context(|| format!("Failed to write to output database: {}", self.dir_path.display()))?;
+        info!("Wrote {} DOIs to: {}", db.len(), self.dir_path.display());
+        Ok(())
+    }
+
+    fn report_files_created(&self) -> usize {
+        1
+    }
+}
+
+// XLSX output is a workbook for curation staff: the rows on a "Rows" sheet
+// and counts per field on a "Stats" sheet. Whole numbers and true/false in
+// the value and member_id columns are written as number and boolean cells,
+// so that they sort and filter as such. Rows are streamed to a temporary
+// file as they come, but a sheet holds at most 1,048,575 rows below its
+// header, so this is for small runs, such as one member's records. Rows past
+// the limit are left out and counted on the Stats sheet.
+#[cfg(feature = "xlsx")]
+const XLSX_MAX_ROWS: u32 = 1_048_575;
+
+// Excel's limit on the length of the text in a cell.
+#[cfg(feature = "xlsx")]
+const XLSX_MAX_CELL_CHARS: usize = 32_767;
+
+#[cfg(feature = "xlsx")]
+const XLSX_TYPED_COLUMNS: [&str; 3] = ["value", "member_id", "source_line"];
+
+#[cfg(feature = "xlsx")]
+enum XlsxCell<'a> {
+    Integer(f64),
+    Boolean(bool),
+    Text(&'a str),
+}
+
+// Only whole numbers of up to 15 digits, as many as Excel keeps, and without
+// leading zeros, so that identifiers like 0042 keep their zeros. Decimals
+// stay text, so that a version such as 1.10 is not shown as 1.1.
+#[cfg(feature = "xlsx")]
+fn xlsx_cell(value: &str) -> XlsxCell<'_> {
+    match value {
+        "true" => return XlsxCell::Boolean(true),
+        "false" => return XlsxCell::Boolean(false),
+        _ => {}
+    }
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let integer = !digits.is_empty()
+        && digits.len() <= 15
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && (digits == "0" || !digits.starts_with('0'));
+    match value.parse::<i64>() {
+        Ok(n) if integer => XlsxCell::Integer(n as f64),
+        _ => XlsxCell::Text(value),
+    }
+}
+
+#[cfg(feature = "xlsx")]
+fn truncate_chars(value: &str, max_chars: usize) -> Option<&str> {
+    value.char_indices().nth(max_chars).map(|(end, _)| &value[..end])
+}
+
+#[cfg(feature = "xlsx")]
+struct XlsxOutput {
+    workbook: Option<rust_xlsxwriter::Workbook>,
+    file_path: PathBuf,
+    layout: RowLayout,
+    fsync: Fsync,
+    typed: Vec<bool>,
+    integer_format: rust_xlsxwriter::Format,
+    values: Vec<String>,
+    rows: u32,
+    rows_left_out: usize,
+    truncated_values: usize,
+    dois: HashSet<String>,
+    // Rows and distinct DOIs per field, for the Stats sheet.
+    field_stats: HashMap<String, (usize, HashSet<String>)>,
+}
+
+#[cfg(feature = "xlsx")]
+impl XlsxOutput {
+    fn new<P: AsRef<Path>>(path: P, layout: RowLayout, fsync: Fsync) -> Result<Self> {
+        let file_path = path.as_ref().to_path_buf();
+        info!("Initializing XLSX output file: {}", file_path.display());
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory structure for: {}", file_path.display()))?;
+        }
+
+        let headers = output_headers(layout);
+        let header_format = rust_xlsxwriter::Format::new().set_bold();
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let sheet = workbook.add_worksheet_with_constant_memory();
+        sheet.set_name("Rows")?;
+        for (col, header) in headers.iter().enumerate() {
+            let width = match header.as_str() {
+                "doi" | "parent_doi" | "subfield_path" | "source_file_path" => 32,
+                "value" => 60,
+                "field_name" | "award" => 24,
+                _ => 12,
+            };
+            sheet.set_column_width(col as u16, width)?;
+            sheet.write_string_with_format(0, col as u16, header, &header_format)?;
+        }
+        sheet.set_freeze_panes(1, 0)?;
+
+        Ok(Self {
+            workbook: Some(workbook),
+            file_path,
+            layout,
+            fsync,
+            typed: headers.iter().map(|h| XLSX_TYPED_COLUMNS.contains(&h.as_str())).collect(),
+            integer_format: rust_xlsxwriter::Format::new().set_num_format("0"),
+            values: Vec::new(),
+            rows: 0,
+            rows_left_out: 0,
+            truncated_values: 0,
+            dois: HashSet::new(),
+            field_stats: HashMap::new(),
+        })
+    }
+
+    fn write_row(&mut self, field_data: &FieldData) -> Result<()> {
+        let Some(workbook) = self.workbook.as_mut() else {
+            return Ok(());
+        };
+        if self.rows >= XLSX_MAX_ROWS {
+            if self.rows_left_out == 0 {
+                error!(
+                    "XLSX output holds at most {} rows; the rest are left out of {}. Narrow the run with --member, --doi-prefix or --deposited-from, or write CSV",
+                    XLSX_MAX_ROWS, self.file_path.display()
+                );
+            }
+            self.rows_left_out += 1;
+            return Ok(());
+        }
+        self.values.clear();
+        for_each_output_value(field_data, self.layout, |value| self.values.push(value.to_string()));
+
+        let row = self.rows + 1;
+        let sheet = workbook.worksheet_from_index(0)?;
+        for (col, value) in self.values.iter().enumerate() {
+            let col = col as u16;
+            let cell = if self.typed[col as usize] { xlsx_cell(value) } else { XlsxCell::Text(value) };
+            match cell {
+                XlsxCell::Integer(n) => {
+                    sheet.write_number_with_format(row, col, n, &self.integer_format)?;
+                }
+                XlsxCell::Boolean(b) => {
+                    sheet.write_boolean(row, col, b)?;
+                }
+                XlsxCell::Text(text) => {
+                    let text = match truncate_chars(text, XLSX_MAX_CELL_CHARS) {
+                        Some(truncated) => {
+                            self.truncated_values += 1;
+                            truncated
+                        }
+                        None => text,
+                    };
+                    sheet.write_string(row, col, text)?;
+                }
+            }
+        }
+        self.rows = row;
+
+        let doi = &field_data.doi.0;
+        if !self.dois.contains(doi) {
+            self.dois.insert(doi.clone());
+        }
+        let (rows, dois) = self.field_stats.entry(field_data.field_name.clone()).or_default();
+        *rows += 1;
+        if !dois.contains(doi) {
+            dois.insert(doi.clone());
+        }
+        Ok(())
+    }
+
+    fn write_stats_sheet(&self, workbook: &mut rust_xlsxwriter::Workbook) -> Result<()> {
+        let header_format = rust_xlsxwriter::Format::new().set_bold();
+        let sheet = workbook.add_worksheet();
+        sheet.set_name("Stats")?;
+        sheet.set_column_width(0, 32)?;
+        sheet.set_column_width(1, 12)?;
+        sheet.set_column_width(2, 12)?;
+        sheet.write_string_with_format(0, 0, "DOIs", &header_format)?;
+        sheet.write_number(0, 1, self.dois.len() as f64)?;
+        sheet.write_string_with_format(1, 0, "Rows", &header_format)?;
+        sheet.write_number(1, 1, self.rows as f64)?;
+        sheet.write_string_with_format(2, 0, "Rows left out, past the row limit", &header_format)?;
+        sheet.write_number(2, 1, self.rows_left_out as f64)?;
+        sheet.write_string_with_format(3, 0, "Values cut to 32,767 characters", &header_format)?;
+        sheet.write_number(3, 1, self.truncated_values as f64)?;
+
+        sheet.write_string_with_format(5, 0, "field_name", &header_format)?;
+        sheet.write_string_with_format(5, 1, "rows", &header_format)?;
+        sheet.write_string_with_format(5, 2, "dois", &header_format)?;
+        let mut fields: Vec<_> = self.field_stats.iter().collect();
+        fields.sort_by(|a, b| a.0.cmp(b.0));
+        for (row, (field_name, (rows, dois))) in (6u32..).zip(fields) {
+            sheet.write_string(row, 0, field_name)?;
+            sheet.write_number(row, 1, *rows as f64)?;
+            sheet.write_number(row, 2, dois.len() as f64)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "xlsx")]
+impl OutputStrategy for XlsxOutput {
+    fn write_batch(&mut self, batch: &[FieldData]) -> Result<()> {
+        for field_data in batch {
+            self.write_row(field_data)
+                .with_context(|| format!("Failed to write to XLSX output file: {}", self.file_path.display()))?;
+        }
+        Ok(())
+    }
+
+    // The workbook is only written out here, once every row is in. Later
+    // flushes do nothing.
+    fn flush(&mut self) -> Result<()> {
+        let Some(mut workbook) = self.workbook.take() else {
+            return Ok(());
+        };
+        info!("Flushing final data to: {}", self.file_path.display());
+        let columns = self.typed.len() as u16;
+        workbook.worksheet_from_index(0)?.autofilter(0, 0, self.rows, columns - 1)?;
+        self.write_stats_sheet(&mut workbook)?;
+        workbook.save(&self.file_path)
+            .with_context(|| format!("Failed to write XLSX output file: {}", self.file_path.display()))?;
+        if self.rows_left_out > 0 {
+            warn!("{} rows past the XLSX row limit were left out of: {}", self.rows_left_out, self.file_path.display());
+        }
+        if self.truncated_values > 0 {
+            warn!(
+                "{} values longer than Excel's {} characters per cell were cut short in: {}",
+                self.truncated_values, XLSX_MAX_CELL_CHARS, self.file_path.display()
+            );
+        }
+        sync_closed_file(&self.file_path, self.fsync)
+    }
+
+    fn report_files_created(&self) -> usize {
+        1
+    }
+}
+
+struct CsvWriterManager {
+    output_strategy: Box<dyn OutputStrategy>,
+    flushed: bool,
+    upload: Option<Arc<remote_output::RemoteOutput>>,
+}
+
+impl CsvWriterManager {
+    fn new<P: AsRef<Path>>(output_path: P, organize: Option<OrganizeBy>, max_open_files: usize, layout: RowLayout, output_format: OutputFormat, csv: CsvSettings, doi_index: bool) -> Result<Self> {
+        let strategy: Box<dyn OutputStrategy> = match (output_format, organize) {
+            #[cfg(feature = "parquet")]
+            (OutputFormat::Parquet, _) => Box::new(ParquetOutput::new(output_path, layout, csv.fsync)?),
+            #[cfg(feature = "sqlite")]
+            (OutputFormat::Sqlite, _) => Box::new(SqliteOutput::new(output_path, layout, csv.fsync)?),
+            (OutputFormat::Avro, _) => Box::new(AvroOutput::new(output_path, layout, csv.fsync)?),
+            #[cfg(feature = "sled")]
+            (OutputFormat::Sled, _) => Box::new(SledOutput::new(output_path, layout)?),
+            #[cfg(feature = "xlsx")]
+            (OutputFormat::Xlsx, _) => Box::new(XlsxOutput::new(output_path, layout, csv.fsync)?),
+            #[cfg(feature = "arrow")]
+            (OutputFormat::Arrow, Some(_)) => Box::new(OrganizedArrowOutput::new(output_path, max_open_files, layout, csv.fsync, doi_index)?),
+            #[cfg(feature = "arrow")]
+            (OutputFormat::Arrow, None) => Box::new(ArrowOutput::new(output_path, layout, csv.fsync)?),
+            (OutputFormat::Csv, Some(organize_by)) => Box::new(OrganizedOutput::new(output_path, max_open_files, layout, csv, organize_by, doi_index)?),
+            (OutputFormat::Csv, None) => Box::new(SingleFileOutput::new(output_path, layout, csv)?),
+            #[allow(unreachable_patterns)]
+            (format, _) => unreachable!("--output-format {:?} is checked against the built features in main", format),
+        };
+
+        Ok(Self {
+            output_strategy: strategy,
+            flushed: false,
+            upload: None,
+        })
+    }
+
+    fn pivoted<P: AsRef<Path>>(output_path: P, record_kind: RecordKind, csv: CsvSettings, options: PivotOptions) -> Result<Self> {
+        Ok(Self {
+            output_strategy: Box::new(PivotOutput::new(output_path, record_kind, csv, options)?),
+            flushed: false,
+            upload: None,
+        })
+    }
+
+    // Finished parts are uploaded as they close. A part whose upload fails
+    // stays staged and is retried with the rest by RemoteOutput::finish.
+    fn with_upload(mut self, upload: Option<Arc<remote_output::RemoteOutput>>) -> Self {
+        self.upload = upload;
+        self
+    }
+
+    fn write_batch(&mut self, batch: &[FieldData]) -> Result<()> {
+        self.output_strategy.write_batch(batch)
+            .context("Error writing batch via CsvWriterManager")?;
+        if let Some(upload) = &self.upload {
+            for file in self.output_strategy.take_finished_files() {
+                if let Err(e) = upload.upload(&file) {
+                    warn!("{:#}; retrying at the end of the run", e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn flush_all(&mut self) -> Result<()> {
+        self.output_strategy.flush()
+            .context("Error flushing all files via CsvWriterManager")
+    }
+
+    fn report_files_created(&self) -> usize {
+        self.output_strategy.report_files_created()
+    }
+
+    // Organized output only creates files as buffered rows are written, so the
+    // count of files created is taken after the final flush.
+    fn finish(mut self) -> usize {
+        self.final_flush();
+        self.report_files_created()
+    }
+
+    fn final_flush(&mut self) {
+        if std::mem::replace(&mut self.flushed, true) {
+            return;
+        }
+        info!("Attempting final flush...");
+        if let Err(e) = self.flush_all() {
+            error!("Error flushing CSV writers during cleanup: {}", e);
+        } else {
+            info!("Final flush completed successfully.");
+        }
+    }
+}
+
+impl Drop for CsvWriterManager {
+    fn drop(&mut self) {
+        self.final_flush();
+    }
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+    
+    Ok(())
+}
+
+fn setup_thread_pool(thread_count: usize) -> Result<()> {
+    let num_threads = if thread_count == 0 {
+        let cores = num_cpus::get();
+        info!("Auto-detected {} CPU cores. Using {} threads.", cores, cores);
+        cores
+    } else {
+        info!("Using specified {} threads.", thread_count);
+        thread_count
+    };
+    
+    if let Err(e) = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build_global() {
+        error!("Failed to build global thread pool: {}. Proceeding with default.", e);
+    }
+    
+    Ok(())
+}
+
+fn prepare_extractor(fields_spec: &str) -> Result<(Vec<Vec<String>>, PatternTrie)> {
+    let field_specifications = parse_field_specifications(fields_spec);
+    if field_specifications.is_empty() {
+        return Err(anyhow::anyhow!("No fields specified for extraction"));
+    }
+    
+    info!("Fields to extract:");
+    for spec in &field_specifications {
+        info!("  - {}", spec.join("."));
+    }
+    
+    info!("Building efficient pattern extractor (Trie)...");
+    let extractor = PatternTrie::new(&field_specifications);
+    debug!("Extractor Trie structure: {:?}", extractor.root);
+    
+    Ok((field_specifications, extractor))
+}
+
+// `--input -` reads a single JSONL stream from stdin.
+const STDIN_INPUT: &str = "-";
+
+// Remote inputs: objects under an s3://, az:// or gs:// prefix, an http(s)://
+// URL, or a manifest of URLs (a local file given as --input that is neither an
+// input file nor a tar archive).
+fn remote_source(cli: &Cli) -> Result<Option<Box<dyn remote_input::RemoteSource>>> {
+    let Some(input) = cli.input.as_deref() else {
+        return Ok(None);
+    };
+    let input_path = Path::new(input);
+    let remote: Box<dyn remote_input::RemoteSource> = if remote_input::is_remote_uri(input) {
+        info!("Listing input objects under: {}", input);
+        Box::new(remote_input::RemoteInput::list(input, cli.no_sign_request, |key| is_listed_input(key, cli.format) || is_archive(Path::new(key)))?)
+    } else if http_input::is_http_url(input) {
+        Box::new(http_input::HttpInput::new(vec![input.to_string()])?)
+    } else if input_path.is_file() && !is_archive(input_path) && !is_input_member(input) {
+        info!("Reading input URLs from manifest: {}", input);
+        Box::new(http_input::HttpInput::new(http_input::read_manifest(input_path)?)?)
+    } else {
+        return Ok(None);
+    };
+    info!("Found {} remote inputs to process.", remote.files().len());
+    Ok(Some(remote))
+}
+
+fn find_input_files(input_dir: &str, file_glob: Option<&str>, format: InputFormat) -> Result<Vec<PathBuf>> {
+    if file_glob.is_some() && !Path::new(input_dir).is_dir() {
+        return Err(anyhow::anyhow!("--glob selects files in a directory, but --input is not one: {}", input_dir));
+    }
+    if input_dir == STDIN_INPUT {
+        info!("Reading JSONL from stdin");
+        return Ok(vec![PathBuf::from(STDIN_INPUT)]);
+    }
+    if Path::new(input_dir).is_file() && is_archive(Path::new(input_dir)) {
+        info!("Reading input members from archive: {}", input_dir);
+        return Ok(vec![PathBuf::from(input_dir)]);
+    }
+    info!("Searching for input files in: {}", input_dir);
+    let files = find_jsonl_files(input_dir, file_glob, format)?;
+    info!("Found {} files to process.", files.len());
+    Ok(files)
+}
+
+fn input_files(cli: &Cli, remote: Option<&dyn remote_input::RemoteSource>, manifest: Option<&[input_manifest::Entry]>) -> Result<Vec<PathBuf>> {
+    match (remote, manifest) {
+        (Some(_), _) if cli.glob.is_some() => Err(anyhow::anyhow!("--glob selects files in a local directory and cannot be used with remote input")),
+        (Some(remote), _) => Ok(remote.files()),
+        (None, Some(manifest)) => {
+            info!("Found {} files to process in the input manifest.", manifest.len());
+            Ok(manifest.iter().map(|entry| entry.path.clone()).collect())
+        }
+        (None, None) => Ok(without_run_outputs(find_input_files(cli.input.as_deref().unwrap_or_default(), cli.glob.as_deref(), cli.format)?, cli)),
+    }
+}
+
+const SPACE_SAMPLE_FILES: usize = 3;
+// How much smaller --compress-output is taken to make the CSV. Rows repeat the
+// same ids, prefixes and field names, and gzip and zstd at their default levels
+// usually shrink them five- to tenfold; the low end leaves room for outputs of
+// long free-text values.
+const ASSUMED_COMPRESSION_RATIO: f64 = 4.0;
+const SPACE_SAMPLE_RECORDS: usize = 5000;
+
+struct CountingReader<R> {
+    inner: R,
+    bytes: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+}
+
+// Estimated output bytes for the first SPACE_SAMPLE_RECORDS records of one
+// JSONL stream or Parquet file, and the number of lines (or rows) they took.
+fn sample_output_bytes<I: Iterator<Item = io::Result<String>>>(lines: I, extractor: &PatternTrie, json_shape: JsonShape, record_kind: RecordKind) -> (u64, usize) {
+    let mut sampled_output_bytes = 0u64;
+    let mut records_sampled = 0;
+    let mut lines_sampled = 0;
+    for line in lines {
+        let Ok(line) = line else { break };
+        lines_sampled += 1;
+        let Ok(parsed) = serde_json::from_str::<Value>(&line) else { continue };
+        for record in json_shape.records(parsed) {
+            if record_kind.required_type().is_some_and(|t| record.get("type").and_then(Value::as_str) != Some(t)) {
+                continue;
+            }
+            let doi = extract_doi(&record);
+            let link_bytes = match record_kind.parent_relation() {
+                Some(relation) => related_dois(&record, relation).first().map_or(0, |p| p.len()),
+                None => record.get("award").and_then(Value::as_str).filter(|_| record_kind == RecordKind::Grants).map_or(0, str::len),
+            };
+            let key_bytes = doi.as_ref().map_or(0, |d| d.0.len())
+                + extract_doi_prefix(&record, doi.as_ref()).map_or(0, |p| p.0.len())
+                + extract_member_id(&record).map_or(0, |m| m.0.len())
+                + link_bytes;
+            for (field_name, subfield_path, value) in extractor.extract(&record) {
+                sampled_output_bytes += (key_bytes + field_name.len() + subfield_path.len() + value.len() + 7) as u64;
+            }
+            records_sampled += 1;
+        }
+        if records_sampled >= SPACE_SAMPLE_RECORDS {
+            break;
+        }
+    }
+    (sampled_output_bytes, lines_sampled)
+}
+
+fn sample_jsonl_output_bytes<R: Read>(input: R, format: InputFormat, extractor: &PatternTrie, json_shape: JsonShape, record_kind: RecordKind) -> Result<u64> {
+    let documents = input_documents(input, format, GzipMemberCount::default())?;
+    Ok(sample_output_bytes(documents, extractor, json_shape, record_kind).0)
+}
+
+// Only the first rows of a Parquet file are sampled, so the sample is scaled
+// up to the file's row count to match its whole size as input bytes.
+fn sample_parquet_output_bytes(rows: parquet_input::JsonLines, extractor: &PatternTrie, json_shape: JsonShape, record_kind: RecordKind) -> u64 {
+    let total_rows = rows.num_rows() as u64;
+    let (sampled_output_bytes, rows_sampled) = sample_output_bytes(rows, extractor, json_shape, record_kind);
+    if rows_sampled == 0 {
+        return 0;
+    }
+    sampled_output_bytes * total_rows / rows_sampled as u64
+}
+
+// A Parquet archive member has to be read into memory whole to reach its
+// footer, so members larger than this are left out of the sample.
+const SPACE_SAMPLE_MAX_PARQUET_MEMBER_BYTES: u64 = 256 * 1024 * 1024;
+
+fn parquet_member_too_large(archive: &Path, name: &str, size: u64) -> bool {
+    let too_large = size > SPACE_SAMPLE_MAX_PARQUET_MEMBER_BYTES;
+    if too_large {
+        info!("Not sampling {} in {} for the output size estimate: Parquet members over {} MiB are not read ahead.",
+            name, archive.display(), SPACE_SAMPLE_MAX_PARQUET_MEMBER_BYTES / (1024 * 1024));
+    }
+    too_large
+}
+
+// Samples the first members of a zip archive. The input bytes are the share of
+// each member's compressed size that the sample read.
+fn sample_zip_output_bytes(filepath: &Path, format: InputFormat, extractor: &PatternTrie, json_shape: JsonShape, record_kind: RecordKind) -> Result<(u64, u64)> {
+    let mut zip = zip_input::open(filepath, None, None)?;
+    let mut sampled_input_bytes = 0u64;
+    let mut sampled_output_bytes = 0u64;
+    for (index, name) in zip_input::input_members(&zip, is_input_member).into_iter().take(SPACE_SAMPLE_FILES) {
+        let entry = zip.by_index(index)?;
+        let (size, compressed_size) = (entry.size(), entry.compressed_size());
+        let mut counter = CountingReader { inner: entry, bytes: 0 };
+        sampled_output_bytes += if is_parquet(Path::new(&name)) {
+            if parquet_member_too_large(filepath, &name, size) {
+                continue;
+            }
+            sample_parquet_output_bytes(read_parquet(Path::new(&name), &mut counter)?, extractor, json_shape, record_kind)
+        } else {
+            sample_jsonl_output_bytes(&mut counter, format, extractor, json_shape, record_kind)?
+        };
+        sampled_input_bytes += (compressed_size * counter.bytes.min(size)).checked_div(size).unwrap_or(0);
+    }
+    Ok((sampled_input_bytes, sampled_output_bytes))
+}
+
+// The input bytes read and the output bytes estimated for the sample of one
+// local input file or archive.
+fn sample_file_output_bytes(filepath: &Path, format: InputFormat, extractor: &PatternTrie, json_shape: JsonShape, record_kind: RecordKind) -> Result<(u64, u64)> {
+    if is_zip_archive(filepath) {
+        return sample_zip_output_bytes(filepath, format, extractor, json_shape, record_kind);
+    }
+    if is_parquet(filepath) {
+        let rows = parquet_input::JsonLines::open(filepath)?;
+        let input_bytes = fs::metadata(filepath)?.len();
+        return Ok((input_bytes, sample_parquet_output_bytes(rows, extractor, json_shape, record_kind)));
+    }
+    let file = File::open(filepath).with_context(|| format!("Failed to open file: {}", filepath.display()))?;
+    let mut counter = CountingReader { inner: file, bytes: 0 };
+    let mut sampled_output_bytes = 0u64;
+    if is_tar_archive(filepath) {
+        let mut tar = tar::Archive::new(decompress(&mut counter)?);
+        let mut members_sampled = 0;
+        for entry in tar.entries()? {
+            let entry = entry?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            if !entry.header().entry_type().is_file() || !is_input_member(&name) {
+                continue;
+            }
+            sampled_output_bytes += if is_parquet(Path::new(&name)) {
+                // Stopping here keeps the member, which would be read to skip it, out of the input bytes.
+                if parquet_member_too_large(filepath, &name, entry.size()) {
+                    break;
+                }
+                sample_parquet_output_bytes(read_parquet(Path::new(&name), entry)?, extractor, json_shape, record_kind)
+            } else {
+                sample_jsonl_output_bytes(entry, format, extractor, json_shape, record_kind)?
+            };
+            members_sampled += 1;
+            if members_sampled >= SPACE_SAMPLE_FILES {
+                break;
+            }
+        }
+    } else {
+        sampled_output_bytes += sample_jsonl_output_bytes(&mut counter, format, extractor, json_shape, record_kind)?;
+    }
+    Ok((counter.bytes, sampled_output_bytes))
+}
+
+// Estimates the output size from the CSV bytes that the first records of a few
+// local input files (or archive members) produce per compressed input byte,
+// scaled to the total input size. Filters are not applied, so with --member,
+// --doi-prefix or a deposited window this is an upper bound. A file that cannot
+// be sampled is left out of the estimate, and left for processing to report.
+fn estimate_output_bytes(files: &[PathBuf], format: InputFormat, extractor: &PatternTrie, json_shape: JsonShape, record_kind: RecordKind) -> u64 {
+    let total_input_bytes: u64 = files.iter()
+        .filter_map(|f| fs::metadata(f).ok().map(|m| m.len()))
+        .sum();
+    let step = files.len().div_ceil(SPACE_SAMPLE_FILES).max(1);
+    let mut sampled_input_bytes = 0u64;
+    let mut sampled_output_bytes = 0u64;
+
+    for filepath in files.iter().step_by(step) {
+        match sample_file_output_bytes(filepath, format, extractor, json_shape, record_kind) {
+            Ok((input_bytes, output_bytes)) => {
+                sampled_input_bytes += input_bytes;
+                sampled_output_bytes += output_bytes;
+            }
+            Err(e) => warn!("Not sampling {} for the output size estimate: {:#}", filepath.display(), e),
+        }
+    }
+
+    if sampled_input_bytes == 0 {
+        return 0;
+    }
+    let ratio = sampled_output_bytes as f64 / sampled_input_bytes as f64;
+    (ratio * total_input_bytes as f64).ceil() as u64
+}
+
+const FORMAT_SAMPLE_LINES: usize = 100;
+
+// The first `limit` lines of an input file, of the first input member of a tar
+// or zip archive, or of stdin.
+fn first_input_lines(filepath: &Path, remote: Option<&dyn remote_input::RemoteSource>, format: InputFormat, limit: usize) -> Result<Vec<String>> {
+    if filepath == Path::new(STDIN_INPUT) {
+        return input_lines(filepath, io::stdin().lock(), format, limit);
+    }
+    if is_zip_archive(filepath) {
+        let mut zip = zip_input::open(filepath, remote, None)?;
+        let Some((index, name)) = zip_input::input_members(&zip, is_input_member).into_iter().next() else {
+            return Ok(Vec::new());
+        };
+        return input_lines(Path::new(&name), zip.by_index(index)?, format, limit);
+    }
+    let input = open_input(filepath, remote)?;
+    if !is_tar_archive(filepath) {
+        return input_lines(filepath, input, format, limit);
+    }
+    let mut tar = tar::Archive::new(decompress(input)?);
+    for entry in tar.entries()? {
+        let entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        if entry.header().entry_type().is_file() && is_input_member(&name) {
+            return input_lines(Path::new(&name), entry, format, limit);
+        }
+    }
+    Ok(Vec::new())
+}
+
+fn input_lines<R: Read>(filepath: &Path, input: R, format: InputFormat, limit: usize) -> Result<Vec<String>> {
+    if is_parquet(filepath) {
+        return Ok(read_parquet(filepath, input)?.take(limit).map_while(Result::ok).collect());
+    }
+    Ok(input_documents(input, format, GzipMemberCount::default())?.take(limit).map_while(Result::ok).collect())
+}
+
+// Zip archives and Parquet files are refused up front when this binary was
+// built without the feature that reads them, rather than failing one by one.
+fn check_input_features(files: &[PathBuf]) -> Result<()> {
+    if let Some(file) = files.iter().find(|file| !cfg!(feature = "zip") && is_zip_archive(file)) {
+        return Err(missing_feature(&format!("Reading zip archive {}", file.display()), "zip"));
+    }
+    if let Some(file) = files.iter().find(|file| !cfg!(feature = "parquet") && is_parquet(file)) {
+        return Err(missing_feature(&format!("Reading Parquet file {}", file.display()), "parquet"));
+    }
+    Ok(())
+}
+
+// Pointing this parser at an OpenAlex snapshot (or another JSON dump) used to
+// produce an empty CSV with no explanation, since no record has a DOI and
+// member. The first records of the first input are classified so that case
+// fails up front instead.
+fn check_input_format(cli: &Cli, files: &[PathBuf], remote: Option<&dyn remote_input::RemoteSource>) -> Result<()> {
+    if cli.no_format_check || cli.input.as_deref() == Some(STDIN_INPUT) {
+        return Ok(());
+    }
+    let Some(first) = files.first() else {
+        return Ok(());
+    };
+    let lines = match first_input_lines(first, remote, cli.format, FORMAT_SAMPLE_LINES) {
+        Ok(lines) => lines,
+        Err(e) => {
+            warn!("Could not read {} to check the input format: {}", first.display(), e);
+            return Ok(());
+        }
+    };
+    if cli.format == InputFormat::Jsonl && lines.iter().find(|line| !line.trim().is_empty()).is_some_and(|line| line.trim_start().starts_with('[')) {
+        return Err(anyhow::anyhow!(
+            "{} holds a JSON array rather than one JSON document per line. Use --format json-array to read it.",
+            first.display()
+        ));
+    }
+    if cli.format == InputFormat::Jsonl && lines.first().is_some_and(|line| line.trim_start().starts_with("{\"items\"")) {
+        return Err(anyhow::anyhow!(
+            "{} looks like a chunk of the Crossref public data file torrent (one object with an \"items\" array). Use --format torrent to read it.",
+            first.display()
+        ));
     }
-
-    fn flush_all(&mut self) -> Result<()> {
-        self.output_strategy.flush()
-            .context("Error flushing all files via CsvWriterManager")
+    let formats: Vec<snapshot_format::Format> = lines.iter()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .map(|record| snapshot_format::classify(&record))
+        .collect();
+    if formats.is_empty() {
+        return Ok(());
     }
 
-    fn report_files_created(&self) -> usize {
-        self.output_strategy.report_files_created()
+    match snapshot_format::dominant(&formats) {
+        Some(snapshot_format::Format::CrossrefWork) => Ok(()),
+        Some(snapshot_format::Format::CrossrefApiResponse) if cli.json_shape == JsonShape::Api => Ok(()),
+        Some(snapshot_format::Format::CrossrefApiResponse) => Err(anyhow::anyhow!(
+            "{} holds Crossref REST API responses (records wrapped in \"message\"), not bare works. Use --json-shape api to read them.",
+            first.display()
+        )),
+        Some(snapshot_format::Format::OpenAlex(entity)) => Err(anyhow::anyhow!(
+            "{} looks like an OpenAlex {} snapshot (ids start with https://openalex.org/), not Crossref metadata. Use openalex-fast-field-parse for OpenAlex works, or pass --no-format-check to run anyway.",
+            first.display(), entity
+        )),
+        Some(snapshot_format::Format::Unknown) | None => {
+            warn!(
+                "None of the first {} records of {} look like Crossref works (a DOI with member, prefix or deposited). Records without a DOI and member are skipped, so the output may be empty.",
+                formats.len(), first.display()
+            );
+            Ok(())
+        }
     }
 }
 
-impl Drop for CsvWriterManager {
-    fn drop(&mut self) {
-        info!("CsvWriterManager dropping. Attempting final flush...");
-        if let Err(e) = self.flush_all() {
-            error!("Error flushing CSV writers during cleanup: {}", e);
-        } else {
-            info!("Final flush completed successfully.");
+const SUGGEST_SAMPLE_FILES: usize = 3;
+const SUGGEST_SAMPLE_LINES: usize = 2000;
+// Fields filled in fewer sampled records than this (in percent) are reported
+// but left out of the suggested --fields list.
+const SUGGEST_MIN_COVERAGE: f64 = 1.0;
+
+// Counts, over the first records of a few inputs, how many records have a
+// non-empty value for each candidate field of the goal. The counts come from
+// the same extractor a run uses, so a field at 0% would produce no rows.
+fn suggest_fields(cli: &Cli, goal: SuggestGoal) -> Result<()> {
+    if cli.record_kind != RecordKind::Works {
+        return Err(anyhow::anyhow!("--suggest-fields suggests fields for works; grant, peer review and component records have their own default fields"));
+    }
+    let manifest = cli.input_manifest.as_deref().map(|manifest| input_manifest::read(manifest, is_input_file)).transpose()?;
+    let remote = remote_source(cli)?;
+    let files = input_files(cli, remote.as_deref(), manifest.as_deref())?;
+    if files.is_empty() {
+        warn!("No input files found in the specified directory. Exiting.");
+        return Ok(());
+    }
+    check_input_format(cli, &files, remote.as_deref())?;
+
+    let goal_name = goal.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default();
+    let candidates = goal.candidate_fields();
+    let extractor = PatternTrie::new(&parse_field_specifications(&candidates.join(",")));
+    let mut filled: HashMap<String, usize> = HashMap::new();
+    let mut records_sampled = 0usize;
+    let step = files.len().div_ceil(SUGGEST_SAMPLE_FILES).max(1);
+    for filepath in files.iter().step_by(step) {
+        let lines = first_input_lines(filepath, remote.as_deref(), cli.format, SUGGEST_SAMPLE_LINES)?;
+        for line in lines {
+            let Ok(parsed) = serde_json::from_str::<Value>(&line) else { continue };
+            for record in cli.json_shape.records(parsed) {
+                let fields: HashSet<String> = extractor.extract(&record).into_iter()
+                    .filter(|(_, _, value)| !value.is_empty())
+                    .map(|(field_name, _, _)| field_name)
+                    .collect();
+                for field in fields {
+                    *filled.entry(field).or_insert(0) += 1;
+                }
+                records_sampled += 1;
+            }
         }
     }
-}
+    if records_sampled == 0 {
+        return Err(anyhow::anyhow!("No records could be read from the input to suggest fields"));
+    }
 
-fn setup_logging(log_level_str: &str) -> Result<()> {
-    let log_level = match log_level_str.to_uppercase().as_str() {
-        "DEBUG" => LevelFilter::Debug,
-        "INFO" => LevelFilter::Info,
-        "WARN" | "WARNING" => LevelFilter::Warn,
-        "ERROR" => LevelFilter::Error,
-        other => {
-            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
-            LevelFilter::Info
-        }
-    };
+    let mut coverage: Vec<(&str, f64)> = candidates.iter()
+        .map(|field| (*field, filled.get(*field).copied().unwrap_or(0) as f64 * 100.0 / records_sampled as f64))
+        .collect();
+    coverage.sort_by(|a, b| b.1.total_cmp(&a.1));
 
-    SimpleLogger::new()
-        .with_level(log_level)
-        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
-        .init()?;
-    
+    info!("Field coverage for {} over {} sampled records:", goal_name, records_sampled);
+    for (field, percent) in &coverage {
+        info!("  {:>6.2}%  {}", percent, field);
+    }
+    let suggested: Vec<&str> = coverage.iter()
+        .filter(|(_, percent)| *percent >= SUGGEST_MIN_COVERAGE)
+        .map(|(field, _)| *field)
+        .collect();
+    if suggested.is_empty() {
+        warn!("None of the {} fields are filled in at least {}% of the sampled records.", goal_name, SUGGEST_MIN_COVERAGE);
+        return Ok(());
+    }
+    println!("--fields {}", suggested.join(","));
     Ok(())
 }
 
-fn setup_thread_pool(thread_count: usize) -> Result<usize> {
-    let num_threads = if thread_count == 0 {
-        let cores = num_cpus::get();
-        info!("Auto-detected {} CPU cores. Using {} threads.", cores, cores);
-        cores
-    } else {
-        info!("Using specified {} threads.", thread_count);
-        thread_count
-    };
-    
-    if let Err(e) = rayon::ThreadPoolBuilder::new()
-        .num_threads(num_threads)
-        .build_global() {
-        error!("Failed to build global thread pool: {}. Proceeding with default.", e);
+fn check_output_space(cli: &Cli, files: &[PathBuf], remote: Option<&dyn remote_input::RemoteSource>, extractor: &PatternTrie) -> Result<()> {
+    if cli.no_space_check {
+        return Ok(());
     }
-    
-    Ok(num_threads)
+    if cli.input.as_deref() == Some(STDIN_INPUT) {
+        info!("Skipping the disk space check: the size of stdin input is not known in advance.");
+        return Ok(());
+    }
+    if remote.is_some() {
+        info!("Skipping the disk space check: remote inputs are not sampled, as that would download part of them twice.");
+        return Ok(());
+    }
+    let mut estimate = estimate_output_bytes(files, cli.format, extractor, cli.json_shape, cli.record_kind);
+    if cli.compress_output.is_some() {
+        estimate = (estimate as f64 / ASSUMED_COMPRESSION_RATIO).ceil() as u64;
+        info!("Output is compressed: the space estimate assumes it shrinks {}-fold.", ASSUMED_COMPRESSION_RATIO);
+    }
+    disk_space::check("extraction output", Path::new(&cli.output), estimate)
 }
 
-fn prepare_extractor(fields_spec: &str) -> Result<(Vec<Vec<String>>, PatternTrie)> {
-    let field_specifications = parse_field_specifications(fields_spec);
-    if field_specifications.is_empty() {
-        return Err(anyhow::anyhow!("No fields specified for extraction"));
+// --error-sidecar, or next to the output.
+fn error_sidecar_path(cli: &Cli) -> PathBuf {
+    match &cli.error_sidecar {
+        Some(path) => PathBuf::from(path),
+        None => error_sidecar::path_for(Path::new(&cli.output), cli.organize || cli.max_rows_per_file.is_some()),
     }
-    
-    info!("Fields to extract:");
-    for spec in &field_specifications {
-        info!("  - {}", spec.join("."));
+}
+
+fn load_validator(cli: &Cli, field_specifications: &[Vec<String>]) -> Result<Option<validation::Validator>> {
+    let Some(rules_path) = &cli.validation_rules else {
+        return Ok(None);
+    };
+    let validator = validation::Validator::from_file(rules_path, |field| SCHEMA_STRUCTURE.contains_key(field))?;
+    let requested: HashSet<String> = field_specifications.iter().map(|spec| spec.join(".")).collect();
+    for field in validator.fields() {
+        if !requested.contains(field) {
+            warn!("Validation rules for '{}' will not be applied because the field is not in --fields.", field);
+        }
     }
-    
-    info!("Building efficient pattern extractor (Trie)...");
-    let extractor = PatternTrie::new(&field_specifications);
-    debug!("Extractor Trie structure: {:?}", extractor.root);
-    
-    Ok((field_specifications, extractor))
+    info!("Validation violations will be reported to: {}", cli.violations_report);
+    Ok(Some(validator))
 }
 
-fn find_input_files(input_dir: &str) -> Result<Vec<PathBuf>> {
-    info!("Searching for input files in: {}", input_dir);
-    let files = find_jsonl_gz_files(input_dir)?;
-    info!("Found {} files to process.", files.len());
-    Ok(files)
+// The settings that decide what is extracted, as recorded in snapshot locks
+// and provenance.
+fn run_config(cli: &Cli, fields_spec: &str) -> Value {
+    serde_json::json!({
+        "fields": fields_spec,
+        "json_shape": cli.json_shape.to_possible_value().map(|v| v.get_name().to_string()),
+        "record_kind": cli.record_kind.to_possible_value().map(|v| v.get_name().to_string()),
+        "member": cli.member,
+        "doi_prefix": cli.doi_prefix,
+        "deposited_from": cli.deposited_from,
+        "deposited_until": cli.deposited_until,
+        "organize": cli.organize,
+        "organize_by": cli.organize_by.to_possible_value().map(|v| v.get_name().to_string()),
+    })
+}
+
+fn csv_dialect(cli: &Cli) -> CsvDialect {
+    CsvDialect { delimiter: cli.delimiter, quote_style: cli.quote_style, terminator: cli.terminator }
 }
 
 fn run_extraction_pipeline(
     cli: &Cli,
     files: Vec<PathBuf>,
     extractor: PatternTrie,
-    num_threads: usize,
+    validator: Option<validation::Validator>,
+    remote: Option<Box<dyn remote_input::RemoteSource>>,
+    error_sidecar: Arc<error_sidecar::ErrorSidecar>,
+    upload: Option<Arc<remote_output::RemoteOutput>>,
 ) -> Result<(FinalStats, Option<usize>, Vec<PathBuf>)> {
+    let deposited_window = DepositedWindow::from_bounds(cli.deposited_from.as_deref(), cli.deposited_until.as_deref())?;
     info!("Using target batch size for writer: {} records.", cli.batch_size);
     if let Some(member_filter) = &cli.member {
         info!("Filtering by member ID: {}", member_filter);
@@ -1445,14 +4869,57 @@ fn run_extraction_pipeline(
     if let Some(prefix_filter) = &cli.doi_prefix {
         info!("Filtering by DOI prefix: {}", prefix_filter);
     }
-    if cli.organize {
-        info!("Output will be organized by member ID in directory: {}", cli.output);
+    if let Some(window) = &deposited_window {
+        info!("Filtering by deposit date: {}", window);
+    }
+    if let Some(mbps) = cli.max_read_mbps {
+        info!("Limiting combined input reads to {} MB/s", mbps);
+    }
+    if cli.json_shape == JsonShape::Api {
+        info!("Reading REST API responses: works are unwrapped from 'message' and 'message.items'");
+    }
+    if cli.record_kind == RecordKind::Grants {
+        info!("Extracting grant records only (type = grant), with award numbers in the output");
+    }
+    if let Some(relation) = cli.record_kind.parent_relation() {
+        info!("Extracting {} records only, with parent DOIs from relation.{} in the output", cli.record_kind.required_type().unwrap_or_default(), relation);
+        info!("Parent links will be written to: {}", cli.links_output);
+    }
+    if cli.organize && cli.output_format == OutputFormat::Arrow {
+        info!("Output will be organized by member ID into Arrow files in directory: {}", cli.output);
+        info!("Using max {} open output files.", cli.max_open_files);
+    } else if cli.organize {
+        let organize_by = match cli.organize_by {
+            OrganizeBy::Member => "member ID",
+            OrganizeBy::Prefix => "DOI prefix",
+            OrganizeBy::Year => "issued year",
+        };
+        info!("Output will be organized by {} in directory: {}", organize_by, cli.output);
         info!("Using max {} open output files.", cli.max_open_files);
+    } else if cli.output_format == OutputFormat::Parquet {
+        info!("Output will be written to single Parquet file: {}", cli.output);
+    } else if cli.output_format == OutputFormat::Arrow {
+        info!("Output will be written to single Arrow file: {}", cli.output);
+    } else if cli.output_format == OutputFormat::Sqlite {
+        info!("Output will be written to SQLite database: {}", cli.output);
+    } else if cli.output_format == OutputFormat::Avro {
+        info!("Output will be written to single Avro file: {}", cli.output);
+    } else if cli.output_format == OutputFormat::Sled {
+        info!("Output will be written to key-value database: {}", cli.output);
+    } else if cli.output_format == OutputFormat::Xlsx {
+        info!("Output will be written to XLSX workbook: {}", cli.output);
+    } else if cli.pivot {
+        info!("Output will be pivoted to one row per DOI in single file: {}", cli.output);
+    } else if let Some(max_rows) = cli.max_rows_per_file {
+        info!("Output will be split into files of at most {} rows in directory: {}", max_rows, cli.output);
     } else {
         info!("Output will be written to single file: {}", cli.output);
     }
 
-    let progress_bar = ProgressBar::new(files.len() as u64);
+    let (archives, plain_files): (Vec<PathBuf>, Vec<PathBuf>) = files.into_iter().partition(|f| is_archive(f));
+
+    // Archive members are added to the length as they are read.
+    let progress_bar = ProgressBar::new(plain_files.len() as u64);
     progress_bar.set_style(
         ProgressStyle::default_bar()
             .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta} @ {per_sec}) {msg}")
@@ -1461,22 +4928,46 @@ fn run_extraction_pipeline(
     );
     progress_bar.set_message("Starting processing...");
 
-    let stats = Arc::new(IncrementalStats::new());
-
+    let num_threads = rayon::current_num_threads();
     let channel_capacity = (num_threads * 4).max(8);
     let (batch_sender, batch_receiver): (Sender<Vec<FieldData>>, Receiver<Vec<FieldData>>) = bounded(channel_capacity);
     info!("Using writer channel with capacity: {}", channel_capacity);
 
     let output_path_clone = cli.output.clone();
-    let organize_clone = cli.organize;
+    let organize_clone = cli.organize.then_some(cli.organize_by);
     let max_open_files_clone = cli.max_open_files;
+    let record_kind = cli.record_kind;
+    let layout = RowLayout { record_kind, source_columns: cli.source_columns };
+    let output_format = cli.output_format;
+    let csv = CsvSettings {
+        compression: cli.compress_output.map(|format| CsvCompression {
+            format,
+            level: cli.compress_level.unwrap_or(format.default_level()),
+        }),
+        dialect: csv_dialect(cli),
+        max_rows_per_file: cli.max_rows_per_file,
+        flush_every_rows: cli.flush_every_rows,
+        fsync: cli.fsync,
+    };
+    let doi_index = cli.doi_index;
+    let pivot = cli.pivot.then(|| PivotOptions {
+        fields: extractor.patterns.clone(),
+        delimiter: cli.pivot_delimiter.clone(),
+    });
     let writer_thread = thread::spawn(move || -> Result<usize> {
         info!("Writer thread started.");
-        let mut csv_writer_manager = CsvWriterManager::new(
-            &output_path_clone,
-            organize_clone,
-            max_open_files_clone
-        )?;
+        let mut csv_writer_manager = match pivot {
+            Some(options) => CsvWriterManager::pivoted(&output_path_clone, record_kind, csv, options)?,
+            None => CsvWriterManager::new(
+                &output_path_clone,
+                organize_clone,
+                max_open_files_clone,
+                layout,
+                output_format,
+                csv,
+                doi_index
+            )?,
+        }.with_upload(upload);
 
         let mut batches_written = 0;
         let mut records_written = 0;
@@ -1495,68 +4986,109 @@ fn run_extraction_pipeline(
         }
 
         info!("Writer thread finished receiving. Wrote {} records in {} batches.", records_written, batches_written);
-         Ok(csv_writer_manager.report_files_created())
+         Ok(csv_writer_manager.finish())
     });
 
+    let link_table = match cli.record_kind.parent_relation() {
+        Some(_) => Some(Arc::new(link_table::LinkTable::create(&cli.links_output)?)),
+        None => None,
+    };
+
     info!("Starting parallel file processing...");
     let extractor_arc = Arc::new(extractor);
 
-    let processor = Arc::new(JsonlProcessor {
+    let processor = JsonlProcessor {
         extractor: extractor_arc,
+        json_shape: cli.json_shape,
+        record_kind: cli.record_kind,
         filter_member: cli.member.clone(),
         filter_doi_prefix: cli.doi_prefix.clone(),
-    });
+        deposited_window,
+        validator: validator.map(Arc::new),
+        read_limiter: cli.max_read_mbps.map(|mbps| Arc::new(throttle::RateLimiter::new(mbps))),
+        input_format: cli.format,
+        remote,
+        error_sidecar,
+        link_table: link_table.clone(),
+    };
 
-    let processing_results: Vec<ProcessedFileResult> = files
-        .par_iter()
-        .map(|filepath| {
-            let processor_ref = Arc::clone(&processor);
-            let sender_clone = batch_sender.clone();
-            let pb_clone = progress_bar.clone();
-            let target_batch_size = cli.batch_size;
+    let process_input = |filepath: &Path, member_data: Option<Vec<u8>>| -> ProcessedFileResult {
+        let process_start_time = Instant::now();
 
-            let process_start_time = Instant::now();
+        let result = match member_data {
+            Some(data) => processor.process_archive_member(filepath, data, &batch_sender, cli.batch_size),
+            None => processor.process(filepath, &batch_sender, cli.batch_size),
+        };
+        let duration = process_start_time.elapsed();
 
-            let result = processor_ref.process(filepath, &sender_clone, target_batch_size);
-            let duration = process_start_time.elapsed();
+        let file_name_msg = filepath.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| filepath.display().to_string());
 
-            let file_name_msg = filepath.file_name()
-                .map(|n| n.to_string_lossy().to_string())
-                .unwrap_or_else(|| filepath.display().to_string());
+        progress_bar.inc(1);
 
-            pb_clone.inc(1);
+        if result.error.is_some() {
+            progress_bar.set_message(format!("ERR: {} ({})", file_name_msg, format_elapsed(duration)));
+        } else {
+            let num_extracted = result.stats.total_fields_extracted;
+            progress_bar.set_message(format!("OK: {} ({} fields, {})", file_name_msg, num_extracted, format_elapsed(duration)));
+        }
 
-            if result.error.is_some() {
-                pb_clone.set_message(format!("ERR: {} ({})", file_name_msg, format_elapsed(duration)));
-            } else {
-                let num_extracted = result.stats.total_fields_extracted;
-                pb_clone.set_message(format!("OK: {} ({} fields, {})", file_name_msg, num_extracted, format_elapsed(duration)));
+        result
+    };
+
+    let mut stats = plain_files
+        .par_iter()
+        .map(|filepath| process_input(filepath, None))
+        .fold(IncrementalStats::default, IncrementalStats::add_result)
+        .reduce(IncrementalStats::default, IncrementalStats::merge);
+
+    for archive in &archives {
+        let (member_sender, member_receiver) = bounded::<(PathBuf, Vec<u8>)>(num_threads * 2);
+        let read_limiter = processor.read_limiter.clone();
+        let remote = processor.remote.as_deref();
+        let progress_bar_ref = &progress_bar;
+        let (reader_result, member_stats) = thread::scope(|scope| {
+            let reader = scope.spawn(move || read_archive_members(archive, remote, read_limiter, member_sender, progress_bar_ref));
+            let member_stats = member_receiver
+                .into_iter()
+                .par_bridge()
+                .map(|(label, data)| process_input(&label, Some(data)))
+                .fold(IncrementalStats::default, IncrementalStats::add_result)
+                .reduce(IncrementalStats::default, IncrementalStats::merge);
+            (reader.join(), member_stats)
+        });
+        stats = stats.merge(member_stats);
+        let error = match reader_result {
+            Ok(Ok(members)) => {
+                info!("Read {} input members from archive {}", members, archive.display());
+                None
             }
-            
-            result
-        })
-        .collect();
+            Ok(Err(e)) => Some(e),
+            Err(_) => Some(anyhow::anyhow!("Archive reader thread panicked")),
+        };
+        if let Some(err) = error {
+            stats = stats.add_result(ProcessedFileResult { stats: FileStats::default(), error: Some(err), filepath: archive.clone() });
+        }
+    }
 
-    info!("File processing complete. Aggregating final stats...");
-    progress_bar.set_message("Aggregating stats...");
+    info!("File processing complete.");
 
     drop(batch_sender);
+    if let Some(link_table) = &link_table {
+        link_table.flush()?;
+    }
 
     let mut files_with_errors = Vec::new();
-    for result in processing_results {
-        if let Some(e) = result.error {
-            error!("Error processing file {}: {}", result.filepath.display(), e);
-            stats.increment_error_files();
-            files_with_errors.push(result.filepath);
-        } else {
-            stats.aggregate_file_stats(result.stats);
-        }
+    for (filepath, e) in std::mem::take(&mut stats.file_errors) {
+        error!("Error processing file {}: {}", filepath.display(), e);
+        files_with_errors.push(filepath);
     }
 
     progress_bar.finish_with_message(format!(
         "Processing finished. {} files OK, {} errors.",
-        stats.processed_files_ok.load(Ordering::Relaxed),
-        stats.processed_files_error.load(Ordering::Relaxed)
+        stats.processed_files_ok,
+        stats.processed_files_error
     ));
 
     info!("Waiting for writer thread to finish writing remaining batches...");
@@ -1577,7 +5109,7 @@ fn run_extraction_pipeline(
          }
     };
 
-    let final_stats = stats.get_final_stats();
+    let final_stats = stats.into_final_stats();
     Ok((final_stats, files_created, files_with_errors))
 }
 
@@ -1588,6 +5120,7 @@ fn print_final_summary(
     files_created: Option<usize>,
     files_count: usize,
     files_with_errors: &[PathBuf],
+    error_sidecar: &error_sidecar::ErrorSidecar,
 ) -> Result<()> {
     info!("-------------------- FINAL SUMMARY --------------------");
     let total_runtime = start_time.elapsed();
@@ -1595,6 +5128,20 @@ fn print_final_summary(
     info!("Input files found: {}", files_count);
 
     info!("Files processed successfully: {}", final_stats.processed_files_ok);
+    if final_stats.gzip_members > 0 {
+        info!("Gzip members decoded: {}", final_stats.gzip_members);
+    }
+    if !final_stats.multi_member_files.is_empty() {
+        info!("Files with concatenated gzip members: {}", final_stats.multi_member_files.len());
+        let mut sorted_multi_member: Vec<_> = final_stats.multi_member_files.iter().collect();
+        sorted_multi_member.sort_by_key(|&(_, members)| std::cmp::Reverse(*members));
+        for (path, members) in sorted_multi_member.iter().take(10) {
+            info!("  - {}: {} members", path.display(), members);
+        }
+        if sorted_multi_member.len() > 10 {
+            info!("  ... (and {} more)", sorted_multi_member.len() - 10);
+        }
+    }
     if final_stats.processed_files_error > 0 {
         warn!("Files with processing errors: {}", final_stats.processed_files_error);
         if !files_with_errors.is_empty() {
@@ -1606,11 +5153,35 @@ fn print_final_summary(
             }
         }
     }
+    if final_stats.records_panicked > 0 {
+        warn!("Records skipped after extraction panics: {}", final_stats.records_panicked);
+    }
+    if error_sidecar.entries() > 0 {
+        warn!("Error sidecar: {} records written to {}", error_sidecar.entries(), error_sidecar.path().display());
+    }
     info!("Total field records extracted: {}", final_stats.total_field_records);
     info!("Unique DOIs encountered: {}", final_stats.unique_dois);
     info!("Unique Members encountered: {}", final_stats.unique_members.len());
     info!("Unique DOI Prefixes encountered: {}", final_stats.unique_prefixes.len());
 
+    if cli.record_kind == RecordKind::Grants {
+        info!("Grant statistics:");
+        info!("  - Grant records extracted: {}", final_stats.grants);
+        info!("  - Without award number: {}", final_stats.grants_missing_award);
+        info!("  - With funder identifier: {}", final_stats.grants_with_funder_id);
+        info!("  - With a ROR-identified investigator affiliation: {}", final_stats.grants_with_investigator_ror);
+        info!("  - Unique investigator ORCIDs: {}", final_stats.unique_investigator_orcids);
+        info!("  - Non-grant records skipped: {}", final_stats.other_type_records_skipped);
+    }
+    if let Some(relation) = cli.record_kind.parent_relation() {
+        let record_type = cli.record_kind.required_type().unwrap_or_default();
+        info!("Parent link statistics:");
+        info!("  - {} records extracted: {}", record_type, final_stats.parent_link_records);
+        info!("  - Without a parent DOI in relation.{}: {}", relation, final_stats.parent_link_records_without_parent);
+        info!("  - Parent links written to {}: {}", cli.links_output, final_stats.parent_links);
+        info!("  - Other records skipped: {}", final_stats.other_type_records_skipped);
+    }
+
     info!("Final Field breakdown:");
     let mut final_sorted_fields: Vec<_> = final_stats.unique_fields.iter().collect();
     final_sorted_fields.sort_by_key(|&(_, count)| std::cmp::Reverse(*count));
@@ -1632,6 +5203,22 @@ fn print_final_summary(
         info!("(Skipping detailed stats for {} members)", final_stats.unique_members.len());
     }
 
+    if cli.validation_rules.is_some() {
+        let total_violations: usize = final_stats.violations.values().map(|t| t.count).sum();
+        if total_violations > 0 {
+            warn!("Validation violations: {} across {} field/rule pairs", total_violations, final_stats.violations.len());
+            let mut sorted_violations: Vec<_> = final_stats.violations.iter().collect();
+            sorted_violations.sort_by_key(|&(_, tally)| std::cmp::Reverse(tally.count));
+            for ((field, rule), tally) in sorted_violations.iter().take(20) {
+                warn!("  - {} [{}]: {} values", field, rule, tally.count);
+            }
+        } else {
+            info!("Validation violations: none");
+        }
+        validation::write_report(&cli.violations_report, &final_stats.violations, "doi")?;
+        info!("Violations report written to: {}", cli.violations_report);
+    }
+
     if let Some(count) = files_created {
          if cli.organize {
             info!("Total unique output files created/opened: {}", count);
@@ -1647,30 +5234,532 @@ fn print_final_summary(
 
 fn main() -> Result<()> {
     let start_time = Instant::now();
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
 
     setup_logging(&cli.log_level)?;
     info!("Starting Field Extractor");
     memory_usage::log_memory_usage("initial");
 
-    let num_threads = setup_thread_pool(cli.threads)?;
+    if cli.max_read_mbps.is_some_and(|mbps| !mbps.is_finite() || mbps <= 0.0) {
+        return Err(anyhow::anyhow!("--max-read-MBps must be a positive number"));
+    }
+    // A spreadsheet is what curation staff open, so -o report.xlsx is taken
+    // to mean one rather than a CSV file with the wrong extension.
+    if cli.output_format == OutputFormat::Csv && cli.output.to_lowercase().ends_with(".xlsx") {
+        cli.output_format = OutputFormat::Xlsx;
+    }
+    if let Some(feature) = cli.output_format.missing_feature() {
+        return Err(missing_feature(&format!("--output-format {}", feature), feature));
+    }
+    if cli.organize && cli.output_format == OutputFormat::Parquet {
+        return Err(anyhow::anyhow!("--output-format parquet writes a single file and cannot be used with --organize"));
+    }
+    if cli.compress_output.is_some() && cli.output_format != OutputFormat::Csv {
+        return Err(anyhow::anyhow!("--compress-output applies to CSV output only"));
+    }
+    if csv_dialect(&cli) != CsvDialect::default() && cli.output_format != OutputFormat::Csv {
+        return Err(anyhow::anyhow!("--delimiter, --quote-style and --terminator apply to CSV output only"));
+    }
+    if let (Some(format), Some(level)) = (cli.compress_output, cli.compress_level) {
+        let levels = format.levels();
+        if !levels.contains(&level) {
+            let name = format.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default();
+            return Err(anyhow::anyhow!(
+                "--compress-level for --compress-output {} must be between {} and {}",
+                name, levels.start(), levels.end()
+            ));
+        }
+    }
+    if cli.compare_with.is_some() && cli.output_format != OutputFormat::Csv {
+        return Err(anyhow::anyhow!("--compare-with compares CSV output only"));
+    }
+    if remote_input::is_remote_uri(&cli.output) {
+        if !matches!(cli.output_format, OutputFormat::Csv | OutputFormat::Parquet) {
+            return Err(anyhow::anyhow!("A remote --output takes CSV or Parquet output"));
+        }
+        if cli.organize {
+            return Err(anyhow::anyhow!("--organize keeps its files open until the run ends and cannot write to a remote --output; use --max-rows-per-file to upload parts as they fill"));
+        }
+        if cli.compare_with.is_some() {
+            return Err(anyhow::anyhow!("--compare-with reads the output back and cannot be used with a remote --output"));
+        }
+    } else if cli.staging_dir.is_some() {
+        return Err(anyhow::anyhow!("--staging-dir applies to a remote --output only"));
+    }
+    if cli.organize && cli.output_format == OutputFormat::Sqlite {
+        return Err(anyhow::anyhow!("--output-format sqlite writes a single database and cannot be used with --organize"));
+    }
+    if cli.organize && cli.output_format == OutputFormat::Avro {
+        return Err(anyhow::anyhow!("--output-format avro writes a single file and cannot be used with --organize"));
+    }
+    if cli.organize && cli.output_format == OutputFormat::Sled {
+        return Err(anyhow::anyhow!("--output-format sled writes a single database and cannot be used with --organize"));
+    }
+    if cli.organize && cli.output_format == OutputFormat::Xlsx {
+        return Err(anyhow::anyhow!("--output-format xlsx writes a single workbook and cannot be used with --organize"));
+    }
+    if cli.organize_by != OrganizeBy::Member && cli.output_format != OutputFormat::Csv {
+        return Err(anyhow::anyhow!("--organize-by {} applies to CSV output only; Arrow output is organized by member", cli.organize_by.label()));
+    }
+    if cli.pivot && cli.organize {
+        return Err(anyhow::anyhow!("--pivot writes a single CSV file and cannot be used with --organize"));
+    }
+    if cli.pivot && cli.output_format != OutputFormat::Csv {
+        return Err(anyhow::anyhow!("--pivot writes CSV output only"));
+    }
+    if cli.pivot && cli.source_columns {
+        return Err(anyhow::anyhow!("--pivot merges the rows of each DOI into one and cannot be used with --source-columns"));
+    }
+    if cli.max_rows_per_file.is_some() && cli.output_format != OutputFormat::Csv {
+        return Err(anyhow::anyhow!("--max-rows-per-file applies to CSV output only"));
+    }
+    if cli.max_rows_per_file.is_some() && cli.pivot {
+        return Err(anyhow::anyhow!("--pivot writes a single CSV file and cannot be used with --max-rows-per-file"));
+    }
+    if cli.flush_every_rows.is_some() && cli.output_format != OutputFormat::Csv {
+        return Err(anyhow::anyhow!("--flush-every-rows applies to CSV output only; the other formats are only readable once the run has finished"));
+    }
+    if cli.flush_every_rows.is_some() && cli.pivot {
+        return Err(anyhow::anyhow!("--pivot holds its rows until the run ends and cannot be used with --flush-every-rows"));
+    }
+    if cli.fsync == Fsync::Periodic && cli.flush_every_rows.is_none() {
+        return Err(anyhow::anyhow!("--fsync periodic syncs at every --flush-every-rows flush, so it needs --flush-every-rows"));
+    }
+
+    if let Some(goal) = cli.suggest_fields {
+        return suggest_fields(&cli, goal);
+    }
+
+    setup_thread_pool(cli.threads)?;
     
-    let (_field_specifications, extractor) = prepare_extractor(&cli.fields)?;
-    let files = find_input_files(&cli.input)?;
+    let fields_spec = match (&cli.fields, cli.record_kind) {
+        (Some(fields), _) => fields.clone(),
+        (None, record_kind) => match record_kind.default_fields() {
+            Some(fields) => fields.join(","),
+            None => return Err(anyhow::anyhow!("--fields is required unless --record-kind is grants, peer-reviews or components")),
+        },
+    };
+    let (field_specifications, extractor) = prepare_extractor(&fields_spec)?;
+    let validator = load_validator(&cli, &field_specifications)?;
+    let manifest = cli.input_manifest.as_deref().map(|manifest| input_manifest::read(manifest, is_input_file)).transpose()?;
+    let remote = remote_source(&cli)?;
+    let files = input_files(&cli, remote.as_deref(), manifest.as_deref())?;
     
     if files.is_empty() {
-        warn!("No .jsonl.gz files found in the specified directory. Exiting.");
+        warn!("No input files found in the specified directory. Exiting.");
         return Ok(());
     }
 
+    check_input_features(&files)?;
+    check_input_format(&cli, &files, remote.as_deref())?;
+
+    if let Some(lock_path) = &cli.pin {
+        if cli.input.as_deref() == Some(STDIN_INPUT) {
+            return Err(anyhow::anyhow!("--pin cannot be used with --input -, since stdin has no files to pin"));
+        }
+        let config = run_config(&cli, &fields_spec);
+        let schema_files: Vec<(&str, &str)> = cli.validation_rules.iter().map(|p| ("validation_rules", p.as_str())).collect();
+        let inputs = match &remote {
+            Some(_) if cli.pin_hash => return Err(anyhow::anyhow!("--pin-hash hashes local input files; remote inputs are pinned by their ETag instead")),
+            Some(remote) => remote.lock_inputs(),
+            None => {
+                let hash = cli.pin_hash || snapshot_lock::pins_hashes(lock_path)?;
+                snapshot_lock::local_inputs(cli.input.as_deref().unwrap_or_default(), &files, hash)?
+            }
+        };
+        let run = snapshot_lock::describe_run(tool(), inputs, config, &schema_files)?;
+        snapshot_lock::pin_or_verify(lock_path, &run)?;
+    }
+
+    // A remote output is written under the staging directory and uploaded
+    // from there, so --output is pointed at the staged copy from here on.
+    let upload = if remote_input::is_remote_uri(&cli.output) {
+        let staging_dir = cli.staging_dir.as_ref().map_or_else(std::env::temp_dir, PathBuf::from);
+        let upload = Arc::new(remote_output::RemoteOutput::connect(&cli.output, &staging_dir)?);
+        cli.output = upload.local_output().to_string_lossy().to_string();
+        Some(upload)
+    } else {
+        None
+    };
+
+    if upload.is_some() && cli.max_rows_per_file.is_some() {
+        info!("Skipping the disk space check: parts are uploaded as they fill, so only the one being written is kept on disk.");
+    } else {
+        check_output_space(&cli, &files, remote.as_deref(), &extractor)?;
+    }
+
     let files_count = files.len();
-    let (final_stats, files_created, files_with_errors) = run_extraction_pipeline(&cli, files, extractor, num_threads)?;
-    
-    print_final_summary(start_time, &final_stats, &cli, files_created, files_count, &files_with_errors)?;
+    let error_sidecar = Arc::new(error_sidecar::ErrorSidecar::new(error_sidecar_path(&cli)));
+    let (final_stats, files_created, files_with_errors) =
+        run_extraction_pipeline(&cli, files, extractor, validator, remote, Arc::clone(&error_sidecar), upload.clone())?;
+    error_sidecar.flush();
+
+    print_final_summary(start_time, &final_stats, &cli, files_created, files_count, &files_with_errors, &error_sidecar)?;
+    let finished = time::OffsetDateTime::now_utc()
+        .format(format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z"))
+        .unwrap_or_default();
+    provenance::write(Path::new(&cli.output), &serde_json::json!({
+        "tool": tool(),
+        "command": std::env::args().collect::<Vec<_>>(),
+        "finished": finished,
+        "source": {
+            "input": cli.input,
+            "input_manifest": cli.input_manifest,
+            "glob": cli.glob,
+            "files": files_count,
+            "files_with_errors": files_with_errors.len(),
+            "snapshot_lock": cli.pin,
+        },
+        "config": run_config(&cli, &fields_spec),
+        "output": {
+            "format": cli.output_format.to_possible_value().map(|v| v.get_name().to_string()),
+            "source_columns": cli.source_columns,
+            "files": files_created,
+            "records_read": final_stats.records_read.iter().map(|(_, records)| records).sum::<usize>(),
+            "field_records": final_stats.total_field_records,
+            "dois": final_stats.unique_dois,
+        },
+    }))?;
+    if let Some(upload) = &upload {
+        upload.finish()?;
+    }
+    if let Some(manifest) = &manifest {
+        input_manifest::check_record_counts(cli.input_manifest.as_deref().unwrap_or_default(), manifest, &final_stats.records_read, &files_with_errors)?;
+    }
     
+    if let Some(previous) = &cli.compare_with {
+        let dialect = csv_dialect(&cli);
+        let mut csv = csv::ReaderBuilder::new();
+        csv.delimiter(dialect.delimiter).quoting(dialect.quote_style != QuoteStyle::Never);
+        output_compare::compare(Path::new(previous), Path::new(&cli.output), Path::new(&cli.compare_report), &csv, &|file| decompress(file))?;
+    }
+
     memory_usage::log_memory_usage("final");
     info!("Extraction process finished.");
     info!("-------------------------------------------------------");
 
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::DeflateDecoder;
+
+    // A scratch file or directory under the system temp directory, removed
+    // when dropped.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("{}-{}-{}", env!("CARGO_PKG_NAME"), std::process::id(), name)))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            if self.0.is_dir() {
+                let _ = fs::remove_dir_all(&self.0);
+            } else {
+                let _ = fs::remove_file(&self.0);
+            }
+        }
+    }
+
+    fn field_data(doi: &str, field_name: &str, value: &str, source_line: usize) -> FieldData {
+        FieldData {
+            doi: Doi(doi.to_string()),
+            field_name: field_name.to_string(),
+            subfield_path: format!("{}[0]", field_name),
+            value: value.to_string(),
+            member_id: MemberId("78".to_string()),
+            doi_prefix: DoiPrefix("10.1000".to_string()),
+            source_file_path: Arc::from(Path::new("part-0.jsonl.gz")),
+            source_line,
+            ..FieldData::default()
+        }
+    }
+
+    // Decoding follows the Avro specification rather than the encoder, so the
+    // two cannot agree on a mistake.
+    fn read_avro_long(data: &[u8], pos: &mut usize) -> i64 {
+        let mut zigzag = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = data[*pos];
+            *pos += 1;
+            zigzag |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+            assert!(shift < 70, "varint longer than 10 bytes");
+        }
+        (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64)
+    }
+
+    fn read_avro_bytes<'a>(data: &'a [u8], pos: &mut usize) -> &'a [u8] {
+        let len = read_avro_long(data, pos);
+        assert!(len >= 0, "negative length {}", len);
+        let bytes = &data[*pos..*pos + len as usize];
+        *pos += len as usize;
+        bytes
+    }
+
+    fn read_avro_string(data: &[u8], pos: &mut usize) -> String {
+        String::from_utf8(read_avro_bytes(data, pos).to_vec()).unwrap()
+    }
+
+    struct AvroFile {
+        metadata: HashMap<String, String>,
+        sync_marker: Vec<u8>,
+        // The rows of each block, in file order.
+        blocks: Vec<Vec<Vec<String>>>,
+    }
+
+    fn read_avro_file(data: &[u8]) -> AvroFile {
+        assert_eq!(&data[..4], AVRO_MAGIC);
+        let mut pos = 4;
+        let mut metadata = HashMap::new();
+        loop {
+            let mut count = read_avro_long(data, &mut pos);
+            if count == 0 {
+                break;
+            }
+            if count < 0 {
+                count = -count;
+                read_avro_long(data, &mut pos);
+            }
+            for _ in 0..count {
+                let key = read_avro_string(data, &mut pos);
+                metadata.insert(key, read_avro_string(data, &mut pos));
+            }
+        }
+        let sync_marker = data[pos..pos + 16].to_vec();
+        pos += 16;
+
+        let schema: Value = serde_json::from_str(&metadata["avro.schema"]).unwrap();
+        let columns = schema["fields"].as_array().unwrap().len();
+        let mut blocks = Vec::new();
+        while pos < data.len() {
+            let rows = read_avro_long(data, &mut pos);
+            let compressed = read_avro_bytes(data, &mut pos);
+            assert_eq!(&data[pos..pos + 16], sync_marker.as_slice(), "block {} does not end with the sync marker", blocks.len());
+            pos += 16;
+
+            let mut block = Vec::new();
+            DeflateDecoder::new(compressed).read_to_end(&mut block).unwrap();
+            let mut block_pos = 0;
+            let decoded: Vec<Vec<String>> = (0..rows)
+                .map(|_| (0..columns).map(|_| read_avro_string(&block, &mut block_pos)).collect())
+                .collect();
+            assert_eq!(block_pos, block.len(), "block {} has bytes after its {} rows", blocks.len(), rows);
+            blocks.push(decoded);
+        }
+        AvroFile { metadata, sync_marker, blocks }
+    }
+
+    #[test]
+    fn avro_longs_use_zigzag_varints() {
+        let cases: [(i64, &[u8]); 9] = [
+            (0, &[0x00]),
+            (-1, &[0x01]),
+            (1, &[0x02]),
+            (-64, &[0x7f]),
+            (64, &[0x80, 0x01]),
+            (-65, &[0x81, 0x01]),
+            (8192, &[0x80, 0x80, 0x01]),
+            (i64::MAX, &[0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]),
+            (i64::MIN, &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]),
+        ];
+        for (n, expected) in cases {
+            let mut buf = Vec::new();
+            push_avro_long(&mut buf, n);
+            assert_eq!(buf, expected, "encoding of {}", n);
+        }
+    }
+
+    #[test]
+    fn avro_longs_round_trip() {
+        let mut values = vec![0, -1, 1, i64::MIN, i64::MAX, i64::MIN + 1, i64::MAX - 1];
+        values.extend((0..63).flat_map(|bit| [1i64 << bit, -(1i64 << bit), (1i64 << bit) - 1]));
+        let mut buf = Vec::new();
+        for &n in &values {
+            push_avro_long(&mut buf, n);
+        }
+        let mut pos = 0;
+        let decoded: Vec<i64> = values.iter().map(|_| read_avro_long(&buf, &mut pos)).collect();
+        assert_eq!(decoded, values);
+        assert_eq!(pos, buf.len());
+    }
+
+    #[test]
+    fn avro_output_round_trips_through_blocks() {
+        let path = TempPath::new("round-trip.avro");
+        let layout = RowLayout { record_kind: RecordKind::Works, source_columns: true };
+        let first = [
+            field_data("10.1000/a", "title", "Über \"quoted\", with a comma", 1),
+            field_data("10.1000/a", "author.family", "", 1),
+        ];
+        let second = [field_data("10.1000/b", "abstract", &"line\nbreak ".repeat(200), 2)];
+
+        let mut output = AvroOutput::new(&path.0, layout, Fsync::Never).unwrap();
+        output.write_batch(&first).unwrap();
+        output.write_block().unwrap();
+        output.write_batch(&second).unwrap();
+        output.flush().unwrap();
+        // Flushing again writes no empty block.
+        output.flush().unwrap();
+
+        let file = read_avro_file(&fs::read(&path.0).unwrap());
+        assert_eq!(file.metadata["avro.codec"], "deflate");
+        assert_eq!(file.sync_marker, output.sync_marker);
+        let schema: Value = serde_json::from_str(&file.metadata["avro.schema"]).unwrap();
+        let names: Vec<&str> = schema["fields"].as_array().unwrap().iter().map(|f| f["name"].as_str().unwrap()).collect();
+        assert_eq!(names, output_headers(layout));
+
+        let rows = |batch: &[FieldData]| -> Vec<Vec<String>> {
+            batch.iter().map(|field_data| {
+                let mut row = Vec::new();
+                for_each_output_value(field_data, layout, |value| row.push(value.to_string()));
+                row
+            }).collect()
+        };
+        assert_eq!(file.blocks, [rows(&first), rows(&second)]);
+    }
+
+    #[test]
+    fn avro_output_without_rows_is_a_header_only() {
+        let path = TempPath::new("empty.avro");
+        let layout = RowLayout { record_kind: RecordKind::Grants, source_columns: false };
+        let mut output = AvroOutput::new(&path.0, layout, Fsync::Never).unwrap();
+        output.flush().unwrap();
+
+        let file = read_avro_file(&fs::read(&path.0).unwrap());
+        assert!(file.blocks.is_empty());
+        let schema: Value = serde_json::from_str(&file.metadata["avro.schema"]).unwrap();
+        assert_eq!(schema["fields"][1]["name"], "award");
+    }
+
+    // The README's may_contain, for checking the filters written to
+    // _index.json the way a reader of the index would.
+    fn bloom_may_contain(bloom: &Value, doi: &str) -> bool {
+        let digest = Sha256::digest(doi.to_lowercase().as_bytes());
+        let hash = u64::from_le_bytes(digest[..8].try_into().unwrap());
+        let (h1, h2) = (hash & 0xffff_ffff, (hash >> 32) | 1);
+        let data = BASE64_STANDARD.decode(bloom["base64"].as_str().unwrap()).unwrap();
+        let bits = bloom["bits"].as_u64().unwrap();
+        (0..bloom["hashes"].as_u64().unwrap()).all(|i| {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % bits;
+            data[(bit / 8) as usize] >> (bit % 8) & 1 == 1
+        })
+    }
+
+    #[test]
+    fn doi_index_filters_have_no_false_negatives() {
+        let dir = TempPath::new("doi-index");
+        fs::create_dir_all(&dir.0).unwrap();
+        let many: Vec<String> = (0..5000).map(|i| format!("10.1000/Many.{}", i)).collect();
+        let mut index = DoiIndex::default();
+        for doi in &many {
+            index.add_row("many", doi);
+            index.add_row("many", doi);
+        }
+        index.add_row("one", "10.1000/one");
+        index.add_row("none", "");
+        index.add_file("many", "many.csv".to_string());
+        index.write(&dir.0, OrganizeBy::Member).unwrap();
+
+        let written: Value = serde_json::from_slice(&fs::read(dir.0.join(DOI_INDEX_FILE)).unwrap()).unwrap();
+        let groups = &written["members"];
+        assert_eq!(groups["many"]["files"], serde_json::json!(["many.csv"]));
+        assert_eq!(groups["many"]["rows"], 10_000);
+        assert_eq!(groups["many"]["dois"], 5000);
+        for doi in &many {
+            assert!(bloom_may_contain(&groups["many"]["bloom"], doi), "{} rejected", doi);
+            assert!(bloom_may_contain(&groups["many"]["bloom"], &doi.to_uppercase()), "{} rejected", doi.to_uppercase());
+        }
+        assert!(bloom_may_contain(&groups["one"]["bloom"], "10.1000/ONE"));
+        assert_eq!(groups["none"]["dois"], 0);
+        assert!(!bloom_may_contain(&groups["none"]["bloom"], "10.1000/one"));
+
+        // Well within twice the configured rate, so the test is not flaky.
+        let false_positives = (0..20_000)
+            .filter(|i| bloom_may_contain(&groups["many"]["bloom"], &format!("10.2000/other.{}", i)))
+            .count();
+        assert!(false_positives < 400, "{} false positives in 20000", false_positives);
+    }
+
+    const CSV_FIELDS: [&str; 14] = [
+        "", "plain", "with,comma", "with;semicolon", "with\ttab", "say \"hi\"", "\"",
+        "line\nbreak", "carriage\rreturn", " padded ", "Über", "42", "-1.5e3", "0x1f",
+    ];
+
+    fn csv_dialects() -> Vec<CsvDialect> {
+        let mut dialects = Vec::new();
+        for delimiter in [b',', b';', b'\t', b'|'] {
+            for quote_style in [QuoteStyle::Necessary, QuoteStyle::Always, QuoteStyle::NonNumeric] {
+                for terminator in [Terminator::Lf, Terminator::Crlf] {
+                    dialects.push(CsvDialect { delimiter, quote_style, terminator });
+                }
+            }
+        }
+        dialects
+    }
+
+    #[test]
+    fn csv_records_match_the_csv_crate() {
+        for dialect in csv_dialects() {
+            let mut writer = csv::WriterBuilder::new()
+                .flexible(true)
+                .delimiter(dialect.delimiter)
+                .quote_style(match dialect.quote_style {
+                    QuoteStyle::Necessary => csv::QuoteStyle::Necessary,
+                    QuoteStyle::Always => csv::QuoteStyle::Always,
+                    QuoteStyle::NonNumeric => csv::QuoteStyle::NonNumeric,
+                    QuoteStyle::Never => unreachable!("never quoting writes fields as is"),
+                })
+                .terminator(match dialect.terminator {
+                    Terminator::Lf => csv::Terminator::Any(b'\n'),
+                    Terminator::Crlf => csv::Terminator::CRLF,
+                })
+                .from_writer(Vec::new());
+            let mut buf = Vec::new();
+            for field in CSV_FIELDS {
+                // Each field alone, and between two others.
+                writer.write_record([field]).unwrap();
+                writer.write_record(["a", field, "b"]).unwrap();
+                push_csv_record(&mut buf, &[field], &dialect);
+                push_csv_record(&mut buf, &["a", field, "b"], &dialect);
+            }
+            let expected = writer.into_inner().unwrap();
+            assert_eq!(String::from_utf8(buf).unwrap(), String::from_utf8(expected).unwrap(), "{:?}", dialect);
+        }
+    }
+
+    #[test]
+    fn csv_records_read_back_unchanged() {
+        for dialect in csv_dialects() {
+            let mut buf = Vec::new();
+            push_csv_record(&mut buf, &CSV_FIELDS, &dialect);
+            push_csv_record(&mut buf, &CSV_FIELDS[1..], &dialect);
+            let mut reader = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .delimiter(dialect.delimiter)
+                .from_reader(buf.as_slice());
+            let records: Vec<Vec<String>> = reader.records()
+                .map(|record| record.unwrap().iter().map(String::from).collect())
+                .collect();
+            assert_eq!(records, [CSV_FIELDS.to_vec(), CSV_FIELDS[1..].to_vec()], "{:?}", dialect);
+        }
+    }
+
+    #[test]
+    fn csv_fields_are_written_as_is_without_quoting() {
+        let dialect = CsvDialect { quote_style: QuoteStyle::Never, ..CsvDialect::default() };
+        let mut buf = Vec::new();
+        push_csv_record(&mut buf, &["with,comma", "say \"hi\"", ""], &dialect);
+        push_csv_record(&mut buf, &[""], &dialect);
+        assert_eq!(buf, b"with,comma,say \"hi\",\n\n");
+    }
+}
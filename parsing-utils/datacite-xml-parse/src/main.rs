@@ -0,0 +1,738 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser};
+use crossbeam_channel::{bounded, Receiver, Sender};
+use csv::Writer;
+use flate2::read::GzDecoder;
+use glob::glob;
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, error, info, warn, LevelFilter};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use rayon::prelude::*;
+use simple_logger::SimpleLogger;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::thread;
+use std::time::{Duration, Instant};
+use time::macros::format_description;
+
+#[derive(Parser)]
+#[command(name = "DataCite XML Parser")]
+#[command(about = "Extract field data from DataCite metadata schema 4.x XML")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(short, long, help = "DataCite XML file (.xml or .xml.gz) or directory of files", required = true)]
+    input: String,
+
+    #[arg(short, long, default_value = "datacite_field_data.csv", help = "Output CSV file")]
+    output: String,
+
+    #[arg(short, long, help = "Comma-separated list of fields to extract (e.g., 'creators.name,creators.affiliation.name,titles.title'); default all")]
+    fields: Option<String>,
+
+    #[arg(long, help = "Filter by DOI prefix")]
+    doi_prefix: Option<String>,
+
+    #[arg(long, value_name = "PATH", help = "Also write a CSV grouping DOIs linked by IsNewVersionOf/IsPreviousVersionOf into version clusters, with the head (newest) version of each")]
+    version_clusters: Option<String>,
+
+    #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
+    log_level: String,
+
+    #[arg(short, long, default_value = "0", help = "Number of threads to use (0 for auto)")]
+    threads: usize,
+
+    #[arg(short, long, default_value = "10000", help = "Target number of records per batch sent to writer")]
+    batch_size: usize,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the output columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// Source schemas whose structure is built into the binary.
+const BUNDLED_SCHEMAS: &[&str] = &["DataCite Metadata Schema 4.x"];
+
+// The --version-info report: enough to tell which build wrote a file.
+static VERSION_INFO: LazyLock<String> = LazyLock::new(|| format!(
+    "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+    VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), BUNDLED_SCHEMAS.join(", "), OUTPUT_SCHEMA_VERSION
+));
+
+// Wrapper elements whose children form a list, e.g. <creators><creator/>...</creators>
+// becomes creators[0], creators[1], ...
+const LIST_WRAPPERS: &[&str] = &[
+    "creators", "contributors", "titles", "subjects", "dates", "alternateIdentifiers",
+    "relatedIdentifiers", "sizes", "formats", "rightsList", "descriptions", "geoLocations",
+    "fundingReferences", "relatedItems",
+];
+
+// Elements that repeat directly inside a list item, with the list name and the key
+// their text is stored under (matching the DataCite JSON attribute names).
+const REPEATED_ELEMENTS: &[(&str, &str, &str)] = &[
+    ("nameIdentifier", "nameIdentifiers", "nameIdentifier"),
+    ("affiliation", "affiliation", "name"),
+    ("publisherIdentifier", "publisherIdentifiers", "publisherIdentifier"),
+];
+
+const ELEMENT_RENAMES: &[(&str, &str)] = &[
+    ("creatorName", "name"),
+    ("contributorName", "name"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Doi(String);
+
+#[derive(Debug, Clone)]
+struct FieldData {
+    doi: Doi,
+    field_name: String,
+    subfield_path: String,
+    value: String,
+    source_file_path: String,
+}
+
+#[derive(Debug, Default)]
+struct XmlNode {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    children: Vec<XmlNode>,
+}
+
+#[derive(Debug, Default)]
+struct FileStats {
+    resources: usize,
+    resources_missing_doi: usize,
+    resources_filtered_out: usize,
+    fields_extracted: usize,
+    field_counts: HashMap<String, usize>,
+    unique_dois: HashSet<Doi>,
+    // Only collected with --version-clusters: (older, newer) DOI pairs, and the
+    // publication year of each resource read.
+    version_links: Vec<(Doi, Doi)>,
+    publication_years: HashMap<Doi, i32>,
+}
+
+struct ProcessedFileResult {
+    stats: FileStats,
+    error: Option<anyhow::Error>,
+    filepath: PathBuf,
+}
+
+fn join_path(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) }
+}
+
+fn renamed(element: &str) -> &str {
+    ELEMENT_RENAMES.iter().find(|(from, _)| *from == element).map(|(_, to)| *to).unwrap_or(element)
+}
+
+fn node_from_start(element: &BytesStart) -> XmlNode {
+    let attributes = element.attributes()
+        .filter_map(|a| a.ok())
+        .filter(|a| {
+            let key = a.key.as_ref();
+            !key.starts_with(b"xmlns") && a.key.local_name().as_ref() != b"schemaLocation"
+        })
+        .filter_map(|a| {
+            let key = String::from_utf8_lossy(a.key.local_name().as_ref()).into_owned();
+            a.unescape_value().ok().map(|v| (key, v.trim().to_string()))
+        })
+        .collect();
+    XmlNode {
+        name: String::from_utf8_lossy(element.local_name().as_ref()).into_owned(),
+        attributes,
+        ..Default::default()
+    }
+}
+
+// Flattens one element into (field_name, subfield_path, value) triples. The element's
+// text goes under `text_key` (or the element's own path when None) and list wrappers /
+// repeated elements get array indices. Attributes are keys of the element's own object,
+// except for plain elements inside a list item, whose attributes are hoisted onto the
+// item as in DataCite JSON (creatorName@nameType -> creators[0].nameType).
+fn flatten_item(node: &XmlNode, path: &str, field: &str, text_key: Option<&str>, out: &mut Vec<(String, String, String)>) {
+    flatten_node(node, path, field, text_key, (path, field), out);
+}
+
+fn flatten_node(
+    node: &XmlNode,
+    path: &str,
+    field: &str,
+    text_key: Option<&str>,
+    attribute_owner: (&str, &str),
+    out: &mut Vec<(String, String, String)>,
+) {
+    let text = node.text.trim();
+    if !text.is_empty() {
+        match text_key {
+            Some(key) => out.push((join_path(field, key), join_path(path, key), text.to_string())),
+            None => out.push((field.to_string(), path.to_string(), text.to_string())),
+        }
+    }
+    let (owner_path, owner_field) = attribute_owner;
+    for (attr, value) in &node.attributes {
+        if !value.is_empty() {
+            out.push((join_path(owner_field, attr), join_path(owner_path, attr), value.clone()));
+        }
+    }
+
+    let mut repeated_counts: HashMap<&str, usize> = HashMap::new();
+    for child in &node.children {
+        if LIST_WRAPPERS.contains(&child.name.as_str()) {
+            let wrapper_path = join_path(path, &child.name);
+            let wrapper_field = join_path(field, &child.name);
+            for (i, item) in child.children.iter().enumerate() {
+                let item_path = format!("{}[{}]", wrapper_path, i);
+                flatten_item(item, &item_path, &wrapper_field, Some(renamed(&item.name)), out);
+            }
+        } else if let Some(&(_, list_name, key)) = REPEATED_ELEMENTS.iter().find(|(e, _, _)| *e == child.name) {
+            let index = repeated_counts.entry(list_name).or_insert(0);
+            let item_path = format!("{}[{}]", join_path(path, list_name), index);
+            *index += 1;
+            flatten_item(child, &item_path, &join_path(field, list_name), Some(key), out);
+        } else {
+            let key = renamed(&child.name);
+            let (child_path, child_field) = (join_path(path, key), join_path(field, key));
+            let owner = if path.is_empty() { (child_path.as_str(), child_field.as_str()) } else { (path, field) };
+            flatten_node(child, &child_path, &child_field, None, owner, out);
+        }
+    }
+}
+
+fn normalize_doi(raw: &str) -> Option<Doi> {
+    normalization::normalize_doi(raw).map(Doi)
+}
+
+fn resource_doi(resource: &XmlNode) -> Option<Doi> {
+    resource.children.iter()
+        .find(|c| c.name == "identifier"
+            && c.attributes.iter().any(|(k, v)| k == "identifierType" && v.eq_ignore_ascii_case("DOI")))
+        .and_then(|c| normalize_doi(&c.text))
+}
+
+fn attribute<'a>(node: &'a XmlNode, name: &str) -> Option<&'a str> {
+    node.attributes.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+}
+
+// The version links a resource declares to other DOIs, as (older, newer) pairs:
+// `IsNewVersionOf` points at an older version and `IsPreviousVersionOf` at a newer one.
+// Related DOIs are normalized as the resource's own are, so a link written as
+// http://dx.doi.org/10.5281/ZENODO.1 meets the resource 10.5281/zenodo.1. Some
+// repositories type a doi.org link as `URL`; those are followed too.
+fn version_links(resource: &XmlNode, doi: &Doi) -> Vec<(Doi, Doi)> {
+    resource.children.iter()
+        .filter(|c| c.name == "relatedIdentifiers")
+        .flat_map(|wrapper| wrapper.children.iter())
+        .filter(|item| attribute(item, "relatedIdentifierType")
+            .is_some_and(|t| t.eq_ignore_ascii_case("DOI") || t.eq_ignore_ascii_case("URL")))
+        .filter_map(|item| {
+            let related = normalize_doi(&item.text).filter(|related| related != doi)?;
+            match attribute(item, "relationType") {
+                Some("IsNewVersionOf") => Some((related, doi.clone())),
+                Some("IsPreviousVersionOf") => Some((doi.clone(), related)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn publication_year(resource: &XmlNode) -> Option<i32> {
+    resource.children.iter()
+        .find(|c| c.name == "publicationYear")
+        .and_then(|c| c.text.trim().parse().ok())
+}
+
+// Reads every <resource> element in the document, so single-record files as well as
+// OAI-PMH (oai_datacite) harvests with many records are handled.
+fn parse_resources<R: BufRead>(reader: R, mut on_resource: impl FnMut(XmlNode) -> Result<()>) -> Result<()> {
+    let mut reader = Reader::from_reader(reader);
+    reader.config_mut().trim_text(true);
+    let mut buf = Vec::new();
+    let mut stack: Vec<XmlNode> = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf).with_context(|| format!("Malformed XML at byte {}", reader.buffer_position()))? {
+            Event::Start(e) => {
+                let node = node_from_start(&e);
+                if !stack.is_empty() || node.name == "resource" {
+                    stack.push(node);
+                }
+            }
+            Event::Empty(e) => {
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(node_from_start(&e));
+                }
+            }
+            Event::Text(t) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&t.unescape().context("Invalid XML text")?);
+                }
+            }
+            Event::CData(c) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&String::from_utf8_lossy(&c));
+                }
+            }
+            Event::End(_) => {
+                if let Some(node) = stack.pop() {
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(node),
+                        None => on_resource(node)?,
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(())
+}
+
+struct XmlProcessor {
+    fields: Option<HashSet<String>>,
+    filter_doi_prefix: Option<String>,
+    collect_versions: bool,
+}
+
+impl XmlProcessor {
+    fn process(&self, filepath: &Path, sender: &Sender<Vec<FieldData>>, batch_size: usize) -> ProcessedFileResult {
+        let mut stats = FileStats::default();
+        let result = self.process_file(filepath, sender, batch_size, &mut stats);
+        debug!(
+            "Finished {}: {} resources ({} missing DOI, {} filtered out), {} fields extracted.",
+            filepath.display(), stats.resources, stats.resources_missing_doi, stats.resources_filtered_out, stats.fields_extracted
+        );
+        ProcessedFileResult { stats, error: result.err(), filepath: filepath.to_path_buf() }
+    }
+
+    fn process_file(&self, filepath: &Path, sender: &Sender<Vec<FieldData>>, batch_size: usize, stats: &mut FileStats) -> Result<()> {
+        let file = File::open(filepath).with_context(|| format!("Failed to open file: {}", filepath.display()))?;
+        let reader: Box<dyn BufRead> = if filepath.extension().is_some_and(|e| e == "gz") {
+            Box::new(BufReader::new(GzDecoder::new(file)))
+        } else {
+            Box::new(BufReader::new(file))
+        };
+        let source = filepath.display().to_string();
+        let mut batch_buffer = Vec::with_capacity(batch_size);
+
+        parse_resources(reader, |resource| {
+            stats.resources += 1;
+            let Some(doi) = resource_doi(&resource) else {
+                stats.resources_missing_doi += 1;
+                return Ok(());
+            };
+            if let Some(prefix) = &self.filter_doi_prefix {
+                if doi.0.split_once('/').map(|(p, _)| p) != Some(prefix.as_str()) {
+                    stats.resources_filtered_out += 1;
+                    return Ok(());
+                }
+            }
+
+            if self.collect_versions {
+                stats.version_links.extend(version_links(&resource, &doi));
+                if let Some(year) = publication_year(&resource) {
+                    stats.publication_years.insert(doi.clone(), year);
+                }
+            }
+
+            let mut extracted = Vec::new();
+            flatten_item(&resource, "", "", None, &mut extracted);
+            stats.unique_dois.insert(doi.clone());
+            for (field_name, subfield_path, value) in extracted {
+                if self.fields.as_ref().is_some_and(|f| !f.contains(&field_name)) {
+                    continue;
+                }
+                *stats.field_counts.entry(field_name.clone()).or_insert(0) += 1;
+                stats.fields_extracted += 1;
+                batch_buffer.push(FieldData { doi: doi.clone(), field_name, subfield_path, value, source_file_path: source.clone() });
+                if batch_buffer.len() >= batch_size {
+                    sender.send(std::mem::take(&mut batch_buffer))
+                        .map_err(|_| anyhow::anyhow!("Writer thread channel closed unexpectedly on file {}", source))?;
+                }
+            }
+            Ok(())
+        })?;
+
+        if !batch_buffer.is_empty() {
+            sender.send(batch_buffer)
+                .map_err(|_| anyhow::anyhow!("Writer thread channel closed unexpectedly on final batch for {}", source))?;
+        }
+        Ok(())
+    }
+}
+
+struct VersionClusterSummary {
+    clusters: usize,
+    dois: usize,
+    multiple_heads: usize,
+}
+
+// Groups DOIs joined by version links into clusters and picks each cluster's
+// head: a version nothing newer points past. A cluster that branches (two
+// versions both marked as the newest) or loops has several candidates; the head
+// is then the one read from the input with the latest publication year, with
+// ties going to the greatest DOI. DOIs only seen as link targets are included,
+// so a cluster can be headed by a version missing from the input.
+fn write_version_clusters(
+    path: &Path,
+    links: &[(Doi, Doi)],
+    publication_years: &HashMap<Doi, i32>,
+    dois_read: &HashSet<Doi>,
+) -> Result<VersionClusterSummary> {
+    let mut index: HashMap<&Doi, usize> = HashMap::new();
+    let mut dois: Vec<&Doi> = Vec::new();
+    for doi in links.iter().flat_map(|(older, newer)| [older, newer]) {
+        index.entry(doi).or_insert_with(|| {
+            dois.push(doi);
+            dois.len() - 1
+        });
+    }
+
+    fn root(parents: &mut [usize], mut node: usize) -> usize {
+        while parents[node] != node {
+            parents[node] = parents[parents[node]];
+            node = parents[node];
+        }
+        node
+    }
+    let mut parents: Vec<usize> = (0..dois.len()).collect();
+    let mut has_newer = vec![false; dois.len()];
+    for (older, newer) in links {
+        let (a, b) = (index[older], index[newer]);
+        has_newer[a] = true;
+        let (root_a, root_b) = (root(&mut parents, a), root(&mut parents, b));
+        parents[root_a] = root_b;
+    }
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    for node in 0..dois.len() {
+        let cluster_root = root(&mut parents, node);
+        clusters.entry(cluster_root).or_default().push(node);
+    }
+
+    let rank = |node: usize| (dois_read.contains(dois[node]), publication_years.get(dois[node]).copied(), &dois[node].0);
+    let mut rows = Vec::with_capacity(dois.len());
+    let mut multiple_heads = 0;
+    for members in clusters.values() {
+        let mut candidates: Vec<usize> = members.iter().copied().filter(|&node| !has_newer[node]).collect();
+        if candidates.is_empty() {
+            candidates.clone_from(members);
+        }
+        if candidates.len() > 1 {
+            multiple_heads += 1;
+        }
+        let head = candidates.into_iter().max_by(|&a, &b| rank(a).cmp(&rank(b))).unwrap_or(members[0]);
+        for &node in members {
+            rows.push((dois[head], dois[node], members.len()));
+        }
+    }
+    rows.sort_by(|a, b| (&a.0 .0, &a.1 .0).cmp(&(&b.0 .0, &b.1 .0)));
+
+    let mut writer = Writer::from_path(path)
+        .with_context(|| format!("Failed to create version cluster file: {}", path.display()))?;
+    writer.write_record(["doi", "head_doi", "is_head", "in_input", "publication_year", "cluster_size"])?;
+    for (head, doi, size) in &rows {
+        let year = publication_years.get(doi).map(i32::to_string).unwrap_or_default();
+        writer.write_record([
+            doi.0.as_str(),
+            head.0.as_str(),
+            if head == doi { "true" } else { "false" },
+            if dois_read.contains(doi) { "true" } else { "false" },
+            &year,
+            &size.to_string(),
+        ])?;
+    }
+    writer.flush()
+        .with_context(|| format!("Failed to flush version cluster file: {}", path.display()))?;
+    Ok(VersionClusterSummary { clusters: clusters.len(), dois: dois.len(), multiple_heads })
+}
+
+fn find_input_files(input: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if !path.is_dir() {
+        return Err(anyhow::anyhow!("Input path does not exist: {}", input));
+    }
+    let mut files = Vec::new();
+    for pattern in ["**/*.xml", "**/*.xml.gz"] {
+        let full = path.join(pattern);
+        files.extend(glob(&full.to_string_lossy())?.filter_map(Result::ok));
+    }
+    files.sort();
+    if files.is_empty() {
+        warn!("No .xml or .xml.gz files found under: {}", input);
+    }
+    Ok(files)
+}
+
+fn parse_field_filter(fields: &Option<String>) -> Option<HashSet<String>> {
+    fields.as_ref().map(|spec| {
+        spec.split(',')
+            .map(|f| f.trim().to_string())
+            .filter(|f| !f.is_empty())
+            .collect()
+    })
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let total_secs = elapsed.as_secs();
+    let hours = total_secs / 3600;
+    let minutes = (total_secs % 3600) / 60;
+    let seconds = total_secs % 60;
+    let millis = elapsed.subsec_millis();
+
+    if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}.{:03}s", seconds, millis)
+    }
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+
+    Ok(())
+}
+
+fn setup_thread_pool(thread_count: usize) -> Result<usize> {
+    let num_threads = if thread_count == 0 {
+        let cores = num_cpus::get();
+        info!("Auto-detected {} CPU cores. Using {} threads.", cores, cores);
+        cores
+    } else {
+        info!("Using specified {} threads.", thread_count);
+        thread_count
+    };
+
+    if let Err(e) = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build_global() {
+        error!("Failed to build global thread pool: {}. Proceeding with default.", e);
+    }
+
+    Ok(num_threads)
+}
+
+fn spawn_writer(output_path: String, receiver: Receiver<Vec<FieldData>>) -> thread::JoinHandle<Result<usize>> {
+    thread::spawn(move || -> Result<usize> {
+        let path = PathBuf::from(&output_path);
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create directory structure for: {}", path.display()))?;
+            }
+        }
+        let mut writer = Writer::from_path(&path)
+            .with_context(|| format!("Failed to create output file: {}", path.display()))?;
+        writer.write_record(["doi", "field_name", "subfield_path", "value", "doi_prefix", "source_file_path"])?;
+
+        let mut records_written = 0;
+        for batch in receiver {
+            for row in &batch {
+                let prefix = row.doi.0.split_once('/').map(|(p, _)| p).unwrap_or("");
+                writer.write_record([
+                    row.doi.0.as_str(),
+                    &row.field_name,
+                    &row.subfield_path,
+                    &row.value,
+                    prefix,
+                    &row.source_file_path,
+                ])?;
+            }
+            records_written += batch.len();
+        }
+        writer.flush()
+            .with_context(|| format!("Failed to flush output file: {}", path.display()))?;
+        info!("Writer thread finished. Wrote {} records.", records_written);
+        Ok(records_written)
+    })
+}
+
+fn main() -> Result<()> {
+    let start_time = Instant::now();
+    let cli = Cli::parse();
+
+    setup_logging(&cli.log_level)?;
+    info!("Starting DataCite XML parser");
+
+    let num_threads = setup_thread_pool(cli.threads)?;
+    let files = find_input_files(&cli.input)?;
+    info!("Found {} XML files to process.", files.len());
+    if files.is_empty() {
+        return Ok(());
+    }
+    if let Some(prefix_filter) = &cli.doi_prefix {
+        info!("Filtering by DOI prefix: {}", prefix_filter);
+    }
+
+    let progress_bar = ProgressBar::new(files.len() as u64);
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}")
+            .expect("Failed to create progress bar template")
+            .progress_chars("=> "),
+    );
+
+    let (batch_sender, batch_receiver) = bounded::<Vec<FieldData>>((num_threads * 4).max(8));
+    let writer_thread = spawn_writer(cli.output.clone(), batch_receiver);
+
+    let processor = XmlProcessor {
+        fields: parse_field_filter(&cli.fields),
+        filter_doi_prefix: cli.doi_prefix.clone(),
+        collect_versions: cli.version_clusters.is_some(),
+    };
+    let results: Vec<ProcessedFileResult> = files
+        .par_iter()
+        .map(|filepath| {
+            let result = processor.process(filepath, &batch_sender, cli.batch_size);
+            progress_bar.inc(1);
+            result
+        })
+        .collect();
+    drop(batch_sender);
+
+    let mut totals = FileStats::default();
+    let mut files_with_errors = Vec::new();
+    for result in results {
+        if let Some(e) = result.error {
+            error!("Error processing {}: {:#}", result.filepath.display(), e);
+            files_with_errors.push(result.filepath);
+        }
+        totals.resources += result.stats.resources;
+        totals.resources_missing_doi += result.stats.resources_missing_doi;
+        totals.resources_filtered_out += result.stats.resources_filtered_out;
+        totals.fields_extracted += result.stats.fields_extracted;
+        totals.unique_dois.extend(result.stats.unique_dois);
+        totals.version_links.extend(result.stats.version_links);
+        totals.publication_years.extend(result.stats.publication_years);
+        for (field, count) in result.stats.field_counts {
+            *totals.field_counts.entry(field).or_insert(0) += count;
+        }
+    }
+    progress_bar.finish_with_message(format!("Processing finished. {} files with errors.", files_with_errors.len()));
+
+    let records_written = match writer_thread.join() {
+        Ok(Ok(count)) => count,
+        Ok(Err(e)) => return Err(e.context("Writer thread failed")),
+        Err(e) => return Err(anyhow::anyhow!("Writer thread panicked: {:?}", e)),
+    };
+
+    info!("-------------------- FINAL SUMMARY --------------------");
+    info!("Total execution time: {}", format_elapsed(start_time.elapsed()));
+    info!("Input files: {} ({} with errors)", files.len(), files_with_errors.len());
+    info!(
+        "Resources: {} ({} missing DOI, {} filtered out)",
+        totals.resources, totals.resources_missing_doi, totals.resources_filtered_out
+    );
+    info!("Unique DOIs: {}", totals.unique_dois.len());
+    info!("Field breakdown:");
+    let mut sorted_fields: Vec<_> = totals.field_counts.iter().collect();
+    sorted_fields.sort_by_key(|&(_, count)| std::cmp::Reverse(*count));
+    for (field, count) in sorted_fields.iter().take(20) {
+        info!("  - {}: {} records", field, count);
+    }
+    if sorted_fields.len() > 20 {
+        info!("  ... ({} more fields)", sorted_fields.len() - 20);
+    }
+    info!("Rows written: {} -> {}", records_written, cli.output);
+    if let Some(clusters_path) = &cli.version_clusters {
+        let summary = write_version_clusters(Path::new(clusters_path), &totals.version_links, &totals.publication_years, &totals.unique_dois)?;
+        info!(
+            "Version clusters: {} clusters of {} DOIs from {} version links -> {}",
+            summary.clusters, summary.dois, totals.version_links.len(), clusters_path
+        );
+        if summary.multiple_heads > 0 {
+            warn!("Version clusters with more than one candidate head (branched or circular links): {}", summary.multiple_heads);
+        }
+    }
+    info!("-------------------------------------------------------");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resource(doi: &str, year: i32, related: &[(&str, &str, &str)]) -> String {
+        let related: String = related.iter()
+            .map(|(relation, kind, target)| format!(
+                r#"<relatedIdentifier relationType="{}" relatedIdentifierType="{}">{}</relatedIdentifier>"#,
+                relation, kind, target
+            ))
+            .collect();
+        format!(
+            r#"<resource><identifier identifierType="DOI">{}</identifier><publicationYear>{}</publicationYear><relatedIdentifiers>{}</relatedIdentifiers></resource>"#,
+            doi, year, related
+        )
+    }
+
+    #[test]
+    fn normalize_doi_strips_resolver_prefixes() {
+        for raw in ["10.5281/zenodo.1", " https://doi.org/10.5281/ZENODO.1", "http://dx.doi.org/10.5281/zenodo.1", "http://doi.org/10.5281/zenodo.1", "doi:10.5281/zenodo.1"] {
+            assert_eq!(normalize_doi(raw), Some(Doi("10.5281/zenodo.1".to_string())), "{}", raw);
+        }
+        assert_eq!(normalize_doi("https://zenodo.org/record/1"), None);
+        assert_eq!(normalize_doi(""), None);
+    }
+
+    #[test]
+    fn version_chain_resolves_to_newest_across_identifier_forms() {
+        let xml = format!(
+            "<records>{}{}{}</records>",
+            resource("10.5281/zenodo.1", 2019, &[]),
+            resource("10.5281/zenodo.2", 2020, &[("IsNewVersionOf", "DOI", "http://dx.doi.org/10.5281/zenodo.1")]),
+            resource("https://doi.org/10.5281/ZENODO.3", 2021, &[("IsNewVersionOf", "URL", "https://doi.org/10.5281/zenodo.2")]),
+        );
+        let mut links = Vec::new();
+        let mut years = HashMap::new();
+        let mut dois_read = HashSet::new();
+        parse_resources(xml.as_bytes(), |resource| {
+            let doi = resource_doi(&resource).expect("resource DOI");
+            links.extend(version_links(&resource, &doi));
+            years.insert(doi.clone(), publication_year(&resource).expect("publication year"));
+            dois_read.insert(doi);
+            Ok(())
+        }).unwrap();
+
+        let path = std::env::temp_dir().join(format!("datacite-version-clusters-{}.csv", std::process::id()));
+        let summary = write_version_clusters(&path, &links, &years, &dois_read).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!((summary.clusters, summary.dois, summary.multiple_heads), (1, 3, 0));
+        assert_eq!(written, "\
+doi,head_doi,is_head,in_input,publication_year,cluster_size
+10.5281/zenodo.1,10.5281/zenodo.3,false,true,2019,3
+10.5281/zenodo.2,10.5281/zenodo.3,false,true,2020,3
+10.5281/zenodo.3,10.5281/zenodo.3,true,true,2021,3
+");
+    }
+}
@@ -0,0 +1,3 @@
+fn main() {
+    build_helper::record_git_commit();
+}
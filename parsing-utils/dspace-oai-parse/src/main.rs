@@ -0,0 +1,516 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser};
+use csv::Writer;
+use flate2::read::GzDecoder;
+use glob::glob;
+use log::{info, warn, LevelFilter};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use simple_logger::SimpleLogger;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use time::macros::format_description;
+
+#[derive(Parser)]
+#[command(name = "DSpace OAI-PMH Parser")]
+#[command(about = "Extract Dublin Core fields from DSpace OAI-PMH harvests (oai_dc and dim) into the pipeline's long field format")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(short, long, help = "OAI-PMH response file (.xml or .xml.gz) or directory of harvested pages", required = true)]
+    input: String,
+
+    #[arg(short, long, default_value = "dspace_field_data.csv", help = "Output CSV file")]
+    output: String,
+
+    #[arg(short, long, help = "Comma-separated list of fields to keep (e.g., 'dc.contributor.author,dc.title'); default all")]
+    fields: Option<String>,
+
+    #[arg(long, help = "Skip records without a DOI")]
+    require_doi: bool,
+
+    #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
+    log_level: String,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the output columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+static VERSION_INFO: LazyLock<String> = LazyLock::new(|| format!(
+    "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+    VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+));
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Doi(String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum MetadataFormat {
+    OaiDc,
+    Dim,
+}
+
+// One Dublin Core value with its qualified DSpace field name (dc.contributor.author).
+#[derive(Debug)]
+struct DcValue {
+    field: String,
+    value: String,
+    lang: String,
+    authority: String,
+}
+
+#[derive(Debug, Default)]
+struct OaiRecord {
+    identifier: String,
+    datestamp: String,
+    deleted: bool,
+    format: Option<MetadataFormat>,
+    values: Vec<DcValue>,
+}
+
+fn attribute_value(element: &BytesStart, name: &[u8]) -> Option<String> {
+    element.attributes()
+        .filter_map(|a| a.ok())
+        .find(|a| a.key.local_name().as_ref() == name)
+        .and_then(|a| a.unescape_value().ok().map(|v| v.trim().to_string()))
+}
+
+fn local_name(element: &BytesStart) -> String {
+    String::from_utf8_lossy(element.local_name().as_ref()).into_owned()
+}
+
+// DSpace's oai_dc crosswalk writes dc.contributor.author as dc:creator; map it back so
+// both formats produce the same field names.
+fn oai_dc_field(element: &str) -> String {
+    match element {
+        "creator" => "dc.contributor.author".to_string(),
+        other => format!("dc.{}", other),
+    }
+}
+
+fn dim_field(element: &BytesStart) -> Option<String> {
+    let schema = attribute_value(element, b"mdschema")?;
+    let name = attribute_value(element, b"element")?;
+    Some(match attribute_value(element, b"qualifier").filter(|q| !q.is_empty()) {
+        Some(qualifier) => format!("{}.{}.{}", schema, name, qualifier),
+        None => format!("{}.{}", schema, name),
+    })
+}
+
+fn open_input(path: &Path) -> Result<Box<dyn BufRead>> {
+    let file = File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    if path.extension().and_then(|e| e.to_str()) == Some("gz") {
+        Ok(Box::new(BufReader::new(GzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+// Streams the <record> elements of an OAI-PMH ListRecords or GetRecord response.
+fn parse_file(path: &Path) -> Result<Vec<OaiRecord>> {
+    let mut reader = Reader::from_reader(open_input(path)?);
+    let mut buf = Vec::new();
+    let mut records = Vec::new();
+    let mut current: Option<OaiRecord> = None;
+    let mut in_header = false;
+    let mut in_metadata = false;
+    // The open header child or DC value element, and its text so far.
+    let mut open_text: Option<(String, Option<DcValue>)> = None;
+    let mut text = String::new();
+
+    loop {
+        let event = reader.read_event_into(&mut buf)
+            .with_context(|| format!("Malformed XML in {} at byte {}", path.display(), reader.buffer_position()))?;
+        match event {
+            Event::Start(e) => {
+                let name = local_name(&e);
+                match (name.as_str(), current.as_mut()) {
+                    ("record", None) => current = Some(OaiRecord::default()),
+                    ("header", Some(record)) => {
+                        in_header = true;
+                        record.deleted = attribute_value(&e, b"status").as_deref() == Some("deleted");
+                    }
+                    ("metadata", Some(_)) => in_metadata = true,
+                    ("dc", Some(record)) if in_metadata => record.format = Some(MetadataFormat::OaiDc),
+                    ("dim", Some(record)) if in_metadata => record.format = Some(MetadataFormat::Dim),
+                    (_, Some(_)) if in_header => {
+                        open_text = Some((name, None));
+                        text.clear();
+                    }
+                    (_, Some(record)) if in_metadata => {
+                        let field = match record.format {
+                            Some(MetadataFormat::Dim) if name == "field" => dim_field(&e),
+                            Some(MetadataFormat::OaiDc) => Some(oai_dc_field(&name)),
+                            _ => None,
+                        };
+                        if let Some(field) = field {
+                            let value = DcValue {
+                                field,
+                                value: String::new(),
+                                lang: attribute_value(&e, b"lang").unwrap_or_default(),
+                                authority: attribute_value(&e, b"authority").unwrap_or_default(),
+                            };
+                            open_text = Some((name, Some(value)));
+                            text.clear();
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(t) if open_text.is_some() => {
+                text.push_str(&t.unescape().context("Invalid XML text")?);
+            }
+            Event::CData(c) if open_text.is_some() => {
+                text.push_str(&String::from_utf8_lossy(&c));
+            }
+            Event::End(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                if open_text.as_ref().is_some_and(|(open, _)| *open == name) {
+                    let (element, value) = open_text.take().expect("checked above");
+                    if let Some(record) = current.as_mut() {
+                        match value {
+                            Some(mut value) => {
+                                value.value = text.trim().to_string();
+                                if !value.value.is_empty() {
+                                    record.values.push(value);
+                                }
+                            }
+                            None if element == "identifier" => record.identifier = text.trim().to_string(),
+                            None if element == "datestamp" => record.datestamp = text.trim().to_string(),
+                            None => {}
+                        }
+                    }
+                } else {
+                    match name.as_str() {
+                        "header" => in_header = false,
+                        "metadata" => in_metadata = false,
+                        "record" => {
+                            if let Some(record) = current.take() {
+                                records.push(record);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(records)
+}
+
+fn normalize_doi(raw: &str) -> Option<Doi> {
+    normalization::normalize_doi(raw).map(Doi)
+}
+
+// dim records carry dc.identifier.doi; oai_dc flattens every identifier into
+// dc:identifier, so any identifier that parses as a DOI is recognised and renamed.
+fn find_doi(record: &mut OaiRecord) -> Option<Doi> {
+    let mut found = None;
+    for value in record.values.iter_mut() {
+        if value.field == "dc.identifier.doi" {
+            if let Some(doi) = normalize_doi(&value.value) {
+                found.get_or_insert(doi);
+            }
+        } else if value.field.starts_with("dc.identifier") {
+            if let Some(doi) = normalize_doi(&value.value) {
+                if record.format == Some(MetadataFormat::OaiDc) {
+                    value.field = "dc.identifier.doi".to_string();
+                }
+                found.get_or_insert(doi);
+            }
+        }
+    }
+    found
+}
+
+fn find_input_files(input: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if !path.is_dir() {
+        return Err(anyhow::anyhow!("Input path does not exist: {}", input));
+    }
+    let mut files = Vec::new();
+    for pattern in ["**/*.xml", "**/*.xml.gz"] {
+        let full = path.join(pattern);
+        files.extend(glob(&full.to_string_lossy())?.filter_map(Result::ok));
+    }
+    files.sort();
+    if files.is_empty() {
+        warn!("No .xml or .xml.gz files found under: {}", input);
+    }
+    Ok(files)
+}
+
+fn parse_field_filter(fields: &Option<String>) -> Option<HashSet<String>> {
+    fields.as_ref().map(|spec| {
+        spec.split(',')
+            .map(|f| f.trim().to_string())
+            .filter(|f| !f.is_empty())
+            .collect()
+    })
+}
+
+#[derive(Debug, Default)]
+struct HarvestStats {
+    records: usize,
+    deleted: usize,
+    without_metadata: usize,
+    with_doi: usize,
+    skipped_without_doi: usize,
+    by_format: HashMap<MetadataFormat, usize>,
+    rows: usize,
+}
+
+fn write_record<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    mut record: OaiRecord,
+    cli: &Cli,
+    fields: &Option<HashSet<String>>,
+    source: &str,
+    stats: &mut HarvestStats,
+) -> Result<()> {
+    stats.records += 1;
+    if record.deleted {
+        stats.deleted += 1;
+        return Ok(());
+    }
+    let Some(format) = record.format else {
+        stats.without_metadata += 1;
+        return Ok(());
+    };
+    *stats.by_format.entry(format).or_insert(0) += 1;
+
+    let doi = find_doi(&mut record);
+    match doi {
+        Some(_) => stats.with_doi += 1,
+        None if cli.require_doi => {
+            stats.skipped_without_doi += 1;
+            return Ok(());
+        }
+        None => {}
+    }
+    let doi_str = doi.as_ref().map(|d| d.0.as_str()).unwrap_or("");
+    let prefix = doi_str.split_once('/').map(|(p, _)| p).unwrap_or("");
+
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+    for value in &record.values {
+        if fields.as_ref().is_some_and(|f| !f.contains(&value.field)) {
+            continue;
+        }
+        let position = positions.entry(value.field.as_str()).or_insert(0);
+        let path = format!("{}[{}]", value.field, position);
+        *position += 1;
+
+        let mut rows = vec![(value.field.clone(), path.clone(), value.value.as_str())];
+        if !value.lang.is_empty() {
+            rows.push((format!("{}.lang", value.field), format!("{}.lang", path), value.lang.as_str()));
+        }
+        if !value.authority.is_empty() {
+            rows.push((format!("{}.authority", value.field), format!("{}.authority", path), value.authority.as_str()));
+        }
+        for (field_name, subfield_path, row_value) in rows {
+            writer.write_record([
+                record.identifier.as_str(),
+                doi_str,
+                &field_name,
+                &subfield_path,
+                row_value,
+                prefix,
+                &record.datestamp,
+                source,
+            ])?;
+            stats.rows += 1;
+        }
+    }
+    Ok(())
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let total_secs = elapsed.as_secs();
+    let minutes = total_secs / 60;
+    let seconds = total_secs % 60;
+    let millis = elapsed.subsec_millis();
+
+    if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}.{:03}s", seconds, millis)
+    }
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let start_time = Instant::now();
+    let cli = Cli::parse();
+
+    setup_logging(&cli.log_level)?;
+    info!("Starting DSpace OAI-PMH parser");
+
+    let files = find_input_files(&cli.input)?;
+    info!("Found {} files to process.", files.len());
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let output_path = PathBuf::from(&cli.output);
+    if let Some(parent) = output_path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory structure for: {}", output_path.display()))?;
+        }
+    }
+    let mut writer = Writer::from_path(&output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path.display()))?;
+    writer.write_record([
+        "oai_identifier", "doi", "field_name", "subfield_path", "value", "doi_prefix", "datestamp", "source_file_path",
+    ])?;
+
+    let fields = parse_field_filter(&cli.fields);
+    let mut stats = HarvestStats::default();
+    let mut files_with_errors = 0;
+    for file in &files {
+        let records = match parse_file(file) {
+            Ok(records) => records,
+            Err(e) => {
+                warn!("Error parsing {}: {:#}", file.display(), e);
+                files_with_errors += 1;
+                continue;
+            }
+        };
+        let source = file.to_string_lossy();
+        for record in records {
+            write_record(&mut writer, record, &cli, &fields, &source, &mut stats)?;
+        }
+    }
+    writer.flush()?;
+
+    info!("-------------------- FINAL SUMMARY --------------------");
+    info!("Total execution time: {}", format_elapsed(start_time.elapsed()));
+    info!("Files: {} ({} with errors)", files.len(), files_with_errors);
+    info!(
+        "Records: {} ({} oai_dc, {} dim, {} deleted, {} without oai_dc/dim metadata)",
+        stats.records,
+        stats.by_format.get(&MetadataFormat::OaiDc).copied().unwrap_or(0),
+        stats.by_format.get(&MetadataFormat::Dim).copied().unwrap_or(0),
+        stats.deleted,
+        stats.without_metadata
+    );
+    info!("Records with DOI: {}", stats.with_doi);
+    if cli.require_doi {
+        info!("Skipped without DOI: {}", stats.skipped_without_doi);
+    }
+    info!("Rows written: {} -> {}", stats.rows, cli.output);
+    info!("-------------------------------------------------------");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HARVEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<OAI-PMH xmlns="http://www.openarchives.org/OAI/2.0/">
+  <ListRecords>
+    <record>
+      <header><identifier>oai:repo.example.org:1/10</identifier><datestamp>2024-01-02T03:04:05Z</datestamp></header>
+      <metadata>
+        <oai_dc:dc xmlns:oai_dc="http://www.openarchives.org/OAI/2.0/oai_dc/" xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:title xml:lang="en">Graphene &amp; sensors</dc:title>
+          <dc:creator>Doe, Ann</dc:creator>
+          <dc:creator>Roe, Bo</dc:creator>
+          <dc:identifier>http://hdl.handle.net/1/10</dc:identifier>
+          <dc:identifier>https://doi.org/10.1234/ABC</dc:identifier>
+        </oai_dc:dc>
+      </metadata>
+    </record>
+    <record>
+      <header><identifier>oai:repo.example.org:1/11</identifier><datestamp>2024-01-03</datestamp></header>
+      <metadata>
+        <dim:dim xmlns:dim="http://www.dspace.org/xmlns/dspace/dim">
+          <dim:field mdschema="dc" element="contributor" qualifier="author" authority="rp-7">Doe, Ann</dim:field>
+          <dim:field mdschema="dc" element="date" qualifier="issued">2021</dim:field>
+          <dim:field mdschema="dc" element="subject"><![CDATA[Physics]]></dim:field>
+        </dim:dim>
+      </metadata>
+    </record>
+    <record>
+      <header status="deleted"><identifier>oai:repo.example.org:1/12</identifier><datestamp>2024-01-04</datestamp></header>
+    </record>
+  </ListRecords>
+</OAI-PMH>
+"#;
+
+    fn harvest(name: &str, args: &[&str]) -> (String, HarvestStats) {
+        let path = std::env::temp_dir().join(format!("dspace-oai-parse-{}-{}.xml", std::process::id(), name));
+        fs::write(&path, HARVEST).unwrap();
+        let records = parse_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        let cli = Cli::parse_from([&["dspace-oai-parse", "-i", "page.xml"], args].concat());
+        let mut stats = HarvestStats::default();
+        let mut writer = Writer::from_writer(Vec::new());
+        for record in records.unwrap() {
+            write_record(&mut writer, record, &cli, &None, "page.xml", &mut stats).unwrap();
+        }
+        (String::from_utf8(writer.into_inner().unwrap()).unwrap(), stats)
+    }
+
+    #[test]
+    fn oai_dc_and_dim_records_map_to_the_same_field_names() {
+        let (rows, stats) = harvest("all", &[]);
+        assert_eq!(rows, "\
+oai:repo.example.org:1/10,10.1234/abc,dc.title,dc.title[0],Graphene & sensors,10.1234,2024-01-02T03:04:05Z,page.xml
+oai:repo.example.org:1/10,10.1234/abc,dc.title.lang,dc.title[0].lang,en,10.1234,2024-01-02T03:04:05Z,page.xml
+oai:repo.example.org:1/10,10.1234/abc,dc.contributor.author,dc.contributor.author[0],\"Doe, Ann\",10.1234,2024-01-02T03:04:05Z,page.xml
+oai:repo.example.org:1/10,10.1234/abc,dc.contributor.author,dc.contributor.author[1],\"Roe, Bo\",10.1234,2024-01-02T03:04:05Z,page.xml
+oai:repo.example.org:1/10,10.1234/abc,dc.identifier,dc.identifier[0],http://hdl.handle.net/1/10,10.1234,2024-01-02T03:04:05Z,page.xml
+oai:repo.example.org:1/10,10.1234/abc,dc.identifier.doi,dc.identifier.doi[0],https://doi.org/10.1234/ABC,10.1234,2024-01-02T03:04:05Z,page.xml
+oai:repo.example.org:1/11,,dc.contributor.author,dc.contributor.author[0],\"Doe, Ann\",,2024-01-03,page.xml
+oai:repo.example.org:1/11,,dc.contributor.author.authority,dc.contributor.author[0].authority,rp-7,,2024-01-03,page.xml
+oai:repo.example.org:1/11,,dc.date.issued,dc.date.issued[0],2021,,2024-01-03,page.xml
+oai:repo.example.org:1/11,,dc.subject,dc.subject[0],Physics,,2024-01-03,page.xml
+");
+        assert_eq!((stats.records, stats.deleted, stats.with_doi), (3, 1, 1));
+    }
+
+    #[test]
+    fn require_doi_skips_records_without_one() {
+        let (rows, stats) = harvest("require-doi", &["--require-doi"]);
+        assert!(rows.lines().all(|line| line.starts_with("oai:repo.example.org:1/10,")));
+        assert_eq!(stats.skipped_without_doi, 1);
+    }
+}
@@ -0,0 +1,930 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser, ValueEnum};
+use csv::{ReaderBuilder, StringRecord, Writer};
+use flate2::read::MultiGzDecoder;
+use lazy_static::lazy_static;
+use log::{debug, info, warn, LevelFilter};
+use normalization::normalize_doi;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use regex::Regex;
+use serde_json::Value;
+use simple_logger::SimpleLogger;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use time::macros::format_description;
+
+#[derive(Parser)]
+#[command(name = "Entity Tables")]
+#[command(about = "Reshape long-format field parser output into works, contributors, affiliations, funders and identifiers tables with surrogate keys")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(short, long, required = true, help = "Long-format CSV from crossref-fast-field-parse or openalex-fast-field-parse (plain, .gz or .zst), or a directory of them such as organized output or parts. Repeat for several")]
+    input: Vec<String>,
+
+    #[arg(short, long, help = "Directory to write the tables to")]
+    output: String,
+
+    #[arg(long, value_enum, default_value_t = TableFormat::Csv, help = "Format of the tables: csv or parquet")]
+    format: TableFormat,
+
+    #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
+    log_level: String,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the tables, their columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+lazy_static! {
+    static ref VERSION_INFO: String = format!(
+        "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+        VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+    );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TableFormat {
+    Csv,
+    Parquet,
+}
+
+impl TableFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            TableFormat::Csv => "csv",
+            TableFormat::Parquet => "parquet",
+        }
+    }
+}
+
+lazy_static! {
+    static ref INDEXED_SEGMENT_RE: Regex = Regex::new(r"^([A-Za-z_-]+)\[(\d+)\]$").unwrap();
+    static ref CROSSREF_AFFILIATION_ID_RE: Regex = Regex::new(r"^id\[\d+\]\.id$").unwrap();
+}
+
+// Lists whose entries are contributors: Crossref's author, editor, chair and
+// translator lists, and OpenAlex's authorships.
+const CONTRIBUTOR_LISTS: [&str; 5] = ["author", "editor", "chair", "translator", "authorships"];
+
+// Lists within a contributor whose entries are affiliations: Crossref's
+// affiliation, and OpenAlex's affiliations, institutions and the older
+// raw_affiliation_strings. An OpenAlex authorship can carry several of these
+// for the same institution; each list gives its own rows, told apart by path.
+const AFFILIATION_LISTS: [&str; 4] = ["affiliation", "affiliations", "institutions", "raw_affiliation_strings"];
+
+// Crossref's funder, and OpenAlex's grants and funders.
+const FUNDER_LISTS: [&str; 3] = ["funder", "grants", "funders"];
+
+#[derive(Debug, Clone, Copy)]
+enum ColumnKind {
+    Key,
+    Text,
+}
+
+enum Cell<'a> {
+    Key(u64),
+    Text(&'a str),
+}
+
+struct TableSpec {
+    name: &'static str,
+    columns: Vec<(String, ColumnKind)>,
+}
+
+fn table_spec(name: &'static str, keys: &[&str], texts: &[&str]) -> TableSpec {
+    let columns = keys.iter().map(|c| (c.to_string(), ColumnKind::Key))
+        .chain(texts.iter().map(|c| (c.to_string(), ColumnKind::Text)))
+        .collect();
+    TableSpec { name, columns }
+}
+
+trait TableSink {
+    fn write_row(&mut self, row: &[Cell]) -> Result<()>;
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+struct CsvTable {
+    writer: Writer<BufWriter<File>>,
+    fields: Vec<String>,
+}
+
+impl CsvTable {
+    fn create(path: &Path, spec: &TableSpec) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Failed to create table: {}", path.display()))?;
+        let mut writer = Writer::from_writer(BufWriter::new(file));
+        writer.write_record(spec.columns.iter().map(|(name, _)| name))?;
+        Ok(Self { writer, fields: Vec::with_capacity(spec.columns.len()) })
+    }
+}
+
+impl TableSink for CsvTable {
+    fn write_row(&mut self, row: &[Cell]) -> Result<()> {
+        self.fields.clear();
+        for cell in row {
+            self.fields.push(match cell {
+                Cell::Key(key) => key.to_string(),
+                Cell::Text(text) => text.to_string(),
+            });
+        }
+        self.writer.write_record(&self.fields)?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+// Rows are buffered column by column and written as a row group once this
+// many have built up, as the field parsers do for their Parquet output.
+const PARQUET_ROW_GROUP_ROWS: usize = 250_000;
+
+enum ColumnBuffer {
+    Key(Vec<i64>),
+    Text(Vec<ByteArray>),
+}
+
+// Keys are INT64 and everything else UTF8 strings, left empty rather than
+// null when a record has no value, as in the parsers' output.
+struct ParquetTable {
+    writer: SerializedFileWriter<File>,
+    columns: Vec<ColumnBuffer>,
+    rows: usize,
+}
+
+impl ParquetTable {
+    fn create(path: &Path, spec: &TableSpec) -> Result<Self> {
+        let schema = format!(
+            "message {} {{ {} }}",
+            spec.name,
+            spec.columns.iter().map(|(name, kind)| match kind {
+                ColumnKind::Key => format!("REQUIRED INT64 {};", name),
+                ColumnKind::Text => format!("REQUIRED BYTE_ARRAY {} (UTF8);", name),
+            }).collect::<Vec<_>>().join(" ")
+        );
+        let schema = Arc::new(parse_message_type(&schema).context("Failed to build Parquet table schema")?);
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .build();
+        let file = File::create(path).with_context(|| format!("Failed to create table: {}", path.display()))?;
+        let writer = SerializedFileWriter::new(file, schema, Arc::new(properties))
+            .with_context(|| format!("Failed to start Parquet table: {}", path.display()))?;
+        let columns = spec.columns.iter().map(|(_, kind)| match kind {
+            ColumnKind::Key => ColumnBuffer::Key(Vec::new()),
+            ColumnKind::Text => ColumnBuffer::Text(Vec::new()),
+        }).collect();
+        Ok(Self { writer, columns, rows: 0 })
+    }
+
+    fn write_row_group(&mut self) -> Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let mut row_group = self.writer.next_row_group()?;
+        for buffer in &mut self.columns {
+            let mut column = row_group.next_column()?
+                .ok_or_else(|| anyhow::anyhow!("Parquet table schema has fewer columns than the rows"))?;
+            match buffer {
+                ColumnBuffer::Key(values) => {
+                    column.typed::<Int64Type>().write_batch(values, None, None)?;
+                    values.clear();
+                }
+                ColumnBuffer::Text(values) => {
+                    column.typed::<ByteArrayType>().write_batch(values, None, None)?;
+                    values.clear();
+                }
+            }
+            column.close()?;
+        }
+        row_group.close()?;
+        self.rows = 0;
+        Ok(())
+    }
+}
+
+impl TableSink for ParquetTable {
+    fn write_row(&mut self, row: &[Cell]) -> Result<()> {
+        for (buffer, cell) in self.columns.iter_mut().zip(row) {
+            match (buffer, cell) {
+                (ColumnBuffer::Key(values), Cell::Key(key)) => values.push(*key as i64),
+                (ColumnBuffer::Text(values), Cell::Text(text)) => values.push(ByteArray::from(*text)),
+                _ => return Err(anyhow::anyhow!("Row does not match the Parquet table schema")),
+            }
+        }
+        self.rows += 1;
+        if self.rows >= PARQUET_ROW_GROUP_ROWS {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.write_row_group()?;
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+struct Table {
+    path: PathBuf,
+    sink: Box<dyn TableSink>,
+    rows: u64,
+}
+
+impl Table {
+    fn create(dir: &Path, spec: &TableSpec, format: TableFormat) -> Result<Self> {
+        let path = dir.join(format!("{}.{}", spec.name, format.extension()));
+        let sink: Box<dyn TableSink> = match format {
+            TableFormat::Csv => Box::new(CsvTable::create(&path, spec)?),
+            TableFormat::Parquet => Box::new(ParquetTable::create(&path, spec)?),
+        };
+        Ok(Self { path, sink, rows: 0 })
+    }
+
+    fn write_row(&mut self, row: &[Cell]) -> Result<()> {
+        self.rows += 1;
+        self.sink.write_row(row)
+            .with_context(|| format!("Failed to write to table: {}", self.path.display()))
+    }
+
+    fn finish(self) -> Result<u64> {
+        let path = self.path.clone();
+        self.sink.finish().with_context(|| format!("Failed to finish table: {}", path.display()))?;
+        Ok(self.rows)
+    }
+}
+
+#[derive(Default)]
+struct Affiliation {
+    path: String,
+    name: String,
+    ror: String,
+    institution_id: String,
+}
+
+#[derive(Default)]
+struct Contributor {
+    path: String,
+    role: String,
+    name: String,
+    given_name: String,
+    family_name: String,
+    orcid: String,
+    contributor_id: String,
+    sequence: String,
+    affiliations: BTreeMap<(String, usize), Affiliation>,
+}
+
+#[derive(Default)]
+struct Funder {
+    path: String,
+    name: String,
+    funder_id: String,
+    ror: String,
+    award: String,
+}
+
+// Everything gathered for one work from its rows; written out once the next
+// work's rows start.
+#[derive(Default)]
+struct Work {
+    id: String,
+    doi: String,
+    group: String,
+    title: String,
+    work_type: String,
+    year: String,
+    identifiers: Vec<(String, String)>,
+    contributors: BTreeMap<(String, usize), Contributor>,
+    funders: BTreeMap<(String, usize), Funder>,
+}
+
+// Several values for one column, such as a funder's awards, are joined.
+const MULTI_VALUE_SEPARATOR: &str = "; ";
+
+fn append(target: &mut String, value: &str) {
+    if !target.is_empty() {
+        target.push_str(MULTI_VALUE_SEPARATOR);
+    }
+    target.push_str(value);
+}
+
+fn set_if_empty(target: &mut String, value: &str) {
+    if target.is_empty() {
+        target.push_str(value);
+    }
+}
+
+// "author[3]" -> ("author", 3)
+fn indexed_segment(segment: &str) -> Option<(&str, usize)> {
+    let captures = INDEXED_SEGMENT_RE.captures(segment)?;
+    let index = captures.get(2)?.as_str().parse().ok()?;
+    Some((captures.get(1)?.as_str(), index))
+}
+
+// Crossref's issued date as the parser writes it: {"date-parts":[[2019,5,8]]}
+// for the issued field, [[2019,5,8]] for issued.date-parts.
+fn issued_year(raw: &str) -> Option<String> {
+    let parsed: Value = serde_json::from_str(raw).ok()?;
+    let parts = parsed.get("date-parts").unwrap_or(&parsed);
+    parts.get(0)?.get(0)?.as_i64().map(|year| year.to_string())
+}
+
+impl Work {
+    fn add_identifier(&mut self, scheme: &str, value: &str) {
+        // DOIs as the normalize tool writes them, so the DOI column and the
+        // resolver URLs OpenAlex writes come out alike.
+        let value = if scheme == "doi" { normalize_doi(value).unwrap_or_default() } else { value.trim().to_string() };
+        if !value.is_empty() && !self.identifiers.iter().any(|(s, v)| s == scheme && *v == value) {
+            self.identifiers.push((scheme.to_string(), value));
+        }
+    }
+
+    // Returns false for a value no table has a column for.
+    fn apply(&mut self, field_name: &str, path: &str, value: &str) -> bool {
+        let (head, rest) = path.split_once('.').unwrap_or((path, ""));
+        match indexed_segment(head) {
+            Some((list, index)) if CONTRIBUTOR_LISTS.contains(&list) => {
+                let contributor = self.contributors.entry((list.to_string(), index)).or_insert_with(|| Contributor {
+                    path: head.to_string(),
+                    role: if list == "authorships" { "author".to_string() } else { list.to_string() },
+                    ..Default::default()
+                });
+                contributor.apply(rest, value)
+            }
+            Some((list, index)) if FUNDER_LISTS.contains(&list) => {
+                let funder = self.funders.entry((list.to_string(), index)).or_insert_with(|| Funder {
+                    path: head.to_string(),
+                    ..Default::default()
+                });
+                funder.apply(rest, value)
+            }
+            _ => self.apply_work_field(field_name, value),
+        }
+    }
+
+    fn apply_work_field(&mut self, field_name: &str, value: &str) -> bool {
+        match field_name {
+            "title" | "display_name" => set_if_empty(&mut self.title, value),
+            "type" => set_if_empty(&mut self.work_type, value),
+            "publication_year" => set_if_empty(&mut self.year, value),
+            "issued" | "issued.date-parts" => {
+                if let Some(year) = issued_year(value) {
+                    set_if_empty(&mut self.year, &year);
+                }
+            }
+            "DOI" | "doi" => self.add_identifier("doi", value),
+            "id" => self.add_identifier("openalex", value),
+            "ISSN" => self.add_identifier("issn", value),
+            "ISBN" => self.add_identifier("isbn", value),
+            "alternative-id" => self.add_identifier("alternative-id", value),
+            _ => match field_name.strip_prefix("ids.") {
+                Some(scheme) => self.add_identifier(scheme, value),
+                None => return false,
+            },
+        }
+        true
+    }
+}
+
+impl Contributor {
+    fn apply(&mut self, rest: &str, value: &str) -> bool {
+        let (head, tail) = rest.split_once('.').unwrap_or((rest, ""));
+        if let Some((list, index)) = indexed_segment(head).filter(|(list, _)| AFFILIATION_LISTS.contains(list)) {
+            let affiliation = self.affiliations.entry((list.to_string(), index)).or_insert_with(|| Affiliation {
+                path: format!("{}.{}", self.path, head),
+                ..Default::default()
+            });
+            return affiliation.apply(list, tail, value);
+        }
+        match rest {
+            "name" | "author.display_name" => self.name = value.to_string(),
+            "raw_author_name" => set_if_empty(&mut self.name, value),
+            "given" => self.given_name = value.to_string(),
+            "family" => self.family_name = value.to_string(),
+            "ORCID" | "author.orcid" => self.orcid = value.to_string(),
+            "author.id" => self.contributor_id = value.to_string(),
+            "sequence" | "author_position" => self.sequence = value.to_string(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Affiliation {
+    fn apply(&mut self, list: &str, tail: &str, value: &str) -> bool {
+        match tail {
+            "name" | "display_name" | "raw_affiliation_string" => self.name = value.to_string(),
+            "" if list == "raw_affiliation_strings" => self.name = value.to_string(),
+            "ror" => self.ror = value.to_string(),
+            "id" if list == "institutions" => self.institution_id = value.to_string(),
+            _ if indexed_segment(tail).is_some_and(|(name, _)| name == "institution_ids") => append(&mut self.institution_id, value),
+            // Crossref affiliation identifiers: a ROR ID, or another scheme's ID.
+            _ if CROSSREF_AFFILIATION_ID_RE.is_match(tail) => {
+                if value.contains("ror.org/") {
+                    set_if_empty(&mut self.ror, value);
+                } else {
+                    append(&mut self.institution_id, value);
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Funder {
+    fn apply(&mut self, rest: &str, value: &str) -> bool {
+        match rest {
+            "name" | "display_name" | "funder_display_name" => self.name = value.to_string(),
+            "DOI" | "id" | "funder" => self.funder_id = value.to_string(),
+            "ror" => self.ror = value.to_string(),
+            "award_id" => append(&mut self.award, value),
+            _ if indexed_segment(rest).is_some_and(|(name, _)| name == "award") => append(&mut self.award, value),
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Where the columns the tables need are in an input's header. The work is
+// identified by work_id in OpenAlex output and by doi in Crossref output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct InputColumns {
+    work_id: Option<usize>,
+    doi: usize,
+    field_name: usize,
+    subfield_path: usize,
+    value: usize,
+    group: Option<(String, usize)>,
+}
+
+impl InputColumns {
+    fn from_headers(headers: &StringRecord, path: &Path) -> Result<Self> {
+        let find = |name: &str| headers.iter().position(|h| h == name);
+        let require = |name: &str| find(name).with_context(|| format!(
+            "{} has no '{}' column; expected the long-format output of crossref-fast-field-parse or openalex-fast-field-parse (columns: {})",
+            path.display(), name, headers.iter().collect::<Vec<_>>().join(", ")
+        ));
+        Ok(Self {
+            work_id: find("work_id"),
+            doi: require("doi")?,
+            field_name: require("field_name")?,
+            subfield_path: require("subfield_path")?,
+            value: require("value")?,
+            group: ["member_id", "source_id"].iter().find_map(|name| find(name).map(|index| (name.to_string(), index))),
+        })
+    }
+
+    fn work_key<'a>(&self, record: &'a StringRecord) -> &'a str {
+        record.get(self.work_id.unwrap_or(self.doi)).unwrap_or("")
+    }
+}
+
+#[derive(Debug, Default)]
+struct TransformStats {
+    rows: u64,
+    rows_without_work: u64,
+    unused_rows: u64,
+    unused_fields: BTreeSet<String>,
+}
+
+struct Tables {
+    works: Table,
+    contributors: Table,
+    affiliations: Table,
+    funders: Table,
+    identifiers: Table,
+    next_contributor: u64,
+    next_affiliation: u64,
+    next_funder: u64,
+}
+
+impl Tables {
+    fn create(dir: &Path, format: TableFormat, group_column: Option<&str>) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create output directory: {}", dir.display()))?;
+        let mut work_columns = vec!["work_id", "doi", "title", "type", "year"];
+        work_columns.extend(group_column);
+        Ok(Self {
+            works: Table::create(dir, &table_spec("works", &["work_key"], &work_columns), format)?,
+            contributors: Table::create(dir, &table_spec(
+                "contributors",
+                &["contributor_key", "work_key", "position"],
+                &["role", "name", "given_name", "family_name", "orcid", "contributor_id", "sequence", "path"],
+            ), format)?,
+            affiliations: Table::create(dir, &table_spec(
+                "affiliations",
+                &["affiliation_key", "contributor_key", "work_key", "position"],
+                &["name", "ror", "institution_id", "path"],
+            ), format)?,
+            funders: Table::create(dir, &table_spec(
+                "funders",
+                &["funder_key", "work_key", "position"],
+                &["name", "funder_id", "ror", "award", "path"],
+            ), format)?,
+            identifiers: Table::create(dir, &table_spec("identifiers", &["work_key"], &["scheme", "value"]), format)?,
+            next_contributor: 0,
+            next_affiliation: 0,
+            next_funder: 0,
+        })
+    }
+
+    // Keys count up from 1 in input order, so a run over the same input
+    // gives the same keys.
+    fn write_work(&mut self, work: &Work, has_group: bool) -> Result<()> {
+        let work_key = self.works.rows + 1;
+        let mut row = vec![
+            Cell::Key(work_key),
+            Cell::Text(&work.id),
+            Cell::Text(&work.doi),
+            Cell::Text(&work.title),
+            Cell::Text(&work.work_type),
+            Cell::Text(&work.year),
+        ];
+        if has_group {
+            row.push(Cell::Text(&work.group));
+        }
+        self.works.write_row(&row)?;
+
+        for (scheme, value) in &work.identifiers {
+            self.identifiers.write_row(&[Cell::Key(work_key), Cell::Text(scheme), Cell::Text(value)])?;
+        }
+
+        for ((_, position), contributor) in &work.contributors {
+            self.next_contributor += 1;
+            let contributor_key = self.next_contributor;
+            let joined_name;
+            let name = if contributor.name.is_empty() {
+                joined_name = [contributor.given_name.as_str(), contributor.family_name.as_str()]
+                    .iter().filter(|part| !part.is_empty()).copied().collect::<Vec<_>>().join(" ");
+                joined_name.as_str()
+            } else {
+                contributor.name.as_str()
+            };
+            self.contributors.write_row(&[
+                Cell::Key(contributor_key),
+                Cell::Key(work_key),
+                Cell::Key(*position as u64),
+                Cell::Text(&contributor.role),
+                Cell::Text(name),
+                Cell::Text(&contributor.given_name),
+                Cell::Text(&contributor.family_name),
+                Cell::Text(&contributor.orcid),
+                Cell::Text(&contributor.contributor_id),
+                Cell::Text(&contributor.sequence),
+                Cell::Text(&contributor.path),
+            ])?;
+
+            for ((_, position), affiliation) in &contributor.affiliations {
+                self.next_affiliation += 1;
+                self.affiliations.write_row(&[
+                    Cell::Key(self.next_affiliation),
+                    Cell::Key(contributor_key),
+                    Cell::Key(work_key),
+                    Cell::Key(*position as u64),
+                    Cell::Text(&affiliation.name),
+                    Cell::Text(&affiliation.ror),
+                    Cell::Text(&affiliation.institution_id),
+                    Cell::Text(&affiliation.path),
+                ])?;
+            }
+        }
+
+        for ((_, position), funder) in &work.funders {
+            self.next_funder += 1;
+            self.funders.write_row(&[
+                Cell::Key(self.next_funder),
+                Cell::Key(work_key),
+                Cell::Key(*position as u64),
+                Cell::Text(&funder.name),
+                Cell::Text(&funder.funder_id),
+                Cell::Text(&funder.ror),
+                Cell::Text(&funder.award),
+                Cell::Text(&funder.path),
+            ])?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        for (name, table) in [
+            ("works", self.works),
+            ("contributors", self.contributors),
+            ("affiliations", self.affiliations),
+            ("funders", self.funders),
+            ("identifiers", self.identifiers),
+        ] {
+            let path = table.path.clone();
+            let rows = table.finish()?;
+            info!("Wrote {} rows to the {} table: {}", rows, name, path.display());
+        }
+        Ok(())
+    }
+}
+
+fn is_table_input(path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    [".csv", ".csv.gz", ".csv.zst"].iter().any(|suffix| name.ends_with(suffix))
+}
+
+// Files in a directory are read in path order, so the parts of a split
+// output come in sequence and a work's rows stay together.
+fn collect_inputs(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)
+        .with_context(|| format!("Failed to read input directory: {}", path.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_inputs(&entry, files)?;
+        } else if is_table_input(&entry) {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+fn open_input(path: &Path) -> Result<Box<dyn Read>> {
+    let file = File::open(path).with_context(|| format!("Failed to open input file: {}", path.display()))?;
+    let reader = BufReader::new(file);
+    let name = path.to_string_lossy().to_lowercase();
+    Ok(if name.ends_with(".gz") {
+        Box::new(MultiGzDecoder::new(reader))
+    } else if name.ends_with(".zst") {
+        Box::new(zstd::Decoder::with_buffer(reader).with_context(|| format!("Failed to start decompressing: {}", path.display()))?)
+    } else {
+        Box::new(reader)
+    })
+}
+
+fn work_hash(id: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    hasher.finish()
+}
+
+// A work's rows have to come together, as the field parsers write them, since
+// each work is written out when the next one starts. Finished works are
+// remembered by a 64-bit hash of their ID, so rows of a work turning up again
+// later are caught instead of giving it a second key.
+struct Transformer {
+    tables: Tables,
+    has_group: bool,
+    current: Option<Work>,
+    finished: HashSet<u64>,
+    stats: TransformStats,
+}
+
+impl Transformer {
+    fn finish_current(&mut self) -> Result<()> {
+        if let Some(work) = self.current.take() {
+            self.tables.write_work(&work, self.has_group)?;
+            self.finished.insert(work_hash(&work.id));
+        }
+        Ok(())
+    }
+
+    fn read_file(&mut self, path: &Path, columns: &InputColumns) -> Result<()> {
+        let mut reader = ReaderBuilder::new().flexible(true).from_reader(open_input(path)?);
+        let mut record = StringRecord::new();
+        while reader.read_record(&mut record).with_context(|| format!("Failed to read CSV row from {}", path.display()))? {
+            self.stats.rows += 1;
+            let id = columns.work_key(&record);
+            if id.is_empty() {
+                self.stats.rows_without_work += 1;
+                continue;
+            }
+            if self.current.as_ref().is_none_or(|work| work.id != id) {
+                self.finish_current()?;
+                if self.finished.contains(&work_hash(id)) {
+                    return Err(anyhow::anyhow!(
+                        "Rows of work {} in {} come after rows of other works; each work's rows have to be together, as the field parsers write them",
+                        id, path.display()
+                    ));
+                }
+                self.current = Some(Work {
+                    id: id.to_string(),
+                    doi: record.get(columns.doi).unwrap_or("").to_string(),
+                    group: columns.group.as_ref().and_then(|(_, index)| record.get(*index)).unwrap_or("").to_string(),
+                    ..Default::default()
+                });
+                let work = self.current.as_mut().expect("work just started");
+                let doi = work.doi.clone();
+                work.add_identifier("doi", &doi);
+                if columns.work_id.is_some() {
+                    work.add_identifier("openalex", id);
+                }
+            }
+
+            let field_name = record.get(columns.field_name).unwrap_or("");
+            let path_value = record.get(columns.subfield_path).unwrap_or("");
+            let value = record.get(columns.value).unwrap_or("");
+            let work = self.current.as_mut().expect("work started above");
+            if !work.apply(field_name, path_value, value) {
+                self.stats.unused_rows += 1;
+                if self.stats.unused_fields.insert(field_name.to_string()) {
+                    debug!("Field {} is not used by any table", field_name);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_headers(path: &Path) -> Result<InputColumns> {
+    let mut reader = ReaderBuilder::new().flexible(true).from_reader(open_input(path)?);
+    let headers = reader.headers().with_context(|| format!("Failed to read CSV header from {}", path.display()))?.clone();
+    InputColumns::from_headers(&headers, path)
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    setup_logging(&cli.log_level)?;
+
+    let mut files = Vec::new();
+    for input in &cli.input {
+        collect_inputs(Path::new(input), &mut files)?;
+    }
+    let Some(first) = files.first() else {
+        return Err(anyhow::anyhow!("No CSV files found in: {}", cli.input.join(", ")));
+    };
+    info!("Reading {} input files", files.len());
+
+    // Every input has to have the first one's columns, so that the works
+    // table has one layout.
+    let columns = read_headers(first)?;
+    let group_column = columns.group.as_ref().map(|(name, _)| name.as_str());
+    let tables = Tables::create(Path::new(&cli.output), cli.format, group_column)?;
+    let mut transformer = Transformer {
+        tables,
+        has_group: group_column.is_some(),
+        current: None,
+        finished: HashSet::new(),
+        stats: TransformStats::default(),
+    };
+
+    for file in &files {
+        let file_columns = read_headers(file)?;
+        if file_columns != columns {
+            return Err(anyhow::anyhow!("{} has different columns from {}; the inputs have to come from the same parser", file.display(), first.display()));
+        }
+        debug!("Reading {}", file.display());
+        transformer.read_file(file, &columns)?;
+    }
+    transformer.finish_current()?;
+
+    let stats = transformer.stats;
+    transformer.tables.finish()?;
+    info!("Read {} rows", stats.rows);
+    if stats.rows_without_work > 0 {
+        warn!("Skipped {} rows without a work ID", stats.rows_without_work);
+    }
+    if stats.unused_rows > 0 {
+        info!(
+            "{} rows were of fields no table has a column for: {}",
+            stats.unused_rows,
+            stats.unused_fields.iter().cloned().collect::<Vec<_>>().join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs the transformation over `input` and returns each CSV table's contents.
+    fn tables_for(name: &str, input: &str) -> BTreeMap<String, String> {
+        let dir = std::env::temp_dir().join(format!("entity-tables-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let input_path = dir.join("long.csv");
+        fs::write(&input_path, input).unwrap();
+
+        let columns = read_headers(&input_path).unwrap();
+        let group_column = columns.group.as_ref().map(|(name, _)| name.clone());
+        let mut transformer = Transformer {
+            tables: Tables::create(&dir.join("tables"), TableFormat::Csv, group_column.as_deref()).unwrap(),
+            has_group: group_column.is_some(),
+            current: None,
+            finished: HashSet::new(),
+            stats: TransformStats::default(),
+        };
+        transformer.read_file(&input_path, &columns).unwrap();
+        transformer.finish_current().unwrap();
+        transformer.tables.finish().unwrap();
+
+        let tables = ["works", "contributors", "affiliations", "funders", "identifiers"].iter()
+            .map(|table| (table.to_string(), fs::read_to_string(dir.join("tables").join(format!("{}.csv", table))).unwrap()))
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+        tables
+    }
+
+    #[test]
+    fn crossref_rows_become_related_tables() {
+        let tables = tables_for("crossref", "\
+doi,field_name,subfield_path,value,member_id,doi_prefix
+10.1/a,title,title[0],Graphene sensors,78,10.1
+10.1/a,type,type,journal-article,78,10.1
+10.1/a,issued.date-parts,issued.date-parts,\"[[2019,5,8]]\",78,10.1
+10.1/a,ISSN,ISSN[0],1424-8220,78,10.1
+10.1/a,author.given,author[0].given,Ann,78,10.1
+10.1/a,author.family,author[0].family,Doe,78,10.1
+10.1/a,author.ORCID,author[0].ORCID,https://orcid.org/0000-0002-1825-0097,78,10.1
+10.1/a,author.sequence,author[0].sequence,first,78,10.1
+10.1/a,author.affiliation.name,author[0].affiliation[0].name,University of Oxford,78,10.1
+10.1/a,author.affiliation.id.id,author[0].affiliation[0].id[0].id,https://ror.org/052gg0110,78,10.1
+10.1/a,author.family,author[1].family,Roe,78,10.1
+10.1/a,funder.name,funder[0].name,European Commission,78,10.1
+10.1/a,funder.DOI,funder[0].DOI,10.13039/501100000780,78,10.1
+10.1/a,funder.award,funder[0].award[0],H2020-1,78,10.1
+10.1/a,funder.award,funder[0].award[1],H2020-2,78,10.1
+10.1/a,reference-count,reference-count,12,78,10.1
+10.1/b,editor.family,editor[0].family,Itor,311,10.1
+");
+        assert_eq!(tables["works"], "\
+work_key,work_id,doi,title,type,year,member_id
+1,10.1/a,10.1/a,Graphene sensors,journal-article,2019,78
+2,10.1/b,10.1/b,,,,311
+");
+        assert_eq!(tables["contributors"], "\
+contributor_key,work_key,position,role,name,given_name,family_name,orcid,contributor_id,sequence,path
+1,1,0,author,Ann Doe,Ann,Doe,https://orcid.org/0000-0002-1825-0097,,first,author[0]
+2,1,1,author,Roe,,Roe,,,,author[1]
+3,2,0,editor,Itor,,Itor,,,,editor[0]
+");
+        assert_eq!(tables["affiliations"], "\
+affiliation_key,contributor_key,work_key,position,name,ror,institution_id,path
+1,1,1,0,University of Oxford,https://ror.org/052gg0110,,author[0].affiliation[0]
+");
+        assert_eq!(tables["funders"], "\
+funder_key,work_key,position,name,funder_id,ror,award,path
+1,1,0,European Commission,10.13039/501100000780,,H2020-1; H2020-2,funder[0]
+");
+        assert_eq!(tables["identifiers"], "\
+work_key,scheme,value
+1,doi,10.1/a
+1,issn,1424-8220
+2,doi,10.1/b
+");
+    }
+
+    #[test]
+    fn rows_of_a_work_must_be_together() {
+        let dir = std::env::temp_dir().join(format!("entity-tables-{}-split", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input_path = dir.join("long.csv");
+        fs::write(&input_path, "doi,field_name,subfield_path,value\n10.1/a,title,title[0],A\n10.1/b,title,title[0],B\n10.1/a,type,type,x\n").unwrap();
+        let columns = read_headers(&input_path).unwrap();
+        let mut transformer = Transformer {
+            tables: Tables::create(&dir.join("tables"), TableFormat::Csv, None).unwrap(),
+            has_group: false,
+            current: None,
+            finished: HashSet::new(),
+            stats: TransformStats::default(),
+        };
+        let error = transformer.read_file(&input_path, &columns).unwrap_err().to_string();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(error.contains("Rows of work 10.1/a"), "{}", error);
+    }
+}
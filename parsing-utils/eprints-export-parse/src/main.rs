@@ -0,0 +1,484 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser};
+use csv::Writer;
+use flate2::read::GzDecoder;
+use glob::glob;
+use log::{debug, info, warn, LevelFilter};
+use normalization::normalize_orcid;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use serde_json::{Map, Value};
+use simple_logger::SimpleLogger;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use time::macros::format_description;
+
+#[derive(Parser)]
+#[command(name = "EPrints Export Parser")]
+#[command(about = "Convert EPrints XML/JSON exports into the long field format consumed by the author/affiliation normalizer")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(short, long, help = "EPrints export file (.xml, .json, .jsonl, optionally .gz) or directory of them", required = true)]
+    input: String,
+
+    #[arg(short, long, default_value = "eprints_field_data.csv", help = "Output CSV file")]
+    output: String,
+
+    #[arg(long, help = "Skip eprints without a DOI")]
+    require_doi: bool,
+
+    #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
+    log_level: String,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the output columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+static VERSION_INFO: LazyLock<String> = LazyLock::new(|| format!(
+    "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+    VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+));
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Doi(String);
+
+// EPrints XML is converted to the shape of the EPrints JSON export so both go through
+// one extractor: multi-value fields (<creators><item>...</item></creators>) become
+// arrays, compound values become objects and leaves become strings.
+#[derive(Debug, Default)]
+struct XmlNode {
+    name: String,
+    text: String,
+    children: Vec<XmlNode>,
+}
+
+fn xml_to_json(node: &XmlNode) -> Value {
+    if node.children.is_empty() {
+        return Value::String(node.text.trim().to_string());
+    }
+    if node.children.iter().all(|c| c.name == "item") {
+        return Value::Array(node.children.iter().map(xml_to_json).collect());
+    }
+    let mut object = Map::new();
+    for child in &node.children {
+        object.insert(child.name.clone(), xml_to_json(child));
+    }
+    Value::Object(object)
+}
+
+fn local_name(element: &BytesStart) -> String {
+    String::from_utf8_lossy(element.local_name().as_ref()).into_owned()
+}
+
+fn id_attribute(element: &BytesStart) -> Option<String> {
+    element.attributes()
+        .filter_map(|a| a.ok())
+        .find(|a| a.key.local_name().as_ref() == b"id")
+        .and_then(|a| a.unescape_value().ok().map(|v| v.trim().to_string()))
+}
+
+fn open_input(path: &Path) -> Result<Box<dyn BufRead>> {
+    let file = File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    if path.extension().and_then(|e| e.to_str()) == Some("gz") {
+        Ok(Box::new(BufReader::new(GzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+// Streams the <eprint> elements of an EP3 XML export. The eprint's id attribute (its
+// URI) is kept as "uri", matching the JSON export.
+fn read_xml_eprints(path: &Path) -> Result<Vec<Value>> {
+    let mut reader = Reader::from_reader(open_input(path)?);
+    let mut buf = Vec::new();
+    let mut eprints = Vec::new();
+    let mut stack: Vec<XmlNode> = Vec::new();
+    let mut uri: Option<String> = None;
+
+    loop {
+        let event = reader.read_event_into(&mut buf)
+            .with_context(|| format!("Malformed XML in {} at byte {}", path.display(), reader.buffer_position()))?;
+        match event {
+            Event::Start(e) => {
+                let name = local_name(&e);
+                if !stack.is_empty() || name == "eprint" {
+                    if stack.is_empty() {
+                        uri = id_attribute(&e);
+                    }
+                    stack.push(XmlNode { name, ..Default::default() });
+                }
+            }
+            Event::Empty(e) => {
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(XmlNode { name: local_name(&e), ..Default::default() });
+                }
+            }
+            Event::Text(t) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&t.unescape().context("Invalid XML text")?);
+                }
+            }
+            Event::CData(c) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&String::from_utf8_lossy(&c));
+                }
+            }
+            Event::End(_) => {
+                if let Some(node) = stack.pop() {
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(node),
+                        None => {
+                            let mut eprint = xml_to_json(&node);
+                            if let (Value::Object(object), Some(uri)) = (&mut eprint, uri.take()) {
+                                object.entry("uri").or_insert(Value::String(uri));
+                            }
+                            eprints.push(eprint);
+                        }
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(eprints)
+}
+
+fn json_eprints(document: Value) -> Vec<Value> {
+    match document {
+        Value::Array(items) => items,
+        Value::Object(_) => vec![document],
+        _ => Vec::new(),
+    }
+}
+
+fn base_name(path: &Path) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    name.strip_suffix(".gz").map(str::to_string).unwrap_or(name)
+}
+
+fn read_eprints(path: &Path) -> Result<Vec<Value>> {
+    let name = base_name(path);
+    if name.ends_with(".xml") {
+        return read_xml_eprints(path);
+    }
+    let mut reader = open_input(path)?;
+    if name.ends_with(".jsonl") {
+        let mut eprints = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.with_context(|| format!("Failed to read line {} of {}", i + 1, path.display()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Value>(&line) {
+                Ok(value) => eprints.extend(json_eprints(value)),
+                Err(e) => warn!("Skipping invalid JSON on line {} of {}: {}", i + 1, path.display(), e),
+            }
+        }
+        return Ok(eprints);
+    }
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content).with_context(|| format!("Failed to read file: {}", path.display()))?;
+    let document: Value = serde_json::from_slice(&content)
+        .with_context(|| format!("Invalid JSON in {}", path.display()))?;
+    Ok(json_eprints(document))
+}
+
+fn text(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn as_list(value: Option<&Value>) -> Vec<&Value> {
+    match value {
+        Some(Value::Array(items)) => items.iter().collect(),
+        Some(Value::Null) | None => Vec::new(),
+        Some(other) => vec![other],
+    }
+}
+
+fn normalize_doi(raw: &str) -> Option<Doi> {
+    normalization::normalize_doi(raw).map(Doi)
+}
+
+// Repositories record the DOI in `doi` (newer EPrints), `id_number` or `official_url`.
+fn find_doi(eprint: &Value) -> Option<Doi> {
+    ["doi", "id_number", "official_url"].iter()
+        .filter_map(|key| text(eprint.get(*key)))
+        .find_map(|value| normalize_doi(&value))
+}
+
+struct ConvertedEprint {
+    work_id: String,
+    doi: Option<Doi>,
+    fields: Vec<(String, String, String)>,
+}
+
+fn push(fields: &mut Vec<(String, String, String)>, name: &str, path: String, value: Option<String>) {
+    if let Some(value) = value.filter(|v| !v.is_empty()) {
+        fields.push((name.to_string(), path, value));
+    }
+}
+
+// Creators are written under OpenAlex's authorships paths so the output can go
+// straight into the author/affiliation normalizer.
+fn convert_eprint(eprint: &Value) -> Option<ConvertedEprint> {
+    let eprintid = text(eprint.get("eprintid"))?;
+    let work_id = text(eprint.get("uri")).unwrap_or_else(|| eprintid.clone());
+    let doi = find_doi(eprint);
+    let mut fields = Vec::new();
+
+    push(&mut fields, "eprintid", "eprintid".to_string(), Some(eprintid));
+    push(&mut fields, "doi", "doi".to_string(), doi.as_ref().map(|d| d.0.clone()));
+    push(&mut fields, "title", "title".to_string(), text(eprint.get("title")));
+
+    for (i, creator) in as_list(eprint.get("creators")).into_iter().enumerate() {
+        let name = creator.get("name");
+        let family = text(name.and_then(|n| n.get("family")));
+        let given = text(name.and_then(|n| n.get("given")));
+        let display_name = match (&given, &family) {
+            (Some(g), Some(f)) => Some(format!("{} {}", g, f)),
+            (None, Some(f)) => Some(f.clone()),
+            (Some(g), None) => Some(g.clone()),
+            (None, None) => None,
+        };
+        let base = format!("authorships[{}].author", i);
+        push(&mut fields, "authorships.author.display_name", format!("{}.display_name", base), display_name);
+        push(&mut fields, "authorships.author.family", format!("{}.family", base), family);
+        push(&mut fields, "authorships.author.given", format!("{}.given", base), given);
+        push(&mut fields, "authorships.author.orcid", format!("{}.orcid", base), text(creator.get("orcid")).and_then(|o| normalize_orcid(&o)));
+    }
+
+    for (i, division) in as_list(eprint.get("divisions")).into_iter().enumerate() {
+        push(&mut fields, "divisions", format!("divisions[{}]", i), text(Some(division)));
+    }
+
+    Some(ConvertedEprint { work_id, doi, fields })
+}
+
+fn find_input_files(input: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if !path.is_dir() {
+        return Err(anyhow::anyhow!("Input path does not exist: {}", input));
+    }
+    let mut files = Vec::new();
+    for extension in ["xml", "json", "jsonl"] {
+        for pattern in [format!("**/*.{}", extension), format!("**/*.{}.gz", extension)] {
+            let full = path.join(pattern);
+            files.extend(glob(&full.to_string_lossy())?.filter_map(Result::ok));
+        }
+    }
+    files.sort();
+    if files.is_empty() {
+        warn!("No .xml, .json or .jsonl files found under: {}", input);
+    }
+    Ok(files)
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let total_secs = elapsed.as_secs();
+    let minutes = total_secs / 60;
+    let seconds = total_secs % 60;
+    let millis = elapsed.subsec_millis();
+
+    if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}.{:03}s", seconds, millis)
+    }
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let start_time = Instant::now();
+    let cli = Cli::parse();
+
+    setup_logging(&cli.log_level)?;
+    info!("Starting EPrints export parser");
+
+    let files = find_input_files(&cli.input)?;
+    info!("Found {} files to process.", files.len());
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let output_path = PathBuf::from(&cli.output);
+    if let Some(parent) = output_path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory structure for: {}", output_path.display()))?;
+        }
+    }
+    let mut writer = Writer::from_path(&output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path.display()))?;
+    // Same columns as the OpenAlex field parser, which the normalizer reads.
+    writer.write_record(["work_id", "doi", "field_name", "subfield_path", "value", "source_id", "doi_prefix", "source_file_path"])?;
+
+    let mut files_with_errors = 0;
+    let mut eprints = 0;
+    let mut without_eprintid = 0;
+    let mut with_doi = 0;
+    let mut skipped_without_doi = 0;
+    let mut rows = 0;
+
+    for file in &files {
+        let items = match read_eprints(file) {
+            Ok(items) => items,
+            Err(e) => {
+                warn!("Error reading {}: {:#}", file.display(), e);
+                files_with_errors += 1;
+                continue;
+            }
+        };
+        debug!("{} eprints in {}", items.len(), file.display());
+        let source = file.to_string_lossy();
+        for item in &items {
+            eprints += 1;
+            let Some(converted) = convert_eprint(item) else {
+                without_eprintid += 1;
+                continue;
+            };
+            match &converted.doi {
+                Some(_) => with_doi += 1,
+                None if cli.require_doi => {
+                    skipped_without_doi += 1;
+                    continue;
+                }
+                None => {}
+            }
+            let doi = converted.doi.as_ref().map(|d| d.0.as_str()).unwrap_or("");
+            let prefix = doi.split_once('/').map(|(p, _)| p).unwrap_or("");
+            for (field_name, subfield_path, value) in &converted.fields {
+                writer.write_record([&converted.work_id, doi, field_name, subfield_path, value, "", prefix, &source])?;
+                rows += 1;
+            }
+        }
+    }
+    writer.flush()?;
+
+    info!("-------------------- FINAL SUMMARY --------------------");
+    info!("Total execution time: {}", format_elapsed(start_time.elapsed()));
+    info!("Files: {} ({} with errors)", files.len(), files_with_errors);
+    info!("EPrints: {} ({} with DOI, {} without eprintid skipped)", eprints, with_doi, without_eprintid);
+    if cli.require_doi {
+        info!("Skipped without DOI: {}", skipped_without_doi);
+    }
+    info!("Rows written: {} -> {}", rows, cli.output);
+    info!("-------------------------------------------------------");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Field = (String, String, String);
+
+    fn converted(name: &str, contents: &str) -> Vec<(String, Option<Doi>, Vec<Field>)> {
+        let path = std::env::temp_dir().join(format!("eprints-export-parse-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        let eprints = read_eprints(&path);
+        fs::remove_file(&path).unwrap();
+        eprints.unwrap()
+            .iter()
+            .filter_map(convert_eprint)
+            .map(|c| (c.work_id, c.doi, c.fields))
+            .collect()
+    }
+
+    fn expected_fields() -> Vec<Field> {
+        [
+            ("eprintid", "eprintid", "42"),
+            ("doi", "doi", "10.1234/abc"),
+            ("title", "title", "Graphene sensors"),
+            ("authorships.author.display_name", "authorships[0].author.display_name", "Ann Doe"),
+            ("authorships.author.family", "authorships[0].author.family", "Doe"),
+            ("authorships.author.given", "authorships[0].author.given", "Ann"),
+            ("authorships.author.orcid", "authorships[0].author.orcid", "https://orcid.org/0000-0002-1825-0097"),
+            ("authorships.author.display_name", "authorships[1].author.display_name", "Roe"),
+            ("authorships.author.family", "authorships[1].author.family", "Roe"),
+            ("divisions", "divisions[0]", "physics"),
+        ].iter().map(|(f, p, v)| (f.to_string(), p.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn xml_and_json_exports_give_the_same_rows() {
+        let xml = converted("export.xml", r#"<?xml version="1.0" encoding="utf-8"?>
+<eprints xmlns="http://eprints.org/ep2/data/2.0">
+  <eprint id="https://repo.example.org/id/eprint/42">
+    <eprintid>42</eprintid>
+    <id_number>doi:10.1234/ABC</id_number>
+    <title>Graphene sensors</title>
+    <creators>
+      <item><name><family>Doe</family><given>Ann</given></name><orcid>https://orcid.org/0000-0002-1825-0097</orcid></item>
+      <item><name><family>Roe</family></name><id/></item>
+    </creators>
+    <divisions><item>physics</item></divisions>
+  </eprint>
+  <eprint id="https://repo.example.org/id/eprint/43"><title>No eprintid</title></eprint>
+</eprints>
+"#);
+        let json = converted("export.json", r#"[{
+            "eprintid": 42, "uri": "https://repo.example.org/id/eprint/42", "doi": "https://doi.org/10.1234/abc",
+            "title": "Graphene sensors",
+            "creators": [
+                {"name": {"family": "Doe", "given": "Ann"}, "orcid": "0000-0002-1825-0097"},
+                {"name": {"family": "Roe", "given": null}}
+            ],
+            "divisions": "physics"
+        }]"#);
+
+        let expected = vec![(
+            "https://repo.example.org/id/eprint/42".to_string(),
+            Some(Doi("10.1234/abc".to_string())),
+            expected_fields(),
+        )];
+        assert_eq!(xml, expected);
+        assert_eq!(json, expected);
+    }
+
+    #[test]
+    fn jsonl_exports_skip_invalid_lines() {
+        let eprints = converted("export.jsonl", "{\"eprintid\": 1}\nnot json\n\n[{\"eprintid\": 2, \"official_url\": \"http://dx.doi.org/10.1/X\"}]\n");
+        let ids: Vec<(&str, Option<&str>)> = eprints.iter()
+            .map(|(work_id, doi, _)| (work_id.as_str(), doi.as_ref().map(|d| d.0.as_str())))
+            .collect();
+        assert_eq!(ids, [("1", None), ("2", Some("10.1/x"))]);
+    }
+}
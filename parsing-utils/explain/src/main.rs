@@ -0,0 +1,188 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+#[derive(Parser)]
+#[command(name = "Explain")]
+#[command(about = "Print the lineage of an output from its provenance: the tool and build that wrote it, the command, the source snapshot, the filters and the row counts")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(help = "Output file or directory to explain, or its provenance file")]
+    output: String,
+
+    #[arg(long, help = "Print the provenance as JSON instead of a summary")]
+    json: bool,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the printed summary changes.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+static VERSION_INFO: LazyLock<String> = LazyLock::new(|| format!(
+    "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+    VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+));
+
+// As written by crossref-fast-field-parse and openalex-fast-field-parse: inside
+// a directory output, or beside a single output file.
+const DIRECTORY_FILE: &str = "_provenance.json";
+const FILE_SUFFIX: &str = ".provenance.json";
+
+// A member file, a part or a year=YYYY partition of a directory output is
+// covered by the directory's provenance, at most two levels up.
+fn find_provenance(output: &Path) -> Result<PathBuf> {
+    let name = output.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    if output.is_file() && (name == DIRECTORY_FILE || name.ends_with(FILE_SUFFIX)) {
+        return Ok(output.to_path_buf());
+    }
+    if !output.exists() {
+        return Err(anyhow::anyhow!("Output not found: {}", output.display()));
+    }
+
+    let mut candidates = Vec::new();
+    if output.is_dir() {
+        candidates.push(output.join(DIRECTORY_FILE));
+    } else {
+        let mut beside = output.as_os_str().to_owned();
+        beside.push(FILE_SUFFIX);
+        candidates.push(PathBuf::from(beside));
+    }
+    candidates.extend(output.ancestors().skip(1).take(2).map(|dir| dir.join(DIRECTORY_FILE)));
+
+    candidates.into_iter().find(|candidate| candidate.is_file()).ok_or_else(|| anyhow::anyhow!(
+        "No provenance found for {}: looked for {} beside it and {} in its directory. \
+         Outputs written before provenance was recorded, or copied without it, cannot be explained",
+        output.display(), FILE_SUFFIX, DIRECTORY_FILE
+    ))
+}
+
+fn read_provenance(path: &Path) -> Result<Value> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read provenance {}", path.display()))?;
+    let provenance: Value = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse provenance {}", path.display()))?;
+    if provenance.get("tool").is_none() {
+        return Err(anyhow::anyhow!("{} is not a provenance file: it has no \"tool\" entry", path.display()));
+    }
+    Ok(provenance)
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "-".to_string(),
+        other => other.to_string(),
+    }
+}
+
+// Quoted so that the command can be pasted back into a shell.
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+// Entries left at null or false are settings that were not used.
+fn print_section(title: &str, section: Option<&Value>) {
+    let Some(Value::Object(entries)) = section else {
+        return;
+    };
+    println!("{}:", title);
+    for (key, value) in entries {
+        if !matches!(value, Value::Null | Value::Bool(false)) {
+            println!("  {}: {}", key, display_value(value));
+        }
+    }
+}
+
+fn explain(output: &Path, provenance_path: &Path, provenance: &Value) {
+    let tool = &provenance["tool"];
+    println!("Output: {}", output.display());
+    println!("Provenance: {}", provenance_path.display());
+    println!(
+        "Written by: {} {} (git commit {})",
+        display_value(&tool["name"]), display_value(&tool["version"]), display_value(&tool["git_commit"])
+    );
+    println!("Output schema: {}", display_value(&tool["output_schema"]));
+    println!("Finished: {}", display_value(&provenance["finished"]));
+    if let Some(command) = provenance["command"].as_array() {
+        let args: Vec<String> = command.iter().map(|arg| shell_quote(&display_value(arg))).collect();
+        println!("Command: {}", args.join(" "));
+    }
+    println!();
+    print_section("Source", provenance.get("source"));
+    print_section("Settings", provenance.get("config"));
+    print_section("Counts", provenance.get("output"));
+
+    let name = provenance_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let covered = provenance_path.parent().filter(|_| name == DIRECTORY_FILE);
+    if let Some(dir) = covered.filter(|dir| !output.starts_with(provenance_path) && output != *dir) {
+        println!();
+        println!("Counts are for the whole output in {}", dir.display());
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let output = Path::new(&cli.output);
+    let provenance_path = find_provenance(output)?;
+    let provenance = read_provenance(&provenance_path)?;
+
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&provenance)?);
+    } else {
+        explain(output, &provenance_path, &provenance);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provenance_is_found_beside_files_and_up_to_two_directories_up() {
+        let dir = std::env::temp_dir().join(format!("explain-{}-find", std::process::id()));
+        let organized = dir.join("organized");
+        fs::create_dir_all(organized.join("78")).unwrap();
+        fs::write(organized.join(DIRECTORY_FILE), r#"{"tool": {"name": "crossref-fast-field-parse"}}"#).unwrap();
+        fs::write(organized.join("78").join("part-00001.csv"), "").unwrap();
+        fs::write(dir.join("out.csv"), "").unwrap();
+        fs::write(dir.join("out.csv.provenance.json"), r#"{"tool": {}}"#).unwrap();
+        fs::write(dir.join("other.csv"), "").unwrap();
+
+        let part = find_provenance(&organized.join("78").join("part-00001.csv")).unwrap();
+        let directory = find_provenance(&organized).unwrap();
+        let file = find_provenance(&dir.join("out.csv")).unwrap();
+        let provenance = read_provenance(&part).unwrap();
+        let missing = find_provenance(&dir.join("other.csv")).unwrap_err().to_string();
+        let not_provenance = read_provenance(&dir.join("other.csv")).unwrap_err().to_string();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(part, organized.join(DIRECTORY_FILE));
+        assert_eq!(directory, organized.join(DIRECTORY_FILE));
+        assert_eq!(file, dir.join("out.csv.provenance.json"));
+        assert_eq!(display_value(&provenance["tool"]["name"]), "crossref-fast-field-parse");
+        assert!(missing.contains("No provenance found"), "{}", missing);
+        assert!(not_provenance.contains("Failed to parse provenance"), "{}", not_provenance);
+    }
+
+    #[test]
+    fn commands_are_quoted_for_the_shell() {
+        assert_eq!(shell_quote("--input=/data/a.jsonl.gz"), "--input=/data/a.jsonl.gz");
+        assert_eq!(shell_quote("author.given,title"), "author.given,title");
+        assert_eq!(shell_quote("it's here"), r"'it'\''s here'");
+        assert_eq!(shell_quote(""), "''");
+    }
+}
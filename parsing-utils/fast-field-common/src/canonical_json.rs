@@ -0,0 +1,124 @@
+use serde_json::Value;
+
+// Serializes complex values so that equal JSON compares and hashes equal
+// whichever registry it came from: object keys sorted, no whitespace, and
+// numbers written the same however they were written in the source
+// (`1.0`, `1` and `1e0` all become `1`).
+pub fn to_string(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(value, &mut out);
+    out
+}
+
+fn write_value(value: &Value, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => out.push_str(&number(n)),
+        Value::String(s) => write_string(s, out),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(key, out);
+                out.push(':');
+                write_value(item, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_string(s: &str, out: &mut String) {
+    // Serializing a str cannot fail.
+    out.push_str(&serde_json::to_string(s).unwrap_or_default());
+}
+
+// Integers are written as is. Floats with no fractional part that are
+// exactly representable (below 2^53) are written as integers, other floats
+// in their shortest round-trip form.
+fn number(n: &serde_json::Number) -> String {
+    if n.is_i64() || n.is_u64() {
+        return n.to_string();
+    }
+    match n.as_f64() {
+        Some(f) if f.fract() == 0.0 && f.abs() < 9_007_199_254_740_992.0 => format!("{}", f as i64),
+        Some(f) => format!("{}", f),
+        None => n.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_string;
+    use serde_json::{json, Value};
+
+    fn canonical(source: &str) -> String {
+        to_string(&serde_json::from_str::<Value>(source).unwrap())
+    }
+
+    #[test]
+    fn object_keys_are_sorted_by_bytes_at_every_level() {
+        assert_eq!(
+            canonical(r#"{"b": 1, "a": {"d": [{"z": 1, "y": 2}], "c": 3}, "B": 0, "é": 4}"#),
+            r#"{"B":0,"a":{"c":3,"d":[{"y":2,"z":1}]},"b":1,"é":4}"#
+        );
+    }
+
+    #[test]
+    fn key_order_and_whitespace_in_the_source_do_not_matter() {
+        assert_eq!(canonical("{ \"x\" : [ 1 , 2 ] ,\n\t\"w\" : null }"), canonical(r#"{"w":null,"x":[1,2]}"#));
+    }
+
+    #[test]
+    fn control_characters_are_escaped_and_non_ascii_is_kept() {
+        assert_eq!(to_string(&json!("tab\there\nquote\" back\\ bell\u{7}")), r#""tab\there\nquote\" back\\ bell\u0007""#);
+        assert_eq!(to_string(&json!("Ivanov / Иванов 王")), "\"Ivanov / Иванов 王\"");
+        assert_eq!(to_string(&json!({"k\u{1}": "\u{1f}"})), r#"{"k\u0001":"\u001f"}"#);
+    }
+
+    #[test]
+    fn integers_are_written_as_is() {
+        assert_eq!(canonical("[0, -7, 9007199254740993, 18446744073709551615, -9223372036854775808]"),
+            "[0,-7,9007199254740993,18446744073709551615,-9223372036854775808]");
+    }
+
+    #[test]
+    fn whole_floats_are_written_as_integers() {
+        assert_eq!(canonical("[1.0, 1e0, 10E1, -2.000, 1.5e1]"), "[1,1,100,-2,15]");
+    }
+
+    #[test]
+    fn negative_zero_is_zero() {
+        assert_eq!(canonical("[-0, -0.0, 0e5, -0e-3]"), "[0,0,0,0]");
+    }
+
+    #[test]
+    fn fractions_and_exponents_use_the_shortest_round_trip_form() {
+        assert_eq!(canonical("[0.5, 1.25e-1, 1E-7, 2.5e+2, 0.1]"), "[0.5,0.125,0.0000001,250,0.1]");
+        // Past 2^53 a whole float is no longer written as an integer.
+        assert_eq!(canonical("[9007199254740992.0, 1e20]"), "[9007199254740992,100000000000000000000]");
+    }
+
+    #[test]
+    fn nested_arrays_and_objects_keep_array_order() {
+        assert_eq!(
+            canonical(r#"[[3, [2, [1]]], {"b": [], "a": {}}, [], {}, [true, false, null]]"#),
+            r#"[[3,[2,[1]]],{"a":{},"b":[]},[],{},[true,false,null]]"#
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use anyhow::Result;
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+
+// Estimates come from sampling, so free space within this factor of the
+// estimate is reported as tight rather than refused.
+const WARN_MARGIN: f64 = 1.2;
+
+pub fn check(stage: &str, target: &Path, needed_bytes: u64) -> Result<()> {
+    let location = existing_ancestor(target);
+    let available = match fs4::available_space(&location) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Could not determine free space on {}: {}. Skipping the {} space check.", location.display(), e, stage);
+            return Ok(());
+        }
+    };
+    info!(
+        "Disk space for {}: an estimated {} needed, {} free on {}",
+        stage, format_bytes(needed_bytes), format_bytes(available), location.display()
+    );
+    if needed_bytes > available {
+        return Err(anyhow::anyhow!(
+            "Not enough disk space for {}: an estimated {} is needed but only {} is free on {}. Free up space, write elsewhere, or pass --no-space-check to run anyway.",
+            stage, format_bytes(needed_bytes), format_bytes(available), location.display()
+        ));
+    }
+    if needed_bytes as f64 * WARN_MARGIN > available as f64 {
+        warn!("Disk space for {} is tight: the estimate is within {:.0}% of the free space on {}.", stage, (WARN_MARGIN - 1.0) * 100.0, location.display());
+    }
+    Ok(())
+}
+
+// The output file or directory usually does not exist yet.
+fn existing_ancestor(path: &Path) -> PathBuf {
+    let mut current = if path.is_absolute() { path.to_path_buf() } else { Path::new(".").join(path) };
+    while !current.exists() {
+        if !current.pop() {
+            return PathBuf::from(".");
+        }
+    }
+    current
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
@@ -0,0 +1,100 @@
+use log::warn;
+use serde_json::json;
+use std::any::Any;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// Inside a directory output (--organize, --max-rows-per-file); beside a single
+// output file otherwise.
+pub const DIRECTORY_FILE: &str = "_errors.jsonl";
+pub const FILE_SUFFIX: &str = ".errors.jsonl";
+
+pub fn path_for(output: &Path, output_is_dir: bool) -> PathBuf {
+    if output_is_dir {
+        output.join(DIRECTORY_FILE)
+    } else {
+        let mut path = output.as_os_str().to_owned();
+        path.push(FILE_SUFFIX);
+        PathBuf::from(path)
+    }
+}
+
+pub fn is_sidecar(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == DIRECTORY_FILE || name.to_string_lossy().ends_with(FILE_SUFFIX))
+}
+
+// JSONL file of records that could not be processed, one object per record with
+// the source location, the failure kind and the raw line. Created on first use.
+pub struct ErrorSidecar {
+    path: PathBuf,
+    writer: Mutex<Option<BufWriter<File>>>,
+    entries: AtomicUsize,
+}
+
+impl ErrorSidecar {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            writer: Mutex::new(None),
+            entries: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn record(&self, file: &Path, line: usize, kind: &str, message: &str, raw_record: &str) {
+        let entry = json!({
+            "file": file.display().to_string(),
+            "line": line,
+            "error": kind,
+            "message": message,
+            "record": raw_record,
+        });
+        let mut guard = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if guard.is_none() {
+            match File::create(&self.path) {
+                Ok(f) => *guard = Some(BufWriter::new(f)),
+                Err(e) => {
+                    warn!("Failed to create error sidecar {}: {}", self.path.display(), e);
+                    return;
+                }
+            }
+        }
+        if let Some(writer) = guard.as_mut() {
+            match writeln!(writer, "{}", entry) {
+                Ok(()) => {
+                    self.entries.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => warn!("Failed to write to error sidecar {}: {}", self.path.display(), e),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn entries(&self) -> usize {
+        self.entries.load(Ordering::Relaxed)
+    }
+
+    pub fn flush(&self) {
+        let mut guard = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(writer) = guard.as_mut() {
+            if let Err(e) = writer.flush() {
+                warn!("Failed to flush error sidecar {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "non-string panic payload".to_string()
+    }
+}
@@ -0,0 +1,303 @@
+#[cfg(feature = "http")]
+use crate::remote_input::RemoteSource;
+use anyhow::{Context, Result};
+#[cfg(feature = "http")]
+use log::warn;
+#[cfg(feature = "http")]
+use rayon::prelude::*;
+#[cfg(feature = "http")]
+use serde_json::{json, Map, Value};
+#[cfg(feature = "http")]
+use std::collections::BTreeMap;
+use std::fs;
+#[cfg(feature = "http")]
+use std::io::{self, Read};
+use std::path::Path;
+#[cfg(feature = "http")]
+use std::path::PathBuf;
+#[cfg(feature = "http")]
+use std::thread;
+#[cfg(feature = "http")]
+use std::time::Duration;
+
+#[cfg(not(feature = "http"))]
+pub use self::unavailable::HttpInput;
+
+#[cfg(feature = "http")]
+const MAX_ATTEMPTS: u32 = 5;
+#[cfg(feature = "http")]
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+pub fn is_http_url(input: &str) -> bool {
+    input.starts_with("https://") || input.starts_with("http://")
+}
+
+// A manifest lists one URL per line. Blank lines and lines starting with
+// '#' are skipped.
+pub fn read_manifest(path: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read URL manifest: {}", path.display()))?;
+    let mut urls = Vec::new();
+    for (line_number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if !is_http_url(line) {
+            return Err(anyhow::anyhow!("{} line {}: not an http(s) URL: {}", path.display(), line_number + 1, line));
+        }
+        urls.push(line.to_string());
+    }
+    Ok(urls)
+}
+
+// Built without the `http` feature: URLs are refused.
+#[cfg(not(feature = "http"))]
+mod unavailable {
+    use crate::remote_input::RemoteSource;
+    use anyhow::Result;
+    use serde_json::{Map, Value};
+    use std::io::Read;
+    use std::path::{Path, PathBuf};
+
+    pub enum HttpInput {}
+
+    impl HttpInput {
+        pub fn new(urls: Vec<String>) -> Result<Self> {
+            let what = match urls.as_slice() {
+                [url] => format!("Reading {}", url),
+                _ => "Reading a URL manifest".to_string(),
+            };
+            Err(crate::missing_feature(&what, "http"))
+        }
+    }
+
+    impl RemoteSource for HttpInput {
+        fn files(&self) -> Vec<PathBuf> {
+            match *self {}
+        }
+
+        fn lock_inputs(&self) -> Map<String, Value> {
+            match *self {}
+        }
+
+        fn open(&self, _uri: &Path) -> Result<Box<dyn Read>> {
+            match *self {}
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Default)]
+struct UrlMeta {
+    size: Option<u64>,
+    e_tag: Option<String>,
+    last_modified: Option<String>,
+}
+
+// Files fetched over HTTP(S). Each URL is checked with a HEAD request up
+// front for its size, ETag and Last-Modified; servers that refuse HEAD are
+// still read, just without a size for the disk space estimate.
+#[cfg(feature = "http")]
+pub struct HttpInput {
+    agent: ureq::Agent,
+    urls: BTreeMap<PathBuf, (String, UrlMeta)>,
+}
+
+#[cfg(feature = "http")]
+impl HttpInput {
+    pub fn new(urls: Vec<String>) -> Result<Self> {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(Duration::from_secs(30))
+            .timeout_read(Duration::from_secs(120))
+            .build();
+        let metas: Vec<(String, UrlMeta)> = urls.into_par_iter()
+            .map(|url| {
+                let meta = match with_retries(&url, || agent.head(&url)) {
+                    Ok(response) => UrlMeta {
+                        size: response.header("Content-Length").and_then(|v| v.parse().ok()),
+                        e_tag: response.header("ETag").map(str::to_string),
+                        last_modified: response.header("Last-Modified").map(str::to_string),
+                    },
+                    Err(e) => {
+                        warn!("HEAD {} failed ({}); reading it without a known size", url, e);
+                        UrlMeta::default()
+                    }
+                };
+                (url, meta)
+            })
+            .collect();
+        let urls = metas.into_iter().map(|(url, meta)| (PathBuf::from(&url), (url, meta))).collect();
+        Ok(Self { agent, urls })
+    }
+}
+
+#[cfg(feature = "http")]
+impl RemoteSource for HttpInput {
+    fn files(&self) -> Vec<PathBuf> {
+        self.urls.keys().cloned().collect()
+    }
+
+    // Lock entries keyed by URL. Last-Modified is kept as the server sent it.
+    fn lock_inputs(&self) -> Map<String, Value> {
+        self.urls.values()
+            .map(|(url, meta)| (url.clone(), json!({
+                "size": meta.size,
+                "last_modified": meta.last_modified,
+                "e_tag": meta.e_tag,
+            })))
+            .collect()
+    }
+
+    fn open(&self, uri: &Path) -> Result<Box<dyn Read>> {
+        let (url, _) = self.urls.get(uri)
+            .with_context(|| format!("{} was not in the input URLs", uri.display()))?;
+        let response = with_retries(url, || self.agent.get(url))
+            .with_context(|| format!("Failed to open {}", url))?;
+        Ok(Box::new(HttpReader {
+            agent: self.agent.clone(),
+            url: url.clone(),
+            validator: range_validator(&response),
+            body: response.into_reader(),
+            position: 0,
+        }))
+    }
+}
+
+#[cfg(feature = "http")]
+fn is_retryable(error: &ureq::Error) -> bool {
+    match error {
+        ureq::Error::Status(code, _) => *code == 429 || *code >= 500,
+        ureq::Error::Transport(_) => true,
+    }
+}
+
+// Sends the request, retrying connection failures, 429s and 5xx responses
+// with exponential backoff.
+#[cfg(feature = "http")]
+fn with_retries(url: &str, mut request: impl FnMut() -> ureq::Request) -> Result<ureq::Response> {
+    let mut delay = FIRST_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match request().call() {
+            Ok(response) => return Ok(response),
+            Err(e) if attempt < MAX_ATTEMPTS && is_retryable(&e) => {
+                warn!("Request for {} failed ({}); retrying in {:?}", url, e, delay);
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(anyhow::Error::new(e)),
+        }
+    }
+}
+
+// The If-Range validator for resuming a download: the first response's
+// ETag, or its Last-Modified date when the ETag is weak or missing, since
+// If-Range only takes a strong ETag.
+#[cfg(feature = "http")]
+fn range_validator(response: &ureq::Response) -> Option<String> {
+    response.header("ETag")
+        .filter(|e_tag| !e_tag.starts_with("W/"))
+        .or_else(|| response.header("Last-Modified"))
+        .map(str::to_string)
+}
+
+// Response body that resumes with a Range request from the last byte read
+// when the connection drops mid-download. With If-Range the server sends
+// the whole file instead if it changed in the meantime, as do servers
+// without range support. The whole file is only read on from the byte the
+// download broke off at if its ETag or Last-Modified is still the one the
+// first response had, so a file is never spliced together from two versions.
+#[cfg(feature = "http")]
+struct HttpReader {
+    agent: ureq::Agent,
+    url: String,
+    validator: Option<String>,
+    body: Box<dyn Read + Send + Sync>,
+    position: u64,
+}
+
+#[cfg(feature = "http")]
+impl HttpReader {
+    fn resume(&mut self) -> io::Result<()> {
+        let response = with_retries(&self.url, || {
+            let mut request = self.agent.get(&self.url).set("Range", &format!("bytes={}-", self.position));
+            if let Some(validator) = &self.validator {
+                request = request.set("If-Range", validator);
+            }
+            request
+        })
+        .map_err(io::Error::other)?;
+        match response.status() {
+            206 if content_range_start(&response) == Some(self.position) => {
+                self.body = response.into_reader();
+                Ok(())
+            }
+            206 => Err(io::Error::other(format!(
+                "{} cannot be resumed at byte {}: server answered a range request with Content-Range {}",
+                self.url, self.position, response.header("Content-Range").unwrap_or("(none)")
+            ))),
+            200 => self.restart(response),
+            status => Err(io::Error::other(format!(
+                "{} cannot be resumed at byte {}: server answered {} to a range request",
+                self.url, self.position, status
+            ))),
+        }
+    }
+
+    // The server sent the whole file rather than the requested range.
+    fn restart(&mut self, response: ureq::Response) -> io::Result<()> {
+        if self.validator.is_none() {
+            return Err(io::Error::other(format!(
+                "{} was sent again from the start after {} bytes of it were read, without an ETag or Last-Modified to show it is unchanged; it was not read again, as rows from the earlier version were already written",
+                self.url, self.position
+            )));
+        }
+        if range_validator(&response) != self.validator {
+            return Err(io::Error::other(format!(
+                "{} changed on the server after {} bytes of it were read; it was not read again, as rows from the earlier version were already written",
+                self.url, self.position
+            )));
+        }
+        warn!("{} was sent again from the start; skipping the {} bytes already read", self.url, self.position);
+        let mut body = response.into_reader();
+        let skipped = io::copy(&mut (&mut body).take(self.position), &mut io::sink())?;
+        if skipped < self.position {
+            return Err(io::Error::other(format!(
+                "{} ended after {} bytes when sent again, short of the {} already read",
+                self.url, skipped, self.position
+            )));
+        }
+        self.body = body;
+        Ok(())
+    }
+}
+
+// The first byte of a 206 response, from "Content-Range: bytes 100-199/200".
+#[cfg(feature = "http")]
+fn content_range_start(response: &ureq::Response) -> Option<u64> {
+    let range = response.header("Content-Range")?.trim().strip_prefix("bytes ")?;
+    range.split_once('-')?.0.trim().parse().ok()
+}
+
+#[cfg(feature = "http")]
+impl Read for HttpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut attempt = 1;
+        loop {
+            match self.body.read(buf) {
+                Ok(n) => {
+                    self.position += n as u64;
+                    return Ok(n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) if attempt < MAX_ATTEMPTS => {
+                    warn!("Reading {} failed at byte {} ({}); resuming", self.url, self.position, e);
+                    self.resume()?;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// --input-manifest: an explicit list of input files, read in the order given
+// instead of searching a directory. Each line is a path, optionally followed by
+// a tab and the number of records (non-empty lines, array elements or Parquet
+// rows) the file holds; for an archive, the total over its members. Blank
+// lines and lines starting with # are skipped. `is_input` says whether a
+// listed path is a file or archive the tool reads.
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub struct Entry {
+    pub path: PathBuf,
+    pub expected_records: Option<usize>,
+}
+
+pub fn read(manifest_path: &str, is_input: impl Fn(&Path) -> bool) -> Result<Vec<Entry>> {
+    info!("Reading input files from manifest: {}", manifest_path);
+    let content = fs::read_to_string(manifest_path)
+        .with_context(|| format!("Failed to read input manifest: {}", manifest_path))?;
+    let mut entries = Vec::new();
+    let mut seen = HashSet::new();
+    for (line_number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (path, expected_records) = match line.rsplit_once('\t') {
+            Some((path, count)) => {
+                let count = count.trim().parse::<usize>().map_err(|_| anyhow::anyhow!(
+                    "{} line {}: expected record count is not a number: {}", manifest_path, line_number + 1, count
+                ))?;
+                (path.trim(), Some(count))
+            }
+            None => (line, None),
+        };
+        let path = PathBuf::from(path);
+        if !path.is_file() {
+            return Err(anyhow::anyhow!("{} line {}: no such file: {}", manifest_path, line_number + 1, path.display()));
+        }
+        if !is_input(&path) {
+            return Err(anyhow::anyhow!("{} line {}: not an input file or archive: {}", manifest_path, line_number + 1, path.display()));
+        }
+        if !seen.insert(path.clone()) {
+            return Err(anyhow::anyhow!("{} line {}: listed more than once: {}", manifest_path, line_number + 1, path.display()));
+        }
+        entries.push(Entry { path, expected_records });
+    }
+    Ok(entries)
+}
+
+// Compares the records read from each input with the count the manifest
+// gives for it. Archive members are counted towards their archive. Inputs
+// that failed are already reported as errors and are not compared.
+pub fn check_record_counts(manifest_path: &str, entries: &[Entry], records_read: &[(PathBuf, usize)], failed: &[PathBuf]) -> Result<()> {
+    let failed: HashSet<&Path> = failed.iter().map(PathBuf::as_path).collect();
+    let mut mismatches = Vec::new();
+    let mut checked = 0;
+    for entry in entries {
+        let Some(expected) = entry.expected_records else { continue };
+        if failed.contains(entry.path.as_path()) {
+            continue;
+        }
+        let member_prefix = format!("{}!", entry.path.display());
+        let read: usize = records_read.iter()
+            .filter(|(path, _)| path == &entry.path || path.to_string_lossy().starts_with(&member_prefix))
+            .map(|(_, count)| count)
+            .sum();
+        checked += 1;
+        if read != expected {
+            mismatches.push((&entry.path, expected, read));
+        }
+    }
+    if checked == 0 {
+        return Ok(());
+    }
+    if mismatches.is_empty() {
+        info!("Record counts match the input manifest ({} files checked).", checked);
+        return Ok(());
+    }
+    warn!("Input files whose record count differs from the input manifest: {}", mismatches.len());
+    for (path, expected, read) in mismatches.iter().take(10) {
+        warn!("  - {}: {} records listed, {} read", path.display(), expected, read);
+    }
+    if mismatches.len() > 10 {
+        warn!("  ... (and {} more)", mismatches.len() - 10);
+    }
+    Err(anyhow::anyhow!(
+        "{} of {} input files did not hold the number of records listed in {}", mismatches.len(), checked, manifest_path
+    ))
+}
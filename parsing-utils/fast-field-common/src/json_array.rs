@@ -0,0 +1,232 @@
+use std::io::{self, BufRead};
+
+// Splits a top-level JSON array into the raw text of its elements as the
+// input streams past, so a multi-gigabyte array is never held in memory
+// whole. Elements are only delimited here (strings, escapes and nesting are
+// tracked); parsing them is left to the caller, as for JSONL lines.
+// Crossref's torrent chunks hold the array as the "items" key of an object;
+// `items` steps into that first, and anything after the array is ignored.
+pub struct Elements<R> {
+    reader: R,
+    state: State,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    ItemsObject,
+    Start,
+    FirstElement,
+    Element,
+    AfterElement,
+    Done,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+impl<R: BufRead> Elements<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, state: State::Start }
+    }
+
+    pub fn items(reader: R) -> Self {
+        Self { reader, state: State::ItemsObject }
+    }
+
+    // The next byte that is not JSON whitespace, left unconsumed.
+    fn peek_non_whitespace(&mut self) -> io::Result<Option<u8>> {
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+                Some(i) => {
+                    let byte = buf[i];
+                    self.reader.consume(i);
+                    return Ok(Some(byte));
+                }
+                None => {
+                    let len = buf.len();
+                    self.reader.consume(len);
+                }
+            }
+        }
+    }
+
+    fn read_element(&mut self) -> io::Result<String> {
+        let mut element = Vec::new();
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                return Err(invalid("JSON array ends in the middle of an element"));
+            }
+            let mut end = None;
+            for (i, &b) in buf.iter().enumerate() {
+                if in_string {
+                    if escaped {
+                        escaped = false;
+                    } else if b == b'\\' {
+                        escaped = true;
+                    } else if b == b'"' {
+                        in_string = false;
+                        if depth == 0 {
+                            end = Some(i + 1);
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                match b {
+                    b'"' => in_string = true,
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' if depth > 0 => {
+                        depth -= 1;
+                        if depth == 0 {
+                            end = Some(i + 1);
+                            break;
+                        }
+                    }
+                    // A number, true, false or null ends at the next separator.
+                    b',' | b']' | b' ' | b'\t' | b'\n' | b'\r' if depth == 0 => {
+                        end = Some(i);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            match end {
+                Some(end) => {
+                    element.extend_from_slice(&buf[..end]);
+                    self.reader.consume(end);
+                    return String::from_utf8(element).map_err(|_| invalid("JSON array element is not valid UTF-8"));
+                }
+                None => {
+                    let len = buf.len();
+                    element.extend_from_slice(buf);
+                    self.reader.consume(len);
+                }
+            }
+        }
+    }
+
+    fn advance(&mut self) -> io::Result<Option<String>> {
+        loop {
+            let next = self.peek_non_whitespace()?;
+            match (self.state, next) {
+                (State::ItemsObject, Some(b'{')) => {
+                    self.reader.consume(1);
+                    if self.peek_non_whitespace()? != Some(b'"') || self.read_element()? != "\"items\"" {
+                        return Err(invalid("expected \"items\" as the first key of the object"));
+                    }
+                    if self.peek_non_whitespace()? != Some(b':') {
+                        return Err(invalid("expected ':' after \"items\""));
+                    }
+                    self.reader.consume(1);
+                    self.state = State::Start;
+                }
+                (State::ItemsObject, _) => return Err(invalid("input does not start with a JSON object")),
+                (State::Start, Some(b'[')) => {
+                    self.reader.consume(1);
+                    self.state = State::FirstElement;
+                }
+                (State::Start, _) => return Err(invalid("input does not start with a JSON array")),
+                (State::FirstElement | State::AfterElement, Some(b']')) => {
+                    self.reader.consume(1);
+                    self.state = State::Done;
+                    return Ok(None);
+                }
+                (State::AfterElement, Some(b',')) => {
+                    self.reader.consume(1);
+                    self.state = State::Element;
+                }
+                (State::AfterElement, _) => return Err(invalid("expected ',' or ']' after a JSON array element")),
+                (State::Element, Some(b']')) => return Err(invalid("JSON array has a trailing ','")),
+                (State::FirstElement | State::Element, Some(_)) => {
+                    let element = self.read_element()?;
+                    self.state = State::AfterElement;
+                    return Ok(Some(element));
+                }
+                (State::FirstElement | State::Element, None) => return Err(invalid("JSON array is not closed")),
+                (State::Done, _) => return Ok(None),
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Elements<R> {
+    type Item = io::Result<String>;
+
+    // A malformed array cannot be resynchronised, so the first error ends it.
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.advance() {
+            Ok(element) => element.map(Ok),
+            Err(e) => {
+                self.state = State::Done;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Elements;
+    use std::io::{BufRead, BufReader};
+
+    fn split<R: BufRead>(elements: Elements<R>) -> Vec<String> {
+        elements.collect::<std::io::Result<_>>().unwrap()
+    }
+
+    // A one-byte buffer makes every element span many reads.
+    fn split_bytewise(input: &str) -> Vec<String> {
+        split(Elements::new(BufReader::with_capacity(1, input.as_bytes())))
+    }
+
+    #[test]
+    fn brackets_and_braces_inside_strings_do_not_end_elements() {
+        let input = r#"[{"title": "a ] b } c"}, "]", "}", {"x": "[{"}]"#;
+        let expected = [r#"{"title": "a ] b } c"}"#, r#""]""#, r#""}""#, r#"{"x": "[{"}"#];
+        assert_eq!(split(Elements::new(input.as_bytes())), expected);
+        assert_eq!(split_bytewise(input), expected);
+    }
+
+    #[test]
+    fn escaped_quotes_stay_inside_strings() {
+        let input = r#"[{"title": "say \"]\" twice"}, "\\", {"path": "C:\\\"x\""}]"#;
+        let expected = [r#"{"title": "say \"]\" twice"}"#, r#""\\""#, r#"{"path": "C:\\\"x\""}"#];
+        assert_eq!(split(Elements::new(input.as_bytes())), expected);
+        assert_eq!(split_bytewise(input), expected);
+    }
+
+    #[test]
+    fn nested_arrays_are_one_element() {
+        let input = "[ [1, [2, 3]], {\"a\": [[], [{}]]} ,[] , 4, null ]";
+        let expected = ["[1, [2, 3]]", "{\"a\": [[], [{}]]}", "[]", "4", "null"];
+        assert_eq!(split(Elements::new(input.as_bytes())), expected);
+        assert_eq!(split_bytewise(input), expected);
+    }
+
+    #[test]
+    fn empty_array_has_no_elements() {
+        assert!(split_bytewise(" [ \n ] ").is_empty());
+    }
+
+    #[test]
+    fn malformed_arrays_end_with_an_error() {
+        for input in ["[1, 2,]", "[{\"a\": 1}", "[1 2]", "{\"a\": 1}", "[\"unterminated]"] {
+            let results: Vec<_> = Elements::new(input.as_bytes()).collect();
+            assert!(results.last().is_some_and(|r| r.is_err()), "{} should fail", input);
+        }
+    }
+
+    #[test]
+    fn torrent_items_are_read_from_the_object() {
+        let input = r#"{"items": [{"DOI": "10.1/a"}, {"DOI": "10.1/]"}], "next": "x"}"#;
+        assert_eq!(split(Elements::items(input.as_bytes())), [r#"{"DOI": "10.1/a"}"#, r#"{"DOI": "10.1/]"}"#]);
+    }
+}
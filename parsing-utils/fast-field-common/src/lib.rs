@@ -0,0 +1,40 @@
+// The input, output and run-checking code shared by the crossref and openalex
+// fast-field parsers: remote and HTTP inputs, zip and Parquet input, JSON
+// arrays, validation rules, snapshot locks, provenance, output comparison and
+// disk space checks. The parts that depend on large crates sit behind the
+// same `cloud`, `http`, `parquet` and `zip` features as in the parsers.
+
+pub mod canonical_json;
+pub mod disk_space;
+pub mod error_sidecar;
+pub mod http_input;
+pub mod input_manifest;
+pub mod json_array;
+pub mod output_compare;
+#[cfg(feature = "parquet")]
+pub mod parquet_input;
+#[cfg(not(feature = "parquet"))]
+#[path = "unavailable/parquet_input.rs"]
+pub mod parquet_input;
+pub mod provenance;
+pub mod remote_input;
+#[cfg(feature = "cloud")]
+pub mod remote_output;
+#[cfg(not(feature = "cloud"))]
+#[path = "unavailable/remote_output.rs"]
+pub mod remote_output;
+pub mod snapshot_format;
+pub mod snapshot_lock;
+pub mod throttle;
+pub mod validation;
+#[cfg(feature = "zip")]
+pub mod zip_input;
+#[cfg(not(feature = "zip"))]
+#[path = "unavailable/zip_input.rs"]
+pub mod zip_input;
+
+// The error for an input or output that needs a cargo feature the binary was
+// built without.
+pub fn missing_feature(what: &str, feature: &str) -> anyhow::Error {
+    anyhow::anyhow!("{} needs the `{}` feature; rebuild with `cargo build --release --features {}`", what, feature, feature)
+}
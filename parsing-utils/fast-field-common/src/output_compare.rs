@@ -0,0 +1,149 @@
+// Compares this run's CSV output with a previous run's, to check that a change
+// to the extractor leaves its results alone. Rows are compared as multisets,
+// since parallel extraction writes them in no fixed order: each row is hashed,
+// counted up for the previous output and down for this one, and a second pass
+// writes out the rows whose counts did not cancel.
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+const LOGGED_DIFFERENCES: usize = 5;
+
+pub type Decompress = dyn Fn(File) -> io::Result<Box<dyn Read>>;
+
+// A single output file, or the CSV files of an --organize directory,
+// including those under its year=YYYY directories.
+fn output_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    collect_csv_files(path, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn collect_csv_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read output to compare: {}", dir.display()))?;
+    for entry in entries {
+        let file = entry?.path();
+        let name = file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if file.is_dir() {
+            collect_csv_files(&file, files)?;
+        } else if file.is_file() && [".csv", ".csv.gz", ".csv.zst"].iter().any(|ext| name.ends_with(ext)) {
+            files.push(file);
+        }
+    }
+    Ok(())
+}
+
+fn display_row(row: &csv::ByteRecord) -> String {
+    row.iter().map(String::from_utf8_lossy).collect::<Vec<_>>().join(",")
+}
+
+fn row_hash(row: &csv::ByteRecord) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for field in row {
+        field.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+// Calls `visit` with every data row of the output, checking that each file
+// has the same header.
+fn for_each_row(output: &Path, csv: &csv::ReaderBuilder, decompress: &Decompress, header: &mut Option<csv::ByteRecord>, mut visit: impl FnMut(&csv::ByteRecord) -> Result<()>) -> Result<u64> {
+    let mut rows = 0;
+    for file in output_files(output)? {
+        let reader = File::open(&file)
+            .map_err(anyhow::Error::from)
+            .and_then(|f| decompress(f).map_err(anyhow::Error::from))
+            .with_context(|| format!("Failed to open output to compare: {}", file.display()))?;
+        let mut reader = csv.from_reader(reader);
+        let file_header = reader.byte_headers()?.clone();
+        match header {
+            Some(expected) if *expected != file_header => {
+                return Err(anyhow::anyhow!(
+                    "{} has columns {}, expected {}; outputs with different columns cannot be compared row by row",
+                    file.display(), display_row(&file_header), display_row(expected)
+                ));
+            }
+            Some(_) => {}
+            None => *header = Some(file_header),
+        }
+        let mut row = csv::ByteRecord::new();
+        while reader.read_byte_record(&mut row)
+            .with_context(|| format!("Failed to read output to compare: {}", file.display()))?
+        {
+            visit(&row)?;
+            rows += 1;
+        }
+    }
+    Ok(rows)
+}
+
+// `csv` reads the dialect the outputs were written in, with a header row;
+// `decompress` opens .csv.gz and .csv.zst files.
+pub fn compare(previous: &Path, current: &Path, report_path: &Path, csv: &csv::ReaderBuilder, decompress: &Decompress) -> Result<()> {
+    info!("Comparing output {} with previous output {}", current.display(), previous.display());
+    if !previous.exists() {
+        return Err(anyhow::anyhow!("Previous output to compare with not found: {}", previous.display()));
+    }
+
+    let mut header = None;
+    let mut counts: HashMap<u64, i64> = HashMap::new();
+    let previous_rows = for_each_row(previous, csv, decompress, &mut header, |row| {
+        *counts.entry(row_hash(row)).or_default() += 1;
+        Ok(())
+    })?;
+    let current_rows = for_each_row(current, csv, decompress, &mut header, |row| {
+        *counts.entry(row_hash(row)).or_default() -= 1;
+        Ok(())
+    })?;
+    counts.retain(|_, count| *count != 0);
+    info!("Compared {} previous rows with {} rows from this run", previous_rows, current_rows);
+
+    if counts.is_empty() {
+        info!("Output matches the previous output row for row.");
+        return Ok(());
+    }
+
+    let mut report = csv::Writer::from_path(report_path)
+        .with_context(|| format!("Failed to create comparison report: {}", report_path.display()))?;
+    let mut report_header = csv::ByteRecord::from(vec!["change"]);
+    report_header.extend(header.iter().flatten());
+    report.write_byte_record(&report_header)?;
+
+    let mut differences = [0u64; 2];
+    for (index, (change, output, sign)) in [("removed", previous, 1), ("added", current, -1)].into_iter().enumerate() {
+        for_each_row(output, csv, decompress, &mut header, |row| {
+            let Some(count) = counts.get_mut(&row_hash(row)) else {
+                return Ok(());
+            };
+            if *count * sign <= 0 {
+                return Ok(());
+            }
+            *count -= sign;
+            differences[index] += 1;
+            if differences[index] as usize <= LOGGED_DIFFERENCES {
+                warn!("Row {}: {}", change, display_row(row));
+            }
+            let mut line = csv::ByteRecord::from(vec![change]);
+            line.extend(row);
+            report.write_byte_record(&line)?;
+            Ok(())
+        })?;
+    }
+    report.flush()?;
+
+    Err(anyhow::anyhow!(
+        "Output differs from the previous output {}: {} rows removed and {} rows added. The rows are listed in {}",
+        previous.display(), differences[0], differences[1], report_path.display()
+    ))
+}
@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::record::reader::RowIter;
+use serde_json::Value;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+// Rows of a Parquet file as JSON documents, one per row: groups become
+// objects and lists become arrays, so field paths address nested columns
+// the same way they address the JSONL records the file was converted from.
+// A Parquet schema has every column any record had, so keys a record did
+// not have come back as nulls; those are dropped again.
+pub struct JsonLines {
+    rows: RowIter<'static>,
+    num_rows: usize,
+    failed: bool,
+}
+
+impl JsonLines {
+    pub fn new(path: &Path, data: Vec<u8>) -> Result<Self> {
+        let reader = SerializedFileReader::new(Bytes::from(data))
+            .with_context(|| format!("Failed to read Parquet metadata: {}", path.display()))?;
+        let num_rows = reader.metadata().file_metadata().num_rows().max(0) as usize;
+        Ok(Self { rows: RowIter::from_file_into(Box::new(reader)), num_rows, failed: false })
+    }
+
+    // A local file is read in place: only its footer and the pages of the
+    // rows actually taken are read.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+        let reader = SerializedFileReader::new(file)
+            .with_context(|| format!("Failed to read Parquet metadata: {}", path.display()))?;
+        let num_rows = reader.metadata().file_metadata().num_rows().max(0) as usize;
+        Ok(Self { rows: RowIter::from_file_into(Box::new(reader)), num_rows, failed: false })
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+}
+
+impl Iterator for JsonLines {
+    type Item = io::Result<String>;
+
+    // A row that fails to decode means a corrupt page, and the rows after it
+    // cannot be located either, so the first error ends the file.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.rows.next()? {
+            Ok(row) => {
+                let mut record = row.to_json_value();
+                drop_nulls(&mut record);
+                Some(Ok(record.to_string()))
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(io::Error::new(io::ErrorKind::InvalidData, e)))
+            }
+        }
+    }
+}
+
+fn drop_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(drop_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(drop_nulls),
+        _ => {}
+    }
+}
@@ -0,0 +1,37 @@
+// A record of what produced an output, written next to it once the run has
+// finished so that a file passed on to another team keeps its lineage: the
+// tool and build, the command line, the input, the extraction settings and
+// the row counts. The explain tool prints it.
+
+use anyhow::{Context, Result};
+use log::info;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Inside a directory output (--organize, --max-rows-per-file), next to
+// _index.json; beside a single output file otherwise.
+pub const DIRECTORY_FILE: &str = "_provenance.json";
+pub const FILE_SUFFIX: &str = ".provenance.json";
+
+pub fn path_for(output: &Path) -> PathBuf {
+    if output.is_dir() {
+        output.join(DIRECTORY_FILE)
+    } else {
+        let mut path = output.as_os_str().to_owned();
+        path.push(FILE_SUFFIX);
+        PathBuf::from(path)
+    }
+}
+
+pub fn is_provenance(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == DIRECTORY_FILE || name.to_string_lossy().ends_with(FILE_SUFFIX))
+}
+
+pub fn write(output: &Path, provenance: &Value) -> Result<()> {
+    let path = path_for(output);
+    fs::write(&path, serde_json::to_string_pretty(provenance)? + "\n")
+        .with_context(|| format!("Failed to write provenance {}", path.display()))?;
+    info!("Provenance written to: {}", path.display());
+    Ok(())
+}
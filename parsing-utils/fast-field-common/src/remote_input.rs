@@ -0,0 +1,209 @@
+#[cfg(feature = "cloud")]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(feature = "cloud")]
+use bytes::Bytes;
+#[cfg(feature = "cloud")]
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+#[cfg(feature = "cloud")]
+use object_store::aws::AmazonS3Builder;
+#[cfg(feature = "cloud")]
+use object_store::azure::MicrosoftAzureBuilder;
+#[cfg(feature = "cloud")]
+use object_store::gcp::GoogleCloudStorageBuilder;
+#[cfg(feature = "cloud")]
+use object_store::path::Path as ObjectPath;
+#[cfg(feature = "cloud")]
+use object_store::{ObjectMeta, ObjectStore};
+#[cfg(feature = "cloud")]
+use serde_json::json;
+use serde_json::{Map, Value};
+#[cfg(feature = "cloud")]
+use std::collections::BTreeMap;
+#[cfg(feature = "cloud")]
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "cloud")]
+use tokio::runtime::{Handle, Runtime};
+
+#[cfg(not(feature = "cloud"))]
+pub use self::unavailable::RemoteInput;
+
+pub const SCHEMES: [&str; 3] = ["s3://", "az://", "gs://"];
+
+pub fn is_remote_uri(input: &str) -> bool {
+    SCHEMES.iter().any(|scheme| input.starts_with(scheme))
+}
+
+// Inputs that are not local files: the objects listed under a bucket
+// prefix, or URLs fetched over HTTP. Each input is identified by its URI.
+pub trait RemoteSource: Send + Sync {
+    fn files(&self) -> Vec<PathBuf>;
+    // Snapshot lock entries for every input.
+    fn lock_inputs(&self) -> Map<String, Value>;
+    fn open(&self, uri: &Path) -> Result<Box<dyn Read>>;
+}
+
+// Splits a URI into its scheme, bucket (or Azure container) and the key or
+// prefix within it.
+#[cfg(feature = "cloud")]
+pub fn split_uri(uri: &str) -> Result<(&'static str, &str, &str)> {
+    let scheme = SCHEMES.iter().find(|scheme| uri.starts_with(*scheme))
+        .with_context(|| format!("Unsupported remote location: {}", uri))?;
+    let (bucket, key) = uri[scheme.len()..].split_once('/').unwrap_or((&uri[scheme.len()..], ""));
+    Ok((scheme, bucket, key))
+}
+
+// Built without the `cloud` feature: remote locations are refused.
+#[cfg(not(feature = "cloud"))]
+mod unavailable {
+    use super::RemoteSource;
+    use anyhow::Result;
+    use serde_json::{Map, Value};
+    use std::io::Read;
+    use std::path::{Path, PathBuf};
+
+    pub enum RemoteInput {}
+
+    impl RemoteInput {
+        pub fn list(uri: &str, _no_sign_request: bool, _is_input: impl Fn(&str) -> bool) -> Result<Self> {
+            Err(crate::missing_feature(&format!("Reading {}", uri), "cloud"))
+        }
+    }
+
+    impl RemoteSource for RemoteInput {
+        fn files(&self) -> Vec<PathBuf> {
+            match *self {}
+        }
+
+        fn lock_inputs(&self) -> Map<String, Value> {
+            match *self {}
+        }
+
+        fn open(&self, _uri: &Path) -> Result<Box<dyn Read>> {
+            match *self {}
+        }
+    }
+}
+
+// Builds the store for a bucket (or Azure container). Each provider is
+// configured from its standard environment variables: AWS_* for S3,
+// AZURE_STORAGE_* for Azure Blob Storage and GOOGLE_* for Cloud Storage.
+#[cfg(feature = "cloud")]
+pub fn build_store(scheme: &str, bucket: &str, no_sign_request: bool) -> object_store::Result<Box<dyn ObjectStore>> {
+    Ok(match scheme {
+        "az://" => Box::new(MicrosoftAzureBuilder::from_env()
+            .with_container_name(bucket)
+            .with_skip_signature(no_sign_request)
+            .build()?),
+        "gs://" => Box::new(GoogleCloudStorageBuilder::from_env()
+            .with_bucket_name(bucket)
+            .with_skip_signature(no_sign_request)
+            .build()?),
+        _ => Box::new(AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .with_skip_signature(no_sign_request)
+            .build()?),
+    })
+}
+
+// The objects under an s3://, az:// or gs://bucket/prefix URI, listed once
+// up front. With `no_sign_request` requests are anonymous, as public
+// buckets such as OpenAlex's require.
+#[cfg(feature = "cloud")]
+pub struct RemoteInput {
+    runtime: Runtime,
+    store: Box<dyn ObjectStore>,
+    prefix: String,
+    objects: BTreeMap<PathBuf, ObjectMeta>,
+}
+
+#[cfg(feature = "cloud")]
+impl RemoteInput {
+    pub fn list(uri: &str, no_sign_request: bool, is_input: impl Fn(&str) -> bool) -> Result<Self> {
+        let (scheme, bucket, prefix) = split_uri(uri)?;
+        let store = build_store(scheme, bucket, no_sign_request)
+            .with_context(|| format!("Failed to configure access to {}", uri))?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .context("Failed to start the object store client runtime")?;
+
+        let prefix_path = ObjectPath::from(prefix.trim_end_matches('/'));
+        let mut metas: Vec<ObjectMeta> = runtime
+            .block_on(store.list(Some(&prefix_path)).try_collect())
+            .with_context(|| format!("Failed to list {}", uri))?;
+        // A URI naming a single object lists nothing beneath it.
+        if metas.is_empty() && !prefix.is_empty() {
+            if let Ok(meta) = runtime.block_on(store.head(&prefix_path)) {
+                metas.push(meta);
+            }
+        }
+
+        let objects = metas.into_iter()
+            .filter(|meta| is_input(meta.location.as_ref()))
+            .map(|meta| (PathBuf::from(format!("{}{}/{}", scheme, bucket, meta.location)), meta))
+            .collect();
+        Ok(Self { runtime, store, prefix: prefix_path.to_string(), objects })
+    }
+}
+
+#[cfg(feature = "cloud")]
+impl RemoteSource for RemoteInput {
+    fn files(&self) -> Vec<PathBuf> {
+        self.objects.keys().cloned().collect()
+    }
+
+    // Lock entries keyed by path relative to the listed prefix. The ETag
+    // changes whenever an object is rewritten, even at the same size.
+    fn lock_inputs(&self) -> Map<String, Value> {
+        self.objects.values()
+            .map(|meta| {
+                let key = meta.location.as_ref();
+                let relative = key.strip_prefix(&self.prefix).map_or(key, |r| r.trim_start_matches('/'));
+                let relative = if relative.is_empty() { key } else { relative };
+                (relative.to_string(), json!({
+                    "size": meta.size,
+                    "modified": meta.last_modified.timestamp(),
+                    "e_tag": meta.e_tag,
+                }))
+            })
+            .collect()
+    }
+
+    fn open(&self, uri: &Path) -> Result<Box<dyn Read>> {
+        let meta = self.objects.get(uri)
+            .with_context(|| format!("{} was not in the input listing", uri.display()))?;
+        let result = self.runtime
+            .block_on(self.store.get(&meta.location))
+            .with_context(|| format!("Failed to open {}", uri.display()))?;
+        Ok(Box::new(RemoteReader { handle: self.runtime.handle().clone(), stream: result.into_stream(), chunk: Bytes::new() }))
+    }
+}
+
+// Blocking reader over an object's byte stream, so the object is
+// decompressed and parsed while it downloads. Only called from the rayon
+// workers, which run outside the runtime.
+#[cfg(feature = "cloud")]
+struct RemoteReader {
+    handle: Handle,
+    stream: BoxStream<'static, object_store::Result<Bytes>>,
+    chunk: Bytes,
+}
+
+#[cfg(feature = "cloud")]
+impl Read for RemoteReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.chunk.is_empty() {
+            match self.handle.block_on(self.stream.next()) {
+                Some(Ok(chunk)) => self.chunk = chunk,
+                Some(Err(e)) => return Err(io::Error::other(e)),
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len());
+        buf[..n].copy_from_slice(&self.chunk.split_to(n));
+        Ok(n)
+    }
+}
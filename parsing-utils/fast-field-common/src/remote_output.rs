@@ -0,0 +1,149 @@
+// An s3://, az:// or gs:// --output. The output is written under a local
+// staging directory as usual, and each file is uploaded to the same place
+// relative to the URI once it is finished, then deleted. With
+// --max-rows-per-file, parts go up as each one fills, so the local disk only
+// holds the part being written rather than the whole result set.
+
+use crate::remote_input;
+use anyhow::{Context, Result};
+use log::info;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, PutPayload, WriteMultipart};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tokio::runtime::Runtime;
+
+// Files larger than one part are sent as a multipart upload, with up to
+// UPLOAD_CONCURRENCY parts in flight; smaller files in a single request.
+const PART_BYTES: usize = 16 * 1024 * 1024;
+const UPLOAD_CONCURRENCY: usize = 8;
+
+pub struct RemoteOutput {
+    runtime: Runtime,
+    store: Box<dyn ObjectStore>,
+    uri: String,
+    bucket_uri: String,
+    // The key the staging directory stands for: the URI's key without
+    // its last segment.
+    base: String,
+    staging: PathBuf,
+    local_output: PathBuf,
+}
+
+impl RemoteOutput {
+    pub fn connect(uri: &str, staging_dir: &Path) -> Result<Self> {
+        let (scheme, bucket, key) = remote_input::split_uri(uri)?;
+        let key = key.trim_end_matches('/');
+        if key.is_empty() {
+            return Err(anyhow::anyhow!("Remote output {} names a bucket but no object or prefix to write to", uri));
+        }
+        let store = remote_input::build_store(scheme, bucket, false)
+            .with_context(|| format!("Failed to configure access to {}", uri))?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .context("Failed to start the object store client runtime")?;
+
+        let staging = staging_dir.join(format!("remote-output-{}", std::process::id()));
+        fs::create_dir_all(&staging)
+            .with_context(|| format!("Failed to create staging directory: {}", staging.display()))?;
+        info!("Output for {} will be staged in: {}", uri, staging.display());
+        let (base, name) = key.rsplit_once('/').unwrap_or(("", key));
+        Ok(Self {
+            runtime,
+            store,
+            uri: uri.to_string(),
+            bucket_uri: format!("{}{}", scheme, bucket),
+            base: base.to_string(),
+            local_output: staging.join(name),
+            staging,
+        })
+    }
+
+    // Where the output is written before it is uploaded.
+    pub fn local_output(&self) -> &Path {
+        &self.local_output
+    }
+
+    fn object_path(&self, local: &Path) -> Result<ObjectPath> {
+        let relative = local.strip_prefix(&self.staging)
+            .with_context(|| format!("{} is not in the staging directory {}", local.display(), self.staging.display()))?;
+        let relative = relative.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "/");
+        Ok(ObjectPath::from(match self.base.as_str() {
+            "" => relative,
+            base => format!("{}/{}", base, relative),
+        }))
+    }
+
+    // Uploads a finished file and deletes the local copy.
+    pub fn upload(&self, local: &Path) -> Result<()> {
+        let location = self.object_path(local)?;
+        let destination = format!("{}/{}", self.bucket_uri, location);
+        let mut file = File::open(local)
+            .with_context(|| format!("Failed to open {} for upload", local.display()))?;
+        let size = file.metadata()?.len();
+
+        self.runtime.block_on(async {
+            if size <= PART_BYTES as u64 {
+                let mut bytes = Vec::with_capacity(size as usize);
+                file.read_to_end(&mut bytes)?;
+                self.store.put(&location, PutPayload::from(bytes)).await?;
+                return Ok(());
+            }
+            let mut writer = WriteMultipart::new_with_chunk_size(self.store.put_multipart(&location).await?, PART_BYTES);
+            let mut buf = vec![0; PART_BYTES];
+            loop {
+                let read = match file.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(read) => read,
+                    Err(e) => {
+                        let _ = writer.abort().await;
+                        return Err(anyhow::Error::from(e));
+                    }
+                };
+                if let Err(e) = writer.wait_for_capacity(UPLOAD_CONCURRENCY).await {
+                    let _ = writer.abort().await;
+                    return Err(e.into());
+                }
+                writer.write(&buf[..read]);
+            }
+            writer.finish().await?;
+            Ok(())
+        }).with_context(|| format!("Failed to upload {} to {}", local.display(), destination))?;
+
+        fs::remove_file(local)
+            .with_context(|| format!("Failed to remove {} after uploading it", local.display()))?;
+        info!("Uploaded {} to {}", local.display(), destination);
+        Ok(())
+    }
+
+    // Uploads what is left in the staging directory once the run has
+    // finished: the output file, the last part, the provenance. If an
+    // upload fails, the files not yet uploaded are left in place.
+    pub fn finish(&self) -> Result<()> {
+        let mut files = Vec::new();
+        collect_files(&self.staging, &mut files)?;
+        files.sort();
+        for file in &files {
+            self.upload(file)
+                .with_context(|| format!("Files not yet uploaded are kept in {}", self.staging.display()))?;
+        }
+        fs::remove_dir_all(&self.staging)
+            .with_context(|| format!("Failed to remove staging directory: {}", self.staging.display()))?;
+        info!("Output uploaded to: {}", self.uri);
+        Ok(())
+    }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use serde_json::Value;
+
+// What a single JSON record looks like it was taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    CrossrefWork,
+    CrossrefApiResponse,
+    // The OpenAlex entity, named as in the snapshot's directory layout.
+    OpenAlex(&'static str),
+    Unknown,
+}
+
+// OpenAlex ids are https://openalex.org/ followed by a letter for the
+// entity type and a number.
+const OPENALEX_ID_PREFIX: &str = "https://openalex.org/";
+const OPENALEX_ENTITIES: [(char, &str); 8] = [
+    ('W', "works"),
+    ('A', "authors"),
+    ('S', "sources"),
+    ('I', "institutions"),
+    ('C', "concepts"),
+    ('P', "publishers"),
+    ('F', "funders"),
+    ('T', "topics"),
+];
+
+pub fn classify(record: &Value) -> Format {
+    let Value::Object(map) = record else {
+        return Format::Unknown;
+    };
+    if map.contains_key("message-type") && map.contains_key("message") {
+        return Format::CrossrefApiResponse;
+    }
+    if let Some(id) = map.get("id").and_then(Value::as_str).and_then(|id| id.strip_prefix(OPENALEX_ID_PREFIX)) {
+        let entity = OPENALEX_ENTITIES.iter()
+            .find(|(letter, _)| id.starts_with(*letter) && id[1..].bytes().all(|b| b.is_ascii_digit()))
+            .map_or("entities", |(_, name)| *name);
+        return Format::OpenAlex(entity);
+    }
+    if map.get("DOI").is_some_and(Value::is_string) && ["member", "prefix", "deposited"].iter().any(|key| map.contains_key(*key)) {
+        return Format::CrossrefWork;
+    }
+    Format::Unknown
+}
+
+// The most common recognised format among sampled records, if any.
+pub fn dominant(formats: &[Format]) -> Option<Format> {
+    let mut counts: Vec<(Format, usize)> = Vec::new();
+    for format in formats.iter().filter(|f| **f != Format::Unknown) {
+        match counts.iter_mut().find(|(f, _)| f == format) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((*format, 1)),
+        }
+    }
+    counts.into_iter().max_by_key(|(_, count)| *count).map(|(format, _)| format)
+}
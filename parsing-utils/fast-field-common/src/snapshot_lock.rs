@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use log::{error, info};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// Everything that determines a run's output: the tool version and git commit,
+// the extraction settings, a SHA-256 of each schema/config file, and the size
+// and modification time of every input file (or with --pin-hash its SHA-256;
+// hashing a full snapshot on every run costs about as much as reading it).
+// `tool` is the binary's name, version, git commit and output schema version.
+pub fn describe_run(tool: Value, inputs: Map<String, Value>, config: Value, schema_files: &[(&str, &str)]) -> Result<Value> {
+    let mut schemas = Map::new();
+    for (role, path) in schema_files {
+        schemas.insert(role.to_string(), json!(sha256_file(Path::new(path))?));
+    }
+
+    Ok(json!({
+        "tool": tool,
+        "config": config,
+        "schema_files": schemas,
+        "inputs": inputs,
+    }))
+}
+
+// Size and modification time of each local input file, by path relative
+// to the input directory. With `hash`, a SHA-256 of the contents replaces
+// the modification time, which a copy does not keep and a same-size rewrite
+// within the same second does not change.
+pub fn local_inputs(input_dir: &str, files: &[PathBuf], hash: bool) -> Result<Map<String, Value>> {
+    if hash {
+        info!("Hashing {} input files for the snapshot lock", files.len());
+    }
+    let mut inputs = Map::new();
+    for file in files {
+        let metadata = fs::metadata(file).with_context(|| format!("Failed to read metadata for {}", file.display()))?;
+        let entry = if hash {
+            json!({ "size": metadata.len(), "sha256": sha256_file(file)? })
+        } else {
+            let modified = metadata.modified().ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs());
+            json!({ "size": metadata.len(), "modified": modified })
+        };
+        let relative = file.strip_prefix(input_dir).ok().filter(|r| !r.as_os_str().is_empty()).unwrap_or(file);
+        inputs.insert(relative.to_string_lossy().into_owned(), entry);
+    }
+    Ok(inputs)
+}
+
+// Whether an existing lock pinned its inputs by content hash, so that later
+// runs compare hashes whether or not they pass --pin-hash again.
+pub fn pins_hashes(lock_path: &str) -> Result<bool> {
+    let path = Path::new(lock_path);
+    if !path.exists() {
+        return Ok(false);
+    }
+    let pinned: Value = serde_json::from_str(&fs::read_to_string(path)
+        .with_context(|| format!("Failed to read snapshot lock {}", lock_path))?)
+        .with_context(|| format!("Failed to parse snapshot lock {}", lock_path))?;
+    Ok(pinned["inputs"].as_object().is_some_and(|inputs| inputs.values().any(|entry| entry.get("sha256").is_some())))
+}
+
+// Writes the lock if it does not exist yet, otherwise refuses to continue
+// unless the current run matches it exactly.
+pub fn pin_or_verify(lock_path: &str, current: &Value) -> Result<()> {
+    let path = Path::new(lock_path);
+    if !path.exists() {
+        fs::write(path, serde_json::to_string_pretty(current)? + "\n")
+            .with_context(|| format!("Failed to write snapshot lock {}", lock_path))?;
+        info!("Pinned inputs, tool version and settings to {}", lock_path);
+        return Ok(());
+    }
+
+    let pinned: Value = serde_json::from_str(&fs::read_to_string(path)
+        .with_context(|| format!("Failed to read snapshot lock {}", lock_path))?)
+        .with_context(|| format!("Failed to parse snapshot lock {}", lock_path))?;
+    let mut differences = Vec::new();
+    diff("", &pinned, current, &mut differences);
+    if differences.is_empty() {
+        info!("Inputs, tool version and settings match snapshot lock {}", lock_path);
+        return Ok(());
+    }
+
+    for difference in differences.iter().take(20) {
+        error!("  - {}", difference);
+    }
+    if differences.len() > 20 {
+        error!("  ... (and {} more)", differences.len() - 20);
+    }
+    Err(anyhow::anyhow!(
+        "Run does not match snapshot lock {} ({} differences). Delete the lock to pin the current inputs instead.",
+        lock_path, differences.len()
+    ))
+}
+
+fn diff(path: &str, pinned: &Value, current: &Value, out: &mut Vec<String>) {
+    match (pinned, current) {
+        (Value::Object(p), Value::Object(c)) => {
+            for (key, p_value) in p {
+                let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                match c.get(key) {
+                    Some(c_value) => diff(&child, p_value, c_value, out),
+                    None => out.push(format!("{}: pinned but missing now", child)),
+                }
+            }
+            for key in c.keys().filter(|k| !p.contains_key(*k)) {
+                let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                out.push(format!("{}: not in the lock", child));
+            }
+        }
+        _ if pinned != current => out.push(format!("{}: pinned {}, now {}", path, pinned, current)),
+        _ => {}
+    }
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
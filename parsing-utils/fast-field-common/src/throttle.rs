@@ -0,0 +1,61 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Paces reads so that the combined byte rate of every reader sharing the
+// limiter stays at or under the configured rate. Each read reserves the
+// next slot on a shared schedule and sleeps until that slot begins.
+pub struct RateLimiter {
+    bytes_per_sec: f64,
+    next_free: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(megabytes_per_sec: f64) -> Self {
+        Self {
+            bytes_per_sec: megabytes_per_sec * 1_000_000.0,
+            next_free: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn acquire(&self, bytes: usize) {
+        let wait = {
+            let mut next_free = self.next_free.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let start = (*next_free).max(now);
+            *next_free = start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec);
+            start - now
+        };
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
+
+pub struct ThrottledReader<R> {
+    inner: R,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl<R: Read> ThrottledReader<R> {
+    pub fn new(inner: R, limiter: Option<Arc<RateLimiter>>) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+impl<R: Read> Read for ThrottledReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire(n);
+        }
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for ThrottledReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
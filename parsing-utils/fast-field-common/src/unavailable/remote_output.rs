@@ -0,0 +1,24 @@
+// Built without the `cloud` feature: remote output is refused.
+
+use anyhow::Result;
+use std::path::Path;
+
+pub enum RemoteOutput {}
+
+impl RemoteOutput {
+    pub fn connect(uri: &str, _staging_dir: &Path) -> Result<Self> {
+        Err(crate::missing_feature(&format!("Writing to {}", uri), "cloud"))
+    }
+
+    pub fn local_output(&self) -> &Path {
+        match *self {}
+    }
+
+    pub fn upload(&self, _local: &Path) -> Result<()> {
+        match *self {}
+    }
+
+    pub fn finish(&self) -> Result<()> {
+        match *self {}
+    }
+}
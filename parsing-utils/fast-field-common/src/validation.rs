@@ -0,0 +1,485 @@
+use anyhow::{Context, Result};
+use csv::Writer;
+use log::{info, warn};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+const MAX_EXAMPLES_PER_RULE: usize = 5;
+
+const CROSSREF_TYPES: &[&str] = &[
+    "book", "book-chapter", "book-part", "book-section", "book-series", "book-set", "book-track",
+    "component", "database", "dataset", "dissertation", "edited-book", "grant", "journal",
+    "journal-article", "journal-issue", "journal-volume", "monograph", "other", "peer-review",
+    "posted-content", "proceedings", "proceedings-article", "proceedings-series", "reference-book",
+    "reference-entry", "report", "report-component", "report-series", "standard",
+];
+
+const OPENALEX_TYPES: &[&str] = &[
+    "article", "book", "book-chapter", "dataset", "dissertation", "editorial", "erratum", "grant",
+    "letter", "libguides", "other", "paratext", "peer-review", "preprint", "reference-entry",
+    "report", "retraction", "review", "standard", "supplementary-materials",
+];
+
+const DATACITE_RESOURCE_TYPES_GENERAL: &[&str] = &[
+    "Audiovisual", "Award", "Book", "BookChapter", "Collection", "ComputationalNotebook",
+    "ConferencePaper", "ConferenceProceeding", "DataPaper", "Dataset", "Dissertation", "Event",
+    "Image", "Instrument", "InteractiveResource", "Journal", "JournalArticle", "Model",
+    "OutputManagementPlan", "PeerReview", "PhysicalObject", "Preprint", "Project", "Report",
+    "Service", "Software", "Sound", "Standard", "StudyRegistration", "Text", "Workflow", "Other",
+];
+
+const CREDIT_ROLES: &[&str] = &[
+    "Conceptualization", "Data curation", "Formal analysis", "Funding acquisition", "Investigation",
+    "Methodology", "Project administration", "Resources", "Software", "Supervision", "Validation",
+    "Visualization", "Writing – original draft", "Writing – review & editing",
+];
+
+const CREDIT_URI_PREFIX: &str = "credit.niso.org/contributor-roles/";
+
+fn bundled_vocabulary(name: &str) -> Option<&'static [&'static str]> {
+    match name {
+        "crossref-type" => Some(CROSSREF_TYPES),
+        "openalex-type" => Some(OPENALEX_TYPES),
+        "datacite-resource-type-general" => Some(DATACITE_RESOURCE_TYPES_GENERAL),
+        "credit-role" => Some(CREDIT_ROLES),
+        _ => None,
+    }
+}
+
+// Vocabulary terms are compared on lowercase alphanumerics so that "JournalArticle",
+// "journal-article" and "Writing – review & editing" / "writing-review-editing" line up.
+// CRediT role URIs are reduced to their slug first.
+fn vocabulary_key(term: &str) -> String {
+    let trimmed = term.trim();
+    let slug = match trimmed.find(CREDIT_URI_PREFIX) {
+        Some(pos) => &trimmed[pos + CREDIT_URI_PREFIX.len()..],
+        None => trimmed,
+    };
+    slug.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// Dates are compared as (year, month, day). Missing components in a rule bound are
+// filled so that partial bounds are inclusive ("max": "2020" accepts 2020-12-31).
+type PartialDate = (i32, u32, u32);
+
+#[derive(Debug)]
+enum RuleKind {
+    Regex(Regex),
+    Enumeration(HashSet<String>),
+    Url,
+    DateRange { min: Option<PartialDate>, max: Option<PartialDate> },
+    Vocabulary { terms: HashSet<String>, exact: bool },
+}
+
+#[derive(Debug)]
+struct ValidationRule {
+    name: String,
+    kind: RuleKind,
+}
+
+impl ValidationRule {
+    fn from_json(field: &str, spec: &Value) -> Result<Self> {
+        let rule_type = spec.get("type")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow::anyhow!("Rule for field '{}' is missing a string 'type'", field))?;
+
+        let kind = match rule_type {
+            "regex" => {
+                let pattern = spec.get("pattern")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| anyhow::anyhow!("Regex rule for field '{}' is missing 'pattern'", field))?;
+                RuleKind::Regex(Regex::new(pattern)
+                    .with_context(|| format!("Invalid regex for field '{}': {}", field, pattern))?)
+            }
+            "enum" => {
+                let values = spec.get("values")
+                    .and_then(Value::as_array)
+                    .ok_or_else(|| anyhow::anyhow!("Enum rule for field '{}' is missing a 'values' array", field))?;
+                RuleKind::Enumeration(values.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            }
+            "url" => RuleKind::Url,
+            "date_range" => {
+                let bound = |key: &str, fill_high: bool| -> Result<Option<PartialDate>> {
+                    match spec.get(key).and_then(Value::as_str) {
+                        Some(s) => parse_date_bound(s, fill_high)
+                            .map(Some)
+                            .ok_or_else(|| anyhow::anyhow!("Invalid '{}' date '{}' for field '{}'", key, s, field)),
+                        None => Ok(None),
+                    }
+                };
+                RuleKind::DateRange { min: bound("min", false)?, max: bound("max", true)? }
+            }
+            "vocabulary" => {
+                let vocabulary = spec.get("vocabulary")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| anyhow::anyhow!("Vocabulary rule for field '{}' is missing 'vocabulary'", field))?;
+                let terms = bundled_vocabulary(vocabulary)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown vocabulary '{}' for field '{}'", vocabulary, field))?;
+                let exact = spec.get("exact").and_then(Value::as_bool).unwrap_or(false);
+                let terms = if exact {
+                    terms.iter().map(|t| t.to_string()).collect()
+                } else {
+                    terms.iter().map(|t| vocabulary_key(t)).collect()
+                };
+                RuleKind::Vocabulary { terms, exact }
+            }
+            other => return Err(anyhow::anyhow!("Unknown rule type '{}' for field '{}'", other, field)),
+        };
+
+        let name = spec.get("name")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| default_rule_name(rule_type, spec));
+
+        Ok(Self { name, kind })
+    }
+
+    fn is_satisfied_by(&self, value: &str) -> bool {
+        match &self.kind {
+            RuleKind::Regex(re) => re.is_match(value),
+            RuleKind::Enumeration(values) => values.contains(value),
+            RuleKind::Url => is_valid_url(value),
+            RuleKind::DateRange { min, max } => match parse_value_date(value) {
+                Some(date) => min.is_none_or(|m| date >= m) && max.is_none_or(|m| date <= m),
+                None => false,
+            },
+            RuleKind::Vocabulary { terms, exact: true } => terms.contains(value),
+            RuleKind::Vocabulary { terms, exact: false } => terms.contains(&vocabulary_key(value)),
+        }
+    }
+}
+
+fn default_rule_name(rule_type: &str, spec: &Value) -> String {
+    match rule_type {
+        "regex" => format!("regex:{}", spec.get("pattern").and_then(Value::as_str).unwrap_or("")),
+        "date_range" => format!(
+            "date_range:{}..{}",
+            spec.get("min").and_then(Value::as_str).unwrap_or(""),
+            spec.get("max").and_then(Value::as_str).unwrap_or("")
+        ),
+        "vocabulary" => format!("vocabulary:{}", spec.get("vocabulary").and_then(Value::as_str).unwrap_or("")),
+        other => other.to_string(),
+    }
+}
+
+fn is_valid_url(value: &str) -> bool {
+    let Some((scheme, rest)) = value.split_once("://") else {
+        return false;
+    };
+    let scheme_ok = !scheme.is_empty()
+        && scheme.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    scheme_ok && !host.is_empty() && !value.chars().any(char::is_whitespace)
+}
+
+fn parse_date_components(s: &str) -> Option<(i32, Option<u32>, Option<u32>)> {
+    let date_part = s.split('T').next()?;
+    let mut parts = date_part.split('-');
+    let year = parts.next()?.parse::<i32>().ok()?;
+    let month = match parts.next() {
+        Some(m) => Some(m.parse::<u32>().ok().filter(|m| (1..=12).contains(m))?),
+        None => None,
+    };
+    let day = match parts.next() {
+        Some(d) => Some(d.parse::<u32>().ok().filter(|d| (1..=31).contains(d))?),
+        None => None,
+    };
+    Some((year, month, day))
+}
+
+fn parse_date_bound(s: &str, fill_high: bool) -> Option<PartialDate> {
+    let (year, month, day) = parse_date_components(s.trim())?;
+    let (default_month, default_day) = if fill_high { (12, 31) } else { (1, 1) };
+    Some((year, month.unwrap_or(default_month), day.unwrap_or(default_day)))
+}
+
+// Accepts ISO strings ("2019-05-08T10:14:38Z", "2019-05"), bare years, and serialized
+// date-parts arrays ("[[2019,5,8]]").
+fn parse_value_date(value: &str) -> Option<PartialDate> {
+    let trimmed = value.trim();
+    if trimmed.starts_with('[') {
+        let parsed: Value = serde_json::from_str(trimmed).ok()?;
+        let mut parts = parsed.as_array()?.as_slice();
+        if let Some(inner) = parts.first().and_then(Value::as_array) {
+            parts = inner.as_slice();
+        }
+        let year = parts.first()?.as_i64()? as i32;
+        let month = parts.get(1).and_then(Value::as_u64).unwrap_or(1) as u32;
+        let day = parts.get(2).and_then(Value::as_u64).unwrap_or(1) as u32;
+        return Some((year, month, day));
+    }
+    if let Ok(year) = trimmed.parse::<i32>() {
+        return Some((year, 1, 1));
+    }
+    let (year, month, day) = parse_date_components(trimmed)?;
+    Some((year, month.unwrap_or(1), day.unwrap_or(1)))
+}
+
+#[derive(Debug, Clone)]
+pub struct ViolationExample {
+    pub record_id: String,
+    pub subfield_path: String,
+    pub value: String,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ViolationTally {
+    pub count: usize,
+    pub examples: Vec<ViolationExample>,
+}
+
+impl ViolationTally {
+    pub fn record(&mut self, example: ViolationExample) {
+        self.count += 1;
+        if self.examples.len() < MAX_EXAMPLES_PER_RULE {
+            self.examples.push(example);
+        }
+    }
+
+    pub fn merge(&mut self, other: ViolationTally) {
+        self.count += other.count;
+        let room = MAX_EXAMPLES_PER_RULE.saturating_sub(self.examples.len());
+        self.examples.extend(other.examples.into_iter().take(room));
+    }
+}
+
+/// Keyed by (field_name, rule_name).
+pub type Violations = HashMap<(String, String), ViolationTally>;
+
+pub fn merge_violations(into: &mut Violations, from: Violations) {
+    for (key, tally) in from {
+        into.entry(key).or_default().merge(tally);
+    }
+}
+
+#[derive(Debug)]
+pub struct Validator {
+    rules: HashMap<String, Vec<ValidationRule>>,
+}
+
+impl Validator {
+    /// Loads a JSON object mapping field paths (as passed to --fields) to a list of rules.
+    /// Fields for which `is_known_field` is false are warned about but still validated.
+    pub fn from_file<P: AsRef<Path>>(path: P, is_known_field: impl Fn(&str) -> bool) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read validation rules file: {}", path.display()))?;
+        let parsed: Value = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse validation rules file as JSON: {}", path.display()))?;
+        let fields = parsed.as_object()
+            .ok_or_else(|| anyhow::anyhow!("Validation rules file must contain a JSON object keyed by field path"))?;
+
+        let mut rules = HashMap::new();
+        for (field, specs) in fields {
+            if !is_known_field(field) {
+                warn!("Validation rules reference field '{}' which is not in the known schema.", field);
+            }
+            let spec_list = match specs {
+                Value::Array(list) => list.iter().collect::<Vec<_>>(),
+                single => vec![single],
+            };
+            let parsed_rules = spec_list
+                .into_iter()
+                .map(|spec| ValidationRule::from_json(field, spec))
+                .collect::<Result<Vec<_>>>()?;
+            rules.insert(field.clone(), parsed_rules);
+        }
+
+        info!("Loaded validation rules for {} fields from {}", rules.len(), path.display());
+        Ok(Self { rules })
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = &String> {
+        self.rules.keys()
+    }
+
+    /// Checks an extracted value against the rules of its field. Empty values are not
+    /// validated; missing data is a completeness question, not an invalid value.
+    pub fn check(&self, record_id: &str, field_name: &str, subfield_path: &str, value: &str, violations: &mut Violations) {
+        if value.is_empty() {
+            return;
+        }
+        let Some(rules) = self.rules.get(field_name) else {
+            return;
+        };
+        for rule in rules {
+            if !rule.is_satisfied_by(value) {
+                violations
+                    .entry((field_name.to_string(), rule.name.clone()))
+                    .or_default()
+                    .record(ViolationExample {
+                        record_id: record_id.to_string(),
+                        subfield_path: subfield_path.to_string(),
+                        value: value.to_string(),
+                    });
+            }
+        }
+    }
+}
+
+// Each example names its record under `id_column`, the column that identifies
+// records in the main output.
+pub fn write_report<P: AsRef<Path>>(path: P, violations: &Violations, id_column: &str) -> Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory for violations report: {}", path.display()))?;
+    }
+    let mut writer = Writer::from_path(path)
+        .with_context(|| format!("Failed to create violations report: {}", path.display()))?;
+    writer.write_record(["field_name", "rule", "violation_count", "examples"])?;
+
+    let mut sorted: Vec<_> = violations.iter().collect();
+    sorted.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(b.0)));
+    for ((field_name, rule), tally) in sorted {
+        let examples: Vec<Value> = tally.examples
+            .iter()
+            .map(|e| serde_json::json!({ id_column: e.record_id, "subfield_path": e.subfield_path, "value": e.value }))
+            .collect();
+        writer.write_record([
+            field_name.as_str(),
+            rule.as_str(),
+            &tally.count.to_string(),
+            &Value::Array(examples).to_string(),
+        ])?;
+    }
+    writer.flush()
+        .with_context(|| format!("Failed to flush violations report: {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn rule(spec: Value) -> ValidationRule {
+        ValidationRule::from_json("field", &spec).unwrap()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("fast-field-common-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn partial_date_bounds_are_inclusive() {
+        assert_eq!(parse_date_bound("2020", false), Some((2020, 1, 1)));
+        assert_eq!(parse_date_bound("2020", true), Some((2020, 12, 31)));
+        assert_eq!(parse_date_bound("2020-02", true), Some((2020, 2, 31)));
+        assert_eq!(parse_date_bound(" 2020-02-29 ", false), Some((2020, 2, 29)));
+
+        let range = rule(json!({"type": "date_range", "min": "2000", "max": "2020-06"}));
+        for inside in ["2000", "2000-01-01", "2020-06-30", "2020-06", "[[2010]]"] {
+            assert!(range.is_satisfied_by(inside), "{} should be in range", inside);
+        }
+        for outside in ["1999-12-31", "2020-07-01", "2021", "[[1999,12,31]]"] {
+            assert!(!range.is_satisfied_by(outside), "{} should be out of range", outside);
+        }
+    }
+
+    #[test]
+    fn invalid_dates_are_rejected() {
+        for bound in ["2020-13", "2020-00", "2020-01-32", "2020-01-00", "20x0", "", "May 2020"] {
+            assert_eq!(parse_date_bound(bound, false), None, "bound {:?}", bound);
+        }
+        let spec = json!({"type": "date_range", "max": "2020-13"});
+        let error = ValidationRule::from_json("published", &spec).unwrap_err().to_string();
+        assert!(error.contains("Invalid 'max' date '2020-13'"), "{}", error);
+
+        let range = rule(json!({"type": "date_range", "min": "1900"}));
+        for value in ["not a date", "2020-13-01", "[]", "[[]]", "[[\"2020\"]]", "{}"] {
+            assert!(!range.is_satisfied_by(value), "{:?} should not parse as a date", value);
+        }
+    }
+
+    #[test]
+    fn value_dates_accept_iso_years_and_date_parts() {
+        assert_eq!(parse_value_date("2019-05-08T10:14:38Z"), Some((2019, 5, 8)));
+        assert_eq!(parse_value_date("2019-05"), Some((2019, 5, 1)));
+        assert_eq!(parse_value_date(" 2019 "), Some((2019, 1, 1)));
+        assert_eq!(parse_value_date("[[2019,5,8]]"), Some((2019, 5, 8)));
+        assert_eq!(parse_value_date("[2019, 5]"), Some((2019, 5, 1)));
+    }
+
+    #[test]
+    fn vocabulary_keys_ignore_case_whitespace_and_punctuation() {
+        assert_eq!(vocabulary_key("  Journal-Article\t"), "journalarticle");
+        assert_eq!(vocabulary_key("JournalArticle"), "journalarticle");
+        assert_eq!(vocabulary_key("Writing – review & editing"), vocabulary_key("writing-review-editing"));
+        assert_eq!(vocabulary_key("https://credit.niso.org/contributor-roles/data-curation/"), "datacuration");
+        assert_eq!(vocabulary_key("ÉTUDE"), "étude");
+
+        let loose = rule(json!({"type": "vocabulary", "vocabulary": "crossref-type"}));
+        assert!(loose.is_satisfied_by(" Journal Article "));
+        assert!(loose.is_satisfied_by("PROCEEDINGS-ARTICLE"));
+        assert!(!loose.is_satisfied_by("journal-articles"));
+
+        let exact = rule(json!({"type": "vocabulary", "vocabulary": "crossref-type", "exact": true}));
+        assert!(exact.is_satisfied_by("journal-article"));
+        assert!(!exact.is_satisfied_by("Journal-Article"));
+        assert!(!exact.is_satisfied_by(" journal-article"));
+
+        let credit = rule(json!({"type": "vocabulary", "vocabulary": "credit-role"}));
+        assert!(credit.is_satisfied_by("http://credit.niso.org/contributor-roles/writing-original-draft/"));
+        assert!(!credit.is_satisfied_by("Proofreading"));
+    }
+
+    #[test]
+    fn unknown_rule_types_and_vocabularies_are_errors() {
+        assert!(ValidationRule::from_json("f", &json!({"type": "vocabulary", "vocabulary": "nope"})).is_err());
+        assert!(ValidationRule::from_json("f", &json!({"type": "checksum"})).is_err());
+        assert!(ValidationRule::from_json("f", &json!({"type": "regex", "pattern": "("})).is_err());
+    }
+
+    #[test]
+    fn report_lists_rules_by_violation_count_with_capped_examples() {
+        let rules_path = temp_path("rules.json");
+        fs::write(&rules_path, json!({
+            "type": {"type": "vocabulary", "vocabulary": "crossref-type"},
+            "URL": [{"type": "url"}, {"type": "regex", "pattern": "^https://", "name": "https only"}],
+        }).to_string()).unwrap();
+        let validator = Validator::from_file(&rules_path, |field| field != "URL").unwrap();
+        fs::remove_file(&rules_path).unwrap();
+
+        let mut violations = Violations::new();
+        for i in 0..7 {
+            validator.check(&format!("10.1/{}", i), "type", "type", "bogus", &mut violations);
+        }
+        validator.check("10.1/a", "URL", "URL", "http://example.org/a", &mut violations);
+        validator.check("10.1/b", "URL", "URL", "not a url", &mut violations);
+        validator.check("10.1/c", "URL", "URL", "", &mut violations);
+        validator.check("10.1/d", "title", "title", "anything", &mut violations);
+
+        let mut more = Violations::new();
+        validator.check("10.1/e", "type", "type", "bogus", &mut more);
+        merge_violations(&mut violations, more);
+
+        let report_path = temp_path("violations.csv");
+        write_report(&report_path, &violations, "doi").unwrap();
+        let mut reader = csv::Reader::from_path(&report_path).unwrap();
+        assert_eq!(reader.headers().unwrap(), vec!["field_name", "rule", "violation_count", "examples"]);
+        let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        fs::remove_file(&report_path).unwrap();
+
+        let summary: Vec<(&str, &str, &str)> = rows.iter().map(|r| (&r[0], &r[1], &r[2])).collect();
+        assert_eq!(summary, [
+            ("type", "vocabulary:crossref-type", "8"),
+            ("URL", "https only", "2"),
+            ("URL", "url", "1"),
+        ]);
+
+        let examples: Vec<Value> = serde_json::from_str(&rows[0][3]).unwrap();
+        assert_eq!(examples.len(), MAX_EXAMPLES_PER_RULE);
+        assert_eq!(examples[0], json!({"doi": "10.1/0", "subfield_path": "type", "value": "bogus"}));
+        let examples: Vec<Value> = serde_json::from_str(&rows[2][3]).unwrap();
+        assert_eq!(examples, [json!({"doi": "10.1/b", "subfield_path": "URL", "value": "not a url"})]);
+    }
+}
@@ -0,0 +1,41 @@
+// Zip archives keep their index at the end, so unlike tar archives they are not
+// streamed: a local archive is read in place, seeking to each member, and a
+// remote one is downloaded into memory whole first.
+
+use crate::{remote_input, throttle};
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::Path;
+use std::sync::Arc;
+
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+pub type ZipArchive = zip::ZipArchive<Box<dyn ReadSeek>>;
+
+pub fn open(path: &Path, remote: Option<&dyn remote_input::RemoteSource>, read_limiter: Option<Arc<throttle::RateLimiter>>) -> Result<ZipArchive> {
+    let reader: Box<dyn ReadSeek> = match remote {
+        Some(remote) => {
+            let mut data = Vec::new();
+            throttle::ThrottledReader::new(remote.open(path)?, read_limiter).read_to_end(&mut data)
+                .with_context(|| format!("Failed to download zip archive: {}", path.display()))?;
+            Box::new(io::Cursor::new(data))
+        }
+        None => {
+            let file = File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+            Box::new(BufReader::new(throttle::ThrottledReader::new(file, read_limiter)))
+        }
+    };
+    zip::ZipArchive::new(reader).with_context(|| format!("Failed to read zip archive: {}", path.display()))
+}
+
+// Indices and names of the members to read, in archive order.
+pub fn input_members(archive: &ZipArchive, is_input_member: impl Fn(&str) -> bool) -> Vec<(usize, String)> {
+    (0..archive.len())
+        .filter_map(|index| archive.name_for_index(index).map(|name| (index, name)))
+        .filter(|(_, name)| !name.ends_with('/') && is_input_member(name))
+        .map(|(index, name)| (index, name.to_string()))
+        .collect()
+}
@@ -0,0 +1,418 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser};
+use csv::{ReaderBuilder, Writer};
+use lazy_static::lazy_static;
+use log::{info, warn, LevelFilter};
+use normalization::normalize_text;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use regex::Regex;
+use simple_logger::SimpleLogger;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use time::macros::format_description;
+
+#[derive(Parser)]
+#[command(name = "Crossref Funder Registry Parser")]
+#[command(about = "Extract funder DOIs, names, alternate names, and countries from the Crossref Funder Registry")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(short, long, help = "Funder Registry file (registry.rdf or a funders CSV)", required = true)]
+    input: String,
+
+    #[arg(short, long, default_value = "funder_names.csv", help = "Output CSV file")]
+    output: String,
+
+    #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
+    log_level: String,
+
+    #[arg(long, help = "Skip funders that have been replaced by another funder DOI")]
+    exclude_replaced: bool,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the output columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+lazy_static! {
+    static ref VERSION_INFO: String = format!(
+        "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+        VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+    );
+}
+
+lazy_static! {
+    static ref FUNDER_DOI_RE: Regex = Regex::new(r"(?i)(10\.13039/[^\s/]+)").unwrap();
+    static ref GEONAMES_ID_RE: Regex = Regex::new(r"geonames\.org/(\d+)").unwrap();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FunderDoi(String);
+
+#[derive(Debug, Clone)]
+struct FunderName {
+    name_type: &'static str,
+    name: String,
+    lang: String,
+}
+
+#[derive(Debug, Default)]
+struct Funder {
+    doi: Option<FunderDoi>,
+    names: Vec<FunderName>,
+    country: String,
+    geonames_id: String,
+    funding_body_type: String,
+    funding_body_sub_type: String,
+    replaced_by: Vec<FunderDoi>,
+}
+
+// Funder ids appear as http://dx.doi.org/10.13039/..., https://doi.org/... or bare DOIs;
+// reduce them to the bare form used in Crossref funder.DOI.
+fn funder_doi_from(text: &str) -> Option<FunderDoi> {
+    FUNDER_DOI_RE.captures(text).map(|c| FunderDoi(c[1].to_lowercase()))
+}
+
+fn attribute_value(element: &BytesStart, name: &[u8]) -> Option<String> {
+    element.attributes()
+        .filter_map(|a| a.ok())
+        .find(|a| a.key.local_name().as_ref() == name)
+        .and_then(|a| a.unescape_value().ok().map(|v| v.into_owned()))
+}
+
+fn attribute_value_qualified(element: &BytesStart, name: &[u8]) -> Option<String> {
+    element.attributes()
+        .filter_map(|a| a.ok())
+        .find(|a| a.key.as_ref() == name)
+        .and_then(|a| a.unescape_value().ok().map(|v| v.into_owned()))
+}
+
+// Streams the SKOS-XL registry.rdf: one skos:Concept per funder, with names under
+// skosxl:prefLabel / skosxl:altLabel and country, type and replacement links alongside.
+fn parse_registry_rdf(path: &Path) -> Result<Vec<Funder>> {
+    let mut reader = Reader::from_file(path)
+        .with_context(|| format!("Failed to open registry RDF: {}", path.display()))?;
+    reader.config_mut().trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut stack: Vec<String> = Vec::new();
+    let mut funders = Vec::new();
+    let mut current: Option<Funder> = None;
+    let mut label_lang = String::new();
+
+    loop {
+        let event = reader.read_event_into(&mut buf)
+            .with_context(|| format!("Malformed XML in {} at byte {}", path.display(), reader.buffer_position()))?;
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let is_empty = matches!(event, Event::Empty(_));
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                match name.as_str() {
+                    "Concept" if current.is_none() => {
+                        current = Some(Funder {
+                            doi: attribute_value(e, b"about").and_then(|a| funder_doi_from(&a)),
+                            ..Default::default()
+                        });
+                    }
+                    "literalForm" => {
+                        label_lang = attribute_value_qualified(e, b"xml:lang").unwrap_or_default();
+                    }
+                    "country" => {
+                        if let (Some(funder), Some(resource)) = (current.as_mut(), attribute_value(e, b"resource")) {
+                            if let Some(caps) = GEONAMES_ID_RE.captures(&resource) {
+                                funder.geonames_id = caps[1].to_string();
+                            }
+                        }
+                    }
+                    "isReplacedBy" => {
+                        if let (Some(funder), Some(doi)) = (
+                            current.as_mut(),
+                            attribute_value(e, b"resource").and_then(|r| funder_doi_from(&r)),
+                        ) {
+                            funder.replaced_by.push(doi);
+                        }
+                    }
+                    _ => {}
+                }
+                if !is_empty {
+                    stack.push(name);
+                }
+            }
+            Event::Text(t) => {
+                let Some(funder) = current.as_mut() else {
+                    buf.clear();
+                    continue;
+                };
+                let text = t.unescape().context("Invalid XML text")?;
+                let text = text.trim();
+                let leaf = stack.last().map(String::as_str).unwrap_or("");
+                match leaf {
+                    "literalForm" => {
+                        let name_type = if stack.iter().any(|n| n == "prefLabel") {
+                            Some("name")
+                        } else if stack.iter().any(|n| n == "altLabel") {
+                            Some("alt_name")
+                        } else {
+                            None
+                        };
+                        if let Some(name_type) = name_type {
+                            funder.names.push(FunderName { name_type, name: text.to_string(), lang: label_lang.clone() });
+                        }
+                    }
+                    "addressCountry" => funder.country = text.to_string(),
+                    "fundingBodyType" => funder.funding_body_type = text.to_string(),
+                    "fundingBodySubType" => funder.funding_body_sub_type = text.to_string(),
+                    _ => {}
+                }
+            }
+            Event::End(_) => {
+                let name = stack.pop().unwrap_or_default();
+                if name == "Concept" && !stack.iter().any(|n| n == "Concept") {
+                    if let Some(funder) = current.take() {
+                        funders.push(funder);
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(funders)
+}
+
+fn find_column(headers: &[String], candidates: &[&str]) -> Option<usize> {
+    headers.iter().position(|h| candidates.contains(&h.as_str()))
+}
+
+// Flat funder listings: one row per funder with an id/DOI column, a primary name and
+// an optional "|"- or ";"-separated list of other names.
+fn parse_registry_csv(path: &Path) -> Result<Vec<Funder>> {
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .with_context(|| format!("Failed to open funder CSV: {}", path.display()))?;
+    let headers: Vec<String> = reader.headers()?
+        .iter()
+        .map(|h| h.trim_start_matches('\u{feff}').trim().to_lowercase())
+        .collect();
+
+    let doi_col = find_column(&headers, &["doi", "id", "uri", "funder_doi"])
+        .ok_or_else(|| anyhow::anyhow!("No doi/id/uri column in {}", path.display()))?;
+    let name_col = find_column(&headers, &["name", "primary_name", "primary_name_display"])
+        .ok_or_else(|| anyhow::anyhow!("No name/primary_name column in {}", path.display()))?;
+    let alt_col = find_column(&headers, &["alt_names", "alt-names", "other_names", "other_names_display"]);
+    let country_col = find_column(&headers, &["country", "address_country", "location"]);
+    let replaced_col = find_column(&headers, &["replaced_by", "replaced-by"]);
+
+    let mut funders = Vec::new();
+    for (row_num, row) in reader.records().enumerate() {
+        let row = match row {
+            Ok(r) => r,
+            Err(e) => {
+                warn!("Error reading row {} of {}: {}", row_num + 2, path.display(), e);
+                continue;
+            }
+        };
+        let get = |col: Option<usize>| col.and_then(|c| row.get(c)).map(str::trim).unwrap_or("");
+
+        let mut funder = Funder {
+            doi: funder_doi_from(get(Some(doi_col))),
+            country: get(country_col).to_string(),
+            replaced_by: get(replaced_col).split(['|', ';']).filter_map(funder_doi_from).collect(),
+            ..Default::default()
+        };
+        let name = get(Some(name_col));
+        if !name.is_empty() {
+            funder.names.push(FunderName { name_type: "name", name: name.to_string(), lang: String::new() });
+        }
+        for alt in get(alt_col).split(['|', ';']).map(str::trim).filter(|a| !a.is_empty()) {
+            funder.names.push(FunderName { name_type: "alt_name", name: alt.to_string(), lang: String::new() });
+        }
+        funders.push(funder);
+    }
+    Ok(funders)
+}
+
+fn write_output(path: &str, funders: &[Funder]) -> Result<usize> {
+    let output_path = PathBuf::from(path);
+    if let Some(parent) = output_path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory structure for: {}", output_path.display()))?;
+        }
+    }
+    let mut writer = Writer::from_path(&output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path.display()))?;
+    writer.write_record([
+        "funder_doi", "name_type", "name", "normalized_name", "lang", "country", "geonames_id",
+        "funding_body_type", "funding_body_sub_type", "replaced_by",
+    ])?;
+
+    let mut rows = 0;
+    for funder in funders {
+        let Some(doi) = &funder.doi else {
+            continue;
+        };
+        let replaced_by = funder.replaced_by.iter().map(|d| d.0.as_str()).collect::<Vec<_>>().join("|");
+        for name in &funder.names {
+            writer.write_record([
+                doi.0.as_str(),
+                name.name_type,
+                &name.name,
+                &normalize_text(&name.name),
+                &name.lang,
+                &funder.country,
+                &funder.geonames_id,
+                &funder.funding_body_type,
+                &funder.funding_body_sub_type,
+                &replaced_by,
+            ])?;
+            rows += 1;
+        }
+    }
+    writer.flush()?;
+    Ok(rows)
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let total_secs = elapsed.as_secs();
+    let minutes = total_secs / 60;
+    let seconds = total_secs % 60;
+    let millis = elapsed.subsec_millis();
+
+    if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}.{:03}s", seconds, millis)
+    }
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let start_time = Instant::now();
+    let cli = Cli::parse();
+
+    setup_logging(&cli.log_level)?;
+    info!("Starting Funder Registry parser");
+
+    let input = Path::new(&cli.input);
+    let is_csv = input.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+    let mut funders = if is_csv {
+        parse_registry_csv(input)?
+    } else {
+        parse_registry_rdf(input)?
+    };
+    info!("Parsed {} funders from {}", funders.len(), input.display());
+
+    let missing_doi = funders.iter().filter(|f| f.doi.is_none()).count();
+    if missing_doi > 0 {
+        warn!("{} funders without a recognizable 10.13039 DOI were skipped", missing_doi);
+    }
+    let total = funders.len();
+    let replaced = funders.iter().filter(|f| !f.replaced_by.is_empty()).count();
+    if cli.exclude_replaced {
+        funders.retain(|f| f.replaced_by.is_empty());
+    }
+
+    let rows = write_output(&cli.output, &funders)?;
+
+    info!("-------------------- FINAL SUMMARY --------------------");
+    info!("Total execution time: {}", format_elapsed(start_time.elapsed()));
+    info!("Funders: {} ({} replaced by another funder{})", total, replaced, if cli.exclude_replaced { ", excluded" } else { "" });
+    info!("Name rows written: {} -> {}", rows, cli.output);
+    info!("-------------------------------------------------------");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("funder-registry-parse-{}-{}", std::process::id(), name))
+    }
+
+    fn rows_for(funders: &[Funder], name: &str) -> String {
+        let output = temp_path(name);
+        write_output(output.to_str().unwrap(), funders).unwrap();
+        let written = fs::read_to_string(&output).unwrap();
+        fs::remove_file(&output).unwrap();
+        written
+    }
+
+    #[test]
+    fn rdf_concepts_become_one_row_per_name() {
+        let input = temp_path("registry.rdf");
+        fs::write(&input, r#"<?xml version="1.0" encoding="UTF-8"?>
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#" xmlns:skos="http://www.w3.org/2004/02/skos/core#"
+    xmlns:skosxl="http://www.w3.org/2008/05/skos-xl#" xmlns:svf="http://data.crossref.org/fundingdata/xml/schema/grant/grant-1.2/"
+    xmlns:schema="http://schema.org/" xmlns:dct="http://purl.org/dc/terms/">
+  <skos:Concept rdf:about="http://dx.doi.org/10.13039/501100000780">
+    <skosxl:prefLabel><skosxl:Label><skosxl:literalForm xml:lang="en">European Commission</skosxl:literalForm></skosxl:Label></skosxl:prefLabel>
+    <skosxl:altLabel><skosxl:Label><skosxl:literalForm xml:lang="fr">Commission Européenne</skosxl:literalForm></skosxl:Label></skosxl:altLabel>
+    <svf:country rdf:resource="http://sws.geonames.org/6695072/"/>
+    <schema:address><schema:postalAddress><schema:addressCountry>Belgium</schema:addressCountry></schema:postalAddress></schema:address>
+    <svf:fundingBodyType>gov</svf:fundingBodyType>
+    <svf:fundingBodySubType>National government</svf:fundingBodySubType>
+  </skos:Concept>
+  <skos:Concept rdf:about="http://dx.doi.org/10.13039/100000001">
+    <skosxl:prefLabel><skosxl:Label><skosxl:literalForm xml:lang="en">Old Funder</skosxl:literalForm></skosxl:Label></skosxl:prefLabel>
+    <dct:isReplacedBy rdf:resource="http://dx.doi.org/10.13039/100000002"/>
+  </skos:Concept>
+</rdf:RDF>
+"#).unwrap();
+        let funders = parse_registry_rdf(&input);
+        fs::remove_file(&input).unwrap();
+
+        assert_eq!(rows_for(&funders.unwrap(), "rdf.csv"), "\
+funder_doi,name_type,name,normalized_name,lang,country,geonames_id,funding_body_type,funding_body_sub_type,replaced_by
+10.13039/501100000780,name,European Commission,european commission,en,Belgium,6695072,gov,National government,
+10.13039/501100000780,alt_name,Commission Européenne,commission europeenne,fr,Belgium,6695072,gov,National government,
+10.13039/100000001,name,Old Funder,old funder,en,,,,,10.13039/100000002
+");
+    }
+
+    #[test]
+    fn csv_listings_split_alternative_names() {
+        let input = temp_path("funders.csv");
+        fs::write(&input, "\u{feff}ID,Primary_Name,Other_Names,Country,Replaced_By\n\
+            https://doi.org/10.13039/100000001,National Science Foundation,NSF | N.S.F.,United States,\n\
+            not-a-doi,Skipped,,,\n").unwrap();
+        let funders = parse_registry_csv(&input);
+        fs::remove_file(&input).unwrap();
+
+        assert_eq!(rows_for(&funders.unwrap(), "csv.csv"), "\
+funder_doi,name_type,name,normalized_name,lang,country,geonames_id,funding_body_type,funding_body_sub_type,replaced_by
+10.13039/100000001,name,National Science Foundation,national science foundation,,United States,,,,
+10.13039/100000001,alt_name,NSF,nsf,,United States,,,,
+10.13039/100000001,alt_name,N.S.F.,nsf,,United States,,,,
+");
+    }
+}
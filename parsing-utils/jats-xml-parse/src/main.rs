@@ -0,0 +1,865 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser};
+use crossbeam_channel::{bounded, Receiver, Sender};
+use csv::Writer;
+use flate2::read::GzDecoder;
+use glob::glob;
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, error, info, warn, LevelFilter};
+use normalization::normalize_orcid;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use rayon::prelude::*;
+use simple_logger::SimpleLogger;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::thread;
+use std::time::{Duration, Instant};
+use time::macros::format_description;
+
+#[derive(Parser)]
+#[command(name = "PMC JATS XML Parser")]
+#[command(about = "Extract front-matter metadata from PubMed Central JATS XML into the pipeline's long field format")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(short, long, help = "PMC package (.tar.gz/.tgz/.tar), JATS file (.nxml/.xml), or directory of either", required = true)]
+    input: String,
+
+    #[arg(short, long, default_value = "jats_field_data.csv", help = "Output CSV file")]
+    output: String,
+
+    #[arg(short, long, help = "Comma-separated list of fields to keep (e.g., 'author.family,author.affiliation.name'); default all")]
+    fields: Option<String>,
+
+    #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
+    log_level: String,
+
+    #[arg(short, long, default_value = "0", help = "Number of threads to use (0 for auto)")]
+    threads: usize,
+
+    #[arg(short, long, default_value = "10000", help = "Target number of records per batch sent to writer")]
+    batch_size: usize,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the output columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+static VERSION_INFO: LazyLock<String> = LazyLock::new(|| format!(
+    "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+    VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+));
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Doi(String);
+
+#[derive(Debug, Clone)]
+struct FieldData {
+    doi: Option<Doi>,
+    pmcid: String,
+    field_name: String,
+    subfield_path: String,
+    value: String,
+    source_file_path: String,
+}
+
+#[derive(Debug)]
+enum XmlContent {
+    Text(String),
+    Element(XmlNode),
+}
+
+#[derive(Debug, Default)]
+struct XmlNode {
+    name: String,
+    attributes: Vec<(String, String)>,
+    content: Vec<XmlContent>,
+}
+
+impl XmlNode {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    fn children(&self) -> impl Iterator<Item = &XmlNode> {
+        self.content.iter().filter_map(|c| match c {
+            XmlContent::Element(e) => Some(e),
+            XmlContent::Text(_) => None,
+        })
+    }
+
+    fn child(&self, name: &str) -> Option<&XmlNode> {
+        self.children().find(|c| c.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlNode> + 'a {
+        self.children().filter(move |c| c.name == name)
+    }
+
+    // All descendants with the given name, in document order.
+    fn descendants<'a>(&'a self, name: &str, found: &mut Vec<&'a XmlNode>) {
+        for child in self.children() {
+            if child.name == name {
+                found.push(child);
+            }
+            child.descendants(name, found);
+        }
+    }
+
+    // Concatenated text of this element, skipping the named descendant elements,
+    // with whitespace collapsed.
+    fn text_without(&self, skip: &[&str]) -> String {
+        fn collect(node: &XmlNode, skip: &[&str], out: &mut String) {
+            for c in &node.content {
+                match c {
+                    XmlContent::Text(t) => out.push_str(t),
+                    XmlContent::Element(e) if !skip.contains(&e.name.as_str()) => {
+                        out.push(' ');
+                        collect(e, skip, out);
+                    }
+                    XmlContent::Element(_) => {}
+                }
+            }
+        }
+        let mut raw = String::new();
+        collect(self, skip, &mut raw);
+        let collapsed = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+        collapsed.replace(" ,", ",").trim_matches(|c: char| c == ',' || c == ';' || c.is_whitespace()).to_string()
+    }
+
+    fn text(&self) -> String {
+        self.text_without(&[])
+    }
+}
+
+fn node_from_start(element: &BytesStart) -> XmlNode {
+    let attributes = element.attributes()
+        .filter_map(|a| a.ok())
+        .filter(|a| !a.key.as_ref().starts_with(b"xmlns"))
+        .filter_map(|a| {
+            let key = String::from_utf8_lossy(a.key.local_name().as_ref()).into_owned();
+            a.unescape_value().ok().map(|v| (key, v.trim().to_string()))
+        })
+        .collect();
+    XmlNode {
+        name: String::from_utf8_lossy(element.local_name().as_ref()).into_owned(),
+        attributes,
+        content: Vec::new(),
+    }
+}
+
+// Builds the tree for the article's <front> only; the body and back matter are skipped.
+fn parse_front(content: &[u8]) -> Result<Option<XmlNode>> {
+    let mut reader = Reader::from_reader(content);
+    let mut buf = Vec::new();
+    let mut stack: Vec<XmlNode> = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf).with_context(|| format!("Malformed XML at byte {}", reader.buffer_position()))? {
+            Event::Start(e) => {
+                let node = node_from_start(&e);
+                if !stack.is_empty() || node.name == "front" {
+                    stack.push(node);
+                }
+            }
+            Event::Empty(e) => {
+                if let Some(parent) = stack.last_mut() {
+                    parent.content.push(XmlContent::Element(node_from_start(&e)));
+                }
+            }
+            Event::Text(t) => {
+                if let Some(current) = stack.last_mut() {
+                    current.content.push(XmlContent::Text(t.unescape().context("Invalid XML text")?.into_owned()));
+                }
+            }
+            Event::CData(c) => {
+                if let Some(current) = stack.last_mut() {
+                    current.content.push(XmlContent::Text(String::from_utf8_lossy(&c).into_owned()));
+                }
+            }
+            Event::End(_) => {
+                if let Some(node) = stack.pop() {
+                    match stack.last_mut() {
+                        Some(parent) => parent.content.push(XmlContent::Element(node)),
+                        None => return Ok(Some(node)),
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(None)
+}
+
+fn normalize_doi(raw: &str) -> Option<Doi> {
+    normalization::normalize_doi(raw).map(Doi)
+}
+
+fn normalize_pmcid(raw: &str) -> String {
+    let trimmed = raw.trim();
+    if trimmed.to_uppercase().starts_with("PMC") {
+        format!("PMC{}", &trimmed[3..])
+    } else {
+        format!("PMC{}", trimmed)
+    }
+}
+
+// Affiliation label and institution identifiers, resolved from <aff> elements.
+struct Affiliation {
+    name: String,
+    ids: Vec<(String, String)>,
+}
+
+fn affiliation_from(aff: &XmlNode) -> Affiliation {
+    let mut id_nodes = Vec::new();
+    aff.descendants("institution-id", &mut id_nodes);
+    Affiliation {
+        name: aff.text_without(&["label", "sup", "institution-id"]),
+        ids: id_nodes.iter()
+            .map(|n| (n.attr("institution-id-type").unwrap_or("").to_lowercase(), n.text()))
+            .filter(|(_, v)| !v.is_empty())
+            .collect(),
+    }
+}
+
+// CRediT roles are tagged with vocab="credit" and the NISO term URIs (JATS 1.2
+// and later), or, in older files, with the CASRAI vocabulary as content-type.
+// Free-text roles such as "Corresponding author" are left out.
+fn is_credit_role(role: &XmlNode) -> bool {
+    role.attr("vocab").is_some_and(|v| v.eq_ignore_ascii_case("credit"))
+        || ["vocab-identifier", "vocab-term-identifier", "content-type"].iter()
+            .filter_map(|name| role.attr(name))
+            .any(|v| v.contains("credit.niso.org") || v.contains("credit.casrai.org"))
+}
+
+struct FrontFields {
+    doi: Option<Doi>,
+    pmcid: String,
+    fields: Vec<(String, String, String)>,
+}
+
+fn push(fields: &mut Vec<(String, String, String)>, name: &str, path: String, value: String) {
+    let value = value.trim();
+    if !value.is_empty() {
+        fields.push((name.to_string(), path, value.to_string()));
+    }
+}
+
+// Maps JATS front matter onto Crossref field names and paths so the output can be
+// compared directly with the Crossref field parser's output.
+fn extract_front(front: &XmlNode) -> FrontFields {
+    let mut fields = Vec::new();
+    let journal_meta = front.child("journal-meta");
+    let Some(article_meta) = front.child("article-meta") else {
+        return FrontFields { doi: None, pmcid: String::new(), fields };
+    };
+
+    let mut doi = None;
+    let mut pmcid = String::new();
+    for id in article_meta.children_named("article-id") {
+        let value = id.text();
+        match id.attr("pub-id-type").unwrap_or("") {
+            "doi" => {
+                doi = normalize_doi(&value);
+                if let Some(d) = &doi {
+                    push(&mut fields, "DOI", "DOI".to_string(), d.0.clone());
+                }
+            }
+            "pmc" | "pmcid" => {
+                pmcid = normalize_pmcid(&value);
+                push(&mut fields, "pmcid", "pmcid".to_string(), pmcid.clone());
+            }
+            "pmid" => push(&mut fields, "pmid", "pmid".to_string(), value),
+            _ => {}
+        }
+    }
+
+    if let Some(title) = article_meta.child("title-group").and_then(|g| g.child("article-title")) {
+        push(&mut fields, "title", "title[0]".to_string(), title.text());
+    }
+
+    if let Some(journal) = journal_meta {
+        let mut titles = Vec::new();
+        journal.descendants("journal-title", &mut titles);
+        for (i, t) in titles.iter().enumerate() {
+            push(&mut fields, "container-title", format!("container-title[{}]", i), t.text());
+        }
+        for (i, issn) in journal.children_named("issn").enumerate() {
+            push(&mut fields, "ISSN", format!("ISSN[{}]", i), issn.text());
+        }
+        if let Some(name) = journal.child("publisher").and_then(|p| p.child("publisher-name")) {
+            push(&mut fields, "publisher", "publisher".to_string(), name.text());
+        }
+    }
+
+    for (tag, field) in [("volume", "volume"), ("issue", "issue"), ("elocation-id", "article-number")] {
+        if let Some(node) = article_meta.child(tag) {
+            push(&mut fields, field, field.to_string(), node.text());
+        }
+    }
+    if let Some(first) = article_meta.child("fpage").map(|n| n.text()) {
+        let page = match article_meta.child("lpage").map(|n| n.text()) {
+            Some(last) if !last.is_empty() && last != first => format!("{}-{}", first, last),
+            _ => first,
+        };
+        push(&mut fields, "page", "page".to_string(), page);
+    }
+
+    for date in article_meta.children_named("pub-date") {
+        let kind = date.attr("pub-type")
+            .or_else(|| date.attr("publication-format"))
+            .unwrap_or("");
+        let field = match kind {
+            "epub" | "electronic" => "published-online.date-parts",
+            "ppub" | "print" => "published-print.date-parts",
+            _ => continue,
+        };
+        let parts: Vec<String> = ["year", "month", "day"].iter()
+            .map_while(|p| date.child(p).map(|n| n.text()).and_then(|t| t.parse::<u32>().ok()).map(|n| n.to_string()))
+            .collect();
+        if !parts.is_empty() {
+            push(&mut fields, field, field.to_string(), format!("[[{}]]", parts.join(",")));
+        }
+    }
+
+    extract_contributors(article_meta, &mut fields);
+    extract_funding(article_meta, &mut fields);
+
+    FrontFields { doi, pmcid, fields }
+}
+
+fn extract_contributors(article_meta: &XmlNode, fields: &mut Vec<(String, String, String)>) {
+    let mut aff_nodes = Vec::new();
+    article_meta.descendants("aff", &mut aff_nodes);
+    let affs_by_id: HashMap<&str, &XmlNode> = aff_nodes.iter()
+        .filter_map(|a| a.attr("id").map(|id| (id, *a)))
+        .collect();
+
+    let mut contribs = Vec::new();
+    article_meta.descendants("contrib", &mut contribs);
+    let authors: Vec<&XmlNode> = contribs.into_iter()
+        .filter(|c| c.attr("contrib-type").is_none_or(|t| t == "author"))
+        .collect();
+
+    // Affiliations that no author points at by xref apply to every author without one.
+    let referenced: HashSet<&str> = authors.iter()
+        .flat_map(|c| c.children_named("xref"))
+        .filter(|x| x.attr("ref-type") == Some("aff"))
+        .flat_map(|x| x.attr("rid").unwrap_or("").split_whitespace())
+        .collect();
+    let unreferenced: Vec<&XmlNode> = aff_nodes.iter()
+        .filter(|a| a.attr("id").is_none_or(|id| !referenced.contains(id)))
+        .copied()
+        .collect();
+
+    for (i, contrib) in authors.iter().enumerate() {
+        let base = format!("author[{}]", i);
+        let name = contrib.child("name").or_else(|| contrib.child("name-alternatives").and_then(|n| n.child("name")));
+        if let Some(name) = name {
+            if let Some(surname) = name.child("surname") {
+                push(fields, "author.family", format!("{}.family", base), surname.text());
+            }
+            if let Some(given) = name.child("given-names") {
+                push(fields, "author.given", format!("{}.given", base), given.text());
+            }
+        } else if let Some(collab) = contrib.child("collab") {
+            push(fields, "author.name", format!("{}.name", base), collab.text());
+        }
+        push(fields, "author.sequence", format!("{}.sequence", base), if i == 0 { "first" } else { "additional" }.to_string());
+        for id in contrib.children_named("contrib-id") {
+            if let Some(orcid) = normalize_orcid(&id.text()).filter(|_| id.attr("contrib-id-type") == Some("orcid")) {
+                push(fields, "author.ORCID", format!("{}.ORCID", base), orcid);
+            }
+        }
+
+        for (k, role) in contrib.children_named("role").filter(|r| is_credit_role(r)).enumerate() {
+            let role_base = format!("{}.role[{}]", base, k);
+            let term = role.attr("vocab-term").map(str::to_string).unwrap_or_else(|| role.text());
+            push(fields, "author.role.name", format!("{}.name", role_base), term);
+            if let Some(id) = role.attr("vocab-term-identifier") {
+                push(fields, "author.role.id", format!("{}.id", role_base), id.to_string());
+            }
+        }
+
+        let mut author_affs: Vec<&XmlNode> = contrib.children_named("aff").collect();
+        for xref in contrib.children_named("xref").filter(|x| x.attr("ref-type") == Some("aff")) {
+            for rid in xref.attr("rid").unwrap_or("").split_whitespace() {
+                match affs_by_id.get(rid) {
+                    Some(aff) => author_affs.push(aff),
+                    None => debug!("Unresolved affiliation xref '{}'", rid),
+                }
+            }
+        }
+        if author_affs.is_empty() {
+            author_affs = unreferenced.clone();
+        }
+        for (j, aff_node) in author_affs.iter().enumerate() {
+            let aff = affiliation_from(aff_node);
+            let aff_base = format!("{}.affiliation[{}]", base, j);
+            push(fields, "author.affiliation.name", format!("{}.name", aff_base), aff.name);
+            for (k, (id_type, value)) in aff.ids.iter().enumerate() {
+                push(fields, "author.affiliation.id.id", format!("{}.id[{}].id", aff_base, k), value.clone());
+                push(fields, "author.affiliation.id.id-type", format!("{}.id[{}].id-type", aff_base, k), id_type.to_uppercase());
+            }
+        }
+    }
+}
+
+fn extract_funding(article_meta: &XmlNode, fields: &mut Vec<(String, String, String)>) {
+    let Some(funding_group) = article_meta.child("funding-group") else {
+        return;
+    };
+    for (i, award) in funding_group.children_named("award-group").enumerate() {
+        let base = format!("funder[{}]", i);
+        let mut sources = Vec::new();
+        award.descendants("funding-source", &mut sources);
+        if let Some(source) = sources.first() {
+            push(fields, "funder.name", format!("{}.name", base), source.text_without(&["institution-id"]));
+            let mut ids = Vec::new();
+            source.descendants("institution-id", &mut ids);
+            let funder_doi = ids.iter().filter_map(|n| normalize_doi(&n.text())).next()
+                .or_else(|| source.attr("href").and_then(normalize_doi));
+            if let Some(d) = funder_doi {
+                push(fields, "funder.DOI", format!("{}.DOI", base), d.0);
+            }
+        }
+        for (j, id) in award.children_named("award-id").enumerate() {
+            push(fields, "funder.award", format!("{}.award[{}]", base, j), id.text());
+        }
+    }
+    if let Some(statement) = funding_group.child("funding-statement") {
+        push(fields, "funding-statement", "funding-statement".to_string(), statement.text());
+    }
+}
+
+#[derive(Debug, Default)]
+struct SourceStats {
+    documents: usize,
+    document_errors: usize,
+    documents_without_front: usize,
+    documents_with_doi: usize,
+    rows: usize,
+}
+
+struct ProcessedSourceResult {
+    stats: SourceStats,
+    error: Option<anyhow::Error>,
+    filepath: PathBuf,
+}
+
+fn is_document_name(name: &str) -> bool {
+    name.ends_with(".nxml") || name.ends_with(".xml")
+}
+
+fn is_archive_name(name: &str) -> bool {
+    name.ends_with(".tar.gz") || name.ends_with(".tgz") || name.ends_with(".tar")
+}
+
+fn find_input_sources(input: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if !path.is_dir() {
+        return Err(anyhow::anyhow!("Input path does not exist: {}", input));
+    }
+    let pattern = path.join("**/*");
+    let mut sources: Vec<PathBuf> = glob(&pattern.to_string_lossy())?
+        .filter_map(Result::ok)
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            let name = p.to_string_lossy();
+            is_archive_name(&name) || is_document_name(&name)
+        })
+        .collect();
+    sources.sort();
+    if sources.is_empty() {
+        warn!("No .tar.gz, .tar, .nxml or .xml files found under: {}", input);
+    }
+    Ok(sources)
+}
+
+struct JatsProcessor {
+    fields: Option<HashSet<String>>,
+}
+
+impl JatsProcessor {
+    fn process(&self, filepath: &Path, sender: &Sender<Vec<FieldData>>, batch_size: usize) -> ProcessedSourceResult {
+        let mut stats = SourceStats::default();
+        let mut batch_buffer = Vec::with_capacity(batch_size);
+        let name = filepath.to_string_lossy().to_string();
+
+        let outcome = if is_archive_name(&name) {
+            self.process_archive(filepath, sender, batch_size, &mut batch_buffer, &mut stats)
+        } else {
+            fs::read(filepath)
+                .with_context(|| format!("Failed to read file: {}", filepath.display()))
+                .and_then(|content| self.process_document(&name, &content, sender, batch_size, &mut batch_buffer, &mut stats))
+        };
+        let error = outcome.err().or_else(|| {
+            if !batch_buffer.is_empty() && sender.send(batch_buffer).is_err() {
+                Some(anyhow::anyhow!("Writer thread channel closed unexpectedly on final batch for {}", filepath.display()))
+            } else {
+                None
+            }
+        });
+        ProcessedSourceResult { stats, error, filepath: filepath.to_path_buf() }
+    }
+
+    fn process_archive(
+        &self,
+        filepath: &Path,
+        sender: &Sender<Vec<FieldData>>,
+        batch_size: usize,
+        batch_buffer: &mut Vec<FieldData>,
+        stats: &mut SourceStats,
+    ) -> Result<()> {
+        let file = File::open(filepath).with_context(|| format!("Failed to open archive: {}", filepath.display()))?;
+        let reader: Box<dyn Read> = if filepath.to_string_lossy().ends_with(".tar") {
+            Box::new(file)
+        } else {
+            Box::new(GzDecoder::new(file))
+        };
+        let mut archive = tar::Archive::new(reader);
+        for entry_result in archive.entries().context("Failed to read tar entries")? {
+            let mut entry = entry_result.with_context(|| format!("Corrupt tar entry in {}", filepath.display()))?;
+            let entry_path = entry.path().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+            if !entry.header().entry_type().is_file() || !is_document_name(&entry_path) {
+                continue;
+            }
+            let mut content = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut content)
+                .with_context(|| format!("Failed to read tar entry {} in {}", entry_path, filepath.display()))?;
+            let source = format!("{}!{}", filepath.display(), entry_path);
+            self.process_document(&source, &content, sender, batch_size, batch_buffer, stats)?;
+        }
+        Ok(())
+    }
+
+    fn process_document(
+        &self,
+        source: &str,
+        content: &[u8],
+        sender: &Sender<Vec<FieldData>>,
+        batch_size: usize,
+        batch_buffer: &mut Vec<FieldData>,
+        stats: &mut SourceStats,
+    ) -> Result<()> {
+        stats.documents += 1;
+        let front = match parse_front(content) {
+            Ok(Some(front)) => front,
+            Ok(None) => {
+                stats.documents_without_front += 1;
+                debug!("No <front> element in {}", source);
+                return Ok(());
+            }
+            Err(e) => {
+                stats.document_errors += 1;
+                warn!("Error parsing {}: {:#}", source, e);
+                return Ok(());
+            }
+        };
+
+        let extracted = extract_front(&front);
+        if extracted.doi.is_some() {
+            stats.documents_with_doi += 1;
+        }
+        for (field_name, subfield_path, value) in extracted.fields {
+            if self.fields.as_ref().is_some_and(|f| !f.contains(&field_name)) {
+                continue;
+            }
+            stats.rows += 1;
+            batch_buffer.push(FieldData {
+                doi: extracted.doi.clone(),
+                pmcid: extracted.pmcid.clone(),
+                field_name,
+                subfield_path,
+                value,
+                source_file_path: source.to_string(),
+            });
+            if batch_buffer.len() >= batch_size {
+                if sender.send(std::mem::take(batch_buffer)).is_err() {
+                    return Err(anyhow::anyhow!("Writer thread channel closed unexpectedly on {}", source));
+                }
+                *batch_buffer = Vec::with_capacity(batch_size);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_field_filter(fields: &Option<String>) -> Option<HashSet<String>> {
+    fields.as_ref().map(|spec| {
+        spec.split(',')
+            .map(|f| f.trim().to_string())
+            .filter(|f| !f.is_empty())
+            .collect()
+    })
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let total_secs = elapsed.as_secs();
+    let hours = total_secs / 3600;
+    let minutes = (total_secs % 3600) / 60;
+    let seconds = total_secs % 60;
+    let millis = elapsed.subsec_millis();
+
+    if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}.{:03}s", seconds, millis)
+    }
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+
+    Ok(())
+}
+
+fn setup_thread_pool(thread_count: usize) -> Result<usize> {
+    let num_threads = if thread_count == 0 {
+        let cores = num_cpus::get();
+        info!("Auto-detected {} CPU cores. Using {} threads.", cores, cores);
+        cores
+    } else {
+        info!("Using specified {} threads.", thread_count);
+        thread_count
+    };
+
+    if let Err(e) = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build_global() {
+        error!("Failed to build global thread pool: {}. Proceeding with default.", e);
+    }
+
+    Ok(num_threads)
+}
+
+fn spawn_writer(output_path: String, receiver: Receiver<Vec<FieldData>>) -> thread::JoinHandle<Result<usize>> {
+    thread::spawn(move || -> Result<usize> {
+        let path = PathBuf::from(&output_path);
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create directory structure for: {}", path.display()))?;
+            }
+        }
+        let mut writer = Writer::from_path(&path)
+            .with_context(|| format!("Failed to create output file: {}", path.display()))?;
+        writer.write_record(["doi", "pmcid", "field_name", "subfield_path", "value", "doi_prefix", "source_file_path"])?;
+
+        let mut records_written = 0;
+        for batch in receiver {
+            for row in &batch {
+                let doi = row.doi.as_ref().map(|d| d.0.as_str()).unwrap_or("");
+                let prefix = doi.split_once('/').map(|(p, _)| p).unwrap_or("");
+                writer.write_record([
+                    doi,
+                    &row.pmcid,
+                    &row.field_name,
+                    &row.subfield_path,
+                    &row.value,
+                    prefix,
+                    &row.source_file_path,
+                ])?;
+            }
+            records_written += batch.len();
+        }
+        writer.flush()
+            .with_context(|| format!("Failed to flush output file: {}", path.display()))?;
+        info!("Writer thread finished. Wrote {} records.", records_written);
+        Ok(records_written)
+    })
+}
+
+fn main() -> Result<()> {
+    let start_time = Instant::now();
+    let cli = Cli::parse();
+
+    setup_logging(&cli.log_level)?;
+    info!("Starting PMC JATS XML parser");
+
+    let num_threads = setup_thread_pool(cli.threads)?;
+    let sources = find_input_sources(&cli.input)?;
+    info!("Found {} input sources to process.", sources.len());
+    if sources.is_empty() {
+        return Ok(());
+    }
+
+    let progress_bar = ProgressBar::new(sources.len() as u64);
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}")
+            .expect("Failed to create progress bar template")
+            .progress_chars("=> "),
+    );
+
+    let (batch_sender, batch_receiver) = bounded::<Vec<FieldData>>((num_threads * 4).max(8));
+    let writer_thread = spawn_writer(cli.output.clone(), batch_receiver);
+
+    let processor = JatsProcessor { fields: parse_field_filter(&cli.fields) };
+    let results: Vec<ProcessedSourceResult> = sources
+        .par_iter()
+        .map(|source| {
+            let result = processor.process(source, &batch_sender, cli.batch_size);
+            progress_bar.inc(1);
+            result
+        })
+        .collect();
+    drop(batch_sender);
+
+    let mut totals = SourceStats::default();
+    let mut failed_sources = Vec::new();
+    for result in results {
+        if let Some(e) = result.error {
+            error!("Error processing {}: {:#}", result.filepath.display(), e);
+            failed_sources.push(result.filepath);
+        }
+        totals.documents += result.stats.documents;
+        totals.document_errors += result.stats.document_errors;
+        totals.documents_without_front += result.stats.documents_without_front;
+        totals.documents_with_doi += result.stats.documents_with_doi;
+        totals.rows += result.stats.rows;
+    }
+    progress_bar.finish_with_message(format!("Processing finished. {} sources with errors.", failed_sources.len()));
+
+    let records_written = match writer_thread.join() {
+        Ok(Ok(count)) => count,
+        Ok(Err(e)) => return Err(e.context("Writer thread failed")),
+        Err(e) => return Err(anyhow::anyhow!("Writer thread panicked: {:?}", e)),
+    };
+
+    info!("-------------------- FINAL SUMMARY --------------------");
+    info!("Total execution time: {}", format_elapsed(start_time.elapsed()));
+    info!("Input sources: {} ({} with errors)", sources.len(), failed_sources.len());
+    info!(
+        "Documents: {} ({} with DOI, {} parse errors, {} without <front>)",
+        totals.documents, totals.documents_with_doi, totals.document_errors, totals.documents_without_front
+    );
+    info!("Rows written: {} -> {}", records_written, cli.output);
+    info!("-------------------------------------------------------");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARTICLE: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE article PUBLIC "-//NLM//DTD JATS (Z39.96) Journal Archiving and Interchange DTD v1.2 20190208//EN" "JATS-archivearticle1.dtd">
+<article xmlns:xlink="http://www.w3.org/1999/xlink" article-type="research-article">
+  <front>
+    <journal-meta>
+      <journal-title-group><journal-title>Sensors</journal-title></journal-title-group>
+      <issn pub-type="epub">1424-8220</issn>
+      <publisher><publisher-name>MDPI</publisher-name></publisher>
+    </journal-meta>
+    <article-meta>
+      <article-id pub-id-type="pmid">123</article-id>
+      <article-id pub-id-type="pmc">456</article-id>
+      <article-id pub-id-type="doi">10.3390/S20010001</article-id>
+      <title-group><article-title>Graphene <italic>in situ</italic> sensors</article-title></title-group>
+      <contrib-group>
+        <contrib contrib-type="author">
+          <contrib-id contrib-id-type="orcid">https://orcid.org/0000-0002-1825-0097</contrib-id>
+          <name><surname>Doe</surname><given-names>Ann</given-names></name>
+          <xref ref-type="aff" rid="aff1"><sup>1</sup></xref>
+          <role vocab="credit" vocab-identifier="https://credit.niso.org/" vocab-term="Writing – original draft" vocab-term-identifier="https://credit.niso.org/contributor-roles/writing-original-draft/">Writing - original draft</role>
+          <role content-type="http://credit.casrai.org/">Conceptualization</role>
+          <role>Corresponding author</role>
+        </contrib>
+        <contrib contrib-type="author"><collab>The Graphene Consortium</collab></contrib>
+        <contrib contrib-type="editor"><name><surname>Ed</surname></name></contrib>
+        <aff id="aff1"><label>1</label><institution-wrap><institution-id institution-id-type="ror">https://ror.org/052gg0110</institution-id><institution>University of Oxford</institution></institution-wrap>, Oxford, UK</aff>
+        <aff id="aff2">Shared Lab, Cambridge, UK</aff>
+      </contrib-group>
+      <pub-date pub-type="epub"><day>05</day><month>01</month><year>2020</year></pub-date>
+      <pub-date pub-type="collection"><year>2020</year></pub-date>
+      <volume>20</volume><issue>1</issue><fpage>1</fpage><lpage>12</lpage>
+      <funding-group>
+        <award-group>
+          <funding-source><institution-wrap><institution>European Commission</institution><institution-id institution-id-type="doi">10.13039/501100000780</institution-id></institution-wrap></funding-source>
+          <award-id>H2020-1</award-id>
+        </award-group>
+        <funding-statement>Funded by the EC.</funding-statement>
+      </funding-group>
+    </article-meta>
+  </front>
+  <body><p>Not parsed.</p></body>
+</article>
+"#;
+
+    #[test]
+    fn front_matter_maps_to_crossref_field_paths() {
+        let front = parse_front(ARTICLE.as_bytes()).unwrap().expect("article has a <front>");
+        let extracted = extract_front(&front);
+        assert_eq!(extracted.doi, Some(Doi("10.3390/s20010001".to_string())));
+        assert_eq!(extracted.pmcid, "PMC456");
+
+        let rows: Vec<String> = extracted.fields.iter().map(|(_, path, value)| format!("{} = {}", path, value)).collect();
+        assert_eq!(rows, [
+            "pmid = 123",
+            "pmcid = PMC456",
+            "DOI = 10.3390/s20010001",
+            "title[0] = Graphene in situ sensors",
+            "container-title[0] = Sensors",
+            "ISSN[0] = 1424-8220",
+            "publisher = MDPI",
+            "volume = 20",
+            "issue = 1",
+            "page = 1-12",
+            "published-online.date-parts = [[2020,1,5]]",
+            "author[0].family = Doe",
+            "author[0].given = Ann",
+            "author[0].sequence = first",
+            "author[0].ORCID = https://orcid.org/0000-0002-1825-0097",
+            "author[0].role[0].name = Writing – original draft",
+            "author[0].role[0].id = https://credit.niso.org/contributor-roles/writing-original-draft/",
+            "author[0].role[1].name = Conceptualization",
+            "author[0].affiliation[0].name = University of Oxford, Oxford, UK",
+            "author[0].affiliation[0].id[0].id = https://ror.org/052gg0110",
+            "author[0].affiliation[0].id[0].id-type = ROR",
+            "author[1].name = The Graphene Consortium",
+            "author[1].sequence = additional",
+            "author[1].affiliation[0].name = Shared Lab, Cambridge, UK",
+            "funder[0].name = European Commission",
+            "funder[0].DOI = 10.13039/501100000780",
+            "funder[0].award[0] = H2020-1",
+            "funding-statement = Funded by the EC.",
+        ]);
+    }
+
+    #[test]
+    fn documents_without_front_yield_nothing() {
+        assert!(parse_front(b"<article><body><p>text</p></body></article>").unwrap().is_none());
+        assert_eq!(normalize_pmcid(" pmc789 "), "PMC789");
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::LazyLock;
+
+#[derive(Parser)]
+#[command(name = "Lookup")]
+#[command(about = "Print the extracted fields of DOIs from a key-value database written with --output-format sled")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(help = "Database directory written by crossref-fast-field-parse or openalex-fast-field-parse with --output-format sled")]
+    database: String,
+
+    #[arg(help = "DOIs to look up, bare or as doi.org URLs; read from stdin, one per line, when none are given")]
+    dois: Vec<String>,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the printed output changes.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+static VERSION_INFO: LazyLock<String> = LazyLock::new(|| format!(
+    "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+    VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+));
+
+const DOI_PREFIXES: [&str; 5] = ["https://doi.org/", "http://doi.org/", "https://dx.doi.org/", "http://dx.doi.org/", "doi:"];
+
+const OPENALEX_PREFIX: &str = "https://openalex.org/";
+
+// The parsers key each record by its lowercased bare DOI, and OpenAlex works
+// without a DOI by their lowercased work ID URL.
+fn database_key(doi: &str) -> String {
+    let key = doi.trim().to_lowercase();
+    if let Some(bare) = DOI_PREFIXES.iter().find_map(|prefix| key.strip_prefix(prefix)) {
+        return bare.to_string();
+    }
+    let is_work_id = key.len() > 1 && key.starts_with('w') && key[1..].bytes().all(|b| b.is_ascii_digit());
+    if is_work_id {
+        return format!("{}{}", OPENALEX_PREFIX, key);
+    }
+    key
+}
+
+// sled creates a database at any path it is given, so a mistyped path would
+// otherwise look up nothing in a new, empty database.
+fn open_database(path: &Path) -> Result<sled::Db> {
+    if !path.join("conf").is_file() || !path.join("db").is_file() {
+        return Err(anyhow::anyhow!(
+            "{} is not a key-value database; write one with --output-format sled",
+            path.display()
+        ));
+    }
+    sled::open(path).with_context(|| format!(
+        "Failed to open database {}; it cannot be read while another process, such as the run writing it, has it open",
+        path.display()
+    ))
+}
+
+fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    let db = open_database(Path::new(&cli.database))?;
+
+    let dois: Vec<String> = if cli.dois.is_empty() {
+        io::stdin().lock().lines()
+            .collect::<io::Result<Vec<_>>>()
+            .context("Failed to read DOIs from stdin")?
+            .into_iter()
+            .filter(|line| !line.trim().is_empty())
+            .collect()
+    } else {
+        cli.dois
+    };
+
+    let mut stdout = io::stdout().lock();
+    let mut missing = 0;
+    for doi in &dois {
+        match db.get(database_key(doi).as_bytes())? {
+            Some(value) => {
+                stdout.write_all(&value)?;
+                stdout.write_all(b"\n")?;
+            }
+            None => {
+                eprintln!("Not found: {}", doi.trim());
+                missing += 1;
+            }
+        }
+    }
+    stdout.flush()?;
+
+    if missing > 0 {
+        eprintln!("{} of {} DOIs not found", missing, dois.len());
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dois_and_work_ids_map_to_the_parsers_keys() {
+        assert_eq!(database_key(" https://doi.org/10.1000/ABC "), "10.1000/abc");
+        assert_eq!(database_key("doi:10.1000/abc"), "10.1000/abc");
+        assert_eq!(database_key("10.1000/ABC"), "10.1000/abc");
+        assert_eq!(database_key("W2741809807"), "https://openalex.org/w2741809807");
+        assert_eq!(database_key("https://openalex.org/W2741809807"), "https://openalex.org/w2741809807");
+    }
+
+    #[test]
+    fn records_are_read_back_from_a_written_database() {
+        let dir = std::env::temp_dir().join(format!("lookup-{}-database", std::process::id()));
+        {
+            let db = sled::open(&dir).unwrap();
+            db.insert("10.1000/abc", &br#"{"title":"A"}"#[..]).unwrap();
+            db.flush().unwrap();
+        }
+        let db = open_database(&dir).unwrap();
+        let found = db.get(database_key("https://doi.org/10.1000/ABC").as_bytes()).unwrap();
+        let missing = db.get(database_key("10.1000/xyz").as_bytes()).unwrap();
+        drop(db);
+        let not_database = open_database(&std::env::temp_dir().join(format!("lookup-{}-missing", std::process::id())))
+            .err().unwrap().to_string();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(found.as_deref(), Some(&br#"{"title":"A"}"#[..]));
+        assert_eq!(missing, None);
+        assert!(not_database.contains("is not a key-value database"), "{}", not_database);
+    }
+}
@@ -0,0 +1,562 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser, ValueEnum};
+use csv::{ByteRecord, ReaderBuilder, Writer, WriterBuilder};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use log::{debug, info, warn, LevelFilter};
+use serde_json::Value;
+use simple_logger::SimpleLogger;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use time::macros::format_description;
+
+#[derive(Parser)]
+#[command(name = "Merge Organized")]
+#[command(about = "Merge the organized CSV output directories of several sharded or incremental field parser runs into one directory, with one header per file and optionally without duplicate rows")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(short, long, required = true, help = "Organized output directory of crossref-fast-field-parse or openalex-fast-field-parse (--organize, --max-rows-per-file or both). Repeat for each run to merge, in the order their rows should come")]
+    input: Vec<String>,
+
+    #[arg(short, long, help = "Directory to write the merged output to; must be new or empty")]
+    output: String,
+
+    #[arg(long, help = "Drop rows identical to one already written to the same member, source, prefix or year")]
+    dedupe_rows: bool,
+
+    #[arg(long, help = "Split each merged file into parts of at most this many rows, in a directory per member, source, prefix or year")]
+    max_rows_per_file: Option<u64>,
+
+    #[arg(long, value_enum, help = "Compress the merged files: gz or zst")]
+    compress_output: Option<OutputCompression>,
+
+    #[arg(long, default_value = ",", value_parser = parse_delimiter, help = "Field delimiter of the input files, also used for the output: a single ASCII character, or 'tab' (also '\\t') for TSV")]
+    delimiter: u8,
+
+    #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
+    log_level: String,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the merged layout, or what the files hold, changes.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+static VERSION_INFO: LazyLock<String> = LazyLock::new(|| format!(
+    "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+    VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+));
+
+fn parse_delimiter(value: &str) -> Result<u8, String> {
+    let delimiter = match value {
+        "tab" | "\\t" | "\t" => b'\t',
+        _ if value.len() == 1 && value.is_ascii() => value.as_bytes()[0],
+        _ => return Err(format!("expected a single ASCII character or 'tab', got '{}'", value)),
+    };
+    if matches!(delimiter, b'"' | b'\n' | b'\r') {
+        return Err("the delimiter cannot be a quote or a line break".to_string());
+    }
+    Ok(delimiter)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputCompression {
+    Gz,
+    Zst,
+}
+
+// Written by the parsers inside their output directories, and not CSV rows.
+const PROVENANCE_FILE: &str = "_provenance.json";
+
+const CSV_SUFFIXES: [&str; 3] = [".csv", ".csv.gz", ".csv.zst"];
+
+// The stem of a parser output file name, without .csv and any compression
+// suffix, if it is one.
+fn csv_stem(name: &str) -> Option<&str> {
+    CSV_SUFFIXES.iter().rev().find_map(|suffix| name.strip_suffix(suffix))
+}
+
+// A part written by --max-rows-per-file, or under a year=YYYY directory.
+fn is_part_stem(stem: &str) -> bool {
+    stem.strip_prefix("part-").is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+// A group is the files whose rows end up in one merged file, or one
+// directory of parts: those of a member, source, prefix or year across all
+// the inputs. Files are grouped by their path within the input, so that
+// 78.csv and 78/part-00001.csv.gz are the same member, and
+// year=2020/part-00003.csv joins the other parts of 2020. Parts at the top of
+// an input, from --max-rows-per-file without --organize, make up the group "".
+fn collect_groups(input: &Path, dir: &Path, groups: &mut BTreeMap<String, Vec<PathBuf>>, skipped: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read input directory: {}", dir.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_groups(input, &entry, groups, skipped)?;
+            continue;
+        }
+        let name = entry.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let Some(stem) = csv_stem(&name) else {
+            if name != PROVENANCE_FILE {
+                skipped.push(entry);
+            }
+            continue;
+        };
+        let parent = entry.parent().and_then(|p| p.strip_prefix(input).ok()).unwrap_or(Path::new(""));
+        let parent = parent.to_string_lossy().replace('\\', "/");
+        let key = if is_part_stem(stem) {
+            parent
+        } else if parent.is_empty() {
+            stem.to_string()
+        } else {
+            format!("{}/{}", parent, stem)
+        };
+        groups.entry(key).or_default().push(entry);
+    }
+    Ok(())
+}
+
+fn open_input(path: &Path) -> Result<Box<dyn Read>> {
+    let file = File::open(path).with_context(|| format!("Failed to open input file: {}", path.display()))?;
+    let reader = BufReader::new(file);
+    let name = path.to_string_lossy().to_lowercase();
+    Ok(if name.ends_with(".gz") {
+        Box::new(MultiGzDecoder::new(reader))
+    } else if name.ends_with(".zst") {
+        Box::new(zstd::Decoder::with_buffer(reader).with_context(|| format!("Failed to start decompressing: {}", path.display()))?)
+    } else {
+        Box::new(reader)
+    })
+}
+
+// A merged output file, written as is or through a compressor.
+enum OutputSink {
+    Plain(BufWriter<File>),
+    Gz(GzEncoder<BufWriter<File>>),
+    Zst(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl OutputSink {
+    fn create(path: &Path, compression: Option<OutputCompression>) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create output directory: {}", parent.display()))?;
+        }
+        let file = BufWriter::new(File::create(path)
+            .with_context(|| format!("Failed to create output file: {}", path.display()))?);
+        Ok(match compression {
+            None => OutputSink::Plain(file),
+            Some(OutputCompression::Gz) => OutputSink::Gz(GzEncoder::new(file, flate2::Compression::new(6))),
+            Some(OutputCompression::Zst) => OutputSink::Zst(zstd::Encoder::new(file, 3)?),
+        })
+    }
+
+    fn finish(self) -> io::Result<()> {
+        let mut file = match self {
+            OutputSink::Plain(file) => file,
+            OutputSink::Gz(encoder) => encoder.finish()?,
+            OutputSink::Zst(encoder) => encoder.finish()?,
+        };
+        file.flush()
+    }
+}
+
+impl Write for OutputSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputSink::Plain(file) => file.write(buf),
+            OutputSink::Gz(encoder) => encoder.write(buf),
+            OutputSink::Zst(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputSink::Plain(file) => file.flush(),
+            OutputSink::Gz(encoder) => encoder.flush(),
+            OutputSink::Zst(encoder) => encoder.flush(),
+        }
+    }
+}
+
+fn file_name(stem: &str, compression: Option<OutputCompression>) -> String {
+    match compression {
+        None => format!("{}.csv", stem),
+        Some(OutputCompression::Gz) => format!("{}.csv.gz", stem),
+        Some(OutputCompression::Zst) => format!("{}.csv.zst", stem),
+    }
+}
+
+fn display_row(row: &ByteRecord) -> String {
+    row.iter().map(String::from_utf8_lossy).collect::<Vec<_>>().join(",")
+}
+
+fn row_hash(row: &ByteRecord) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for field in row {
+        field.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[derive(Default)]
+struct MergeStats {
+    files_read: u64,
+    files_written: u64,
+    rows_written: u64,
+    duplicate_rows: u64,
+}
+
+// Writes a group's rows to <key>.csv, or to <key>/part-NNNNN.csv with
+// --max-rows-per-file, as the parsers lay them out. Years and the parts of
+// output without --organize are always written as parts. Each file gets the
+// header once.
+struct GroupWriter<'a> {
+    cli: &'a Cli,
+    output: &'a Path,
+    key: &'a str,
+    parts: bool,
+    header: &'a ByteRecord,
+    writer: Option<Writer<OutputSink>>,
+    part: u32,
+    part_rows: u64,
+}
+
+impl GroupWriter<'_> {
+    fn path(&self) -> PathBuf {
+        if !self.parts {
+            return self.output.join(file_name(self.key, self.cli.compress_output));
+        }
+        self.output.join(self.key).join(file_name(&format!("part-{:05}", self.part), self.cli.compress_output))
+    }
+
+    fn write_row(&mut self, row: &ByteRecord, stats: &mut MergeStats) -> Result<()> {
+        let full = self.cli.max_rows_per_file.is_some_and(|max| self.part_rows >= max);
+        if full {
+            self.close()?;
+        }
+        if self.writer.is_none() {
+            self.part += 1;
+            self.part_rows = 0;
+            let path = self.path();
+            let mut writer = WriterBuilder::new()
+                .delimiter(self.cli.delimiter)
+                .from_writer(OutputSink::create(&path, self.cli.compress_output)?);
+            writer.write_byte_record(self.header)?;
+            self.writer = Some(writer);
+            stats.files_written += 1;
+        }
+        if let Some(writer) = self.writer.as_mut() {
+            writer.write_byte_record(row)?;
+        }
+        self.part_rows += 1;
+        stats.rows_written += 1;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        let Some(writer) = self.writer.take() else {
+            return Ok(());
+        };
+        let path = self.path();
+        writer.into_inner()
+            .map_err(|e| e.into_error())
+            .and_then(OutputSink::finish)
+            .with_context(|| format!("Failed to write output file: {}", path.display()))
+    }
+}
+
+// Groups are merged one after another, so only one output file is open at a
+// time, and with --dedupe-rows only the current group's row hashes are held.
+fn merge_group(cli: &Cli, output: &Path, key: &str, files: &[PathBuf], header: &ByteRecord, stats: &mut MergeStats) -> Result<()> {
+    let mut seen: HashSet<u64> = HashSet::new();
+    let mut writer: Option<GroupWriter> = None;
+    for file in files {
+        debug!("Reading {}", file.display());
+        let mut reader = ReaderBuilder::new()
+            .has_headers(true)
+            .delimiter(cli.delimiter)
+            .from_reader(open_input(file)?);
+        stats.files_read += 1;
+        let mut row = ByteRecord::new();
+        while reader.read_byte_record(&mut row)
+            .with_context(|| format!("Failed to read input file: {}", file.display()))?
+        {
+            if cli.dedupe_rows && !seen.insert(row_hash(&row)) {
+                stats.duplicate_rows += 1;
+                continue;
+            }
+            let writer = writer.get_or_insert_with(|| GroupWriter {
+                cli,
+                output,
+                key,
+                parts: cli.max_rows_per_file.is_some() || key.is_empty() || key.rsplit('/').next().is_some_and(|k| k.starts_with("year=")),
+                header,
+                writer: None,
+                part: 0,
+                part_rows: 0,
+            });
+            writer.write_row(&row, stats)?;
+        }
+    }
+    if let Some(mut writer) = writer {
+        writer.close()?;
+    }
+    Ok(())
+}
+
+fn read_header(file: &Path, delimiter: u8) -> Result<ByteRecord> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(delimiter)
+        .from_reader(open_input(file)?);
+    Ok(reader.byte_headers()
+        .with_context(|| format!("Failed to read CSV header from {}", file.display()))?
+        .clone())
+}
+
+// Every file has to have the same columns, so that each merged file has one
+// header. They are all checked before anything is written, so a mismatch
+// does not leave a half-merged output.
+fn check_headers(groups: &BTreeMap<String, Vec<PathBuf>>, delimiter: u8) -> Result<ByteRecord> {
+    let mut files = groups.values().flatten();
+    let Some(first) = files.next() else {
+        return Err(anyhow::anyhow!("No CSV files to merge"));
+    };
+    let header = read_header(first, delimiter)?;
+    for file in files {
+        let file_header = read_header(file, delimiter)?;
+        if file_header != header {
+            return Err(anyhow::anyhow!(
+                "{} has columns {}, but {} has {}; the inputs have to come from the same parser and settings",
+                file.display(), display_row(&file_header), first.display(), display_row(&header)
+            ));
+        }
+    }
+    Ok(header)
+}
+
+// Runs with different settings, such as different --fields, give files with
+// the same columns that cannot be told apart once merged.
+fn check_provenance(inputs: &[PathBuf]) -> Result<()> {
+    let mut first: Option<(&PathBuf, Value)> = None;
+    for input in inputs {
+        let path = input.join(PROVENANCE_FILE);
+        if !path.is_file() {
+            warn!("{} has no {}; cannot check that it was extracted with the same settings as the other inputs", input.display(), PROVENANCE_FILE);
+            continue;
+        }
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read provenance {}", path.display()))?;
+        let provenance: Value = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse provenance {}", path.display()))?;
+        let config = provenance.get("config").cloned().unwrap_or(Value::Null);
+        match &first {
+            None => first = Some((input, config)),
+            Some((first_input, first_config)) => {
+                let (Value::Object(a), Value::Object(b)) = (first_config, &config) else {
+                    continue;
+                };
+                let differing: Vec<&str> = a.keys().chain(b.keys())
+                    .filter(|k| a.get(*k) != b.get(*k))
+                    .map(String::as_str)
+                    .collect::<std::collections::BTreeSet<_>>()
+                    .into_iter()
+                    .collect();
+                if !differing.is_empty() {
+                    warn!(
+                        "{} was extracted with different settings from {}: {}",
+                        input.display(), first_input.display(), differing.join(", ")
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    setup_logging(&cli.log_level)?;
+    if cli.max_rows_per_file == Some(0) {
+        return Err(anyhow::anyhow!("--max-rows-per-file must be at least 1"));
+    }
+
+    let inputs: Vec<PathBuf> = cli.input.iter().map(PathBuf::from).collect();
+    let output = PathBuf::from(&cli.output);
+    for input in &inputs {
+        if !input.is_dir() {
+            return Err(anyhow::anyhow!("Input is not a directory: {}; merge-organized takes organized or split output directories", input.display()));
+        }
+        if output.starts_with(input) {
+            return Err(anyhow::anyhow!("--output {} is inside input {}", output.display(), input.display()));
+        }
+    }
+    if output.exists() && fs::read_dir(&output).map(|mut d| d.next().is_some()).unwrap_or(true) {
+        return Err(anyhow::anyhow!("--output {} exists and is not empty; merge into a new directory", output.display()));
+    }
+    check_provenance(&inputs)?;
+
+    let mut groups: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    let mut skipped = Vec::new();
+    for input in &inputs {
+        let before: usize = groups.values().map(Vec::len).sum();
+        collect_groups(input, input, &mut groups, &mut skipped)?;
+        let after: usize = groups.values().map(Vec::len).sum();
+        info!("Found {} CSV files in {}", after - before, input.display());
+    }
+    for file in &skipped {
+        warn!("Skipping {}: not a CSV output file", file.display());
+    }
+    if groups.is_empty() {
+        return Err(anyhow::anyhow!("No CSV files found in: {}", cli.input.join(", ")));
+    }
+    let header = check_headers(&groups, cli.delimiter)?;
+    fs::create_dir_all(&output)
+        .with_context(|| format!("Failed to create output directory: {}", output.display()))?;
+
+    let mut stats = MergeStats::default();
+    for (key, files) in &groups {
+        merge_group(&cli, &output, key, files, &header, &mut stats)?;
+    }
+
+    info!(
+        "Merged {} files from {} inputs into {} files in {}: {} rows",
+        stats.files_read, inputs.len(), stats.files_written, output.display(), stats.rows_written
+    );
+    if cli.dedupe_rows {
+        info!("Dropped {} duplicate rows", stats.duplicate_rows);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Merges the given organized directories, each a list of (relative path,
+    // contents), and returns the merged files by relative path.
+    fn merge(name: &str, inputs: &[&[(&str, &str)]], args: &[&str]) -> BTreeMap<String, String> {
+        let dir = std::env::temp_dir().join(format!("merge-organized-{}-{}", std::process::id(), name));
+        let output = dir.join("merged");
+        let mut input_dirs = Vec::new();
+        for (i, files) in inputs.iter().enumerate() {
+            let input = dir.join(format!("run{}", i));
+            for (path, contents) in files.iter() {
+                let path = input.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+            input_dirs.push(input);
+        }
+        let mut argv = vec!["merge-organized".to_string(), "--output".to_string(), output.display().to_string()];
+        for input in &input_dirs {
+            argv.extend(["--input".to_string(), input.display().to_string()]);
+        }
+        argv.extend(args.iter().map(|a| a.to_string()));
+        let cli = Cli::parse_from(argv);
+
+        let mut groups = BTreeMap::new();
+        let mut skipped = Vec::new();
+        for input in &input_dirs {
+            collect_groups(input, input, &mut groups, &mut skipped).unwrap();
+        }
+        let header = check_headers(&groups, cli.delimiter).unwrap();
+        let mut stats = MergeStats::default();
+        for (key, files) in &groups {
+            merge_group(&cli, &output, key, files, &header, &mut stats).unwrap();
+        }
+
+        let mut merged = BTreeMap::new();
+        let mut pending = vec![output.clone()];
+        while let Some(next) = pending.pop() {
+            for entry in fs::read_dir(&next).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else {
+                    let relative = path.strip_prefix(&output).unwrap().to_string_lossy().replace('\\', "/");
+                    merged.insert(relative, fs::read_to_string(&path).unwrap());
+                }
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+        merged
+    }
+
+    const HEADER: &str = "doi,field_name,subfield_path,value\n";
+
+    #[test]
+    fn members_and_parts_of_each_run_are_merged_with_one_header() {
+        let first = format!("{}10.1/a,title,title[0],A\n", HEADER);
+        let second = format!("{}10.1/b,title,title[0],B\n", HEADER);
+        let third = format!("{}10.2/c,title,title[0],C\n", HEADER);
+        let merged = merge("members", &[
+            &[("78.csv", &first), ("_provenance.json", "{}")],
+            &[("78/part-00001.csv", &second), ("311.csv", &third)],
+        ], &[]);
+        assert_eq!(merged.keys().collect::<Vec<_>>(), ["311.csv", "78.csv"]);
+        assert_eq!(merged["78.csv"], "\
+doi,field_name,subfield_path,value
+10.1/a,title,title[0],A
+10.1/b,title,title[0],B
+");
+        assert_eq!(merged["311.csv"], third);
+    }
+
+    #[test]
+    fn dedupe_drops_repeated_rows_and_max_rows_splits_into_parts() {
+        let first = format!("{}10.1/a,title,title[0],A\n10.1/b,title,title[0],B\n", HEADER);
+        let second = format!("{}10.1/b,title,title[0],B\n10.1/c,title,title[0],C\n", HEADER);
+        let merged = merge("dedupe", &[&[("year=2020/part-00001.csv", &first)], &[("year=2020/part-00001.csv", &second)]],
+            &["--dedupe-rows", "--max-rows-per-file", "2"]);
+        assert_eq!(merged.keys().collect::<Vec<_>>(), ["year=2020/part-00001.csv", "year=2020/part-00002.csv"]);
+        assert_eq!(merged["year=2020/part-00001.csv"], first);
+        assert_eq!(merged["year=2020/part-00002.csv"], format!("{}10.1/c,title,title[0],C\n", HEADER));
+    }
+
+    #[test]
+    fn files_with_different_columns_are_refused() {
+        let dir = std::env::temp_dir().join(format!("merge-organized-{}-columns", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("1.csv"), HEADER).unwrap();
+        fs::write(dir.join("2.csv"), "doi,value\n").unwrap();
+        let mut groups = BTreeMap::new();
+        collect_groups(&dir, &dir, &mut groups, &mut Vec::new()).unwrap();
+        let error = check_headers(&groups, b',').unwrap_err().to_string();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(error.contains("the inputs have to come from the same parser"), "{}", error);
+    }
+}
@@ -0,0 +1,23 @@
+// Times each metric on name and affiliation pairs of typical lengths:
+//   cargo bench --features similarity
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use normalization::similarity::Metric;
+
+const PAIRS: [(&str, &str, &str); 3] = [
+    ("family name", "kowalczyk", "kowalczik"),
+    ("full name", "maria garcia lopez", "garcia lopez maria"),
+    ("affiliation", "department of physics university of oxford parks road oxford uk", "dept of physics univ of oxford oxford ox1 3pu united kingdom"),
+];
+
+fn metrics(c: &mut Criterion) {
+    for metric in Metric::ALL {
+        let mut group = c.benchmark_group(metric.name());
+        for (label, a, b) in PAIRS {
+            group.bench_function(label, |bench| bench.iter(|| metric.similarity(black_box(a), black_box(b))));
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, metrics);
+criterion_main!(benches);
@@ -0,0 +1,83 @@
+// The text and identifier normalizers shared by the parsing tools, so that
+// outputs from different sources can be joined on the normalized values. The
+// `normalize` tool gives command-line access to the same functions.
+
+use deunicode::deunicode;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+#[cfg(feature = "similarity")]
+pub mod similarity;
+
+lazy_static! {
+    static ref NORMALIZE_RE: Regex = Regex::new(r"[^\w\s]").unwrap();
+    static ref ROR_ID_RE: Regex = Regex::new(r"^0[a-z0-9]{6}[0-9]{2}$").unwrap();
+}
+
+const DOI_PREFIXES: [&str; 5] = ["https://doi.org/", "http://doi.org/", "https://dx.doi.org/", "http://dx.doi.org/", "doi:"];
+
+// Transliterated to ASCII, lowercased, punctuation removed and trimmed, as the
+// author/affiliation normalizer writes normalized author and affiliation names.
+pub fn normalize_text(text: &str) -> String {
+    let unidecoded = deunicode(text);
+    let lowercased = unidecoded.to_lowercase();
+    let cleaned = NORMALIZE_RE.replace_all(&lowercased, "");
+    cleaned.trim().to_string()
+}
+
+// Lowercased, with resolver and "doi:" prefixes removed. Anything not starting
+// with "10." is not a DOI.
+pub fn normalize_doi(raw: &str) -> Option<String> {
+    let lowered = raw.trim().to_lowercase();
+    let stripped = DOI_PREFIXES.iter()
+        .find_map(|prefix| lowered.strip_prefix(prefix))
+        .unwrap_or(&lowered)
+        .trim();
+    stripped.starts_with("10.").then(|| stripped.to_string())
+}
+
+// OpenAlex and Crossref write ORCIDs as https://orcid.org/XXXX-XXXX-XXXX-XXXX.
+pub fn normalize_orcid(raw: &str) -> Option<String> {
+    let id = raw.trim().rsplit('/').next().unwrap_or("").trim();
+    (id.len() == 19).then(|| format!("https://orcid.org/{}", id.to_uppercase()))
+}
+
+// ROR IDs as the ROR dump and OpenAlex write them: https://ror.org/0xxxxxxNN.
+pub fn normalize_ror(raw: &str) -> Option<String> {
+    let id = raw.trim().trim_end_matches('/').rsplit('/').next().unwrap_or("").to_lowercase();
+    ROR_ID_RE.is_match(&id).then(|| format!("https://ror.org/{}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_transliterated_lowercased_and_stripped_of_punctuation() {
+        assert_eq!(normalize_text("  Université de Montréal! "), "universite de montreal");
+        assert_eq!(normalize_text("Max-Planck-Institut (MPI)"), "maxplanckinstitut mpi");
+    }
+
+    #[test]
+    fn doi_prefixes_are_removed_and_non_dois_rejected() {
+        for raw in ["10.1000/ABC", " https://doi.org/10.1000/abc", "http://dx.doi.org/10.1000/Abc", "DOI:10.1000/abc"] {
+            assert_eq!(normalize_doi(raw).as_deref(), Some("10.1000/abc"), "{}", raw);
+        }
+        assert_eq!(normalize_doi("https://example.org/10.1000/abc"), None);
+        assert_eq!(normalize_doi(""), None);
+    }
+
+    #[test]
+    fn orcids_become_uppercased_urls() {
+        assert_eq!(normalize_orcid("0000-0002-1825-009x").as_deref(), Some("https://orcid.org/0000-0002-1825-009X"));
+        assert_eq!(normalize_orcid("http://orcid.org/0000-0002-1825-0097").as_deref(), Some("https://orcid.org/0000-0002-1825-0097"));
+        assert_eq!(normalize_orcid("0000-0002-1825"), None);
+    }
+
+    #[test]
+    fn ror_ids_become_lowercased_urls() {
+        assert_eq!(normalize_ror("https://ror.org/05DXPS055/").as_deref(), Some("https://ror.org/05dxps055"));
+        assert_eq!(normalize_ror("05dxps055").as_deref(), Some("https://ror.org/05dxps055"));
+        assert_eq!(normalize_ror("grid.1234.5"), None);
+    }
+}
@@ -0,0 +1,224 @@
+// String-similarity metrics, all scaled to 0-1, for comparing names and
+// affiliation strings. They are selected by name, as the query_db matching
+// config names them, so a tool can take the metric as a setting.
+
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    JaroWinkler,
+    Levenshtein,
+    TokenSet,
+    NgramCosine,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 4] = [Metric::JaroWinkler, Metric::Levenshtein, Metric::TokenSet, Metric::NgramCosine];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::JaroWinkler => "jaro_winkler",
+            Metric::Levenshtein => "levenshtein",
+            Metric::TokenSet => "token_set",
+            Metric::NgramCosine => "ngram_cosine",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Metric, String> {
+        Metric::ALL.into_iter().find(|m| m.name() == name).ok_or_else(|| format!(
+            "unknown similarity metric '{}', expected one of: {}",
+            name, Metric::ALL.map(Metric::name).join(", ")
+        ))
+    }
+
+    pub fn similarity(self, a: &str, b: &str) -> f64 {
+        match self {
+            Metric::JaroWinkler => jaro_winkler(a, b),
+            Metric::Levenshtein => levenshtein(a, b),
+            Metric::TokenSet => token_set_ratio(a, b),
+            Metric::NgramCosine => ngram_cosine(a, b, 3),
+        }
+    }
+}
+
+fn jaro(a: &[char], b: &[char]) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let window = (a.len().max(b.len()) / 2).saturating_sub(1);
+    let mut b_matched = vec![false; b.len()];
+    let mut a_matches = Vec::new();
+    for (i, &ca) in a.iter().enumerate() {
+        let start = i.saturating_sub(window);
+        let end = (i + window + 1).min(b.len());
+        if let Some(j) = (start..end).find(|&j| !b_matched[j] && b[j] == ca) {
+            b_matched[j] = true;
+            a_matches.push(ca);
+        }
+    }
+    if a_matches.is_empty() {
+        return 0.0;
+    }
+    let b_matches = b.iter().zip(&b_matched).filter(|(_, &m)| m).map(|(&c, _)| c);
+    let transpositions = a_matches.iter().zip(b_matches).filter(|(&x, y)| x != *y).count() / 2;
+    let m = a_matches.len() as f64;
+    (m / a.len() as f64 + m / b.len() as f64 + (m - transpositions as f64) / m) / 3.0
+}
+
+// Jaro similarity raised for a shared prefix of up to four characters, with the
+// usual scaling factor of 0.1.
+pub fn jaro_winkler(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let similarity = jaro(&a, &b);
+    let prefix = a.iter().zip(&b).take(4).take_while(|(x, y)| x == y).count();
+    similarity + prefix as f64 * 0.1 * (1.0 - similarity)
+}
+
+fn levenshtein_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+// Edit distance relative to the longer string.
+pub fn levenshtein(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein_distance(&a, &b) as f64 / longest as f64
+}
+
+fn longest_common_subsequence(a: &[char], b: &[char]) -> usize {
+    let mut previous = vec![0; b.len() + 1];
+    let mut current = vec![0; b.len() + 1];
+    for ca in a {
+        for (j, cb) in b.iter().enumerate() {
+            current[j + 1] = if ca == cb { previous[j] + 1 } else { previous[j + 1].max(current[j]) };
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+// Insertions and deletions needed, relative to the combined length, as
+// rapidfuzz's ratio.
+fn indel_ratio(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let total = a.len() + b.len();
+    if total == 0 {
+        return 1.0;
+    }
+    2.0 * longest_common_subsequence(&a, &b) as f64 / total as f64
+}
+
+// Compares the shared words with each string's remaining words, as rapidfuzz's
+// token_set_ratio, so word order and repeated words do not count, and a string
+// whose words are all in the other scores 1.
+pub fn token_set_ratio(a: &str, b: &str) -> f64 {
+    let words_a: BTreeSet<&str> = a.split_whitespace().collect();
+    let words_b: BTreeSet<&str> = b.split_whitespace().collect();
+    if words_a.is_empty() || words_b.is_empty() {
+        return 0.0;
+    }
+    let shared: Vec<&str> = words_a.intersection(&words_b).copied().collect();
+    let only_a: Vec<&str> = words_a.difference(&words_b).copied().collect();
+    let only_b: Vec<&str> = words_b.difference(&words_a).copied().collect();
+    if !shared.is_empty() && (only_a.is_empty() || only_b.is_empty()) {
+        return 1.0;
+    }
+    let shared = shared.join(" ");
+    let with = |rest: &[&str]| if shared.is_empty() { rest.join(" ") } else { format!("{} {}", shared, rest.join(" ")) };
+    let (combined_a, combined_b) = (with(&only_a), with(&only_b));
+    let mut best = indel_ratio(&combined_a, &combined_b);
+    if !shared.is_empty() {
+        best = best.max(indel_ratio(&shared, &combined_a)).max(indel_ratio(&shared, &combined_b));
+    }
+    best
+}
+
+// Cosine of the character n-gram counts. The strings are padded with a space
+// on each side, so that short strings have n-grams too and the first and last
+// letters count as much as the others.
+pub fn ngram_cosine(a: &str, b: &str, n: usize) -> f64 {
+    fn ngrams(text: &str, n: usize) -> HashMap<Vec<char>, usize> {
+        let padded: Vec<char> = format!(" {} ", text).chars().collect();
+        let mut counts = HashMap::new();
+        for gram in padded.windows(n.min(padded.len())) {
+            *counts.entry(gram.to_vec()).or_insert(0) += 1;
+        }
+        counts
+    }
+    let (grams_a, grams_b) = (ngrams(a, n), ngrams(b, n));
+    let dot: usize = grams_a.iter().map(|(gram, count)| count * grams_b.get(gram).unwrap_or(&0)).sum();
+    let norm = |grams: &HashMap<Vec<char>, usize>| (grams.values().map(|c| c * c).sum::<usize>() as f64).sqrt();
+    let norms = norm(&grams_a) * norm(&grams_b);
+    // Rounding can put identical strings just above 1.
+    if norms == 0.0 { 0.0 } else { (dot as f64 / norms).min(1.0) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: f64, expected: f64) -> bool {
+        (actual - expected).abs() < 1e-3
+    }
+
+    #[test]
+    fn metrics_are_selected_by_their_config_names() {
+        for metric in Metric::ALL {
+            assert_eq!(Metric::from_name(metric.name()), Ok(metric));
+        }
+        let error = Metric::from_name("soundex").unwrap_err();
+        assert!(error.contains("jaro_winkler, levenshtein, token_set, ngram_cosine"), "{}", error);
+    }
+
+    #[test]
+    fn jaro_winkler_matches_the_reference_values() {
+        assert!(close(jaro_winkler("martha", "marhta"), 0.961));
+        assert!(close(jaro_winkler("dwayne", "duane"), 0.840));
+        assert!(close(jaro_winkler("dixon", "dicksonx"), 0.813));
+        assert_eq!(jaro_winkler("", ""), 1.0);
+        assert_eq!(jaro_winkler("abc", ""), 0.0);
+    }
+
+    #[test]
+    fn levenshtein_is_relative_to_the_longer_string() {
+        assert!(close(levenshtein("kitten", "sitting"), 1.0 - 3.0 / 7.0));
+        assert_eq!(levenshtein("müller", "müller"), 1.0);
+        assert_eq!(levenshtein("", ""), 1.0);
+    }
+
+    #[test]
+    fn token_set_ignores_word_order_and_contained_words() {
+        assert_eq!(token_set_ratio("garcia lopez", "lopez garcia"), 1.0);
+        assert_eq!(token_set_ratio("university of oxford", "oxford university of oxford"), 1.0);
+        assert!(close(token_set_ratio("maria garcia lopez", "maria garcia perez"), 2.0 * 16.0 / 36.0));
+        assert_eq!(token_set_ratio("", "oxford"), 0.0);
+    }
+
+    #[test]
+    fn ngram_cosine_counts_padded_trigrams() {
+        assert_eq!(ngram_cosine("oxford", "oxford", 3), 1.0);
+        // " ab", "ab " against " ab", "abc", "bc ": one shared trigram.
+        assert!(close(ngram_cosine("ab", "abc", 3), 1.0 / (2.0f64.sqrt() * 3.0f64.sqrt())));
+        assert!(ngram_cosine("a", "b", 3) < 1.0);
+        assert_eq!(ngram_cosine("oxford", "cambridge", 3), 0.0);
+    }
+}
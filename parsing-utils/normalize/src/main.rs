@@ -0,0 +1,258 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser, ValueEnum};
+use csv::{ReaderBuilder, Writer};
+use log::{info, LevelFilter};
+use normalization::{normalize_doi, normalize_orcid, normalize_ror, normalize_text};
+use serde_json::Value;
+use simple_logger::SimpleLogger;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::sync::LazyLock;
+use time::macros::format_description;
+
+#[derive(Parser)]
+#[command(name = "Normalize")]
+#[command(about = "Apply the pipeline's text, DOI, ORCID, ROR and date normalizers to values from stdin or a CSV column")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(value_enum, help = "Normalizer to apply")]
+    kind: Kind,
+
+    #[arg(short, long, default_value = "-", help = "Input file, or - for stdin")]
+    input: String,
+
+    #[arg(short, long, default_value = "-", help = "Output file, or - for stdout")]
+    output: String,
+
+    #[arg(short, long, help = "CSV column to normalize. Without it, every input line is one value")]
+    column: Option<String>,
+
+    #[arg(long, help = "Name of the added column (default: normalized_<column>)")]
+    output_column: Option<String>,
+
+    #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
+    log_level: String,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the output columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+static VERSION_INFO: LazyLock<String> = LazyLock::new(|| format!(
+    "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+    VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Kind {
+    Text,
+    Doi,
+    Orcid,
+    Ror,
+    Date,
+}
+
+fn parse_date_components(s: &str) -> Option<(i32, Option<u32>, Option<u32>)> {
+    let date_part = s.split('T').next()?;
+    let mut parts = date_part.split('-');
+    let year = parts.next()?.parse::<i32>().ok()?;
+    let month = match parts.next() {
+        Some(m) => Some(m.parse::<u32>().ok().filter(|m| (1..=12).contains(m))?),
+        None => None,
+    };
+    let day = match parts.next() {
+        Some(d) => Some(d.parse::<u32>().ok().filter(|d| (1..=31).contains(d))?),
+        None => None,
+    };
+    Some((year, month, day))
+}
+
+// Flattens Crossref date-parts ("[[2019,5,8]]", as the field parsers write them)
+// and ISO dates or date-times ("2019-05-08T10:14:38Z") to an ISO date at the
+// precision given: "2019", "2019-05" or "2019-05-08".
+fn flatten_date(raw: &str) -> Option<String> {
+    let trimmed = raw.trim();
+    let (year, month, day) = if trimmed.starts_with('[') {
+        let parsed: Value = serde_json::from_str(trimmed).ok()?;
+        let mut parts = parsed.as_array()?.as_slice();
+        if let Some(inner) = parts.first().and_then(Value::as_array) {
+            parts = inner.as_slice();
+        }
+        let year = parts.first()?.as_i64()? as i32;
+        let month = parts.get(1).and_then(Value::as_u64).map(|m| m as u32).filter(|m| (1..=12).contains(m));
+        let day = month.and(parts.get(2).and_then(Value::as_u64).map(|d| d as u32).filter(|d| (1..=31).contains(d)));
+        (year, month, day)
+    } else {
+        parse_date_components(trimmed)?
+    };
+    Some(match (month, day) {
+        (Some(month), Some(day)) => format!("{:04}-{:02}-{:02}", year, month, day),
+        (Some(month), None) => format!("{:04}-{:02}", year, month),
+        _ => format!("{:04}", year),
+    })
+}
+
+fn normalize(kind: Kind, raw: &str) -> Option<String> {
+    match kind {
+        Kind::Text => Some(normalize_text(raw)),
+        Kind::Doi => normalize_doi(raw),
+        Kind::Orcid => normalize_orcid(raw),
+        Kind::Ror => normalize_ror(raw),
+        Kind::Date => flatten_date(raw),
+    }
+}
+
+#[derive(Debug, Default)]
+struct NormalizeStats {
+    values: usize,
+    empty: usize,
+    rejected: usize,
+}
+
+impl NormalizeStats {
+    // Values that do not parse as the requested identifier or date come out empty.
+    fn apply(&mut self, kind: Kind, raw: &str) -> String {
+        self.values += 1;
+        if raw.trim().is_empty() {
+            self.empty += 1;
+            return String::new();
+        }
+        normalize(kind, raw).unwrap_or_else(|| {
+            self.rejected += 1;
+            String::new()
+        })
+    }
+}
+
+fn open_input(path: &str) -> Result<Box<dyn Read>> {
+    if path == "-" {
+        return Ok(Box::new(io::stdin().lock()));
+    }
+    Ok(Box::new(File::open(path).with_context(|| format!("Failed to open input file: {}", path))?))
+}
+
+fn open_output(path: &str) -> Result<Box<dyn Write>> {
+    if path == "-" {
+        return Ok(Box::new(BufWriter::new(io::stdout().lock())));
+    }
+    Ok(Box::new(BufWriter::new(File::create(path).with_context(|| format!("Failed to create output file: {}", path))?)))
+}
+
+fn normalize_lines(kind: Kind, input: Box<dyn Read>, mut output: Box<dyn Write>, stats: &mut NormalizeStats) -> Result<()> {
+    for line in BufReader::new(input).lines() {
+        let line = line.context("Failed to read input")?;
+        writeln!(output, "{}", stats.apply(kind, line.trim_end_matches('\r')))?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+// Copies the CSV through unchanged, with the normalized value appended as a
+// new last column.
+fn normalize_column(kind: Kind, column: &str, output_column: &str, input: Box<dyn Read>, output: Box<dyn Write>, stats: &mut NormalizeStats) -> Result<()> {
+    let mut reader = ReaderBuilder::new().flexible(true).from_reader(input);
+    let headers = reader.headers().context("Failed to read CSV header")?.clone();
+    let index = headers.iter().position(|h| h == column)
+        .with_context(|| format!("Column '{}' not found. Available columns: {}", column, headers.iter().collect::<Vec<_>>().join(", ")))?;
+
+    let mut writer = Writer::from_writer(output);
+    let mut out_headers = headers.clone();
+    out_headers.push_field(output_column);
+    writer.write_record(&out_headers)?;
+    for record in reader.records() {
+        let mut record = record.context("Failed to read CSV row")?;
+        let normalized = stats.apply(kind, record.get(index).unwrap_or(""));
+        record.push_field(&normalized);
+        writer.write_record(&record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    setup_logging(&cli.log_level)?;
+
+    let input = open_input(&cli.input)?;
+    let output = open_output(&cli.output)?;
+    let mut stats = NormalizeStats::default();
+    match &cli.column {
+        Some(column) => {
+            let output_column = cli.output_column.clone().unwrap_or_else(|| format!("normalized_{}", column));
+            normalize_column(cli.kind, column, &output_column, input, output, &mut stats)?;
+        }
+        None => normalize_lines(cli.kind, input, output, &mut stats)?,
+    }
+
+    let kind = cli.kind.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default();
+    info!("Normalized {} values ({} empty, {} not recognized as a {} value)", stats.values, stats.empty, stats.rejected, kind);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(name: &str, kind: Kind, column: Option<&str>, input: &str) -> (String, NormalizeStats) {
+        let path = std::env::temp_dir().join(format!("normalize-{}-{}", std::process::id(), name));
+        let output = open_output(path.to_str().unwrap()).unwrap();
+        let input: Box<dyn Read> = Box::new(io::Cursor::new(input.as_bytes().to_vec()));
+        let mut stats = NormalizeStats::default();
+        match column {
+            Some(column) => normalize_column(kind, column, &format!("normalized_{}", column), input, output, &mut stats).unwrap(),
+            None => normalize_lines(kind, input, output, &mut stats).unwrap(),
+        }
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        (written, stats)
+    }
+
+    #[test]
+    fn csv_column_gets_a_normalized_copy() {
+        let (written, stats) = run("column", Kind::Doi, Some("doi"), "\
+doi,title
+https://doi.org/10.1000/ABC,A
+,B
+not a doi,C
+");
+        assert_eq!(written, "\
+doi,title,normalized_doi
+https://doi.org/10.1000/ABC,A,10.1000/abc
+,B,
+not a doi,C,
+");
+        assert_eq!((stats.values, stats.empty, stats.rejected), (3, 1, 1));
+    }
+
+    #[test]
+    fn dates_flatten_to_the_precision_given() {
+        let (written, stats) = run("dates", Kind::Date, None, "[[2019,5,8]]\r\n[[2019]]\n2019-05-08T10:14:38Z\n2019-13\n[[2020,2]]\n");
+        assert_eq!(written, "2019-05-08\n2019\n2019-05-08\n\n2020-02\n");
+        assert_eq!(stats.rejected, 1);
+    }
+}
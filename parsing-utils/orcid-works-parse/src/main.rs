@@ -0,0 +1,794 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser};
+use crossbeam_channel::{bounded, Receiver, Sender};
+use csv::Writer;
+use flate2::read::GzDecoder;
+use glob::glob;
+use indicatif::{ProgressBar, ProgressStyle};
+use lazy_static::lazy_static;
+use log::{debug, error, info, warn, LevelFilter};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use rayon::prelude::*;
+use regex::Regex;
+use serde_json::Value;
+use simple_logger::SimpleLogger;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use time::macros::format_description;
+
+#[derive(Parser)]
+#[command(name = "ORCID Public Data File Works Parser")]
+#[command(about = "Extract work DOIs, titles, and put-codes per ORCID iD from the ORCID public data file")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(short, long, help = "ORCID data file archive (.tar.gz/.tar) or directory of archives and extracted XML/JSON files", required = true)]
+    input: String,
+
+    #[arg(short, long, default_value = "orcid_works.csv", help = "Output CSV file")]
+    output: String,
+
+    #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
+    log_level: String,
+
+    #[arg(short, long, default_value = "0", help = "Number of threads to use (0 for auto)")]
+    threads: usize,
+
+    #[arg(short, long, default_value = "10000", help = "Target number of records per batch sent to writer")]
+    batch_size: usize,
+
+    #[arg(long, help = "Only emit works that carry a self DOI")]
+    require_doi: bool,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the output columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+lazy_static! {
+    static ref VERSION_INFO: String = format!(
+        "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+        VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+    );
+}
+
+lazy_static! {
+    static ref ORCID_ID_RE: Regex = Regex::new(r"\d{4}-\d{4}-\d{4}-\d{3}[\dX]").unwrap();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct OrcidId(String);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Doi(String);
+
+#[derive(Debug, Clone, Default)]
+struct ExternalId {
+    id_type: String,
+    value: String,
+    relationship: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct WorkRecord {
+    orcid_id: Option<OrcidId>,
+    put_code: Option<String>,
+    title: Option<String>,
+    journal_title: Option<String>,
+    work_type: Option<String>,
+    year: Option<String>,
+    month: Option<String>,
+    day: Option<String>,
+    external_ids: Vec<ExternalId>,
+}
+
+impl WorkRecord {
+    fn self_doi(&self) -> Option<Doi> {
+        let dois: Vec<&ExternalId> = self.external_ids
+            .iter()
+            .filter(|e| e.id_type.eq_ignore_ascii_case("doi") && !e.value.trim().is_empty())
+            .collect();
+        dois.iter()
+            .find(|e| e.relationship.eq_ignore_ascii_case("self"))
+            .or_else(|| dois.iter().find(|e| e.relationship.is_empty()))
+            .and_then(|e| normalize_doi(&e.value))
+    }
+
+    fn publication_date(&self) -> Option<String> {
+        let year = self.year.as_ref()?;
+        let mut date = year.clone();
+        if let Some(month) = &self.month {
+            date.push_str(&format!("-{:0>2}", month));
+            if let Some(day) = &self.day {
+                date.push_str(&format!("-{:0>2}", day));
+            }
+        }
+        Some(date)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct FieldData {
+    orcid_id: OrcidId,
+    put_code: String,
+    doi: Option<Doi>,
+    field_name: String,
+    subfield_path: String,
+    value: String,
+    source_file_path: String,
+}
+
+#[derive(Debug, Default)]
+struct SourceStats {
+    documents_parsed: usize,
+    document_errors: usize,
+    works: usize,
+    works_with_doi: usize,
+    works_skipped: usize,
+    rows: usize,
+    orcid_ids: HashSet<OrcidId>,
+}
+
+impl SourceStats {
+    fn merge(&mut self, other: SourceStats) {
+        self.documents_parsed += other.documents_parsed;
+        self.document_errors += other.document_errors;
+        self.works += other.works;
+        self.works_with_doi += other.works_with_doi;
+        self.works_skipped += other.works_skipped;
+        self.rows += other.rows;
+        self.orcid_ids.extend(other.orcid_ids);
+    }
+}
+
+struct ProcessedSourceResult {
+    stats: SourceStats,
+    error: Option<anyhow::Error>,
+    filepath: PathBuf,
+}
+
+fn normalize_doi(raw: &str) -> Option<Doi> {
+    normalization::normalize_doi(raw).map(Doi)
+}
+
+fn orcid_from_text(text: &str) -> Option<OrcidId> {
+    ORCID_ID_RE.find(text).map(|m| OrcidId(m.as_str().to_string()))
+}
+
+fn attribute_value(element: &BytesStart, name: &[u8]) -> Option<String> {
+    element.attributes()
+        .filter_map(|a| a.ok())
+        .find(|a| a.key.local_name().as_ref() == name)
+        .and_then(|a| a.unescape_value().ok().map(|v| v.into_owned()))
+}
+
+fn is_work_element(name: &str) -> bool {
+    name == "work" || name == "work-summary"
+}
+
+fn append_text(slot: &mut Option<String>, text: &str) {
+    match slot {
+        Some(existing) => existing.push_str(text),
+        None => *slot = Some(text.to_string()),
+    }
+}
+
+// Handles both full work records (activities file) and work summaries (summaries file).
+// Element names are matched on their local part so the work:/common: prefixes don't matter.
+fn parse_works_xml(content: &[u8]) -> Result<Vec<WorkRecord>> {
+    let mut reader = Reader::from_reader(content);
+    reader.config_mut().trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut stack: Vec<String> = Vec::new();
+    let mut record_orcid: Option<OrcidId> = None;
+    let mut works = Vec::new();
+    let mut current: Option<WorkRecord> = None;
+    let mut current_ext: Option<ExternalId> = None;
+
+    loop {
+        match reader.read_event_into(&mut buf).context("Malformed XML")? {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                if is_work_element(&name) && current.is_none() {
+                    current = Some(WorkRecord {
+                        put_code: attribute_value(&e, b"put-code"),
+                        orcid_id: attribute_value(&e, b"path").and_then(|p| orcid_from_text(&p)),
+                        ..Default::default()
+                    });
+                } else if name == "external-id" && current.is_some() {
+                    current_ext = Some(ExternalId::default());
+                }
+                stack.push(name);
+            }
+            Event::Text(t) => {
+                let text = t.unescape().context("Invalid XML text")?;
+                handle_xml_text(&stack, &text, &mut record_orcid, current.as_mut(), current_ext.as_mut());
+            }
+            Event::CData(c) => {
+                let text = String::from_utf8_lossy(&c).into_owned();
+                handle_xml_text(&stack, &text, &mut record_orcid, current.as_mut(), current_ext.as_mut());
+            }
+            Event::End(_) => {
+                let name = stack.pop().unwrap_or_default();
+                if name == "external-id" {
+                    if let (Some(work), Some(ext)) = (current.as_mut(), current_ext.take()) {
+                        work.external_ids.push(ext);
+                    }
+                } else if is_work_element(&name) && !stack.iter().any(|n| is_work_element(n)) {
+                    if let Some(work) = current.take() {
+                        works.push(work);
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    for work in works.iter_mut() {
+        if work.orcid_id.is_none() {
+            work.orcid_id = record_orcid.clone();
+        }
+    }
+    Ok(works)
+}
+
+fn handle_xml_text(
+    stack: &[String],
+    text: &str,
+    record_orcid: &mut Option<OrcidId>,
+    work: Option<&mut WorkRecord>,
+    ext: Option<&mut ExternalId>,
+) {
+    let tail: Vec<&str> = stack.iter().rev().take(2).map(String::as_str).collect();
+    let (leaf, parent) = (tail.first().copied().unwrap_or(""), tail.get(1).copied().unwrap_or(""));
+
+    if leaf == "path" && parent == "orcid-identifier" && record_orcid.is_none() {
+        *record_orcid = orcid_from_text(text);
+        return;
+    }
+
+    if let Some(ext) = ext {
+        match leaf {
+            "external-id-type" => ext.id_type.push_str(text),
+            "external-id-value" => ext.value.push_str(text),
+            "external-id-relationship" => ext.relationship.push_str(text),
+            _ => {}
+        }
+        return;
+    }
+
+    let Some(work) = work else {
+        return;
+    };
+    match (parent, leaf) {
+        ("title", "title") => append_text(&mut work.title, text),
+        (_, "journal-title") => append_text(&mut work.journal_title, text),
+        (p, "type") if is_work_element(p) => append_text(&mut work.work_type, text),
+        ("publication-date", "year") => append_text(&mut work.year, text),
+        ("publication-date", "month") => append_text(&mut work.month, text),
+        ("publication-date", "day") => append_text(&mut work.day, text),
+        _ => {}
+    }
+}
+
+fn json_text(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Object(obj) => json_text(obj.get("value")),
+        _ => None,
+    }
+}
+
+fn work_from_json(obj: &serde_json::Map<String, Value>) -> WorkRecord {
+    let date = obj.get("publication-date");
+    let external_ids = obj.get("external-ids")
+        .and_then(|e| e.get("external-id"))
+        .and_then(Value::as_array)
+        .map(|ids| ids.iter()
+            .map(|id| ExternalId {
+                id_type: json_text(id.get("external-id-type")).unwrap_or_default(),
+                value: json_text(id.get("external-id-value")).unwrap_or_default(),
+                relationship: json_text(id.get("external-id-relationship")).unwrap_or_default(),
+            })
+            .collect())
+        .unwrap_or_default();
+
+    WorkRecord {
+        orcid_id: json_text(obj.get("path")).and_then(|p| orcid_from_text(&p)),
+        put_code: json_text(obj.get("put-code")),
+        title: json_text(obj.get("title").and_then(|t| t.get("title"))),
+        journal_title: json_text(obj.get("journal-title")),
+        work_type: json_text(obj.get("type")),
+        year: json_text(date.and_then(|d| d.get("year"))),
+        month: json_text(date.and_then(|d| d.get("month"))),
+        day: json_text(date.and_then(|d| d.get("day"))),
+        external_ids,
+    }
+}
+
+fn collect_json_works(value: &Value, works: &mut Vec<WorkRecord>) {
+    match value {
+        Value::Object(obj) => {
+            if obj.contains_key("put-code") && (obj.contains_key("title") || obj.contains_key("external-ids")) {
+                works.push(work_from_json(obj));
+            } else {
+                obj.values().for_each(|v| collect_json_works(v, works));
+            }
+        }
+        Value::Array(arr) => arr.iter().for_each(|v| collect_json_works(v, works)),
+        _ => {}
+    }
+}
+
+fn parse_works_json(content: &[u8]) -> Result<Vec<WorkRecord>> {
+    let parsed: Value = serde_json::from_slice(content).context("Malformed JSON")?;
+    let record_orcid = parsed.get("orcid-identifier")
+        .and_then(|o| json_text(o.get("path")))
+        .and_then(|p| orcid_from_text(&p));
+
+    let mut works = Vec::new();
+    collect_json_works(&parsed, &mut works);
+    for work in works.iter_mut() {
+        if work.orcid_id.is_none() {
+            work.orcid_id = record_orcid.clone();
+        }
+    }
+    Ok(works)
+}
+
+fn is_document_name(name: &str) -> bool {
+    name.ends_with(".xml") || name.ends_with(".json")
+}
+
+fn is_archive_name(name: &str) -> bool {
+    name.ends_with(".tar.gz") || name.ends_with(".tgz") || name.ends_with(".tar")
+}
+
+fn find_input_sources(input: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if !path.is_dir() {
+        return Err(anyhow::anyhow!("Input path does not exist: {}", input));
+    }
+
+    let pattern = path.join("**/*");
+    let pattern_str = pattern.to_string_lossy();
+    info!("Searching for ORCID archives and documents under: {}", input);
+    let mut sources: Vec<PathBuf> = glob(&pattern_str)?
+        .filter_map(Result::ok)
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            let name = p.to_string_lossy();
+            is_archive_name(&name) || is_document_name(&name)
+        })
+        .collect();
+    sources.sort();
+    if sources.is_empty() {
+        warn!("No .tar.gz, .tar, .xml or .json files found under: {}", input);
+    }
+    Ok(sources)
+}
+
+struct SourceProcessor {
+    require_doi: bool,
+}
+
+impl SourceProcessor {
+    fn process(&self, filepath: &Path, sender: &Sender<Vec<FieldData>>, batch_size: usize) -> ProcessedSourceResult {
+        let mut stats = SourceStats::default();
+        let mut batch_buffer = Vec::with_capacity(batch_size);
+        let name = filepath.to_string_lossy().to_string();
+
+        let outcome = if is_archive_name(&name) {
+            self.process_archive(filepath, sender, batch_size, &mut batch_buffer, &mut stats)
+        } else {
+            fs::read(filepath)
+                .with_context(|| format!("Failed to read file: {}", filepath.display()))
+                .and_then(|content| self.process_document(&name, &content, sender, batch_size, &mut batch_buffer, &mut stats))
+        };
+
+        let error = outcome.err().or_else(|| {
+            if !batch_buffer.is_empty() && sender.send(batch_buffer).is_err() {
+                Some(anyhow::anyhow!("Writer thread channel closed unexpectedly on final batch for {}", filepath.display()))
+            } else {
+                None
+            }
+        });
+
+        debug!(
+            "Finished {}: {} documents ({} errors), {} works ({} with DOI, {} skipped), {} rows.",
+            filepath.display(), stats.documents_parsed, stats.document_errors, stats.works,
+            stats.works_with_doi, stats.works_skipped, stats.rows
+        );
+        ProcessedSourceResult { stats, error, filepath: filepath.to_path_buf() }
+    }
+
+    fn process_archive(
+        &self,
+        filepath: &Path,
+        sender: &Sender<Vec<FieldData>>,
+        batch_size: usize,
+        batch_buffer: &mut Vec<FieldData>,
+        stats: &mut SourceStats,
+    ) -> Result<()> {
+        let file = File::open(filepath)
+            .with_context(|| format!("Failed to open archive: {}", filepath.display()))?;
+        let name = filepath.to_string_lossy();
+        let reader: Box<dyn Read> = if name.ends_with(".tar") {
+            Box::new(file)
+        } else {
+            Box::new(GzDecoder::new(file))
+        };
+        let mut archive = tar::Archive::new(reader);
+
+        for entry_result in archive.entries().context("Failed to read tar entries")? {
+            let mut entry = entry_result.with_context(|| format!("Corrupt tar entry in {}", filepath.display()))?;
+            let entry_path = entry.path()
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if !entry.header().entry_type().is_file() || !is_document_name(&entry_path) {
+                continue;
+            }
+            let mut content = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut content)
+                .with_context(|| format!("Failed to read tar entry {} in {}", entry_path, filepath.display()))?;
+            let source = format!("{}!{}", filepath.display(), entry_path);
+            self.process_document(&source, &content, sender, batch_size, batch_buffer, stats)?;
+        }
+        Ok(())
+    }
+
+    fn process_document(
+        &self,
+        source: &str,
+        content: &[u8],
+        sender: &Sender<Vec<FieldData>>,
+        batch_size: usize,
+        batch_buffer: &mut Vec<FieldData>,
+        stats: &mut SourceStats,
+    ) -> Result<()> {
+        let parsed = if source.ends_with(".json") {
+            parse_works_json(content)
+        } else {
+            parse_works_xml(content)
+        };
+        let works = match parsed {
+            Ok(works) => works,
+            Err(e) => {
+                stats.document_errors += 1;
+                warn!("Error parsing {}: {:#}", source, e);
+                return Ok(());
+            }
+        };
+        stats.documents_parsed += 1;
+
+        let fallback_orcid = orcid_from_text(source);
+        for work in works {
+            stats.works += 1;
+            let Some(orcid_id) = work.orcid_id.clone().or_else(|| fallback_orcid.clone()) else {
+                stats.works_skipped += 1;
+                debug!("Skipping work without ORCID iD in {}", source);
+                continue;
+            };
+            let doi = work.self_doi();
+            if doi.is_some() {
+                stats.works_with_doi += 1;
+            } else if self.require_doi {
+                stats.works_skipped += 1;
+                continue;
+            }
+            stats.orcid_ids.insert(orcid_id.clone());
+
+            for (field_name, subfield_path, value) in work_fields(&work) {
+                stats.rows += 1;
+                batch_buffer.push(FieldData {
+                    orcid_id: orcid_id.clone(),
+                    put_code: work.put_code.clone().unwrap_or_default(),
+                    doi: doi.clone(),
+                    field_name,
+                    subfield_path,
+                    value,
+                    source_file_path: source.to_string(),
+                });
+                if batch_buffer.len() >= batch_size {
+                    if sender.send(std::mem::take(batch_buffer)).is_err() {
+                        return Err(anyhow::anyhow!("Writer thread channel closed unexpectedly on {}", source));
+                    }
+                    *batch_buffer = Vec::with_capacity(batch_size);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn work_fields(work: &WorkRecord) -> Vec<(String, String, String)> {
+    let mut fields = Vec::new();
+    let mut push = |name: &str, path: String, value: Option<String>| {
+        if let Some(v) = value.filter(|v| !v.trim().is_empty()) {
+            fields.push((name.to_string(), path, v.trim().to_string()));
+        }
+    };
+    push("put-code", "put-code".to_string(), work.put_code.clone());
+    push("title", "title.title".to_string(), work.title.clone());
+    push("journal-title", "journal-title".to_string(), work.journal_title.clone());
+    push("type", "type".to_string(), work.work_type.clone());
+    push("publication-date", "publication-date".to_string(), work.publication_date());
+    for (i, ext) in work.external_ids.iter().enumerate() {
+        let id_type = ext.id_type.trim().to_lowercase();
+        let value = match normalize_doi(&ext.value) {
+            Some(doi) if id_type == "doi" => doi.0,
+            _ => ext.value.clone(),
+        };
+        push(&format!("external-id.{}", id_type), format!("external-ids[{}]", i), Some(value));
+        push(
+            "external-id.relationship",
+            format!("external-ids[{}].relationship", i),
+            Some(ext.relationship.trim().to_lowercase()),
+        );
+    }
+    fields
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let total_secs = elapsed.as_secs();
+    let hours = total_secs / 3600;
+    let minutes = (total_secs % 3600) / 60;
+    let seconds = total_secs % 60;
+    let millis = elapsed.subsec_millis();
+
+    if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}.{:03}s", seconds, millis)
+    }
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+
+    Ok(())
+}
+
+fn setup_thread_pool(thread_count: usize) -> Result<usize> {
+    let num_threads = if thread_count == 0 {
+        let cores = num_cpus::get();
+        info!("Auto-detected {} CPU cores. Using {} threads.", cores, cores);
+        cores
+    } else {
+        info!("Using specified {} threads.", thread_count);
+        thread_count
+    };
+
+    if let Err(e) = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build_global() {
+        error!("Failed to build global thread pool: {}. Proceeding with default.", e);
+    }
+
+    Ok(num_threads)
+}
+
+fn spawn_writer(output_path: String, receiver: Receiver<Vec<FieldData>>) -> thread::JoinHandle<Result<usize>> {
+    thread::spawn(move || -> Result<usize> {
+        let path = PathBuf::from(&output_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory structure for: {}", path.display()))?;
+        }
+        let mut writer = Writer::from_path(&path)
+            .with_context(|| format!("Failed to create output file: {}", path.display()))?;
+        writer.write_record([
+            "orcid_id", "put_code", "doi", "field_name", "subfield_path", "value", "doi_prefix", "source_file_path",
+        ])?;
+
+        let mut records_written = 0;
+        for batch in receiver {
+            for row in &batch {
+                let doi = row.doi.as_ref().map(|d| d.0.as_str()).unwrap_or("");
+                let prefix = doi.split_once('/').map(|(p, _)| p).unwrap_or("");
+                writer.write_record([
+                    row.orcid_id.0.as_str(),
+                    &row.put_code,
+                    doi,
+                    &row.field_name,
+                    &row.subfield_path,
+                    &row.value,
+                    prefix,
+                    &row.source_file_path,
+                ])?;
+            }
+            records_written += batch.len();
+        }
+        writer.flush()
+            .with_context(|| format!("Failed to flush output file: {}", path.display()))?;
+        info!("Writer thread finished. Wrote {} records.", records_written);
+        Ok(records_written)
+    })
+}
+
+fn main() -> Result<()> {
+    let start_time = Instant::now();
+    let cli = Cli::parse();
+
+    setup_logging(&cli.log_level)?;
+    info!("Starting ORCID works parser");
+
+    let num_threads = setup_thread_pool(cli.threads)?;
+    let sources = find_input_sources(&cli.input)?;
+    info!("Found {} input sources to process.", sources.len());
+    if sources.is_empty() {
+        return Ok(());
+    }
+
+    let progress_bar = ProgressBar::new(sources.len() as u64);
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}")
+            .expect("Failed to create progress bar template")
+            .progress_chars("=> "),
+    );
+
+    let (batch_sender, batch_receiver) = bounded::<Vec<FieldData>>((num_threads * 4).max(8));
+    let writer_thread = spawn_writer(cli.output.clone(), batch_receiver);
+
+    let processor = SourceProcessor { require_doi: cli.require_doi };
+    let results: Vec<ProcessedSourceResult> = sources
+        .par_iter()
+        .map(|source| {
+            let source_start = Instant::now();
+            let result = processor.process(source, &batch_sender, cli.batch_size);
+            progress_bar.inc(1);
+            let label = source.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| source.display().to_string());
+            progress_bar.set_message(format!("{} {} ({})", if result.error.is_some() { "ERR:" } else { "OK:" }, label, format_elapsed(source_start.elapsed())));
+            result
+        })
+        .collect();
+    drop(batch_sender);
+
+    let mut totals = SourceStats::default();
+    let mut failed_sources = Vec::new();
+    for result in results {
+        if let Some(e) = result.error {
+            error!("Error processing {}: {:#}", result.filepath.display(), e);
+            failed_sources.push(result.filepath);
+        }
+        totals.merge(result.stats);
+    }
+    progress_bar.finish_with_message(format!("Processing finished. {} sources with errors.", failed_sources.len()));
+
+    let records_written = match writer_thread.join() {
+        Ok(Ok(count)) => count,
+        Ok(Err(e)) => return Err(e.context("Writer thread failed")),
+        Err(e) => return Err(anyhow::anyhow!("Writer thread panicked: {:?}", e)),
+    };
+
+    info!("-------------------- FINAL SUMMARY --------------------");
+    info!("Total execution time: {}", format_elapsed(start_time.elapsed()));
+    info!("Input sources: {} ({} with errors)", sources.len(), failed_sources.len());
+    info!("Documents parsed: {} ({} parse errors)", totals.documents_parsed, totals.document_errors);
+    info!("Works seen: {} ({} with self DOI, {} skipped)", totals.works, totals.works_with_doi, totals.works_skipped);
+    info!("Unique ORCID iDs: {}", totals.orcid_ids.len());
+    info!("Rows written: {} -> {}", records_written, cli.output);
+    info!("-------------------------------------------------------");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One "orcid | doi | field_name | subfield_path = value" line per output row.
+    fn rows(works: &[WorkRecord]) -> Vec<String> {
+        works.iter()
+            .flat_map(|work| {
+                let orcid = work.orcid_id.as_ref().map(|o| o.0.clone()).unwrap_or_default();
+                let doi = work.self_doi().map(|d| d.0).unwrap_or_default();
+                work_fields(work).into_iter()
+                    .map(move |(field, path, value)| format!("{} | {} | {} | {} = {}", orcid, doi, field, path, value))
+            })
+            .collect()
+    }
+
+    fn expected() -> Vec<String> {
+        [
+            "put-code | put-code = 111",
+            "title | title.title = Graphene sensors",
+            "journal-title | journal-title = Sensors",
+            "type | type = journal-article",
+            "publication-date | publication-date = 2020-03-07",
+            "external-id.doi | external-ids[0] = 10.3390/s20010001",
+            "external-id.relationship | external-ids[0].relationship = self",
+            "external-id.doi | external-ids[1] = 10.9999/parent",
+            "external-id.relationship | external-ids[1].relationship = part-of",
+            "external-id.pmid | external-ids[2] = 123",
+            "external-id.relationship | external-ids[2].relationship = self",
+        ].iter().map(|row| format!("0000-0002-1825-0097 | 10.3390/s20010001 | {}", row)).collect()
+    }
+
+    #[test]
+    fn xml_work_records_map_to_rows() {
+        let works = parse_works_xml(br#"<?xml version="1.0" encoding="UTF-8"?>
+<activities:works xmlns:activities="http://www.orcid.org/ns/activities" xmlns:work="http://www.orcid.org/ns/work" xmlns:common="http://www.orcid.org/ns/common">
+  <work:work put-code="111" path="/0000-0002-1825-0097/work/111">
+    <work:title><common:title>Graphene sensors</common:title></work:title>
+    <work:journal-title>Sensors</work:journal-title>
+    <work:type>journal-article</work:type>
+    <common:publication-date><common:year>2020</common:year><common:month>3</common:month><common:day>07</common:day></common:publication-date>
+    <common:external-ids>
+      <common:external-id><common:external-id-type>doi</common:external-id-type><common:external-id-value>https://doi.org/10.3390/S20010001</common:external-id-value><common:external-id-relationship>self</common:external-id-relationship></common:external-id>
+      <common:external-id><common:external-id-type>doi</common:external-id-type><common:external-id-value>10.9999/PARENT</common:external-id-value><common:external-id-relationship>part-of</common:external-id-relationship></common:external-id>
+      <common:external-id><common:external-id-type>PMID</common:external-id-type><common:external-id-value>123</common:external-id-value><common:external-id-relationship>SELF</common:external-id-relationship></common:external-id>
+    </common:external-ids>
+  </work:work>
+</activities:works>
+"#).unwrap();
+        assert_eq!(rows(&works), expected());
+    }
+
+    #[test]
+    fn json_work_records_take_the_record_orcid() {
+        let works = parse_works_json(br#"{
+            "orcid-identifier": {"path": "0000-0002-1825-0097"},
+            "activities-summary": {"works": {"group": [{"work-summary": [{
+                "put-code": 111,
+                "title": {"title": {"value": "Graphene sensors"}},
+                "journal-title": {"value": "Sensors"},
+                "type": "journal-article",
+                "publication-date": {"year": {"value": "2020"}, "month": {"value": "03"}, "day": {"value": "7"}},
+                "external-ids": {"external-id": [
+                    {"external-id-type": "doi", "external-id-value": "10.3390/s20010001", "external-id-relationship": "self"},
+                    {"external-id-type": "doi", "external-id-value": "10.9999/parent", "external-id-relationship": "part-of"},
+                    {"external-id-type": "pmid", "external-id-value": "123", "external-id-relationship": "self"}
+                ]}
+            }]}]}}
+        }"#).unwrap();
+        assert_eq!(rows(&works), expected());
+    }
+
+    #[test]
+    fn doi_without_relationship_is_used_when_there_is_no_self_doi() {
+        let work = WorkRecord {
+            external_ids: vec![
+                ExternalId { id_type: "doi".to_string(), value: "10.1/part".to_string(), relationship: "part-of".to_string() },
+                ExternalId { id_type: "DOI".to_string(), value: "10.1/Plain".to_string(), relationship: String::new() },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(work.self_doi(), Some(Doi("10.1/plain".to_string())));
+    }
+}
@@ -1,9 +1,9 @@
-use clap::Parser;
+use clap::{ArgAction, Parser};
 use csv::{ReaderBuilder, WriterBuilder};
-use deunicode::deunicode;
 use indicatif::{ProgressBar, ProgressStyle};
 use lazy_static::lazy_static;
 use log::{error, info};
+use normalization::normalize_text;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -13,14 +13,72 @@ use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 
+mod temp_tiers {
+    use log::{info, warn};
+    use std::error::Error;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Temp directories in order of preference, e.g. a small fast NVMe scratch
+    // disk followed by a larger HDD. Each spill file goes to the first tier
+    // with room for it, so work moves to the slower tiers only once the faster
+    // ones fill up.
+    pub struct TempTiers {
+        dirs: Vec<tempfile::TempDir>,
+        last_used: AtomicUsize,
+    }
+
+    impl TempTiers {
+        pub fn new(roots: &[PathBuf]) -> Result<Self, Box<dyn Error + Send + Sync>> {
+            let mut dirs = Vec::with_capacity(roots.len());
+            for root in roots {
+                let dir = tempfile::Builder::new().prefix("csv_proc_").tempdir_in(root)?;
+                info!("Using temporary directory (tier {}): {}", dirs.len() + 1, dir.path().display());
+                dirs.push(dir);
+            }
+            if dirs.is_empty() {
+                return Err("At least one temporary directory is required".into());
+            }
+            Ok(Self { dirs, last_used: AtomicUsize::new(0) })
+        }
+
+        // Returns the first tier with room for `needed_bytes` from every worker
+        // thread at once, since other workers may be spilling to the same tier.
+        // If none has, the tier with the most free space is used.
+        pub fn pick(&self, needed_bytes: u64) -> &Path {
+            let headroom = needed_bytes.saturating_mul(rayon::current_num_threads() as u64);
+            let available: Vec<u64> = self.dirs.iter()
+                .map(|d| fs4::available_space(d.path()).unwrap_or(u64::MAX))
+                .collect();
+            let index = match available.iter().position(|&free| free >= headroom) {
+                Some(index) => index,
+                None => {
+                    let (index, _) = available.iter().enumerate().max_by_key(|(_, free)| **free).unwrap();
+                    warn!(
+                        "No temporary directory has {} bytes free; using the one with the most space, {}",
+                        headroom, self.dirs[index].path().display()
+                    );
+                    index
+                }
+            };
+            let previous = self.last_used.swap(index, Ordering::Relaxed);
+            if index != previous {
+                info!("Spilling to temporary directory tier {}: {}", index + 1, self.dirs[index].path().display());
+            }
+            self.dirs[index].path()
+        }
+    }
+}
+
 mod external_sort {
+    use super::temp_tiers::TempTiers;
     use super::{Cli, InputRecord};
     use crossbeam_channel::bounded;
     use csv::{ReaderBuilder, WriterBuilder};
     use indicatif::{ProgressBar, ProgressStyle};
     use log::{error, info};
     use rayon::prelude::*;
-    use std::cmp::Ordering;
+    use std::cmp::{Ordering, Reverse};
     use std::collections::BinaryHeap;
     use std::error::Error;
     use std::fs::{self, File};
@@ -52,7 +110,7 @@ mod external_sort {
 
     fn create_sorted_chunks(
         input_path: &Path,
-        chunks_dir: &Path,
+        tiers: &TempTiers,
         chunk_size: usize,
     ) -> Result<Vec<PathBuf>, Box<dyn Error + Send + Sync>> {
         info!("Phase 1: Creating sorted chunks in parallel...");
@@ -63,7 +121,6 @@ mod external_sort {
         
         let (tx, rx) = bounded::<(Vec<u8>, bool)>(num_workers * 2);
         let chunk_index = Arc::new(AtomicUsize::new(0));
-        let chunks_dir = chunks_dir.to_path_buf();
         
         // Producer thread - reads blocks from the input file
         let input_path_clone = input_path.to_path_buf();
@@ -143,7 +200,7 @@ mod external_sort {
                     if records.len() >= chunk_size {
                         records.sort_by(|a, b| a.work_id.cmp(&b.work_id));
                         let idx = chunk_index.fetch_add(1, AtomicOrdering::SeqCst);
-                        let temp_path = chunks_dir.join(format!("chunk_{}.csv.zst", idx));
+                        let temp_path = tiers.pick(BLOCK_SIZE as u64).join(format!("chunk_{}.csv.zst", idx));
                         write_chunk(&records, &temp_path)?;
                         chunk_files.push(temp_path);
                         records.clear();
@@ -153,7 +210,7 @@ mod external_sort {
                 if !records.is_empty() {
                     records.sort_by(|a, b| a.work_id.cmp(&b.work_id));
                     let idx = chunk_index.fetch_add(1, AtomicOrdering::SeqCst);
-                    let temp_path = chunks_dir.join(format!("chunk_{}.csv.zst", idx));
+                    let temp_path = tiers.pick(BLOCK_SIZE as u64).join(format!("chunk_{}.csv.zst", idx));
                     write_chunk(&records, &temp_path)?;
                     chunk_files.push(temp_path);
                 }
@@ -253,10 +310,8 @@ mod external_sort {
         Ok(())
     }
     
-    pub fn sort_csv(cli: &Cli, output_path: &Path, chunks_dir: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let mut current_pass_dir = chunks_dir.join("pass_0");
-        fs::create_dir_all(&current_pass_dir)?;
-        let mut chunk_files = create_sorted_chunks(&cli.input, &current_pass_dir, cli.chunk_size)?;
+    pub fn sort_csv(cli: &Cli, output_path: &Path, tiers: &TempTiers) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut chunk_files = create_sorted_chunks(&cli.input, tiers, cli.chunk_size)?;
 
         let mut pass_num = 0;
         while chunk_files.len() > MERGE_WIDTH {
@@ -268,17 +323,18 @@ mod external_sort {
                 MERGE_WIDTH
             );
 
-            let next_pass_dir = chunks_dir.join(format!("pass_{}", pass_num));
-            fs::create_dir_all(&next_pass_dir)?;
-            
             let merge_results: Vec<(PathBuf, Vec<PathBuf>)> = chunk_files
                 .chunks(MERGE_WIDTH)
                 .collect::<Vec<_>>()
                 .into_par_iter()
                 .enumerate()
                 .map(|(i, group)| -> Result<(PathBuf, Vec<PathBuf>), Box<dyn Error + Send + Sync>> {
+                    let group_bytes: u64 = group.iter()
+                        .filter_map(|path| fs::metadata(path).ok())
+                        .map(|m| m.len())
+                        .sum();
                     let intermediate_output_path =
-                        next_pass_dir.join(format!("intermediate_chunk_{}.csv.zst", i));
+                        tiers.pick(group_bytes).join(format!("pass_{}_chunk_{}.csv.zst", pass_num, i));
                     
                     merge_chunks(group, &intermediate_output_path)?;
                     
@@ -294,36 +350,654 @@ mod external_sort {
                 }
             }
             
-            info!("Cleaning up directory: {}", current_pass_dir.display());
-            if let Err(e) = fs::remove_dir_all(&current_pass_dir) {
-                error!("Could not remove pass directory {}: {}", current_pass_dir.display(), e);
-            }
-
             chunk_files = merge_results.into_iter().map(|(path, _)| path).collect();
-            current_pass_dir = next_pass_dir;
         }
 
         info!("Starting final merge of {} chunks...", chunk_files.len());
         merge_chunks(&chunk_files, output_path)?;
 
-        info!("Cleaning up final chunks directory: {}", current_pass_dir.display());
-        if let Err(e) = fs::remove_dir_all(&current_pass_dir) {
-            error!("Could not remove final chunks directory {}: {}", current_pass_dir.display(), e);
+        info!("Cleaning up {} final chunks", chunk_files.len());
+        for chunk_to_delete in &chunk_files {
+            if let Err(e) = fs::remove_file(chunk_to_delete) {
+                error!("Failed to delete final chunk {}: {}", chunk_to_delete.display(), e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Collects candidate pairs of indices and hands them back sorted and
+    /// without duplicates. Up to `max_in_memory` pairs are buffered; beyond that
+    /// each batch is sorted and spilled as a zstd chunk, and the chunks are
+    /// merged like the record chunks above.
+    pub struct PairSpill<'a> {
+        tiers: &'a TempTiers,
+        max_in_memory: usize,
+        pairs: Vec<(usize, usize)>,
+        chunk_files: Vec<PathBuf>,
+    }
+
+    type PairReader = csv::Reader<zstd::Decoder<'static, BufReader<File>>>;
+    type PairResult = Result<(usize, usize), Box<dyn Error + Send + Sync>>;
+
+    impl<'a> PairSpill<'a> {
+        pub fn new(tiers: &'a TempTiers, max_in_memory: usize) -> Self {
+            Self { tiers, max_in_memory: max_in_memory.max(1), pairs: Vec::new(), chunk_files: Vec::new() }
+        }
+
+        pub fn push(&mut self, a: usize, b: usize) -> Result<(), Box<dyn Error + Send + Sync>> {
+            self.pairs.push((a.min(b), a.max(b)));
+            if self.pairs.len() >= self.max_in_memory {
+                self.pairs.sort_unstable();
+                self.pairs.dedup();
+                // Only spill if deduplication did not free enough room.
+                if self.pairs.len() * 2 > self.max_in_memory {
+                    self.spill()?;
+                }
+            }
+            Ok(())
+        }
+
+        pub fn spilled_chunks(&self) -> usize {
+            self.chunk_files.len()
+        }
+
+        fn spill(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+            self.pairs.sort_unstable();
+            self.pairs.dedup();
+            let bytes = (self.pairs.len() * std::mem::size_of::<(usize, usize)>()) as u64;
+            let path = self.tiers.pick(bytes).join(format!("pairs_{}.csv.zst", self.chunk_files.len()));
+            write_pairs(self.pairs.iter().copied().map(Ok), &path)?;
+            self.chunk_files.push(path);
+            self.pairs.clear();
+            Ok(())
+        }
+
+        /// Calls `f` once for every distinct pair, in ascending order.
+        pub fn for_each_sorted<F>(mut self, mut f: F) -> Result<(), Box<dyn Error + Send + Sync>>
+        where
+            F: FnMut(usize, usize) -> Result<(), Box<dyn Error + Send + Sync>>,
+        {
+            if self.chunk_files.is_empty() {
+                self.pairs.sort_unstable();
+                self.pairs.dedup();
+                for &(a, b) in &self.pairs {
+                    f(a, b)?;
+                }
+                return Ok(());
+            }
+            if !self.pairs.is_empty() {
+                self.spill()?;
+            }
+
+            let mut pass_num = 0;
+            while self.chunk_files.len() > MERGE_WIDTH {
+                pass_num += 1;
+                info!("Merging {} candidate pair chunks in groups of {} (pass {})", self.chunk_files.len(), MERGE_WIDTH, pass_num);
+                let mut merged = Vec::new();
+                for (i, group) in self.chunk_files.chunks(MERGE_WIDTH).enumerate() {
+                    let group_bytes: u64 = group.iter().filter_map(|path| fs::metadata(path).ok()).map(|m| m.len()).sum();
+                    let path = self.tiers.pick(group_bytes).join(format!("pairs_pass_{}_chunk_{}.csv.zst", pass_num, i));
+                    write_pairs(merge_pairs(group)?, &path)?;
+                    remove_chunks(group);
+                    merged.push(path);
+                }
+                self.chunk_files = merged;
+            }
+
+            for pair in merge_pairs(&self.chunk_files)? {
+                let (a, b) = pair?;
+                f(a, b)?;
+            }
+            remove_chunks(&self.chunk_files);
+            Ok(())
+        }
+    }
+
+    fn write_pairs<I>(pairs: I, path: &Path) -> Result<(), Box<dyn Error + Send + Sync>>
+    where
+        I: Iterator<Item = PairResult>,
+    {
+        let file = File::create(path)?;
+        let encoder = zstd::Encoder::new(file, 3)?.auto_finish();
+        let mut wtr = WriterBuilder::new().has_headers(false).from_writer(encoder);
+        for pair in pairs {
+            wtr.serialize(pair?)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    // Merges sorted pair chunks into one sorted stream, dropping pairs that
+    // appear in more than one chunk.
+    fn merge_pairs(
+        chunk_files: &[PathBuf],
+    ) -> Result<impl Iterator<Item = PairResult>, Box<dyn Error + Send + Sync>> {
+        let mut readers: Vec<PairReader> = chunk_files
+            .iter()
+            .map(|path| {
+                let decoder = zstd::Decoder::new(File::open(path)?)?;
+                Ok(ReaderBuilder::new().has_headers(false).from_reader(decoder))
+            })
+            .collect::<Result<_, Box<dyn Error + Send + Sync>>>()?;
+
+        let mut heap = BinaryHeap::new();
+        for (i, reader) in readers.iter_mut().enumerate() {
+            if let Some(result) = reader.deserialize::<(usize, usize)>().next() {
+                heap.push(Reverse((result?, i)));
+            }
+        }
+
+        let mut last = None;
+        Ok(std::iter::from_fn(move || loop {
+            let Reverse((pair, reader_index)) = heap.pop()?;
+            if let Some(result) = readers[reader_index].deserialize::<(usize, usize)>().next() {
+                match result {
+                    Ok(next) => heap.push(Reverse((next, reader_index))),
+                    Err(e) => return Some(Err(e.into())),
+                }
+            }
+            if last != Some(pair) {
+                last = Some(pair);
+                return Some(Ok(pair));
+            }
+        }))
+    }
+
+    fn remove_chunks(chunk_files: &[PathBuf]) {
+        for chunk_to_delete in chunk_files {
+            if let Err(e) = fs::remove_file(chunk_to_delete) {
+                error!("Failed to delete candidate pair chunk {}: {}", chunk_to_delete.display(), e);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn spilled_pairs_come_back_sorted_and_distinct() {
+            let dir = tempfile::tempdir().unwrap();
+            let tiers = TempTiers::new(&[dir.path().to_path_buf()]).unwrap();
+            let mut spill = PairSpill::new(&tiers, 4);
+            let pushed = [(5, 1), (2, 3), (1, 5), (9, 0), (3, 2), (4, 4), (0, 9), (7, 8), (6, 2), (1, 5), (8, 7)];
+            for (a, b) in pushed {
+                spill.push(a, b).unwrap();
+            }
+            assert!(spill.spilled_chunks() > 1);
+
+            let mut seen = Vec::new();
+            spill.for_each_sorted(|a, b| {
+                seen.push((a, b));
+                Ok(())
+            }).unwrap();
+            assert_eq!(seen, [(0, 9), (1, 5), (2, 3), (2, 6), (4, 4), (7, 8)]);
+            assert_eq!(fs::read_dir(tiers.pick(0)).unwrap().count(), 0);
         }
 
+        #[test]
+        fn more_chunks_than_the_merge_width_are_merged_in_passes() {
+            let dir = tempfile::tempdir().unwrap();
+            let tiers = TempTiers::new(&[dir.path().to_path_buf()]).unwrap();
+            let mut spill = PairSpill::new(&tiers, 1);
+            for i in (0..MERGE_WIDTH * 3).rev() {
+                spill.push(i % 50, i).unwrap();
+            }
+            assert_eq!(spill.spilled_chunks(), MERGE_WIDTH * 3);
+
+            let mut seen = Vec::new();
+            spill.for_each_sorted(|a, b| {
+                seen.push((a, b));
+                Ok(())
+            }).unwrap();
+            let mut expected: Vec<(usize, usize)> = (0..MERGE_WIDTH * 3).map(|i| ((i % 50).min(i), i)).collect();
+            expected.sort_unstable();
+            expected.dedup();
+            assert_eq!(seen, expected);
+            assert_eq!(fs::read_dir(tiers.pick(0)).unwrap().count(), 0);
+        }
+    }
+}
+
+mod disk_space {
+    use log::{info, warn};
+    use std::error::Error;
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::{Path, PathBuf};
+
+    const SAMPLE_BYTES: u64 = 16 * 1024 * 1024;
+    // Estimates come from sampling, so free space within this factor of the
+    // estimate is reported as tight rather than refused.
+    const WARN_MARGIN: f64 = 1.2;
+
+    // The external sort keeps every zstd chunk on disk while the final merge
+    // writes the uncompressed sorted copy, so the temp peak is the compressed
+    // input plus the input itself. The output is assumed to be no larger than
+    // the input.
+    // With several temp tiers, their free space is pooled: the sort spills from
+    // one tier to the next as they fill.
+    pub fn check_sort_and_output(input: &Path, temp_dirs: &[PathBuf], output: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let input_bytes = std::fs::metadata(input)?.len();
+        let temp_bytes = (input_bytes as f64 * zstd_ratio(input)?).ceil() as u64 + input_bytes;
+        let output_bytes = input_bytes;
+
+        let mut temp_locations: Vec<PathBuf> = Vec::new();
+        for dir in temp_dirs {
+            let location = existing_ancestor(dir);
+            if !temp_locations.iter().any(|seen| same_filesystem(seen, &location)) {
+                temp_locations.push(location);
+            }
+        }
+        let output_location = existing_ancestor(output);
+        let shares_output = temp_locations.iter().any(|location| same_filesystem(location, &output_location));
+        if shares_output {
+            check("sorting and output", &temp_locations, temp_bytes + output_bytes)
+        } else {
+            check("sorting", &temp_locations, temp_bytes)?;
+            check("output", &[output_location], output_bytes)
+        }
+    }
+
+    fn zstd_ratio(input: &Path) -> Result<f64, Box<dyn Error + Send + Sync>> {
+        let mut sample = Vec::new();
+        File::open(input)?.take(SAMPLE_BYTES).read_to_end(&mut sample)?;
+        if sample.is_empty() {
+            return Ok(0.0);
+        }
+        let compressed = zstd::encode_all(sample.as_slice(), 3)?;
+        Ok(compressed.len() as f64 / sample.len() as f64)
+    }
+
+    fn check(stage: &str, locations: &[PathBuf], needed_bytes: u64) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut available = 0u64;
+        for location in locations {
+            match fs4::available_space(location) {
+                Ok(bytes) => available = available.saturating_add(bytes),
+                Err(e) => {
+                    warn!("Could not determine free space on {}: {}. Skipping the {} space check.", location.display(), e, stage);
+                    return Ok(());
+                }
+            }
+        }
+        let location = locations.iter().map(|l| l.display().to_string()).collect::<Vec<_>>().join(" + ");
+        info!(
+            "Disk space for {}: an estimated {} needed, {} free on {}",
+            stage, format_bytes(needed_bytes), format_bytes(available), location
+        );
+        if needed_bytes > available {
+            return Err(format!(
+                "Not enough disk space for {}: an estimated {} is needed but only {} is free on {}. Free up space, use another --temp-dir or output location, or pass --no-space-check to run anyway.",
+                stage, format_bytes(needed_bytes), format_bytes(available), location
+            ).into());
+        }
+        if needed_bytes as f64 * WARN_MARGIN > available as f64 {
+            warn!("Disk space for {} is tight: the estimate is within {:.0}% of the free space on {}.", stage, (WARN_MARGIN - 1.0) * 100.0, location);
+        }
         Ok(())
     }
+
+    fn existing_ancestor(path: &Path) -> PathBuf {
+        let mut current = if path.is_absolute() { path.to_path_buf() } else { Path::new(".").join(path) };
+        while !current.exists() {
+            if !current.pop() {
+                return PathBuf::from(".");
+            }
+        }
+        current
+    }
+
+    #[cfg(unix)]
+    fn same_filesystem(a: &Path, b: &Path) -> bool {
+        use std::os::unix::fs::MetadataExt;
+        match (std::fs::metadata(a), std::fs::metadata(b)) {
+            (Ok(ma), Ok(mb)) => ma.dev() == mb.dev(),
+            _ => true,
+        }
+    }
+
+    // Without device ids, assume the worst case of one shared filesystem.
+    #[cfg(not(unix))]
+    fn same_filesystem(_a: &Path, _b: &Path) -> bool {
+        true
+    }
+
+    fn format_bytes(bytes: u64) -> String {
+        const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+        let mut value = bytes as f64;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 {
+            format!("{} B", bytes)
+        } else {
+            format!("{:.1} {}", value, UNITS[unit])
+        }
+    }
+}
+
+mod affiliation_clusters {
+    use super::external_sort::PairSpill;
+    use super::temp_tiers::TempTiers;
+    use csv::WriterBuilder;
+    use log::{debug, info};
+    use rayon::prelude::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashMap;
+    use std::error::Error;
+    use std::hash::{Hash, Hasher};
+    use std::path::Path;
+
+    const SHINGLE_SIZE: usize = 3;
+    const BANDS: usize = 20;
+    const ROWS_PER_BAND: usize = 5;
+    const NUM_HASHES: usize = BANDS * ROWS_PER_BAND;
+    // Buckets larger than this are only verified against their first member, which keeps
+    // very common band values from degrading into all-pairs comparison.
+    const MAX_PAIRWISE_BUCKET: usize = 64;
+
+    #[derive(serde::Serialize)]
+    struct ClusterRecord<'a> {
+        normalized_affiliation_name: &'a str,
+        cluster_id: usize,
+        cluster_representative: &'a str,
+        occurrences: usize,
+        cluster_size: usize,
+    }
+
+    fn splitmix64(mut x: u64) -> u64 {
+        x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        x ^ (x >> 31)
+    }
+
+    fn shingles(text: &str) -> Vec<u64> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut hashes: Vec<u64> = if chars.len() <= SHINGLE_SIZE {
+            let mut hasher = DefaultHasher::new();
+            text.hash(&mut hasher);
+            vec![hasher.finish()]
+        } else {
+            chars
+                .windows(SHINGLE_SIZE)
+                .map(|w| {
+                    let mut hasher = DefaultHasher::new();
+                    w.hash(&mut hasher);
+                    hasher.finish()
+                })
+                .collect()
+        };
+        hashes.sort_unstable();
+        hashes.dedup();
+        hashes
+    }
+
+    fn minhash_signature(shingle_hashes: &[u64]) -> Vec<u64> {
+        (0..NUM_HASHES as u64)
+            .map(|seed| {
+                let salt = splitmix64(seed);
+                shingle_hashes
+                    .iter()
+                    .map(|h| splitmix64(h ^ salt))
+                    .min()
+                    .unwrap_or(u64::MAX)
+            })
+            .collect()
+    }
+
+    fn jaccard(a: &[u64], b: &[u64]) -> f64 {
+        let (mut i, mut j, mut intersection) = (0, 0, 0usize);
+        while i < a.len() && j < b.len() {
+            match a[i].cmp(&b[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    intersection += 1;
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        let union = a.len() + b.len() - intersection;
+        if union == 0 { 1.0 } else { intersection as f64 / union as f64 }
+    }
+
+    fn find(parents: &mut [usize], mut x: usize) -> usize {
+        while parents[x] != x {
+            parents[x] = parents[parents[x]];
+            x = parents[x];
+        }
+        x
+    }
+
+    fn union(parents: &mut [usize], a: usize, b: usize) {
+        let (ra, rb) = (find(parents, a), find(parents, b));
+        if ra != rb {
+            parents[ra.max(rb)] = ra.min(rb);
+        }
+    }
+
+    /// Groups near-identical normalized affiliation strings and writes one row per distinct
+    /// string with its cluster id and representative (the most frequent member).
+    ///
+    /// Candidate pairs from all bands are collected in a `PairSpill`, which keeps up to
+    /// `max_pairs_in_memory` of them in memory and spills the rest to the temp tiers, so
+    /// each distinct pair is scored once.
+    pub fn cluster_and_write(
+        counts: &HashMap<String, usize>,
+        threshold: f64,
+        output_path: &Path,
+        tiers: &TempTiers,
+        max_pairs_in_memory: usize,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let mut names: Vec<&String> = counts.keys().collect();
+        names.sort();
+        info!("Clustering {} distinct normalized affiliation strings (Jaccard >= {})...", names.len(), threshold);
+
+        let shingle_sets: Vec<Vec<u64>> = names.par_iter().map(|name| shingles(name)).collect();
+        let signatures: Vec<Vec<u64>> = shingle_sets.par_iter().map(|s| minhash_signature(s)).collect();
+
+        let mut candidates = PairSpill::new(tiers, max_pairs_in_memory);
+        for band in 0..BANDS {
+            let mut buckets: HashMap<&[u64], Vec<usize>> = HashMap::new();
+            for (idx, signature) in signatures.iter().enumerate() {
+                let rows = &signature[band * ROWS_PER_BAND..(band + 1) * ROWS_PER_BAND];
+                buckets.entry(rows).or_default().push(idx);
+            }
+            for members in buckets.values().filter(|m| m.len() > 1) {
+                let pairwise = members.len() <= MAX_PAIRWISE_BUCKET;
+                for (pos, &a) in members.iter().enumerate() {
+                    let partners = if pairwise { &members[pos + 1..] } else if pos == 0 { &members[1..] } else { &[][..] };
+                    for &b in partners {
+                        candidates.push(a, b)?;
+                    }
+                }
+            }
+        }
+        if candidates.spilled_chunks() > 0 {
+            info!("Candidate pairs exceeded the memory budget; spilled {} chunks to disk.", candidates.spilled_chunks());
+        }
+
+        let mut parents: Vec<usize> = (0..names.len()).collect();
+        let mut comparisons = 0usize;
+        candidates.for_each_sorted(|a, b| {
+            if find(&mut parents, a) != find(&mut parents, b) {
+                comparisons += 1;
+                if jaccard(&shingle_sets[a], &shingle_sets[b]) >= threshold {
+                    union(&mut parents, a, b);
+                }
+            }
+            Ok(())
+        })?;
+        debug!("Affiliation clustering verified {} candidate pairs.", comparisons);
+
+        let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+        for idx in 0..names.len() {
+            let root = find(&mut parents, idx);
+            clusters.entry(root).or_default().push(idx);
+        }
+
+        let mut ordered_clusters: Vec<Vec<usize>> = clusters.into_values().collect();
+        ordered_clusters.sort_by_key(|members| members[0]);
+
+        let mut wtr = WriterBuilder::new().from_path(output_path)?;
+        for (cluster_id, members) in ordered_clusters.iter().enumerate() {
+            let representative = members
+                .iter()
+                .max_by(|&&a, &&b| counts[names[a]].cmp(&counts[names[b]]).then_with(|| names[b].cmp(names[a])))
+                .map(|&idx| names[idx].as_str())
+                .unwrap_or("");
+            for &idx in members {
+                wtr.serialize(ClusterRecord {
+                    normalized_affiliation_name: names[idx],
+                    cluster_id,
+                    cluster_representative: representative,
+                    occurrences: counts[names[idx]],
+                    cluster_size: members.len(),
+                })?;
+            }
+        }
+        wtr.flush()?;
+
+        info!(
+            "Grouped {} distinct affiliation strings into {} clusters -> {}",
+            names.len(), ordered_clusters.len(), output_path.display()
+        );
+        Ok(ordered_clusters.len())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn estimated_jaccard(a: &str, b: &str) -> f64 {
+            let (sa, sb) = (minhash_signature(&shingles(a)), minhash_signature(&shingles(b)));
+            sa.iter().zip(&sb).filter(|(x, y)| x == y).count() as f64 / NUM_HASHES as f64
+        }
+
+        fn shares_band(a: &str, b: &str) -> bool {
+            let (sa, sb) = (minhash_signature(&shingles(a)), minhash_signature(&shingles(b)));
+            sa.chunks(ROWS_PER_BAND).zip(sb.chunks(ROWS_PER_BAND)).any(|(x, y)| x == y)
+        }
+
+        fn cluster_ids(names: &[&str], threshold: f64, max_pairs_in_memory: usize) -> HashMap<String, usize> {
+            let counts: HashMap<String, usize> = names.iter().map(|n| (n.to_string(), 1)).collect();
+            let dir = tempfile::tempdir().unwrap();
+            let tiers = TempTiers::new(&[dir.path().to_path_buf()]).unwrap();
+            let path = dir.path().join("clusters.csv");
+            cluster_and_write(&counts, threshold, &path, &tiers, max_pairs_in_memory).unwrap();
+            let mut reader = csv::Reader::from_path(&path).unwrap();
+            reader
+                .records()
+                .map(|r| {
+                    let r = r.unwrap();
+                    (r[0].to_string(), r[1].parse().unwrap())
+                })
+                .collect()
+        }
+
+        #[test]
+        fn exact_jaccard_counts_shared_shingles() {
+            assert_eq!(jaccard(&shingles("university of oxford"), &shingles("university of oxford")), 1.0);
+            assert_eq!(jaccard(&shingles("abcd"), &shingles("wxyz")), 0.0);
+            // "abcde" -> abc bcd cde, "abcdf" -> abc bcd cdf: 2 shared of 4.
+            assert_eq!(jaccard(&shingles("abcde"), &shingles("abcdf")), 0.5);
+            assert_eq!(jaccard(&shingles("ab"), &shingles("ab")), 1.0);
+        }
+
+        #[test]
+        fn minhash_estimates_jaccard_within_tolerance() {
+            let pairs = [
+                ("university of oxford uk", "university oxford uk"),
+                ("univ of oxford uk", "university of oxford uk"),
+                ("department of chemistry university of oxford", "dept of chemistry university of oxford"),
+                ("massachusetts institute of technology", "massachusets institute of technology"),
+                ("max planck institute for biology", "university of cape town"),
+            ];
+            for (a, b) in pairs {
+                let exact = jaccard(&shingles(a), &shingles(b));
+                let estimate = estimated_jaccard(a, b);
+                // With 100 hashes the standard error is at most 0.05.
+                assert!((exact - estimate).abs() <= 0.15, "{} / {}: exact {:.3}, estimate {:.3}", a, b, exact, estimate);
+            }
+            assert_eq!(estimated_jaccard("university of oxford", "university of oxford"), 1.0);
+        }
+
+        #[test]
+        fn banding_finds_pairs_above_the_default_threshold() {
+            let base = "faculty of medicine university of example city";
+            let mut checked = 0;
+            for (i, c) in base.char_indices() {
+                for replacement in ['x', 'q'] {
+                    let variant = format!("{}{}{}", &base[..i], replacement, &base[i + c.len_utf8()..]);
+                    if jaccard(&shingles(base), &shingles(&variant)) < 0.8 {
+                        continue;
+                    }
+                    checked += 1;
+                    assert!(shares_band(base, &variant), "{:?} was never a candidate for {:?}", variant, base);
+                }
+            }
+            assert!(checked > 50, "only {} variants were checked", checked);
+            assert!(!shares_band("university of oxford", "tokyo institute of technology"));
+        }
+
+        #[test]
+        fn union_find_merges_transitively() {
+            let mut parents: Vec<usize> = (0..5).collect();
+            union(&mut parents, 3, 4);
+            union(&mut parents, 1, 3);
+            assert_eq!(find(&mut parents, 4), 1);
+            assert_ne!(find(&mut parents, 0), find(&mut parents, 4));
+            union(&mut parents, 4, 0);
+            assert!((0..5).filter(|&x| x != 2).all(|x| find(&mut parents, x) == 0));
+            assert_eq!(find(&mut parents, 2), 2);
+        }
+
+        #[test]
+        fn clusters_chain_through_intermediate_strings() {
+            let a = "department of physics university of oxford";
+            let b = "department of physics university of oxford parks rd";
+            let c = "department of physics university of oxford parks road uk";
+            assert!(jaccard(&shingles(a), &shingles(b)) >= 0.8);
+            assert!(jaccard(&shingles(b), &shingles(c)) >= 0.8);
+            assert!(jaccard(&shingles(a), &shingles(c)) < 0.8);
+
+            let ids = cluster_ids(&[a, b, c, "university of cape town"], 0.8, 1 << 20);
+            assert_eq!(ids[a], ids[b]);
+            assert_eq!(ids[b], ids[c]);
+            assert_ne!(ids[a], ids["university of cape town"]);
+        }
+
+        #[test]
+        fn spilling_candidate_pairs_gives_the_same_clusters() {
+            let mut names = Vec::new();
+            for place in ["oxford", "cambridge", "edinburgh", "manchester"] {
+                names.push(format!("department of physics university of {}", place));
+                names.push(format!("department of physics university of {} uk", place));
+                names.push(format!("dept of physics university of {}", place));
+                names.push(format!("school of medicine university of {}", place));
+            }
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            let in_memory = cluster_ids(&names, 0.7, 1 << 20);
+            let spilled = cluster_ids(&names, 0.7, 2);
+            assert_eq!(in_memory, spilled);
+            assert!(in_memory.values().collect::<std::collections::HashSet<_>>().len() < names.len());
+        }
+    }
 }
 
 lazy_static! {
     static ref AUTHORSHIP_INDEX_RE: Regex = Regex::new(r"authorships\[(\d+)\]").unwrap();
     static ref AFFILIATION_INDEX_RE: Regex = Regex::new(r"affiliations\[(\d+)\]").unwrap();
     static ref INSTITUTION_INDEX_RE: Regex = Regex::new(r"institutions\[(\d+)\]").unwrap();
-    static ref NORMALIZE_RE: Regex = Regex::new(r"[^\w\s]").unwrap();
 }
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = "A memory-efficient Rust script that first sorts a large CSV by 'work_id' and then processes it.")]
+#[command(author, version, long_version = VERSION_INFO.as_str(), about, long_about = "A memory-efficient Rust script that first sorts a large CSV by 'work_id' and then processes it.")]
 struct Cli {
     #[arg(short = 'i', long)]
     input: PathBuf,
@@ -334,8 +1008,45 @@ struct Cli {
     #[arg(long, default_value_t = 500_000)]
     chunk_size: usize,
 
-    #[arg(long)]
-    temp_dir: Option<PathBuf>,
+    #[arg(long, help = "Temporary directory for the external sort; repeat to list tiers in order of preference (e.g. fast NVMe first, then a larger disk), spilling to the next tier when one fills up")]
+    temp_dir: Vec<PathBuf>,
+
+    #[arg(long, help = "Skip the check that the temp and output filesystems have room for the sort and the output")]
+    no_space_check: bool,
+
+    #[arg(long, help = "Group near-identical normalized affiliation strings (MinHash/LSH) and write a cluster mapping CSV")]
+    cluster_affiliations: bool,
+
+    #[arg(long, default_value_t = 0.8, value_parser = parse_similarity, help = "Minimum character-shingle Jaccard similarity for two affiliation strings to share a cluster, from 0.0 to 1.0")]
+    cluster_threshold: f64,
+
+    #[arg(long, help = "Path for the affiliation cluster mapping (default: <output>_affiliation_clusters.csv)")]
+    cluster_output: Option<PathBuf>,
+
+    #[arg(long, default_value_t = 1024, help = "Memory budget in MB for affiliation clustering candidate pairs; beyond it, pairs are spilled as zstd-compressed chunks to --temp-dir")]
+    cluster_memory_mb: usize,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+fn parse_similarity(value: &str) -> Result<f64, String> {
+    let similarity: f64 = value.parse().map_err(|_| format!("expected a number from 0.0 to 1.0, got '{}'", value))?;
+    if !(0.0..=1.0).contains(&similarity) {
+        return Err(format!("a Jaccard similarity is from 0.0 to 1.0, got {}", value));
+    }
+    Ok(similarity)
+}
+
+// Bump when the output columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+lazy_static! {
+    static ref VERSION_INFO: String = format!(
+        "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+        env!("CARGO_PKG_VERSION"), env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+    );
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
@@ -373,13 +1084,6 @@ struct OutputRecord {
     affiliation_ror: String,
 }
 
-fn normalize_text(text: &str) -> String {
-    let unidecoded = deunicode(text);
-    let lowercased = unidecoded.to_lowercase();
-    let cleaned = NORMALIZE_RE.replace_all(&lowercased, "");
-    cleaned.trim().to_string()
-}
-
 #[derive(Debug, Default)]
 struct TempAffiliation {
     raw_string: Option<String>,
@@ -399,6 +1103,7 @@ fn process_work_group(
     doi: &Option<String>,
     records: &[InputRecord],
     wtr: &mut csv::Writer<File>,
+    mut affiliation_counts: Option<&mut HashMap<String, usize>>,
 ) -> Result<usize, Box<dyn Error + Send + Sync>> {
     let mut records_written = 0;
 
@@ -502,6 +1207,11 @@ fn process_work_group(
             for affiliation in author_affiliations {
                 let affiliation_name = affiliation.raw_string.as_deref().unwrap_or("");
                 let normalized_affiliation_name = normalize_text(affiliation_name);
+                if let Some(counts) = affiliation_counts.as_deref_mut() {
+                    if !normalized_affiliation_name.is_empty() {
+                        *counts.entry(normalized_affiliation_name.clone()).or_insert(0) += 1;
+                    }
+                }
 
                 let mut affiliation_ror = "".to_string();
                 for inst_id in &affiliation.institution_ids {
@@ -544,22 +1254,19 @@ fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     }
     let output_path = cli.output.as_ref().unwrap();
 
-    let _main_temp_dir = if let Some(path) = &cli.temp_dir {
-        tempfile::Builder::new().prefix("csv_proc_").tempdir_in(path)?
-    } else {
-        tempfile::Builder::new().prefix("csv_proc_").tempdir()?
-    };
-    let temp_dir_path = _main_temp_dir.path();
-    info!("Using temporary directory: {}", temp_dir_path.display());
+    let temp_roots = if cli.temp_dir.is_empty() { vec![std::env::temp_dir()] } else { cli.temp_dir.clone() };
+    if !cli.no_space_check {
+        disk_space::check_sort_and_output(&cli.input, &temp_roots, output_path)?;
+    }
+    let temp_tiers = temp_tiers::TempTiers::new(&temp_roots)?;
 
     let sort_start_time = Instant::now();
     info!("Starting external sort...");
     
-    let chunks_dir = temp_dir_path.join("chunks");
-    fs::create_dir_all(&chunks_dir)?;
-    let temp_sorted_path = temp_dir_path.join("sorted_data.csv");
+    let input_size = fs::metadata(&cli.input)?.len();
+    let temp_sorted_path = temp_tiers.pick(input_size).join("sorted_data.csv");
     
-    external_sort::sort_csv(&cli, &temp_sorted_path, &chunks_dir)?;
+    external_sort::sort_csv(&cli, &temp_sorted_path, &temp_tiers)?;
     info!("External sort finished in {:.2?}.", sort_start_time.elapsed());
 
     info!("Starting streaming aggregation from sorted temporary file...");
@@ -586,6 +1293,7 @@ fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     let mut records_for_current_work: Vec<InputRecord> = Vec::new();
     let mut total_records_written = 0;
     let mut total_works_processed = 0;
+    let mut affiliation_counts: HashMap<String, usize> = HashMap::new();
 
     for (i, result) in rdr.deserialize::<InputRecord>().enumerate() {
         let record = match result {
@@ -600,7 +1308,13 @@ fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
             let work_id_to_process = current_work_id.clone().unwrap();
             let doi_to_process = current_doi.clone();
             
-            let written_count = process_work_group(&work_id_to_process, &doi_to_process, &records_for_current_work, &mut wtr)?;
+            let written_count = process_work_group(
+                &work_id_to_process,
+                &doi_to_process,
+                &records_for_current_work,
+                &mut wtr,
+                cli.cluster_affiliations.then_some(&mut affiliation_counts),
+            )?;
             total_records_written += written_count;
             total_works_processed += 1;
             
@@ -614,7 +1328,13 @@ fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
 
     if let Some(work_id) = current_work_id {
         if !records_for_current_work.is_empty() {
-            let written_count = process_work_group(&work_id, &current_doi, &records_for_current_work, &mut wtr)?;
+            let written_count = process_work_group(
+                &work_id,
+                &current_doi,
+                &records_for_current_work,
+                &mut wtr,
+                cli.cluster_affiliations.then_some(&mut affiliation_counts),
+            )?;
             total_records_written += written_count;
             total_works_processed += 1;
         }
@@ -627,6 +1347,19 @@ fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
         "Streaming process complete in {:.2?}. Processed {} unique work IDs and wrote {} records.",
         process_start_time.elapsed(), total_works_processed, total_records_written
     );
+
+    if cli.cluster_affiliations {
+        let cluster_start_time = Instant::now();
+        let cluster_path = cli.cluster_output.clone().unwrap_or_else(|| {
+            let stem = output_path.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+            output_path.with_file_name(format!("{}_affiliation_clusters.csv", stem))
+        });
+        let max_pairs_in_memory = cli.cluster_memory_mb * 1024 * 1024 / std::mem::size_of::<(usize, usize)>();
+        affiliation_clusters::cluster_and_write(
+            &affiliation_counts, cli.cluster_threshold, &cluster_path, &temp_tiers, max_pairs_in_memory,
+        )?;
+        info!("Affiliation clustering finished in {:.2?}.", cluster_start_time.elapsed());
+    }
     info!(
         "Total time for all operations: {:.2?}",
         overall_start_time.elapsed()
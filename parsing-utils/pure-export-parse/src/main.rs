@@ -0,0 +1,650 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser};
+use csv::Writer;
+use flate2::read::GzDecoder;
+use glob::glob;
+use log::{debug, info, warn, LevelFilter};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use serde_json::{Map, Value};
+use simple_logger::SimpleLogger;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use time::macros::format_description;
+
+#[derive(Parser)]
+#[command(name = "Pure Research Output Parser")]
+#[command(about = "Map Elsevier Pure research-output exports (XML or JSON) into the pipeline's long field format")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(short, long, help = "Pure export file (.json, .jsonl, .xml, optionally .gz) or directory of them", required = true)]
+    input: String,
+
+    #[arg(short, long, default_value = "pure_field_data.csv", help = "Output CSV file")]
+    output: String,
+
+    #[arg(short, long, help = "Comma-separated list of fields to keep (e.g., 'author.family,author.affiliation.name'); default all")]
+    fields: Option<String>,
+
+    #[arg(long, help = "Skip research outputs without a DOI")]
+    require_doi: bool,
+
+    #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
+    log_level: String,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the output columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+static VERSION_INFO: LazyLock<String> = LazyLock::new(|| format!(
+    "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+    VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+));
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Doi(String);
+
+// Pure's XML and JSON web-service formats describe the same model, so XML items are
+// converted to the JSON shape and both go through one extractor. Plural wrapper
+// elements (personAssociations/personAssociation) become arrays, attributes become
+// keys, and text in an element with attributes becomes "value".
+#[derive(Debug, Default)]
+struct XmlNode {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    children: Vec<XmlNode>,
+}
+
+// Formatting in formatted titles and abstracts; its text is kept inline in the parent.
+const INLINE_MARKUP: &[&str] = &["i", "b", "em", "strong", "sup", "sub", "u", "span"];
+
+fn node_from_start(element: &BytesStart) -> XmlNode {
+    let attributes = element.attributes()
+        .filter_map(|a| a.ok())
+        .filter(|a| !a.key.as_ref().starts_with(b"xmlns"))
+        .filter_map(|a| {
+            let key = String::from_utf8_lossy(a.key.local_name().as_ref()).into_owned();
+            a.unescape_value().ok().map(|v| (key, v.trim().to_string()))
+        })
+        .collect();
+    XmlNode {
+        name: String::from_utf8_lossy(element.local_name().as_ref()).into_owned(),
+        attributes,
+        text: String::new(),
+        children: Vec::new(),
+    }
+}
+
+fn parse_xml_tree(content: &[u8]) -> Result<Option<XmlNode>> {
+    let mut reader = Reader::from_reader(content);
+    let mut buf = Vec::new();
+    let mut stack: Vec<XmlNode> = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf).with_context(|| format!("Malformed XML at byte {}", reader.buffer_position()))? {
+            Event::Start(e) => stack.push(node_from_start(&e)),
+            Event::Empty(e) => {
+                let node = node_from_start(&e);
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => return Ok(Some(node)),
+                }
+            }
+            Event::Text(t) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&t.unescape().context("Invalid XML text")?);
+                }
+            }
+            Event::CData(c) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&String::from_utf8_lossy(&c));
+                }
+            }
+            Event::End(_) => {
+                if let Some(mut node) = stack.pop() {
+                    match stack.last_mut() {
+                        Some(parent) if INLINE_MARKUP.contains(&node.name.as_str()) && node.children.is_empty() => {
+                            parent.text.push_str(&node.text);
+                        }
+                        Some(parent) => {
+                            node.text = node.text.trim().to_string();
+                            parent.children.push(node);
+                        }
+                        None => {
+                            node.text = node.text.trim().to_string();
+                            return Ok(Some(node));
+                        }
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(None)
+}
+
+fn is_plural_of(wrapper: &str, item: &str) -> bool {
+    wrapper == format!("{}s", item) || wrapper == format!("{}es", item)
+}
+
+fn xml_to_json(node: &XmlNode) -> Value {
+    if node.children.is_empty() && node.attributes.is_empty() {
+        return Value::String(node.text.clone());
+    }
+    let is_list = !node.children.is_empty() && node.children.iter().all(|c| is_plural_of(&node.name, &c.name));
+    if is_list && node.attributes.is_empty() {
+        return Value::Array(node.children.iter().map(xml_to_json).collect());
+    }
+
+    let mut object = Map::new();
+    for (key, value) in &node.attributes {
+        object.insert(key.clone(), Value::String(value.clone()));
+    }
+    if !node.text.is_empty() {
+        object.insert("value".to_string(), Value::String(node.text.clone()));
+    }
+    for child in &node.children {
+        let converted = xml_to_json(child);
+        let repeated = node.children.iter().filter(|c| c.name == child.name).count() > 1;
+        match object.get_mut(&child.name) {
+            Some(Value::Array(existing)) if repeated => existing.push(converted),
+            _ if repeated => {
+                object.insert(child.name.clone(), Value::Array(vec![converted]));
+            }
+            _ => {
+                object.insert(child.name.clone(), converted);
+            }
+        }
+    }
+    Value::Object(object)
+}
+
+// Research outputs are the children of <items> in a web-service response; a file
+// holding a single output has it as the root element.
+fn xml_items(root: &XmlNode) -> Vec<Value> {
+    fn find_items(node: &XmlNode, found: &mut Vec<Value>) {
+        for child in &node.children {
+            if child.name == "items" {
+                found.extend(child.children.iter().map(xml_to_json));
+            } else {
+                find_items(child, found);
+            }
+        }
+    }
+    let mut found = Vec::new();
+    find_items(root, &mut found);
+    if found.is_empty() && root.attributes.iter().any(|(k, _)| k == "uuid") {
+        found.push(xml_to_json(root));
+    }
+    found
+}
+
+fn json_items(document: Value) -> Vec<Value> {
+    match document {
+        Value::Array(items) => items,
+        Value::Object(mut object) => match object.remove("items") {
+            Some(Value::Array(items)) => items,
+            Some(other) => vec![other],
+            None => vec![Value::Object(object)],
+        },
+        _ => Vec::new(),
+    }
+}
+
+fn get_any<'a>(value: &'a Value, keys: &[&str]) -> Option<&'a Value> {
+    keys.iter().find_map(|k| value.get(*k)).filter(|v| !v.is_null())
+}
+
+fn as_list(value: Option<&Value>) -> Vec<&Value> {
+    match value {
+        Some(Value::Array(items)) => items.iter().collect(),
+        Some(Value::Null) | None => Vec::new(),
+        Some(other) => vec![other],
+    }
+}
+
+fn is_locale_key(key: &str) -> bool {
+    let bytes = key.as_bytes();
+    bytes.len() == 5 && bytes[2] == b'_' && bytes[..2].iter().all(u8::is_ascii_lowercase)
+}
+
+// Pure text comes as a plain string, {"value": ...}, {"text": [{"locale", "value"}]},
+// {"term": {"en_GB": ...}} and similar nestings depending on API version and format.
+// English is preferred where several locales are given.
+fn localized_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Array(items) => items.iter()
+            .find(|item| item.get("locale").and_then(Value::as_str).is_some_and(|l| l.starts_with("en")))
+            .or_else(|| items.first())
+            .and_then(localized_text),
+        Value::Object(object) => {
+            for key in ["value", "text", "term", "title", "issn", "formatted"] {
+                if let Some(text) = object.get(key).filter(|v| !v.is_boolean()).and_then(localized_text) {
+                    return Some(text);
+                }
+            }
+            let locales: Vec<(&String, &Value)> = object.iter().filter(|(k, _)| is_locale_key(k)).collect();
+            locales.iter()
+                .find(|(k, _)| k.starts_with("en"))
+                .or_else(|| locales.first())
+                .and_then(|(_, v)| localized_text(v))
+        }
+        _ => None,
+    }
+}
+
+fn text_at(value: &Value, keys: &[&str]) -> Option<String> {
+    get_any(value, keys).and_then(localized_text)
+}
+
+fn normalize_doi(raw: &str) -> Option<Doi> {
+    normalization::normalize_doi(raw).map(Doi)
+}
+
+struct ResearchOutput {
+    uuid: String,
+    doi: Option<Doi>,
+    fields: Vec<(String, String, String)>,
+}
+
+fn push(fields: &mut Vec<(String, String, String)>, name: &str, path: String, value: Option<String>) {
+    if let Some(value) = value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) {
+        fields.push((name.to_string(), path, value));
+    }
+}
+
+fn date_parts(date: &Value) -> Option<String> {
+    let parts: Vec<String> = ["year", "month", "day"].iter()
+        .map_while(|p| date.get(*p).and_then(localized_text).and_then(|t| t.parse::<u32>().ok()).map(|n| n.to_string()))
+        .collect();
+    (!parts.is_empty()).then(|| format!("[[{}]]", parts.join(",")))
+}
+
+// Maps a research output onto Crossref field names so it can be compared directly
+// with the Crossref field parser's output.
+fn extract_output(item: &Value) -> ResearchOutput {
+    let mut fields = Vec::new();
+    let uuid = text_at(item, &["uuid"]).unwrap_or_default();
+    push(&mut fields, "pure-id", "pure-id".to_string(), text_at(item, &["pureId"]));
+
+    let doi = as_list(get_any(item, &["electronicVersions"]))
+        .into_iter()
+        .find_map(|version| text_at(version, &["doi"]).as_deref().and_then(normalize_doi));
+    push(&mut fields, "DOI", "DOI".to_string(), doi.as_ref().map(|d| d.0.clone()));
+
+    push(&mut fields, "title", "title[0]".to_string(), text_at(item, &["title"]));
+    push(&mut fields, "subtitle", "subtitle[0]".to_string(), text_at(item, &["subTitle", "subtitle"]));
+    push(&mut fields, "type", "type".to_string(), text_at(item, &["type"]));
+
+    if let Some(journal) = get_any(item, &["journalAssociation"]) {
+        push(&mut fields, "container-title", "container-title[0]".to_string(), text_at(journal, &["title"]));
+        push(&mut fields, "ISSN", "ISSN[0]".to_string(), text_at(journal, &["issn"]));
+    }
+    push(&mut fields, "volume", "volume".to_string(), text_at(item, &["volume"]));
+    push(&mut fields, "issue", "issue".to_string(), text_at(item, &["journalNumber", "issue"]));
+    push(&mut fields, "page", "page".to_string(), text_at(item, &["pages"]));
+    push(&mut fields, "article-number", "article-number".to_string(), text_at(item, &["articleNumber"]));
+
+    for status in as_list(get_any(item, &["publicationStatuses"])) {
+        let label = get_any(status, &["publicationStatus"])
+            .map(|s| format!("{} {}", text_at(s, &["uri"]).unwrap_or_default(), localized_text(s).unwrap_or_default()).to_lowercase())
+            .unwrap_or_default();
+        let field = if label.contains("epub") || label.contains("e-pub") {
+            "published-online.date-parts"
+        } else if label.contains("published") {
+            "published.date-parts"
+        } else {
+            continue;
+        };
+        push(&mut fields, field, field.to_string(), get_any(status, &["publicationDate"]).and_then(date_parts));
+    }
+
+    let mut counters = [0usize; 2];
+    for contributor in as_list(get_any(item, &["personAssociations", "contributors"])) {
+        let role = text_at(contributor, &["personRole", "role"]).unwrap_or_default();
+        let (list, slot) = if role.to_lowercase().contains("editor") { ("editor", 1) } else { ("author", 0) };
+        let base = format!("{}[{}]", list, counters[slot]);
+        let name = get_any(contributor, &["name"]);
+        push(&mut fields, &format!("{}.family", list), format!("{}.family", base), name.and_then(|n| text_at(n, &["lastName"])));
+        push(&mut fields, &format!("{}.given", list), format!("{}.given", base), name.and_then(|n| text_at(n, &["firstName"])));
+        if list == "author" {
+            push(&mut fields, "author.sequence", format!("{}.sequence", base), Some(if counters[slot] == 0 { "first" } else { "additional" }.to_string()));
+        }
+        push(&mut fields, &format!("{}.role", list), format!("{}.role", base), Some(role));
+        let person = get_any(contributor, &["person", "externalPerson"]);
+        push(&mut fields, &format!("{}.person-uuid", list), format!("{}.person-uuid", base), person.and_then(|p| text_at(p, &["uuid"])));
+
+        let internal = as_list(get_any(contributor, &["organisationalUnits", "organizations", "organisations"]));
+        let external = as_list(get_any(contributor, &["externalOrganisations", "externalOrganizations"]));
+        for (j, (org, is_external)) in internal.into_iter().map(|o| (o, false))
+            .chain(external.into_iter().map(|o| (o, true)))
+            .enumerate()
+        {
+            let aff_base = format!("{}.affiliation[{}]", base, j);
+            push(&mut fields, &format!("{}.affiliation.name", list), format!("{}.name", aff_base), text_at(org, &["name"]));
+            if let Some(org_uuid) = text_at(org, &["uuid"]) {
+                push(&mut fields, &format!("{}.affiliation.id.id", list), format!("{}.id[0].id", aff_base), Some(org_uuid));
+                push(&mut fields, &format!("{}.affiliation.id.id-type", list), format!("{}.id[0].id-type", aff_base), Some("PURE".to_string()));
+            }
+            push(&mut fields, &format!("{}.affiliation.external", list), format!("{}.external", aff_base), Some(is_external.to_string()));
+        }
+        counters[slot] += 1;
+    }
+
+    ResearchOutput { uuid, doi, fields }
+}
+
+fn read_input(path: &Path) -> Result<Vec<u8>> {
+    let file = File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    let mut content = Vec::new();
+    if path.extension().and_then(|e| e.to_str()) == Some("gz") {
+        GzDecoder::new(file).read_to_end(&mut content)
+    } else {
+        BufReader::new(file).read_to_end(&mut content)
+    }
+    .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    Ok(content)
+}
+
+fn base_name(path: &Path) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    name.strip_suffix(".gz").map(str::to_string).unwrap_or(name)
+}
+
+fn read_items(path: &Path) -> Result<Vec<Value>> {
+    let content = read_input(path)?;
+    let name = base_name(path);
+    if name.ends_with(".xml") {
+        let root = parse_xml_tree(&content)?.with_context(|| format!("No XML root element in {}", path.display()))?;
+        Ok(xml_items(&root))
+    } else if name.ends_with(".jsonl") {
+        let mut items = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.with_context(|| format!("Failed to read line {} of {}", i + 1, path.display()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Value>(&line) {
+                Ok(value) => items.extend(json_items(value)),
+                Err(e) => warn!("Skipping invalid JSON on line {} of {}: {}", i + 1, path.display(), e),
+            }
+        }
+        Ok(items)
+    } else {
+        let document: Value = serde_json::from_slice(&content)
+            .with_context(|| format!("Invalid JSON in {}", path.display()))?;
+        Ok(json_items(document))
+    }
+}
+
+fn find_input_files(input: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if !path.is_dir() {
+        return Err(anyhow::anyhow!("Input path does not exist: {}", input));
+    }
+    let mut files = Vec::new();
+    for extension in ["json", "jsonl", "xml"] {
+        for pattern in [format!("**/*.{}", extension), format!("**/*.{}.gz", extension)] {
+            let full = path.join(pattern);
+            files.extend(glob(&full.to_string_lossy())?.filter_map(Result::ok));
+        }
+    }
+    files.sort();
+    if files.is_empty() {
+        warn!("No .json, .jsonl or .xml files found under: {}", input);
+    }
+    Ok(files)
+}
+
+fn parse_field_filter(fields: &Option<String>) -> Option<HashSet<String>> {
+    fields.as_ref().map(|spec| {
+        spec.split(',')
+            .map(|f| f.trim().to_string())
+            .filter(|f| !f.is_empty())
+            .collect()
+    })
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let total_secs = elapsed.as_secs();
+    let minutes = total_secs / 60;
+    let seconds = total_secs % 60;
+    let millis = elapsed.subsec_millis();
+
+    if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}.{:03}s", seconds, millis)
+    }
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let start_time = Instant::now();
+    let cli = Cli::parse();
+
+    setup_logging(&cli.log_level)?;
+    info!("Starting Pure research output parser");
+
+    let files = find_input_files(&cli.input)?;
+    info!("Found {} files to process.", files.len());
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let output_path = PathBuf::from(&cli.output);
+    if let Some(parent) = output_path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory structure for: {}", output_path.display()))?;
+        }
+    }
+    let mut writer = Writer::from_path(&output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path.display()))?;
+    writer.write_record(["pure_uuid", "doi", "field_name", "subfield_path", "value", "doi_prefix", "source_file_path"])?;
+
+    let fields = parse_field_filter(&cli.fields);
+    let mut files_with_errors = 0;
+    let mut outputs = 0;
+    let mut outputs_with_doi = 0;
+    let mut skipped_without_doi = 0;
+    let mut rows = 0;
+
+    for file in &files {
+        let items = match read_items(file) {
+            Ok(items) => items,
+            Err(e) => {
+                warn!("Error reading {}: {:#}", file.display(), e);
+                files_with_errors += 1;
+                continue;
+            }
+        };
+        debug!("{} research outputs in {}", items.len(), file.display());
+        let source = file.to_string_lossy();
+        for item in &items {
+            let output = extract_output(item);
+            outputs += 1;
+            match &output.doi {
+                Some(_) => outputs_with_doi += 1,
+                None if cli.require_doi => {
+                    skipped_without_doi += 1;
+                    continue;
+                }
+                None => {}
+            }
+            let doi = output.doi.as_ref().map(|d| d.0.as_str()).unwrap_or("");
+            let prefix = doi.split_once('/').map(|(p, _)| p).unwrap_or("");
+            for (field_name, subfield_path, value) in &output.fields {
+                if fields.as_ref().is_some_and(|f| !f.contains(field_name)) {
+                    continue;
+                }
+                writer.write_record([&output.uuid, doi, field_name, subfield_path, value, prefix, &source])?;
+                rows += 1;
+            }
+        }
+    }
+    writer.flush()?;
+
+    info!("-------------------- FINAL SUMMARY --------------------");
+    info!("Total execution time: {}", format_elapsed(start_time.elapsed()));
+    info!("Files: {} ({} with errors)", files.len(), files_with_errors);
+    info!("Research outputs: {} ({} with DOI)", outputs, outputs_with_doi);
+    if cli.require_doi {
+        info!("Skipped without DOI: {}", skipped_without_doi);
+    }
+    info!("Rows written: {} -> {}", rows, cli.output);
+    info!("-------------------------------------------------------");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outputs(name: &str, contents: &str) -> Vec<ResearchOutput> {
+        let path = std::env::temp_dir().join(format!("pure-export-parse-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        let items = read_items(&path);
+        fs::remove_file(&path).unwrap();
+        items.unwrap().iter().map(extract_output).collect()
+    }
+
+    fn rows(output: &ResearchOutput) -> Vec<String> {
+        output.fields.iter().map(|(_, path, value)| format!("{} = {}", path, value)).collect()
+    }
+
+    #[test]
+    fn json_research_outputs_map_to_crossref_field_paths() {
+        let outputs = outputs("outputs.json", r#"{"count": 1, "items": [{
+            "pureId": 42,
+            "uuid": "0a1b-uuid",
+            "title": {"value": "Graphene sensors"},
+            "type": {"term": {"text": [{"locale": "nl_NL", "value": "Artikel"}, {"locale": "en_GB", "value": "Article"}]}},
+            "electronicVersions": [{"accessType": {}}, {"doi": "https://doi.org/10.3390/S20010001"}],
+            "journalAssociation": {"title": {"title": "Sensors"}, "issn": {"issn": "1424-8220"}},
+            "volume": "20", "journalNumber": "1", "pages": "1-12",
+            "publicationStatuses": [
+                {"publicationStatus": {"uri": "/dk/atira/pure/researchoutput/status/epub", "term": {"en_GB": "E-pub ahead of print"}},
+                 "publicationDate": {"year": 2019, "month": 12}},
+                {"publicationStatus": {"uri": "/dk/atira/pure/researchoutput/status/published", "term": {"en_GB": "Published"}},
+                 "publicationDate": {"year": 2020, "month": 1, "day": 5}}
+            ],
+            "personAssociations": [
+                {"name": {"firstName": "Ann", "lastName": "Doe"}, "personRole": {"term": {"en_GB": "Author"}},
+                 "person": {"uuid": "p-1"}, "organisationalUnits": [{"uuid": "o-1", "name": {"en_GB": "Physics"}}]},
+                {"name": {"firstName": "Ed", "lastName": "Itor"}, "personRole": {"term": {"en_GB": "Editor"}}},
+                {"name": {"lastName": "Roe"}, "personRole": {"term": {"en_GB": "Author"}},
+                 "externalPerson": {"uuid": "x-2"}, "externalOrganisations": [{"name": {"text": [{"locale": "en_GB", "value": "CERN"}]}}]}
+            ]
+        }]}"#);
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].uuid, "0a1b-uuid");
+        assert_eq!(outputs[0].doi, Some(Doi("10.3390/s20010001".to_string())));
+        assert_eq!(rows(&outputs[0]), [
+            "pure-id = 42",
+            "DOI = 10.3390/s20010001",
+            "title[0] = Graphene sensors",
+            "type = Article",
+            "container-title[0] = Sensors",
+            "ISSN[0] = 1424-8220",
+            "volume = 20",
+            "issue = 1",
+            "page = 1-12",
+            "published-online.date-parts = [[2019,12]]",
+            "published.date-parts = [[2020,1,5]]",
+            "author[0].family = Doe",
+            "author[0].given = Ann",
+            "author[0].sequence = first",
+            "author[0].role = Author",
+            "author[0].person-uuid = p-1",
+            "author[0].affiliation[0].name = Physics",
+            "author[0].affiliation[0].id[0].id = o-1",
+            "author[0].affiliation[0].id[0].id-type = PURE",
+            "author[0].affiliation[0].external = false",
+            "editor[0].family = Itor",
+            "editor[0].given = Ed",
+            "editor[0].role = Editor",
+            "author[1].family = Roe",
+            "author[1].sequence = additional",
+            "author[1].role = Author",
+            "author[1].person-uuid = x-2",
+            "author[1].affiliation[0].name = CERN",
+            "author[1].affiliation[0].external = true",
+        ]);
+    }
+
+    #[test]
+    fn xml_research_outputs_read_lists_and_localized_text() {
+        let outputs = outputs("outputs.xml", r#"<?xml version="1.0" encoding="UTF-8"?>
+<result>
+  <count>1</count>
+  <items>
+    <contributionToJournal uuid="0a1b-uuid" pureId="42">
+      <title formatted="false">Graphene <i>in situ</i> sensors</title>
+      <personAssociations>
+        <personAssociation>
+          <name><firstName>Ann</firstName><lastName>Doe</lastName></name>
+          <personRole uri="/dk/atira/pure/researchoutput/roles/contributiontojournal/author">Author</personRole>
+          <organisationalUnits><organisationalUnit uuid="o-1"><name><text locale="en_GB">Physics</text></name></organisationalUnit></organisationalUnits>
+        </personAssociation>
+      </personAssociations>
+      <electronicVersions><electronicVersion><doi>10.3390/S20010001</doi></electronicVersion></electronicVersions>
+    </contributionToJournal>
+  </items>
+</result>
+"#);
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].uuid, "0a1b-uuid");
+        assert_eq!(rows(&outputs[0]), [
+            "pure-id = 42",
+            "DOI = 10.3390/s20010001",
+            "title[0] = Graphene in situ sensors",
+            "author[0].family = Doe",
+            "author[0].given = Ann",
+            "author[0].sequence = first",
+            "author[0].role = Author",
+            "author[0].affiliation[0].name = Physics",
+            "author[0].affiliation[0].id[0].id = o-1",
+            "author[0].affiliation[0].id[0].id-type = PURE",
+            "author[0].affiliation[0].external = false",
+        ]);
+    }
+}
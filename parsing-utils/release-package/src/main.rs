@@ -0,0 +1,375 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser};
+use csv::Writer;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{debug, info, LevelFilter};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use simple_logger::SimpleLogger;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use time::macros::format_description;
+use time::OffsetDateTime;
+
+#[derive(Parser)]
+#[command(name = "Reconciliation Release Packager")]
+#[command(about = "Bundle reconciliation outputs, reports and provenance into a versioned archive with deposit metadata for Zenodo or an institutional repository")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(long, help = "Package name, used for the archive and its top-level directory (e.g. 'cris-reconciliation')", required = true)]
+    name: String,
+
+    #[arg(long = "release-version", help = "Release version (e.g. '2024-Q3' or '1.2.0')", required = true)]
+    release_version: String,
+
+    #[arg(long, help = "Deposit title", required = true)]
+    title: String,
+
+    #[arg(long = "data", help = "Output file or directory to include under data/ (repeatable)")]
+    data: Vec<String>,
+
+    #[arg(long = "report", help = "Report file or directory to include under reports/ (repeatable)")]
+    reports: Vec<String>,
+
+    #[arg(long = "provenance", help = "Provenance file or directory, e.g. a snapshot lock or config, to include under provenance/ (repeatable)")]
+    provenance: Vec<String>,
+
+    #[arg(long = "creator", help = "Creator as 'Family, Given' (repeatable)")]
+    creators: Vec<String>,
+
+    #[arg(long, help = "Deposit description (default: generated from the package contents)")]
+    description: Option<String>,
+
+    #[arg(long, default_value = "cc-by-4.0", help = "License identifier")]
+    license: String,
+
+    #[arg(long = "keyword", help = "Keyword (repeatable)")]
+    keywords: Vec<String>,
+
+    #[arg(long, help = "Publication date as YYYY-MM-DD (default: today)")]
+    publication_date: Option<String>,
+
+    #[arg(short, long, default_value = ".", help = "Directory to write the archive and metadata JSON to")]
+    output_dir: String,
+
+    #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
+    log_level: String,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the output columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+static VERSION_INFO: LazyLock<String> = LazyLock::new(|| format!(
+    "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+    VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+));
+
+const CATEGORIES: [&str; 3] = ["data", "reports", "provenance"];
+
+#[derive(Debug)]
+struct PackageFile {
+    source: PathBuf,
+    category: &'static str,
+    archive_path: String,
+    size: u64,
+    sha256: String,
+}
+
+fn collect_files(category: &'static str, inputs: &[String], seen: &mut HashSet<String>) -> Result<Vec<PackageFile>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let input_path = Path::new(input);
+        let base = input_path.parent().unwrap_or(Path::new(""));
+        let mut sources = Vec::new();
+        if input_path.is_dir() {
+            walk_dir(input_path, &mut sources)?;
+        } else if input_path.is_file() {
+            sources.push(input_path.to_path_buf());
+        } else {
+            return Err(anyhow::anyhow!("Path to package for {}/ not found: {}", category, input));
+        }
+        sources.sort();
+
+        for source in sources {
+            let relative = source.strip_prefix(base).unwrap_or(&source);
+            let relative = relative.components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/");
+            let archive_path = format!("{}/{}", category, relative);
+            if !seen.insert(archive_path.clone()) {
+                return Err(anyhow::anyhow!("Two inputs would both be packaged as {}", archive_path));
+            }
+            let size = fs::metadata(&source)
+                .with_context(|| format!("Failed to read metadata for {}", source.display()))?
+                .len();
+            let sha256 = sha256_file(&source)?;
+            debug!("{} -> {} ({} bytes)", source.display(), archive_path, size);
+            files.push(PackageFile { source, category, archive_path, size, sha256 });
+        }
+    }
+    Ok(files)
+}
+
+fn walk_dir(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read directory {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk_dir(&path, files)?;
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn is_iso_date(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    parts.len() == 3
+        && [4, 2, 2].iter().zip(&parts).all(|(len, part)| part.len() == *len && part.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn default_description(cli: &Cli, files: &[PackageFile]) -> String {
+    let mut lines = vec![format!("{} release {}.", cli.title, cli.release_version)];
+    for category in CATEGORIES {
+        let names: Vec<&str> = files.iter()
+            .filter(|f| f.category == category)
+            .map(|f| f.archive_path.as_str())
+            .collect();
+        if !names.is_empty() {
+            lines.push(format!("{}: {}", category, names.join(", ")));
+        }
+    }
+    lines.push("File sizes and SHA-256 checksums are listed in MANIFEST.csv.".to_string());
+    // Zenodo renders descriptions as HTML.
+    lines.iter().map(|line| format!("<p>{}</p>", line)).collect()
+}
+
+// Zenodo deposition metadata; institutional repositories accepting the same
+// fields can use it as is.
+fn deposit_metadata(cli: &Cli, publication_date: &str, files: &[PackageFile]) -> Value {
+    let creators: Vec<Value> = cli.creators.iter().map(|name| json!({ "name": name })).collect();
+    let mut metadata = json!({
+        "upload_type": "dataset",
+        "title": cli.title,
+        "creators": creators,
+        "description": cli.description.clone().unwrap_or_else(|| default_description(cli, files)),
+        "version": cli.release_version,
+        "license": cli.license,
+        "publication_date": publication_date,
+    });
+    if !cli.keywords.is_empty() {
+        metadata["keywords"] = json!(cli.keywords);
+    }
+    json!({ "metadata": metadata })
+}
+
+fn manifest_csv(files: &[PackageFile]) -> Result<Vec<u8>> {
+    let mut writer = Writer::from_writer(Vec::new());
+    writer.write_record(["path", "category", "size", "sha256"])?;
+    for file in files {
+        writer.write_record([&file.archive_path, file.category, &file.size.to_string(), &file.sha256])?;
+    }
+    writer.into_inner().map_err(|e| anyhow::anyhow!("Failed to build manifest: {}", e))
+}
+
+fn append_bytes<W: io::Write>(builder: &mut tar::Builder<W>, path: &str, data: &[u8], mtime: u64) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    header.set_cksum();
+    builder.append_data(&mut header, path, data)
+        .with_context(|| format!("Failed to add {} to archive", path))
+}
+
+fn write_archive(archive_path: &Path, root: &str, metadata_json: &[u8], manifest: &[u8], files: &[PackageFile], mtime: u64) -> Result<()> {
+    let file = File::create(archive_path)
+        .with_context(|| format!("Failed to create archive: {}", archive_path.display()))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    append_bytes(&mut builder, &format!("{}/metadata.json", root), metadata_json, mtime)?;
+    append_bytes(&mut builder, &format!("{}/MANIFEST.csv", root), manifest, mtime)?;
+    for file in files {
+        builder.append_path_with_name(&file.source, format!("{}/{}", root, file.archive_path))
+            .with_context(|| format!("Failed to add {} to archive", file.source.display()))?;
+    }
+    builder.into_inner()
+        .context("Failed to finish archive")?
+        .finish()
+        .context("Failed to finish archive compression")?;
+    Ok(())
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let total_secs = elapsed.as_secs();
+    let minutes = total_secs / 60;
+    let seconds = total_secs % 60;
+    let millis = elapsed.subsec_millis();
+
+    if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}.{:03}s", seconds, millis)
+    }
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let start_time = Instant::now();
+    let cli = Cli::parse();
+
+    setup_logging(&cli.log_level)?;
+    info!("Starting release packager");
+
+    for (flag, value) in [("--name", &cli.name), ("--release-version", &cli.release_version)] {
+        if value.is_empty() || value.contains(['/', '\\']) || value.starts_with('.') {
+            return Err(anyhow::anyhow!("{} must be non-empty, not start with '.', and not contain path separators: {}", flag, value));
+        }
+    }
+    if cli.data.is_empty() && cli.reports.is_empty() && cli.provenance.is_empty() {
+        return Err(anyhow::anyhow!("Nothing to package: give at least one --data, --report or --provenance path"));
+    }
+    let publication_date = match &cli.publication_date {
+        Some(date) if is_iso_date(date) => date.clone(),
+        Some(date) => return Err(anyhow::anyhow!("--publication-date must be YYYY-MM-DD: {}", date)),
+        None => OffsetDateTime::now_utc().date().format(format_description!("[year]-[month]-[day]"))?,
+    };
+
+    let mut seen = HashSet::new();
+    let mut files = collect_files("data", &cli.data, &mut seen)?;
+    files.extend(collect_files("reports", &cli.reports, &mut seen)?);
+    files.extend(collect_files("provenance", &cli.provenance, &mut seen)?);
+    info!("Collected {} files", files.len());
+
+    let root = format!("{}-{}", cli.name, cli.release_version);
+    let metadata = deposit_metadata(&cli, &publication_date, &files);
+    let metadata_json = serde_json::to_string_pretty(&metadata)? + "\n";
+    let manifest = manifest_csv(&files)?;
+
+    let output_dir = PathBuf::from(&cli.output_dir);
+    fs::create_dir_all(&output_dir)
+        .with_context(|| format!("Failed to create output directory: {}", output_dir.display()))?;
+    let archive_path = output_dir.join(format!("{}.tar.gz", root));
+    let metadata_path = output_dir.join(format!("{}.metadata.json", root));
+    let mtime = OffsetDateTime::now_utc().unix_timestamp().max(0) as u64;
+    write_archive(&archive_path, &root, metadata_json.as_bytes(), &manifest, &files, mtime)?;
+    fs::write(&metadata_path, &metadata_json)
+        .with_context(|| format!("Failed to write metadata: {}", metadata_path.display()))?;
+
+    info!("-------------------- FINAL SUMMARY --------------------");
+    info!("Total execution time: {}", format_elapsed(start_time.elapsed()));
+    for category in CATEGORIES {
+        let in_category: Vec<&PackageFile> = files.iter().filter(|f| f.category == category).collect();
+        let bytes: u64 = in_category.iter().map(|f| f.size).sum();
+        info!("{}: {} files, {} bytes", category, in_category.len(), bytes);
+    }
+    info!("Archive written to: {}", archive_path.display());
+    info!("Deposit metadata written to: {}", metadata_path.display());
+    info!("-------------------------------------------------------");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn outputs_are_packaged_with_a_manifest_and_deposit_metadata() {
+        let dir = std::env::temp_dir().join(format!("release-package-{}-package", std::process::id()));
+        let outputs = dir.join("outputs");
+        fs::create_dir_all(outputs.join("by_member")).unwrap();
+        fs::write(outputs.join("by_member").join("78.csv"), "doi\n10.1/a\n").unwrap();
+        fs::write(dir.join("report.md"), "ok\n").unwrap();
+        let cli = Cli::parse_from([
+            "release-package", "--name", "cris", "--release-version", "1.0", "--title", "CRIS reconciliation",
+            "--creator", "Doe, Ann", "--keyword", "cris",
+        ]);
+
+        let mut seen = HashSet::new();
+        let mut files = collect_files("data", &[outputs.display().to_string()], &mut seen).unwrap();
+        files.extend(collect_files("reports", &[dir.join("report.md").display().to_string()], &mut seen).unwrap());
+        let manifest = manifest_csv(&files).unwrap();
+        assert_eq!(String::from_utf8(manifest.clone()).unwrap(), "\
+path,category,size,sha256
+data/outputs/by_member/78.csv,data,11,1a557d27dbfe4f0811aa941fa29351b371ebc443d6674f298d6b1bc2e5869e08
+reports/report.md,reports,3,dc51b8c96c2d745df3bd5590d990230a482fd247123599548e0632fdbf97fc22
+");
+        let metadata = deposit_metadata(&cli, "2024-10-01", &files);
+        assert_eq!(metadata["metadata"]["creators"], json!([{ "name": "Doe, Ann" }]));
+        assert_eq!(metadata["metadata"]["keywords"], json!(["cris"]));
+        assert_eq!(metadata["metadata"]["description"], "<p>CRIS reconciliation release 1.0.</p>\
+<p>data: data/outputs/by_member/78.csv</p><p>reports: reports/report.md</p>\
+<p>File sizes and SHA-256 checksums are listed in MANIFEST.csv.</p>");
+
+        let archive = dir.join("cris-1.0.tar.gz");
+        write_archive(&archive, "cris-1.0", b"{}\n", &manifest, &files, 0).unwrap();
+        let mut entries = Vec::new();
+        let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(File::open(&archive).unwrap()));
+        for entry in tar.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents).unwrap();
+            entries.push((entry.path().unwrap().display().to_string(), contents));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(entries.iter().map(|(path, _)| path.as_str()).collect::<Vec<_>>(), [
+            "cris-1.0/metadata.json",
+            "cris-1.0/MANIFEST.csv",
+            "cris-1.0/data/outputs/by_member/78.csv",
+            "cris-1.0/reports/report.md",
+        ]);
+        assert_eq!(entries[2].1, "doi\n10.1/a\n");
+    }
+
+    #[test]
+    fn inputs_packaged_under_the_same_path_are_refused() {
+        let dir = std::env::temp_dir().join(format!("release-package-{}-duplicate", std::process::id()));
+        fs::create_dir_all(dir.join("a")).unwrap();
+        fs::create_dir_all(dir.join("b").join("a")).unwrap();
+        fs::write(dir.join("a").join("x.csv"), "").unwrap();
+        fs::write(dir.join("b").join("a").join("x.csv"), "").unwrap();
+        let inputs = [dir.join("a").display().to_string(), dir.join("b").join("a").display().to_string()];
+        let error = collect_files("data", &inputs, &mut HashSet::new()).unwrap_err().to_string();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(error.contains("both be packaged as data/a/x.csv"), "{}", error);
+    }
+}
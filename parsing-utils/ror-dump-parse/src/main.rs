@@ -0,0 +1,507 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser};
+use csv::Writer;
+use flate2::read::GzDecoder;
+use lazy_static::lazy_static;
+use log::{error, info, warn, LevelFilter};
+use normalization::normalize_text;
+use rayon::prelude::*;
+use regex::Regex;
+use serde_json::Value;
+use simple_logger::SimpleLogger;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use time::macros::format_description;
+
+#[derive(Parser)]
+#[command(name = "ROR Data Dump Parser")]
+#[command(about = "Extract normalized names, aliases, acronyms, and countries from the ROR data dump")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(short, long, help = "ROR data dump (.zip, .json or .json.gz)", required = true)]
+    input: String,
+
+    #[arg(short, long, default_value = "ror_names.csv", help = "Output CSV file")]
+    output: String,
+
+    #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
+    log_level: String,
+
+    #[arg(short, long, default_value = "0", help = "Number of threads to use (0 for auto)")]
+    threads: usize,
+
+    #[arg(long, help = "Skip organizations whose status is not 'active'")]
+    active_only: bool,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the output columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// Source schemas whose structure is built into the binary.
+const BUNDLED_SCHEMAS: &[&str] = &["ROR schema v1", "ROR schema v2"];
+
+// The --version-info report: enough to tell which build wrote a file.
+lazy_static! {
+    static ref VERSION_INFO: String = format!(
+        "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+        VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), BUNDLED_SCHEMAS.join(", "), OUTPUT_SCHEMA_VERSION
+    );
+}
+
+#[derive(Debug, PartialEq)]
+enum FieldType {
+    Array,
+    Object,
+    Value,
+}
+
+// Covers both the v1 (name/aliases/acronyms/labels/country) and the v2
+// (names[].types/locations[].geonames_details) record layouts.
+lazy_static! {
+    static ref SCHEMA_STRUCTURE: HashMap<String, FieldType> = {
+        let mut schema = HashMap::new();
+
+        schema.insert("id".to_string(), FieldType::Value);
+        schema.insert("status".to_string(), FieldType::Value);
+
+        // Schema v1
+        schema.insert("name".to_string(), FieldType::Value);
+        schema.insert("aliases".to_string(), FieldType::Array);
+        schema.insert("acronyms".to_string(), FieldType::Array);
+        schema.insert("labels".to_string(), FieldType::Array);
+        schema.insert("labels.label".to_string(), FieldType::Value);
+        schema.insert("labels.iso639".to_string(), FieldType::Value);
+        schema.insert("country".to_string(), FieldType::Object);
+        schema.insert("country.country_code".to_string(), FieldType::Value);
+        schema.insert("country.country_name".to_string(), FieldType::Value);
+
+        // Schema v2
+        schema.insert("names".to_string(), FieldType::Array);
+        schema.insert("names.value".to_string(), FieldType::Value);
+        schema.insert("names.types".to_string(), FieldType::Array);
+        schema.insert("names.lang".to_string(), FieldType::Value);
+        schema.insert("locations".to_string(), FieldType::Array);
+        schema.insert("locations.geonames_details".to_string(), FieldType::Object);
+        schema.insert("locations.geonames_details.country_code".to_string(), FieldType::Value);
+        schema.insert("locations.geonames_details.country_name".to_string(), FieldType::Value);
+
+        schema
+    };
+
+    static ref ARRAY_INDEX_RE: Regex = Regex::new(r"^[a-z]+\[(\d+)\]").unwrap();
+}
+
+const EXTRACTED_FIELDS: &str = "id,status,\
+    name,aliases,acronyms,labels.label,labels.iso639,country.country_code,country.country_name,\
+    names.value,names.types,names.lang,\
+    locations.geonames_details.country_code,locations.geonames_details.country_name";
+
+#[derive(Debug, Default)]
+struct PatternTrieNode {
+    children: HashMap<String, PatternTrieNode>,
+    terminating_patterns: Vec<String>,
+}
+
+#[derive(Debug)]
+struct PatternTrie {
+    root: PatternTrieNode,
+}
+
+impl PatternTrie {
+    fn new(field_specs: &[Vec<String>]) -> Self {
+        let mut root = PatternTrieNode::default();
+
+        for spec in field_specs {
+            let full_pattern_name = spec.join(".");
+            let mut current_node = &mut root;
+            let mut current_schema_path = String::new();
+
+            for part in spec {
+                if !current_schema_path.is_empty() {
+                    current_schema_path.push('.');
+                }
+                current_schema_path.push_str(part);
+
+                current_node = current_node.children.entry(part.clone()).or_default();
+                // Array fields get a '[]' marker node so extraction iterates their elements
+                if SCHEMA_STRUCTURE.get(&current_schema_path) == Some(&FieldType::Array) {
+                    current_node = current_node.children.entry("[]".to_string()).or_default();
+                }
+            }
+            current_node.terminating_patterns.push(full_pattern_name);
+        }
+        Self { root }
+    }
+
+    fn extract(&self, record: &Value) -> Vec<(String, String, String)> {
+        let mut results = Vec::new();
+        self.traverse(record, &self.root, String::new(), &mut results);
+        results
+    }
+
+    fn traverse(
+        &self,
+        json_node: &Value,
+        trie_node: &PatternTrieNode,
+        current_path: String,
+        results: &mut Vec<(String, String, String)>,
+    ) {
+        if !trie_node.terminating_patterns.is_empty() {
+            let value_str = match json_node {
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) => b.to_string(),
+                Value::Null => "".to_string(),
+                _ => serde_json::to_string(json_node).unwrap_or_default(),
+            };
+            for pattern_name in &trie_node.terminating_patterns {
+                results.push((pattern_name.clone(), current_path.clone(), value_str.clone()));
+            }
+        }
+
+        match json_node {
+            Value::Object(obj) => {
+                for (key, value) in obj {
+                    if let Some(child_trie_node) = trie_node.children.get(key) {
+                        let new_path = if current_path.is_empty() { key.clone() } else { format!("{}.{}", current_path, key) };
+                        self.traverse(value, child_trie_node, new_path, results);
+                    }
+                }
+            }
+            Value::Array(arr) => {
+                if let Some(array_child_node) = trie_node.children.get("[]") {
+                    for (i, item) in arr.iter().enumerate() {
+                        let new_path = format!("{}[{}]", current_path, i);
+                        self.traverse(item, array_child_node, new_path, results);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse_field_specifications(field_specs: &str) -> Vec<Vec<String>> {
+    field_specs
+        .split(',')
+        .filter(|spec| !spec.trim().is_empty())
+        .map(|spec| spec.trim().split('.').map(|part| part.trim().to_string()).collect::<Vec<String>>())
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+struct NameRow {
+    name_type: String,
+    name: String,
+    lang: String,
+}
+
+#[derive(Debug, Default)]
+struct Organization {
+    ror_id: String,
+    status: String,
+    country_code: String,
+    country_name: String,
+    names: Vec<NameRow>,
+}
+
+#[derive(Debug, Default)]
+struct V2Name {
+    value: String,
+    lang: String,
+    types: Vec<String>,
+}
+
+fn array_index(subfield_path: &str) -> Option<usize> {
+    ARRAY_INDEX_RE.captures(subfield_path).and_then(|c| c[1].parse().ok())
+}
+
+// v2 name types map onto the v1 field each one replaced.
+fn v2_name_type(ror_type: &str) -> &str {
+    match ror_type {
+        "ror_display" => "name",
+        "alias" => "alias",
+        "acronym" => "acronym",
+        "label" => "label",
+        other => other,
+    }
+}
+
+fn build_organization(extracted: Vec<(String, String, String)>) -> Organization {
+    let mut org = Organization::default();
+    let mut v1_labels: BTreeMap<usize, (String, String)> = BTreeMap::new();
+    let mut v2_names: BTreeMap<usize, V2Name> = BTreeMap::new();
+    let mut first_location: Option<usize> = None;
+
+    for (pattern, path, value) in extracted {
+        match pattern.as_str() {
+            "id" => org.ror_id = value,
+            "status" => org.status = value,
+            "name" => org.names.push(NameRow { name_type: "name".to_string(), name: value, lang: String::new() }),
+            "aliases" => org.names.push(NameRow { name_type: "alias".to_string(), name: value, lang: String::new() }),
+            "acronyms" => org.names.push(NameRow { name_type: "acronym".to_string(), name: value, lang: String::new() }),
+            "labels.label" | "labels.iso639" => {
+                if let Some(i) = array_index(&path) {
+                    let entry = v1_labels.entry(i).or_default();
+                    if pattern == "labels.label" { entry.0 = value } else { entry.1 = value }
+                }
+            }
+            "country.country_code" => org.country_code = value,
+            "country.country_name" => org.country_name = value,
+            "names.value" | "names.lang" | "names.types" => {
+                if let Some(i) = array_index(&path) {
+                    let entry = v2_names.entry(i).or_default();
+                    match pattern.as_str() {
+                        "names.value" => entry.value = value,
+                        "names.lang" => entry.lang = value,
+                        _ => entry.types.push(value),
+                    }
+                }
+            }
+            "locations.geonames_details.country_code" | "locations.geonames_details.country_name" => {
+                // Only the first location determines the organization's country
+                let i = array_index(&path);
+                if first_location.is_none() {
+                    first_location = i;
+                }
+                if i == first_location {
+                    if pattern.ends_with("country_code") { org.country_code = value } else { org.country_name = value }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (label, lang) in v1_labels.into_values() {
+        org.names.push(NameRow { name_type: "label".to_string(), name: label, lang });
+    }
+    for name in v2_names.into_values() {
+        for ror_type in &name.types {
+            org.names.push(NameRow {
+                name_type: v2_name_type(ror_type).to_string(),
+                name: name.value.clone(),
+                lang: name.lang.clone(),
+            });
+        }
+    }
+    org
+}
+
+fn read_dump(input: &Path) -> Result<Vec<Value>> {
+    let name = input.to_string_lossy().to_lowercase();
+    let file = File::open(input).with_context(|| format!("Failed to open ROR dump: {}", input.display()))?;
+
+    let records: Value = if name.ends_with(".zip") {
+        let mut archive = zip::ZipArchive::new(BufReader::new(file))
+            .with_context(|| format!("Failed to read zip archive: {}", input.display()))?;
+        let json_entries: Vec<String> = archive.file_names()
+            .filter(|n| n.to_lowercase().ends_with(".json"))
+            .map(String::from)
+            .collect();
+        // Dumps carrying both schema versions name the v2 file "*_schema_v2.json"
+        let entry_name = json_entries.iter()
+            .find(|n| n.contains("schema_v2"))
+            .or_else(|| json_entries.first())
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No .json file found in {}", input.display()))?;
+        info!("Reading {} from {}", entry_name, input.display());
+        let entry = archive.by_name(&entry_name)?;
+        serde_json::from_reader(BufReader::new(entry))
+            .with_context(|| format!("Failed to parse {} in {}", entry_name, input.display()))?
+    } else if name.ends_with(".gz") {
+        serde_json::from_reader(BufReader::new(GzDecoder::new(file)))
+            .with_context(|| format!("Failed to parse {}", input.display()))?
+    } else {
+        let mut content = String::new();
+        BufReader::new(file).read_to_string(&mut content)?;
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", input.display()))?
+    };
+
+    match records {
+        Value::Array(arr) => Ok(arr),
+        _ => Err(anyhow::anyhow!("Expected a JSON array of organizations in {}", input.display())),
+    }
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let total_secs = elapsed.as_secs();
+    let minutes = total_secs / 60;
+    let seconds = total_secs % 60;
+    let millis = elapsed.subsec_millis();
+
+    if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}.{:03}s", seconds, millis)
+    }
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+
+    Ok(())
+}
+
+fn setup_thread_pool(thread_count: usize) {
+    let num_threads = if thread_count == 0 { num_cpus::get() } else { thread_count };
+    info!("Using {} threads.", num_threads);
+    if let Err(e) = rayon::ThreadPoolBuilder::new().num_threads(num_threads).build_global() {
+        error!("Failed to build global thread pool: {}. Proceeding with default.", e);
+    }
+}
+
+fn write_output(path: &str, organizations: &[Organization]) -> Result<usize> {
+    let output_path = PathBuf::from(path);
+    if let Some(parent) = output_path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory structure for: {}", output_path.display()))?;
+        }
+    }
+    let mut writer = Writer::from_path(&output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path.display()))?;
+    writer.write_record([
+        "ror_id", "name_type", "name", "normalized_name", "lang", "country_code", "country_name", "status",
+    ])?;
+
+    let mut rows = 0;
+    for org in organizations {
+        for name in &org.names {
+            writer.write_record([
+                org.ror_id.as_str(),
+                &name.name_type,
+                &name.name,
+                &normalize_text(&name.name),
+                &name.lang,
+                &org.country_code,
+                &org.country_name,
+                &org.status,
+            ])?;
+            rows += 1;
+        }
+    }
+    writer.flush()?;
+    Ok(rows)
+}
+
+fn main() -> Result<()> {
+    let start_time = Instant::now();
+    let cli = Cli::parse();
+
+    setup_logging(&cli.log_level)?;
+    info!("Starting ROR data dump parser");
+    setup_thread_pool(cli.threads);
+
+    let records = read_dump(Path::new(&cli.input))?;
+    info!("Loaded {} organization records in {}", records.len(), format_elapsed(start_time.elapsed()));
+
+    let extractor = PatternTrie::new(&parse_field_specifications(EXTRACTED_FIELDS));
+    let organizations: Vec<Organization> = records
+        .par_iter()
+        .map(|record| build_organization(extractor.extract(record)))
+        .filter(|org| {
+            if org.ror_id.is_empty() {
+                warn!("Skipping organization record without an id");
+                return false;
+            }
+            !cli.active_only || org.status == "active"
+        })
+        .collect();
+
+    let skipped = records.len() - organizations.len();
+    let rows = write_output(&cli.output, &organizations)?;
+
+    info!("-------------------- FINAL SUMMARY --------------------");
+    info!("Total execution time: {}", format_elapsed(start_time.elapsed()));
+    info!("Organizations written: {} ({} skipped)", organizations.len(), skipped);
+    info!("Name rows written: {} -> {}", rows, cli.output);
+    info!("-------------------------------------------------------");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name_rows(name: &str, dump: &str) -> String {
+        let input = std::env::temp_dir().join(format!("ror-dump-parse-{}-{}.json", std::process::id(), name));
+        fs::write(&input, dump).unwrap();
+        let records = read_dump(&input);
+        fs::remove_file(&input).unwrap();
+
+        let extractor = PatternTrie::new(&parse_field_specifications(EXTRACTED_FIELDS));
+        let organizations: Vec<Organization> = records.unwrap().iter()
+            .map(|record| build_organization(extractor.extract(record)))
+            .collect();
+        let output = std::env::temp_dir().join(format!("ror-dump-parse-{}-{}.csv", std::process::id(), name));
+        write_output(output.to_str().unwrap(), &organizations).unwrap();
+        let written = fs::read_to_string(&output).unwrap();
+        fs::remove_file(&output).unwrap();
+        written
+    }
+
+    #[test]
+    fn v1_records_give_one_row_per_name() {
+        // Fields are read in key order, so aliases come before the primary name.
+        assert_eq!(name_rows("v1", r#"[{
+            "id": "https://ror.org/052gg0110",
+            "status": "active",
+            "name": "University of Oxford",
+            "aliases": ["Oxford University"],
+            "acronyms": [],
+            "labels": [{"label": "Université d'Oxford", "iso639": "fr"}],
+            "country": {"country_code": "GB", "country_name": "United Kingdom"}
+        }]"#), "\
+ror_id,name_type,name,normalized_name,lang,country_code,country_name,status
+https://ror.org/052gg0110,alias,Oxford University,oxford university,,GB,United Kingdom,active
+https://ror.org/052gg0110,name,University of Oxford,university of oxford,,GB,United Kingdom,active
+https://ror.org/052gg0110,label,Université d'Oxford,universite doxford,fr,GB,United Kingdom,active
+");
+    }
+
+    #[test]
+    fn v2_name_types_map_to_v1_name_types() {
+        assert_eq!(name_rows("v2", r#"[{
+            "id": "https://ror.org/052gg0110",
+            "status": "active",
+            "names": [
+                {"value": "University of Oxford", "types": ["ror_display", "label"], "lang": "en"},
+                {"value": "Oxford University", "types": ["alias"], "lang": null},
+                {"value": "Université d'Oxford", "types": ["label"], "lang": "fr"}
+            ],
+            "locations": [
+                {"geonames_details": {"country_code": "GB", "country_name": "United Kingdom"}},
+                {"geonames_details": {"country_code": "FR", "country_name": "France"}}
+            ]
+        }]"#), "\
+ror_id,name_type,name,normalized_name,lang,country_code,country_name,status
+https://ror.org/052gg0110,name,University of Oxford,university of oxford,en,GB,United Kingdom,active
+https://ror.org/052gg0110,label,University of Oxford,university of oxford,en,GB,United Kingdom,active
+https://ror.org/052gg0110,alias,Oxford University,oxford university,,GB,United Kingdom,active
+https://ror.org/052gg0110,label,Université d'Oxford,universite doxford,fr,GB,United Kingdom,active
+");
+    }
+}
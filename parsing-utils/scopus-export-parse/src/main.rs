@@ -0,0 +1,657 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser};
+use csv::{ReaderBuilder, Writer};
+use glob::glob;
+use lazy_static::lazy_static;
+use log::{debug, info, warn, LevelFilter};
+use regex::Regex;
+use simple_logger::SimpleLogger;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use time::macros::format_description;
+
+#[derive(Parser)]
+#[command(name = "Scopus Export Parser")]
+#[command(about = "Normalize Scopus CSV and RIS exports into the pipeline's long field format")]
+#[command(version = VERSION, long_version = VERSION_INFO.as_str())]
+struct Cli {
+    #[arg(short, long, help = "Scopus export file (.csv or .ris) or directory of exports", required = true)]
+    input: String,
+
+    #[arg(short, long, default_value = "scopus_field_data.csv", help = "Output CSV file")]
+    output: String,
+
+    #[arg(short, long, help = "Comma-separated list of fields to keep (e.g., 'title,author.full_name,affiliation.name'); default all")]
+    fields: Option<String>,
+
+    #[arg(short, long, default_value = "INFO", help = "Logging level (DEBUG, INFO, WARN, ERROR)")]
+    log_level: String,
+
+    #[arg(long, action = ArgAction::Version, help = "Print the crate version, git commit, bundled schemas and output schema version, then exit")]
+    version_info: (),
+}
+
+const VERSION: &str = "1.0";
+
+// Bump when the output columns, or what they hold, change.
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+// The --version-info report: enough to tell which build wrote a file.
+lazy_static! {
+    static ref VERSION_INFO: String = format!(
+        "{}\ncrate: {} {}\ngit commit: {}\nbundled schemas: {}\noutput schema: {}",
+        VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"), "none", OUTPUT_SCHEMA_VERSION
+    );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnKind {
+    // Single value per record
+    Scalar,
+    // "; "-separated values, emitted as <array>[i].<leaf>
+    List,
+    // "Full Name (AuthorId)" entries, split into full_name and scopus_id
+    AuthorFullNames,
+}
+
+lazy_static! {
+    // Scopus CSV column header -> (field path, kind). Unlisted columns fall back to a
+    // snake_cased scalar field named after the header.
+    static ref CSV_COLUMNS: HashMap<&'static str, (&'static str, ColumnKind)> = {
+        let mut m = HashMap::new();
+        m.insert("Authors", ("author.name", ColumnKind::List));
+        m.insert("Author full names", ("author.full_name", ColumnKind::AuthorFullNames));
+        m.insert("Author(s) ID", ("author.scopus_id", ColumnKind::List));
+        m.insert("Title", ("title", ColumnKind::Scalar));
+        m.insert("Year", ("year", ColumnKind::Scalar));
+        m.insert("Source title", ("source_title", ColumnKind::Scalar));
+        m.insert("Abbreviated Source Title", ("abbreviated_source_title", ColumnKind::Scalar));
+        m.insert("Volume", ("volume", ColumnKind::Scalar));
+        m.insert("Issue", ("issue", ColumnKind::Scalar));
+        m.insert("Art. No.", ("article_number", ColumnKind::Scalar));
+        m.insert("Page start", ("page_start", ColumnKind::Scalar));
+        m.insert("Page end", ("page_end", ColumnKind::Scalar));
+        m.insert("Page count", ("page_count", ColumnKind::Scalar));
+        m.insert("Cited by", ("cited_by", ColumnKind::Scalar));
+        m.insert("DOI", ("doi", ColumnKind::Scalar));
+        m.insert("Link", ("link", ColumnKind::Scalar));
+        m.insert("Affiliations", ("affiliation.name", ColumnKind::List));
+        m.insert("Authors with affiliations", ("author_affiliation.value", ColumnKind::List));
+        m.insert("Abstract", ("abstract", ColumnKind::Scalar));
+        m.insert("Author Keywords", ("author_keyword.value", ColumnKind::List));
+        m.insert("Index Keywords", ("index_keyword.value", ColumnKind::List));
+        m.insert("Funding Details", ("funding.value", ColumnKind::List));
+        m.insert("Editors", ("editor.name", ColumnKind::List));
+        m.insert("Publisher", ("publisher", ColumnKind::Scalar));
+        m.insert("ISSN", ("issn", ColumnKind::Scalar));
+        m.insert("ISBN", ("isbn.value", ColumnKind::List));
+        m.insert("CODEN", ("coden", ColumnKind::Scalar));
+        m.insert("PubMed ID", ("pubmed_id", ColumnKind::Scalar));
+        m.insert("Language of Original Document", ("language.value", ColumnKind::List));
+        m.insert("Document Type", ("document_type", ColumnKind::Scalar));
+        m.insert("Publication Stage", ("publication_stage", ColumnKind::Scalar));
+        m.insert("Open Access", ("open_access.value", ColumnKind::List));
+        m.insert("Source", ("source", ColumnKind::Scalar));
+        m.insert("EID", ("eid", ColumnKind::Scalar));
+        m
+    };
+
+    // RIS tag -> (field path, kind), using the same field names as the CSV mapping.
+    static ref RIS_TAGS: HashMap<&'static str, (&'static str, ColumnKind)> = {
+        let mut m = HashMap::new();
+        m.insert("TY", ("document_type", ColumnKind::Scalar));
+        m.insert("TI", ("title", ColumnKind::Scalar));
+        m.insert("T1", ("title", ColumnKind::Scalar));
+        m.insert("T2", ("source_title", ColumnKind::Scalar));
+        m.insert("JO", ("source_title", ColumnKind::Scalar));
+        m.insert("J2", ("abbreviated_source_title", ColumnKind::Scalar));
+        m.insert("AU", ("author.name", ColumnKind::List));
+        m.insert("A1", ("author.name", ColumnKind::List));
+        m.insert("AD", ("affiliation.name", ColumnKind::List));
+        m.insert("PY", ("year", ColumnKind::Scalar));
+        m.insert("Y1", ("year", ColumnKind::Scalar));
+        m.insert("VL", ("volume", ColumnKind::Scalar));
+        m.insert("IS", ("issue", ColumnKind::Scalar));
+        m.insert("C7", ("article_number", ColumnKind::Scalar));
+        m.insert("SP", ("page_start", ColumnKind::Scalar));
+        m.insert("EP", ("page_end", ColumnKind::Scalar));
+        m.insert("DO", ("doi", ColumnKind::Scalar));
+        m.insert("UR", ("link", ColumnKind::Scalar));
+        m.insert("AB", ("abstract", ColumnKind::Scalar));
+        m.insert("KW", ("author_keyword.value", ColumnKind::List));
+        m.insert("PB", ("publisher", ColumnKind::Scalar));
+        m.insert("SN", ("issn", ColumnKind::Scalar));
+        m.insert("LA", ("language.value", ColumnKind::List));
+        m.insert("N1", ("note.value", ColumnKind::List));
+        m
+    };
+
+    static ref AUTHOR_FULL_NAME_RE: Regex = Regex::new(r"^(.*?)\s*\((\d+)\)\s*$").unwrap();
+    static ref EID_IN_LINK_RE: Regex = Regex::new(r"[?&]eid=([^&\s]+)").unwrap();
+    static ref ISSN_RE: Regex = Regex::new(r"^(\d{4})-?(\d{3}[\dXx])$").unwrap();
+    static ref RIS_LINE_RE: Regex = Regex::new(r"^([A-Z][A-Z0-9])  -(?: (.*))?$").unwrap();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Doi(String);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Eid(String);
+
+struct FieldData {
+    doi: Option<Doi>,
+    eid: Option<Eid>,
+    field_name: String,
+    subfield_path: String,
+    value: String,
+}
+
+#[derive(Debug, Default)]
+struct ExportStats {
+    files_processed: usize,
+    files_with_errors: usize,
+    records: usize,
+    records_with_doi: usize,
+    records_with_eid: usize,
+    rows_written: usize,
+    field_counts: HashMap<String, usize>,
+    unique_dois: HashSet<Doi>,
+}
+
+// DOIs are case-insensitive; lowercase them and strip resolver / "doi:" prefixes.
+fn normalize_doi(raw: &str) -> Option<Doi> {
+    normalization::normalize_doi(raw).map(Doi)
+}
+
+// EIDs are exported as "2-s2.0-<digits>"; bare Scopus record numbers get the prefix added.
+fn normalize_eid(raw: &str) -> Option<Eid> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let lowered = trimmed.to_lowercase();
+    if let Some(rest) = lowered.strip_prefix("2-s2.0-") {
+        if !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit()) {
+            return Some(Eid(format!("2-s2.0-{}", rest)));
+        }
+    } else if lowered.chars().all(|c| c.is_ascii_digit()) {
+        return Some(Eid(format!("2-s2.0-{}", lowered)));
+    }
+    None
+}
+
+fn normalize_issn(raw: &str) -> String {
+    match ISSN_RE.captures(raw.trim()) {
+        Some(caps) => format!("{}-{}", &caps[1], caps[2].to_uppercase()),
+        None => raw.trim().to_string(),
+    }
+}
+
+fn snake_case_header(header: &str) -> String {
+    let mut out = String::new();
+    for c in header.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            out.push(c.to_ascii_lowercase());
+        } else if !out.is_empty() && !out.ends_with('_') {
+            out.push('_');
+        }
+    }
+    out.trim_end_matches('_').to_string()
+}
+
+// "author.full_name" with index 2 -> "author[2].full_name"
+fn indexed_path(field_name: &str, index: usize) -> String {
+    match field_name.split_once('.') {
+        Some((array, leaf)) => format!("{}[{}].{}", array, index, leaf),
+        None => format!("{}[{}]", field_name, index),
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(';').map(str::trim).filter(|v| !v.is_empty())
+}
+
+// Builds the (field_name, subfield_path, value) triples for one record. List values
+// keep their position across repeated RIS tags via `list_counters`.
+struct RecordBuilder {
+    fields: Vec<(String, String, String)>,
+    list_counters: HashMap<String, usize>,
+}
+
+impl RecordBuilder {
+    fn new() -> Self {
+        Self { fields: Vec::new(), list_counters: HashMap::new() }
+    }
+
+    fn push_list_item(&mut self, field_name: &str, value: &str) {
+        let counter = self.list_counters.entry(field_name.to_string()).or_insert(0);
+        let path = indexed_path(field_name, *counter);
+        *counter += 1;
+        self.fields.push((field_name.to_string(), path, value.to_string()));
+    }
+
+    fn add(&mut self, field_name: &str, kind: ColumnKind, raw: &str) {
+        let value = raw.trim();
+        if value.is_empty() {
+            return;
+        }
+        match kind {
+            ColumnKind::Scalar => {
+                let normalized = match field_name {
+                    "doi" => normalize_doi(value).map(|d| d.0).unwrap_or_else(|| value.to_string()),
+                    "eid" => normalize_eid(value).map(|e| e.0).unwrap_or_else(|| value.to_string()),
+                    "issn" => normalize_issn(value),
+                    _ => value.to_string(),
+                };
+                self.fields.push((field_name.to_string(), field_name.to_string(), normalized));
+            }
+            ColumnKind::List => {
+                for item in split_list(value) {
+                    self.push_list_item(field_name, item);
+                }
+            }
+            ColumnKind::AuthorFullNames => {
+                for item in split_list(value) {
+                    let index = *self.list_counters.get(field_name).unwrap_or(&0);
+                    match AUTHOR_FULL_NAME_RE.captures(item) {
+                        Some(caps) => {
+                            self.push_list_item(field_name, &caps[1]);
+                            self.fields.push(("author.scopus_id".to_string(), indexed_path("author.scopus_id", index), caps[2].to_string()));
+                        }
+                        None => self.push_list_item(field_name, item),
+                    }
+                }
+            }
+        }
+    }
+
+    fn value_of(&self, field_name: &str) -> Option<&str> {
+        self.fields.iter().find(|(f, _, _)| f == field_name).map(|(_, _, v)| v.as_str())
+    }
+
+    fn finish(self) -> Vec<FieldData> {
+        let doi = self.value_of("doi").and_then(normalize_doi);
+        let eid = self.value_of("eid")
+            .and_then(normalize_eid)
+            .or_else(|| {
+                self.value_of("link")
+                    .and_then(|link| EID_IN_LINK_RE.captures(link))
+                    .and_then(|caps| normalize_eid(&caps[1]))
+            });
+        self.fields
+            .into_iter()
+            .map(|(field_name, subfield_path, value)| FieldData {
+                doi: doi.clone(),
+                eid: eid.clone(),
+                field_name,
+                subfield_path,
+                value,
+            })
+            .collect()
+    }
+}
+
+fn parse_csv_export(path: &Path) -> Result<Vec<RecordBuilder>> {
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .with_context(|| format!("Failed to open CSV export: {}", path.display()))?;
+    let headers: Vec<String> = reader.headers()?
+        .iter()
+        .map(|h| h.trim_start_matches('\u{feff}').trim().to_string())
+        .collect();
+
+    // "Author(s) ID" duplicates the ids already embedded in "Author full names".
+    let has_full_names = headers.iter().any(|h| h == "Author full names");
+    let columns: Vec<(String, ColumnKind)> = headers.iter()
+        .map(|h| match CSV_COLUMNS.get(h.as_str()) {
+            Some(&(field, kind)) => (field.to_string(), kind),
+            None => {
+                debug!("Unmapped Scopus column '{}' in {}", h, path.display());
+                (snake_case_header(h), ColumnKind::Scalar)
+            }
+        })
+        .collect();
+
+    let mut records = Vec::new();
+    for (row_num, row) in reader.records().enumerate() {
+        let row = match row {
+            Ok(r) => r,
+            Err(e) => {
+                warn!("Error reading row {} of {}: {}", row_num + 2, path.display(), e);
+                continue;
+            }
+        };
+        let mut builder = RecordBuilder::new();
+        for ((field_name, kind), value) in columns.iter().zip(row.iter()) {
+            if field_name.is_empty() || (has_full_names && field_name == "author.scopus_id") {
+                continue;
+            }
+            builder.add(field_name, *kind, value);
+        }
+        records.push(builder);
+    }
+    Ok(records)
+}
+
+fn parse_ris_export(path: &Path) -> Result<Vec<RecordBuilder>> {
+    let file = File::open(path).with_context(|| format!("Failed to open RIS export: {}", path.display()))?;
+    let mut records = Vec::new();
+    let mut current: Option<RecordBuilder> = None;
+    let mut last_tag: Option<(String, ColumnKind)> = None;
+
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let line = line.trim_start_matches('\u{feff}').trim_end();
+        let Some(caps) = RIS_LINE_RE.captures(line) else {
+            // Continuation of a wrapped scalar value (e.g. a long abstract)
+            if let (Some(builder), Some((field, ColumnKind::Scalar))) = (current.as_mut(), last_tag.as_ref()) {
+                if let Some(entry) = builder.fields.iter_mut().rev().find(|(f, _, _)| f == field) {
+                    if !line.trim().is_empty() {
+                        entry.2.push(' ');
+                        entry.2.push_str(line.trim());
+                    }
+                }
+            }
+            continue;
+        };
+        let tag = &caps[1];
+        let value = caps.get(2).map(|m| m.as_str()).unwrap_or("");
+
+        if tag == "ER" {
+            if let Some(builder) = current.take() {
+                records.push(builder);
+            }
+            last_tag = None;
+            continue;
+        }
+        let builder = current.get_or_insert_with(RecordBuilder::new);
+        let (field_name, kind) = match RIS_TAGS.get(tag) {
+            Some(&(field, kind)) => (field.to_string(), kind),
+            None => (tag.to_lowercase(), ColumnKind::Scalar),
+        };
+        // Repeated tags (AU, KW, ...) add one list item each, so don't split on ';'.
+        if kind == ColumnKind::List {
+            let trimmed = value.trim();
+            if !trimmed.is_empty() {
+                builder.push_list_item(&field_name, trimmed);
+            }
+        } else if field_name == "issn" && !ISSN_RE.is_match(value.trim()) {
+            builder.add("isbn.value", ColumnKind::List, value);
+        } else {
+            builder.add(&field_name, kind, value);
+        }
+        last_tag = Some((field_name, kind));
+    }
+    if let Some(builder) = current.take() {
+        warn!("Unterminated final record (missing ER) in {}", path.display());
+        records.push(builder);
+    }
+    Ok(records)
+}
+
+fn is_export_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref(),
+        Some("csv") | Some("ris")
+    )
+}
+
+fn find_input_files(input: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if !path.is_dir() {
+        return Err(anyhow::anyhow!("Input path does not exist: {}", input));
+    }
+    let pattern = path.join("**/*");
+    let mut files: Vec<PathBuf> = glob(&pattern.to_string_lossy())?
+        .filter_map(Result::ok)
+        .filter(|p| p.is_file() && is_export_file(p))
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        warn!("No .csv or .ris files found under: {}", input);
+    }
+    Ok(files)
+}
+
+fn parse_field_filter(fields: &Option<String>) -> Option<HashSet<String>> {
+    fields.as_ref().map(|spec| {
+        spec.split(',')
+            .map(|f| f.trim().to_string())
+            .filter(|f| !f.is_empty())
+            .collect()
+    })
+}
+
+fn write_records(
+    writer: &mut Writer<File>,
+    records: Vec<RecordBuilder>,
+    field_filter: Option<&HashSet<String>>,
+    stats: &mut ExportStats,
+) -> Result<()> {
+    for builder in records {
+        let rows = builder.finish();
+        stats.records += 1;
+        if let Some(d) = rows.first().and_then(|r| r.doi.as_ref()) {
+            stats.records_with_doi += 1;
+            stats.unique_dois.insert(d.clone());
+        }
+        if rows.first().is_some_and(|r| r.eid.is_some()) {
+            stats.records_with_eid += 1;
+        }
+        for row in rows {
+            if field_filter.is_some_and(|f| !f.contains(&row.field_name)) {
+                continue;
+            }
+            let doi_str = row.doi.as_ref().map(|d| d.0.as_str()).unwrap_or("");
+            let prefix = doi_str.split_once('/').map(|(p, _)| p).unwrap_or("");
+            writer.write_record([
+                doi_str,
+                &row.field_name,
+                &row.subfield_path,
+                &row.value,
+                row.eid.as_ref().map(|e| e.0.as_str()).unwrap_or(""),
+                prefix,
+            ])?;
+            *stats.field_counts.entry(row.field_name).or_insert(0) += 1;
+            stats.rows_written += 1;
+        }
+    }
+    Ok(())
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let total_secs = elapsed.as_secs();
+    let minutes = total_secs / 60;
+    let seconds = total_secs % 60;
+    let millis = elapsed.subsec_millis();
+
+    if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}.{:03}s", seconds, millis)
+    }
+}
+
+fn setup_logging(log_level_str: &str) -> Result<()> {
+    let log_level = match log_level_str.to_uppercase().as_str() {
+        "DEBUG" => LevelFilter::Debug,
+        "INFO" => LevelFilter::Info,
+        "WARN" | "WARNING" => LevelFilter::Warn,
+        "ERROR" => LevelFilter::Error,
+        other => {
+            eprintln!("Invalid log level '{}', defaulting to INFO.", other);
+            LevelFilter::Info
+        }
+    };
+
+    SimpleLogger::new()
+        .with_level(log_level)
+        .with_timestamp_format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+        .init()?;
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let start_time = Instant::now();
+    let cli = Cli::parse();
+
+    setup_logging(&cli.log_level)?;
+    info!("Starting Scopus export parser");
+
+    let files = find_input_files(&cli.input)?;
+    info!("Found {} export files to process.", files.len());
+    if files.is_empty() {
+        return Ok(());
+    }
+    let field_filter = parse_field_filter(&cli.fields);
+
+    let output_path = PathBuf::from(&cli.output);
+    if let Some(parent) = output_path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory structure for: {}", output_path.display()))?;
+        }
+    }
+    let mut writer = Writer::from_path(&output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path.display()))?;
+    writer.write_record(["doi", "field_name", "subfield_path", "value", "eid", "doi_prefix"])?;
+
+    let mut stats = ExportStats::default();
+    for file in &files {
+        let is_ris = file.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("ris"));
+        let parsed = if is_ris { parse_ris_export(file) } else { parse_csv_export(file) };
+        match parsed {
+            Ok(records) => {
+                info!("{}: {} records", file.display(), records.len());
+                write_records(&mut writer, records, field_filter.as_ref(), &mut stats)?;
+                stats.files_processed += 1;
+            }
+            Err(e) => {
+                warn!("Error processing {}: {:#}", file.display(), e);
+                stats.files_with_errors += 1;
+            }
+        }
+    }
+    writer.flush()?;
+
+    info!("-------------------- FINAL SUMMARY --------------------");
+    info!("Total execution time: {}", format_elapsed(start_time.elapsed()));
+    info!("Files processed: {} ({} with errors)", stats.files_processed, stats.files_with_errors);
+    info!("Records: {} ({} with DOI, {} with EID)", stats.records, stats.records_with_doi, stats.records_with_eid);
+    info!("Unique DOIs: {}", stats.unique_dois.len());
+    info!("Field breakdown:");
+    let mut sorted_fields: Vec<_> = stats.field_counts.iter().collect();
+    sorted_fields.sort_by_key(|&(_, count)| std::cmp::Reverse(*count));
+    for (field, count) in sorted_fields.iter().take(20) {
+        info!("  - {}: {} records", field, count);
+    }
+    info!("Rows written: {} -> {}", stats.rows_written, cli.output);
+    info!("-------------------------------------------------------");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("scopus-export-parse-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    // One "doi | eid | subfield_path | value" line per output row.
+    fn rows(records: Vec<RecordBuilder>) -> Vec<String> {
+        records.into_iter()
+            .flat_map(RecordBuilder::finish)
+            .map(|row| format!(
+                "{} | {} | {} | {}",
+                row.doi.map(|d| d.0).unwrap_or_default(),
+                row.eid.map(|e| e.0).unwrap_or_default(),
+                row.subfield_path,
+                row.value
+            ))
+            .collect()
+    }
+
+    #[test]
+    fn csv_columns_map_to_field_paths() {
+        let path = fixture("export.csv", "\u{feff}Authors,Author full names,Author(s) ID,Title,DOI,Link,ISSN,Author Keywords,Funding Text 1\n\
+            \"Smith J.; Doe A.\",\"Smith, John (57190000001); Doe, Ann\",57190000001;57190000002,A title,https://doi.org/10.1016/J.X.2020.1,\
+            https://www.scopus.com/inward/record.uri?eid=2-s2.0-85000000001&partnerID=40,0028-0836,\"graphene; ; sensors\",Funded by X\n");
+        let records = parse_csv_export(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let (doi, eid) = ("10.1016/j.x.2020.1", "2-s2.0-85000000001");
+        let expected: Vec<String> = [
+            ("author[0].name", "Smith J."),
+            ("author[1].name", "Doe A."),
+            ("author[0].full_name", "Smith, John"),
+            ("author[0].scopus_id", "57190000001"),
+            ("author[1].full_name", "Doe, Ann"),
+            ("title", "A title"),
+            ("doi", doi),
+            ("link", "https://www.scopus.com/inward/record.uri?eid=2-s2.0-85000000001&partnerID=40"),
+            ("issn", "0028-0836"),
+            ("author_keyword[0].value", "graphene"),
+            ("author_keyword[1].value", "sensors"),
+            ("funding_text_1", "Funded by X"),
+        ].iter().map(|(path, value)| format!("{} | {} | {} | {}", doi, eid, path, value)).collect();
+        assert_eq!(rows(records), expected);
+    }
+
+    #[test]
+    fn ris_records_join_continuations_and_repeat_list_tags() {
+        let path = fixture("export.ris", "\
+TY  - JOUR
+TI  - A long title
+  that wraps onto a second line
+AU  - Smith, John
+AU  - Doe, Ann
+KW  - graphene; sensors
+SN  - 00280836
+SN  - 978-3-16-148410-0
+DO  - 10.1016/J.X.2020.1
+ER  - 
+
+TY  - CONF
+T1  - Unterminated
+N1  - Export Date: 1 January 2024
+");
+        let records = parse_ris_export(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let doi = "10.1016/j.x.2020.1";
+        assert_eq!(rows(records), [
+            format!("{} |  | document_type | JOUR", doi),
+            format!("{} |  | title | A long title that wraps onto a second line", doi),
+            format!("{} |  | author[0].name | Smith, John", doi),
+            format!("{} |  | author[1].name | Doe, Ann", doi),
+            format!("{} |  | author_keyword[0].value | graphene; sensors", doi),
+            format!("{} |  | issn | 0028-0836", doi),
+            format!("{} |  | isbn[0].value | 978-3-16-148410-0", doi),
+            format!("{} |  | doi | {}", doi, doi),
+            " |  | document_type | CONF".to_string(),
+            " |  | title | Unterminated".to_string(),
+            " |  | note[0].value | Export Date: 1 January 2024".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn identifiers_are_normalized() {
+        assert_eq!(normalize_eid(" 85000000001 "), Some(Eid("2-s2.0-85000000001".to_string())));
+        assert_eq!(normalize_eid("2-S2.0-85000000001"), Some(Eid("2-s2.0-85000000001".to_string())));
+        assert_eq!(normalize_eid("2-s2.0-"), None);
+        assert_eq!(normalize_issn("1234567x"), "1234-567X");
+        assert_eq!(snake_case_header(" Art. No. "), "art_no");
+    }
+}
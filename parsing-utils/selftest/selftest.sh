@@ -0,0 +1,116 @@
+#!/usr/bin/env bash
+# Runs the extract -> normalize -> join -> diff chain over the bundled fixtures
+# and checks every step's output against the expected files, so that a new
+# installation can be verified in seconds before a real run.
+set -euo pipefail
+
+usage() {
+    cat <<EOF
+Usage: $(basename "$0") [--bin-dir DIR] [--keep]
+
+  --bin-dir DIR  Directory holding openalex-fast-field-parse, normalize and
+                 csv_processor_duckdb (default: look them up on PATH)
+  --keep         Keep the working directory instead of deleting it
+EOF
+}
+
+SELFTEST_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+BIN_DIR=""
+KEEP=0
+while [ $# -gt 0 ]; do
+    case "$1" in
+        --bin-dir) BIN_DIR="${2:?--bin-dir needs a directory}"; shift 2 ;;
+        --keep) KEEP=1; shift ;;
+        -h|--help) usage; exit 0 ;;
+        *) echo "Unknown argument: $1" >&2; usage >&2; exit 2 ;;
+    esac
+done
+
+FAILURES=0
+pass() { echo "ok      $1"; }
+fail() { echo "FAILED  $1"; FAILURES=$((FAILURES + 1)); }
+
+binary() {
+    local name="$1"
+    if [ -n "$BIN_DIR" ]; then
+        [ -x "$BIN_DIR/$name" ] && { echo "$BIN_DIR/$name"; return; }
+    else
+        command -v "$name" && return
+    fi
+    echo "FAILED  $name not found${BIN_DIR:+ in $BIN_DIR}" >&2
+    exit 1
+}
+
+EXTRACT="$(binary openalex-fast-field-parse)"
+NORMALIZE="$(binary normalize)"
+JOIN="$(binary csv_processor_duckdb)"
+
+# The working directory is made under $TMPDIR, as the tools' sort and scratch
+# files would be in a real run.
+WORK_DIR="$(mktemp -d "${TMPDIR:-/tmp}/selftest.XXXXXX")" || { echo "FAILED  cannot create a working directory under ${TMPDIR:-/tmp}"; exit 1; }
+if [ "$KEEP" -eq 1 ]; then
+    echo "Working directory: $WORK_DIR"
+else
+    trap 'rm -rf "$WORK_DIR"' EXIT
+fi
+mkdir "$WORK_DIR/sort_tmp"
+cp -r "$SELFTEST_DIR/fixtures/openalex" "$WORK_DIR/openalex"
+cd "$WORK_DIR"
+
+echo "Locale: LANG=${LANG:-} LC_ALL=${LC_ALL:-}"
+for tool in "$EXTRACT" "$NORMALIZE" "$JOIN"; do
+    "$tool" --version-info | sed -n '2,3p' | paste -sd ' ' -
+done
+echo
+
+# Rows are compared sorted, byte by byte, with the header kept first.
+same_rows() {
+    diff -u --label "expected" --label "$2" \
+        <(head -n 1 "$1"; tail -n +2 "$1" | LC_ALL=C sort) \
+        <(head -n 1 "$2"; tail -n +2 "$2" | LC_ALL=C sort) > "$3.diff"
+}
+
+run() {
+    local step="$1" log="$2"; shift 2
+    if "$@" > "$log" 2>&1; then
+        return 0
+    fi
+    fail "$step exited with an error; errors from its log:"
+    { grep -E 'ERROR|^Error' "$log" || tail -n 5 "$log"; } | tail -n 10 | sed 's/^/        /'
+    return 1
+}
+
+# extract, checked with the extractor's own --compare-with
+if run "extract" extract.log "$EXTRACT" -i openalex -o fields.csv -t 1 \
+        -f "authorships.author.display_name,authorships.affiliations.raw_affiliation_string,authorships.affiliations.institution_ids,authorships.institutions.id,authorships.institutions.ror" \
+        --compare-with "$SELFTEST_DIR/expected/fields.csv" --compare-report fields_report.csv; then
+    pass "extract: openalex-fast-field-parse output matches expected/fields.csv"
+fi
+
+# normalize
+if [ -f fields.csv ] && run "normalize" normalize.log "$NORMALIZE" doi -i fields.csv -c doi -o fields_normalized.csv; then
+    if same_rows "$SELFTEST_DIR/expected/fields_normalized.csv" fields_normalized.csv normalize; then
+        pass "normalize: normalize doi output matches expected/fields_normalized.csv"
+    else
+        fail "normalize: output differs from expected/fields_normalized.csv"
+        sed 's/^/        /' normalize.diff
+    fi
+fi
+
+# join
+if [ -f fields_normalized.csv ] && run "join" join.log "$JOIN" -i fields_normalized.csv -o joined.csv --temp-dir sort_tmp; then
+    if same_rows "$SELFTEST_DIR/expected/joined.csv" joined.csv join; then
+        pass "join: csv_processor_duckdb output matches expected/joined.csv"
+    else
+        fail "join: output differs from expected/joined.csv"
+        sed 's/^/        /' join.diff
+    fi
+fi
+
+echo
+if [ "$FAILURES" -gt 0 ]; then
+    echo "Self-test failed: $FAILURES step(s)."
+    [ "$KEEP" -eq 1 ] || echo "Rerun with --keep to inspect the outputs and logs."
+    exit 1
+fi
+echo "Self-test passed."